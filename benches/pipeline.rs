@@ -0,0 +1,84 @@
+//Each proving pipeline stage over synthetic circuits, cargo bench --features testing
+//(add parallel to compare the multi-threaded setup and prove)
+use std::sync::Arc;
+
+use ark_bls12_381::Fr;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, SynthesisMode};
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use zk_framework::testing::{merkle_path, mul_chain};
+use zk_framework::{prove_with_rng, setup_with_rng, verify, Circuit, NoProgress, R1CSAdapter};
+
+fn circuits() -> Vec<(String, Circuit)> {
+    vec![
+        ("mul_chain/100".to_string(), mul_chain(100)),
+        ("mul_chain/1000".to_string(), mul_chain(1_000)),
+        ("mul_chain/10000".to_string(), mul_chain(10_000)),
+        ("merkle_path/8".to_string(), merkle_path(8)),
+    ]
+}
+
+fn bench_stages(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(10);
+    println!("arkworks {}", if cfg!(feature = "parallel") { "parallel (rayon)" } else { "serial" });
+
+    for (id, circuit) in circuits() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).expect("Witness computation failed");
+        let public_inputs = r1cs.public_inputs_from_witness(&witness).expect("Missing public inputs");
+        let (pk, vk) = setup_with_rng(&r1cs, &mut rng, &NoProgress).expect("Setup failed");
+        let proof = prove_with_rng(&r1cs, &pk, witness.clone(), &mut rng, &NoProgress).expect("Proving failed");
+
+        //Sizes do not vary between runs, so they are reported once instead of measured
+        println!(
+            "{}: {} constraints, {} variables, proof {} bytes, proving key {} bytes, verifying key {} bytes",
+            id,
+            r1cs.raw_constraints.len(),
+            r1cs.num_variables,
+            proof.to_bytes().unwrap().len(),
+            pk.to_bytes().unwrap().len(),
+            vk.to_bytes().unwrap().len(),
+        );
+        let density = r1cs.density();
+        println!(
+            "{}: {:.2} terms per row, longest row {}, estimated cost {} G1 scalar multiplications",
+            id,
+            density.avg_row_len,
+            density.max_row_len,
+            density.estimated_cost(),
+        );
+        for warning in density.warnings() {
+            println!("{}: WARNING dense constraint system: {}", id, warning);
+        }
+
+        group.bench_function(BenchmarkId::new("to_r1cs_system", &id), |b| b.iter(|| circuit.to_r1cs_system()));
+        group.bench_function(BenchmarkId::new("compute_witness", &id), |b| {
+            b.iter(|| circuit.compute_witness(&r1cs.var_map).unwrap())
+        });
+        let shared = Arc::new(r1cs.clone());
+        group.bench_function(BenchmarkId::new("synthesize", &id), |b| {
+            b.iter(|| {
+                let adapter = R1CSAdapter::new(shared.clone(), None).unwrap();
+                let cs = ConstraintSystem::<Fr>::new_ref();
+                cs.set_mode(SynthesisMode::Setup);
+                adapter.generate_constraints(cs).unwrap()
+            })
+        });
+        group.bench_function(BenchmarkId::new("setup", &id), |b| {
+            b.iter(|| setup_with_rng(&r1cs, &mut rng, &NoProgress).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("prove", &id), |b| {
+            b.iter(|| prove_with_rng(&r1cs, &pk, witness.clone(), &mut rng, &NoProgress).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("verify", &id), |b| {
+            b.iter(|| assert!(verify(&vk, &proof, &public_inputs).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_stages);
+criterion_main!(benches);
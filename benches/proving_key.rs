@@ -0,0 +1,71 @@
+//Memory and throughput of parallel proofs sharing one ProvingKeyHandle (peak RSS on Linux only),
+//cargo bench --features testing --bench proving_key
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use zk_framework::testing::mul_chain;
+use zk_framework::{prove, setup_with_rng, NoProgress, ProvingKeyHandle};
+
+const PARALLEL_PROOFS: usize = 8;
+
+//(current, peak) resident set size in bytes
+fn rss() -> Option<(u64, u64)> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let line = status.lines().find(|line| line.starts_with(name))?;
+        Some(line.split_whitespace().nth(1)?.parse::<u64>().ok()? * 1024)
+    };
+    Some((field("VmRSS:")?, field("VmHWM:")?))
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn bench_shared_key(c: &mut Criterion) {
+    let circuit = mul_chain(10_000);
+    let r1cs = circuit.to_r1cs_system();
+    let witness = circuit.compute_witness(&r1cs.var_map).expect("Witness computation failed");
+    let path = std::env::temp_dir().join(format!("zk-bench-pk-{}.uncompressed", std::process::id()));
+    {
+        let (pk, _vk) = setup_with_rng(&r1cs, &mut StdRng::seed_from_u64(0), &NoProgress).expect("Setup failed");
+        ProvingKeyHandle::save(&pk, None, &path).expect("Cannot write proving key");
+    }
+    let pk_file_size = std::fs::metadata(&path).expect("Proving key file is missing").len();
+
+    let handle = ProvingKeyHandle::open(&path, None);
+    let before = rss();
+    handle.get().expect("Cannot load proving key");
+    let loaded = rss();
+    let prove_all = || {
+        std::thread::scope(|scope| {
+            for _ in 0..PARALLEL_PROOFS {
+                let (handle, r1cs, witness) = (handle.clone(), &r1cs, witness.clone());
+                scope.spawn(move || prove(r1cs, handle.get().unwrap(), witness).expect("Proving failed"));
+            }
+        })
+    };
+    prove_all();
+    //Reported once: the numbers do not vary between runs
+    println!("proving_key: {} constraints, key file {:.1} MiB", r1cs.raw_constraints.len(), mib(pk_file_size));
+    if let (Some((before, _)), Some((loaded, _)), Some((_, peak))) = (before, loaded, rss()) {
+        println!(
+            "proving_key: RSS {:.1} MiB before load, {:.1} MiB after load, peak {:.1} MiB over {} parallel proofs \
+             (one key copy per proof would add about {:.1} MiB more)",
+            mib(before),
+            mib(loaded),
+            mib(peak),
+            PARALLEL_PROOFS,
+            mib(pk_file_size * (PARALLEL_PROOFS as u64 - 1)),
+        );
+    }
+
+    let mut group = c.benchmark_group("proving_key");
+    group.sample_size(10);
+    group.bench_function("8_parallel_proofs_shared_handle/mul_chain_10000", |b| b.iter(prove_all));
+    group.finish();
+    let _ = std::fs::remove_file(&path);
+}
+
+criterion_group!(benches, bench_shared_key);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//Incremental witness recomputation against a full one, cargo bench --bench witness
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use zk_framework::{Circuit, Gate, WitnessEngine};
+
+//100 inputs, each feeding its own chain of 100 gates (10k gates in total)
+fn chains(num_inputs: usize, chain_len: usize) -> Circuit {
+    let mut gates = vec![Gate::Const("k".to_string(), 3)];
+    for i in 0..num_inputs {
+        let mut prev = format!("x{}", i);
+        for j in 0..chain_len {
+            let out = format!("c{}_{}", i, j);
+            gates.push(match j % 2 {
+                0 => Gate::Mul(prev, "k".to_string(), out.clone(), None),
+                _ => Gate::Add(prev, "k".to_string(), out.clone(), None),
+            });
+            prev = out;
+        }
+    }
+    Circuit {
+        name: "chains".to_string(),
+        inputs: (0..num_inputs).map(|i| (format!("x{}", i), i as i32 + 1)).collect(),
+        gates,
+        sender: "alice".to_string(),
+        receiver: "bob".to_string(),
+        transfer_amount: 5,
+        ..Default::default()
+    }
+}
+
+fn bench_recompute(c: &mut Criterion) {
+    let circuit = chains(100, 100);
+    let r1cs = circuit.to_r1cs_system();
+    let mut engine = WitnessEngine::new(&circuit, &r1cs);
+    engine.compute(&HashMap::new()).expect("Witness computation failed");
+
+    let mut group = c.benchmark_group("witness/10k_gates_one_of_100_inputs_changed");
+    let mut changed = circuit.clone();
+    let mut next = 0;
+    group.bench_function("compute_witness", |b| {
+        b.iter(|| {
+            next += 1;
+            changed.inputs.insert("x42".to_string(), next);
+            changed.compute_witness(&r1cs.var_map).unwrap()
+        })
+    });
+    group.bench_function("engine_recompute", |b| {
+        b.iter(|| {
+            next += 1;
+            engine.recompute(&HashMap::from([("x42".to_string(), next)])).unwrap().len()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_recompute);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+//Regenerate the C header for the ffi module when the ffi feature is enabled
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).expect("Invalid cbindgen.toml");
+        //Only the ffi module is parsed so public constants of other modules stay out of the header
+        cbindgen::Builder::new()
+            .with_src(format!("{}/src/ffi.rs", crate_dir))
+            .with_config(config)
+            .generate()
+            .expect("Unable to generate the C header")
+            .write_to_file(format!("{}/include/zk_framework.h", crate_dir));
+    }
+}
@@ -0,0 +1,12 @@
+//(a + b) * c built from Gate values, proved and verified
+use zk_framework::examples_support::prove_arithmetic;
+
+fn main() {
+    match prove_arithmetic(2, 3, 4) {
+        Ok(verified) => println!("Verification Result: {}", verified),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
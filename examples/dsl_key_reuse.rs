@@ -0,0 +1,27 @@
+//Proves a DSL circuit for several inputs, running setup once: cargo run --example dsl_key_reuse [circuit file]
+use std::collections::HashMap;
+use zk_framework::examples_support::prove_with_key_reuse;
+use zk_framework::parse_circuit;
+
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or_else(|| concat!(env!("CARGO_MANIFEST_DIR"), "/circuit.txt").to_string());
+    let circuit = match parse_circuit(&path) {
+        Ok(circuit) => circuit,
+        Err(e) => {
+            eprintln!("Error parsing circuit: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let input_sets: Vec<HashMap<String, i32>> = (5..8).map(|x| HashMap::from([("x".to_string(), x)])).collect();
+    match prove_with_key_reuse(&circuit, &input_sets) {
+        Ok(results) => {
+            for (inputs, verified) in input_sets.iter().zip(results) {
+                println!("x = {}: Verification Result: {}", inputs["x"], verified);
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
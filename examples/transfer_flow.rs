@@ -0,0 +1,15 @@
+//Proves the transfer of examples/transfer.txt and applies it to the balances
+use zk_framework::examples_support::transfer_flow;
+
+fn main() {
+    match transfer_flow(100, 20, 30) {
+        Ok(outcome) => {
+            println!("Verification Result: {}", outcome.verified);
+            println!("Balances after the transfer: sender {}, receiver {}", outcome.sender_balance, outcome.receiver_balance);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
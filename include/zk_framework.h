@@ -0,0 +1,84 @@
+#ifndef ZK_FRAMEWORK_H
+#define ZK_FRAMEWORK_H
+
+/* Generated by cbindgen from src/ffi.rs (cargo build --features ffi), do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define ZK_OK 0
+
+#define ZK_ERR_NULL_POINTER -1
+
+#define ZK_ERR_INVALID_ARGUMENT -2
+
+#define ZK_ERR_PROVING -3
+
+#define ZK_ERR_PANIC -4
+
+typedef struct ZkBuffer {
+  uint8_t *data;
+  uintptr_t len;
+} ZkBuffer;
+
+/**
+ * Run setup for an R1CS system exported as JSON, writing the compressed keys to out_pk/out_vk.
+ *
+ * # Safety
+ * r1cs_json must be a valid NUL-terminated string, out_pk and out_vk valid writable pointers.
+ */
+int32_t zk_setup(const char *r1cs_json, struct ZkBuffer *out_pk, struct ZkBuffer *out_vk);
+
+/**
+ * Prove a circuit given as JSON, inputs_json (may be null) overrides circuit inputs.
+ *
+ * # Safety
+ * pk_data must point to pk_len readable bytes, the strings must be NUL-terminated and
+ * out_proof a valid writable pointer.
+ */
+int32_t zk_prove(const uint8_t *pk_data,
+                 uintptr_t pk_len,
+                 const char *circuit_json,
+                 const char *inputs_json,
+                 struct ZkBuffer *out_proof);
+
+/**
+ * Write the ordered public inputs of a circuit instance as a JSON array of hex strings.
+ *
+ * # Safety
+ * The strings must be NUL-terminated (inputs_json may be null) and out_publics a valid writable pointer.
+ */
+int32_t zk_public_inputs(const char *circuit_json,
+                         const char *inputs_json,
+                         struct ZkBuffer *out_publics);
+
+/**
+ * Verify a proof, publics_json is a JSON array of hex field elements.
+ * Returns 1 for a valid proof, 0 for an invalid one and a negative error code otherwise.
+ *
+ * # Safety
+ * vk_data/proof_data must point to vk_len/proof_len readable bytes and publics_json be NUL-terminated.
+ */
+int32_t zk_verify(const uint8_t *vk_data,
+                  uintptr_t vk_len,
+                  const uint8_t *proof_data,
+                  uintptr_t proof_len,
+                  const char *publics_json);
+
+/**
+ * Release a buffer returned by this library.
+ *
+ * # Safety
+ * buf must come from one of the zk_* functions and not have been freed already.
+ */
+void zk_free(struct ZkBuffer buf);
+
+/**
+ * Message of the last error on the calling thread, or null if the last call succeeded.
+ * The pointer stays valid until the next zk_* call on the same thread.
+ */
+const char *zk_last_error_message(void);
+
+#endif  /* ZK_FRAMEWORK_H */
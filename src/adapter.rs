@@ -0,0 +1,268 @@
+//R1CSAdapter: an R1CSSystem (and optionally its witness) as an arkworks ConstraintSynthesizer
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use ark_bls12_381::Fr;
+use ark_ff::One;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, LinearCombination, SynthesisError, Variable};
+
+use crate::{NoProgress, ProgressEvent, ProgressSink, R1CSSystem};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError {
+    //The constant "1" variable is not in var_map
+    MissingOneVariable,
+    //A public_input_names entry is not in var_map
+    MissingPublicInput { name: String },
+    //A constraint refers to an index no var_map entry has
+    UnknownVariable { index: usize },
+    //The witness has no value for a var_map entry
+    MissingWitnessValue { name: String, index: usize },
+    //Two Const gates assign the same wire
+    DuplicateConst { wire: String },
+    //A Const gate assigns a wire that is also a circuit input
+    ConstOnInput { wire: String },
+    //Two gates (or two outputs of one gate) assign the same wire
+    MultipleWriters { wire: String, first_gate: usize, second_gate: usize },
+    //A gate reads the wire it assigns (e.g. `add x y x`)
+    SelfReferential { gate_index: usize, wire: String },
+    //A gate assigns an input or transfer spec wire, whose value is fixed before any gate runs
+    OverwritesInput { gate_index: usize, wire: String },
+    //A declared public input shares its name with another input (or is declared twice)
+    DuplicateInput { wire: String },
+    //An input or gate uses a name reserved for a well-known wire ("1", the transfer spec wires or a `$` name)
+    ReservedName { wire: String },
+    //The sender and receiver are the same party, whose balance would be published twice
+    SelfTransfer { party: String },
+    //Executing the transfer would take the receiver's i32 balance past i32::MAX
+    BalanceOverflow { party: String, amount: u64 },
+    //The sender or receiver input is a negative balance
+    NegativeBalance { party: String, balance: i32 },
+    //An `amount` line that is not a u64
+    InvalidAmount { amount: String },
+    //private_balances without bind_inputs, which leaves the balances unconstrained
+    UnboundPrivateBalances,
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::MissingOneVariable => write!(f, "Variable '1' not found in var_map"),
+            CircuitError::MissingPublicInput { name } => write!(f, "Public input name '{}' not found in var_map", name),
+            CircuitError::UnknownVariable { index } => write!(f, "Constraint refers to unknown variable index {}", index),
+            CircuitError::MissingWitnessValue { name, index } => {
+                write!(f, "Witness has no value for variable '{}' (index {})", name, index)
+            }
+            CircuitError::DuplicateConst { wire } => write!(f, "Constant '{}' is defined more than once", wire),
+            CircuitError::ConstOnInput { wire } => write!(f, "Constant '{}' is also a circuit input", wire),
+            CircuitError::MultipleWriters { wire, first_gate, second_gate } => {
+                write!(f, "Wire '{}' is assigned by gate #{} and again by gate #{}", wire, first_gate, second_gate)
+            }
+            CircuitError::SelfReferential { gate_index, wire } => {
+                write!(f, "Gate #{} reads its own output wire '{}'", gate_index, wire)
+            }
+            CircuitError::OverwritesInput { gate_index, wire } => {
+                write!(f, "Gate #{} assigns '{}', which is already an input", gate_index, wire)
+            }
+            CircuitError::DuplicateInput { wire } => write!(f, "Input '{}' is declared more than once", wire),
+            CircuitError::ReservedName { wire } => write!(f, "'{}' is a reserved wire name", wire),
+            CircuitError::SelfTransfer { party } => write!(f, "'{}' is both the sender and the receiver of the transfer", party),
+            CircuitError::BalanceOverflow { party, amount } => {
+                write!(f, "Receiving {} overflows the balance of '{}'", amount, party)
+            }
+            CircuitError::NegativeBalance { party, balance } => {
+                write!(f, "Balance of '{}' is {}: balances cannot be negative", party, balance)
+            }
+            CircuitError::InvalidAmount { amount } => {
+                write!(f, "Invalid transfer amount '{}': expected an integer from 0 to 2^64 - 1", amount)
+            }
+            CircuitError::UnboundPrivateBalances => {
+                write!(f, "Private balances need bound inputs: without the input digest the prover picks the balance")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CircuitError {}
+
+#[derive(Clone)]
+pub struct R1CSAdapter<'a> {
+    r1cs_system: Arc<R1CSSystem>,
+    witness: Option<Arc<HashMap<usize, Fr>>>,
+    progress: &'a dyn ProgressSink,
+}
+
+impl<'a> R1CSAdapter<'a> {
+    //Validate and build the adapter; without a witness it can only be used for setup
+    pub fn new(r1cs_system: Arc<R1CSSystem>, witness: Option<Arc<HashMap<usize, Fr>>>) -> Result<Self, CircuitError> {
+        let var_map = &r1cs_system.var_map;
+        if !var_map.contains_key("1") {
+            return Err(CircuitError::MissingOneVariable);
+        }
+        for name in &r1cs_system.public_input_names {
+            if !var_map.contains_key(name) {
+                return Err(CircuitError::MissingPublicInput { name: name.clone() });
+            }
+        }
+
+        let mut known: Vec<usize> = var_map.values().copied().collect();
+        known.sort_unstable();
+        for constraint in &r1cs_system.raw_constraints {
+            for index in constraint.a.keys().chain(constraint.b.keys()).chain(constraint.c.keys()) {
+                if known.binary_search(index).is_err() {
+                    return Err(CircuitError::UnknownVariable { index: *index });
+                }
+            }
+        }
+
+        if let Some(witness) = &witness {
+            let mut vars: Vec<(&String, &usize)> = var_map.iter().collect();
+            vars.sort_by_key(|(_, idx)| **idx);
+            for (name, idx) in vars {
+                if name != "1" && !witness.contains_key(idx) {
+                    return Err(CircuitError::MissingWitnessValue { name: name.clone(), index: *idx });
+                }
+            }
+        }
+
+        Ok(R1CSAdapter { r1cs_system, witness, progress: &NoProgress })
+    }
+
+    //Report synthesis events to the progress sink
+    pub fn with_progress(mut self, progress: &'a dyn ProgressSink) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn r1cs_system(&self) -> &R1CSSystem {
+        &self.r1cs_system
+    }
+
+    pub fn witness(&self) -> Option<&HashMap<usize, Fr>> {
+        self.witness.as_deref()
+    }
+
+    //Witness value of a variable, None in setup mode
+    fn value(&self, idx: usize) -> Result<Fr, SynthesisError> {
+        match &self.witness {
+            Some(witness) => witness.get(&idx).copied().ok_or(SynthesisError::AssignmentMissing),
+            None => Err(SynthesisError::AssignmentMissing),
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for R1CSAdapter<'_> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let r1cs = &self.r1cs_system;
+        self.progress.on_event(ProgressEvent::SynthesisStarted {
+            num_constraints: r1cs.raw_constraints.len(),
+            num_variables: r1cs.num_variables,
+        });
+        //arkworks variable of each original index, resolved once so the row loop below is plain
+        //indexing (new checked every index a row reads is in var_map)
+        let len = r1cs.var_map.values().max().map_or(0, |max| max + 1);
+        let mut cs_vars: Vec<Variable> = vec![Variable::Zero; len];
+
+        //Allocate constant 1 (validated to exist in new)
+        let one_cs_var = cs.new_input_variable(|| Ok(Fr::one()))?;
+        cs_vars[r1cs.var_map["1"]] = one_cs_var;
+
+        //Allocate public inputs
+        for name in &r1cs.public_input_names {
+            let original_idx = r1cs.var_map[name];
+            let cs_var = cs.new_input_variable(|| self.value(original_idx))?;
+            cs_vars[original_idx] = cs_var;
+        }
+
+        //Allocate witness variables in index order, so keys stay valid for any R1CSSystem
+        //built from the same circuit (HashMap iteration order differs between instances)
+        let public: HashSet<&String> = r1cs.public_input_names.iter().collect();
+        let mut witness_vars: Vec<(&String, &usize)> = r1cs.var_map.iter().collect();
+        witness_vars.sort_by_key(|(_, idx)| **idx);
+        for (name, original_idx) in witness_vars {
+            if name != "1" && !public.contains(name) {
+                let cs_var = cs.new_witness_variable(|| self.value(*original_idx))?;
+                cs_vars[*original_idx] = cs_var;
+            }
+        }
+
+        //Enforce constraints, each inside a span naming its origin so arkworks constraint
+        //traces (ConstraintLayer) point at the gate
+        for (i, constraint) in r1cs.raw_constraints.iter().enumerate() {
+            let span = tracing::info_span!(target: "r1cs", "constraint", origin = %r1cs.explain(i));
+            let _guard = span.enter();
+            let mut lc_a = LinearCombination::zero();
+            let mut lc_b = LinearCombination::zero();
+            let mut lc_c = LinearCombination::zero();
+            for (original_idx, coeff) in &constraint.a {
+                lc_a += (*coeff, cs_vars[*original_idx]);
+            }
+            for (original_idx, coeff) in &constraint.b {
+                lc_b += (*coeff, cs_vars[*original_idx]);
+            }
+            for (original_idx, coeff) in &constraint.c {
+                lc_c += (*coeff, cs_vars[*original_idx]);
+            }
+            cs.enforce_constraint(lc_a, lc_b, lc_c)?;
+        }
+
+        self.progress.on_event(ProgressEvent::SynthesisFinished {
+            num_constraints: cs.num_constraints(),
+            num_instance_variables: cs.num_instance_variables(),
+            num_witness_variables: cs.num_witness_variables(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mul_circuit;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    fn system() -> (R1CSSystem, HashMap<usize, Fr>) {
+        let mut circuit = mul_circuit("adapter");
+        circuit.inputs.insert("alice".to_string(), 10);
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        (r1cs, witness)
+    }
+
+    #[test]
+    fn test_valid_adapter_synthesizes() {
+        let (r1cs, witness) = system();
+        let adapter = R1CSAdapter::new(Arc::new(r1cs), Some(Arc::new(witness))).expect("Valid system");
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        adapter.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_validation_failures() {
+        let (r1cs, witness) = system();
+
+        let mut no_one = r1cs.clone();
+        no_one.var_map.remove("1");
+        assert_eq!(R1CSAdapter::new(Arc::new(no_one), None).err(), Some(CircuitError::MissingOneVariable));
+
+        let mut no_public = r1cs.clone();
+        no_public.var_map.remove("alice_initial_balance");
+        assert_eq!(
+            R1CSAdapter::new(Arc::new(no_public), None).err(),
+            Some(CircuitError::MissingPublicInput { name: "alice_initial_balance".to_string() })
+        );
+
+        let mut unknown = r1cs.clone();
+        unknown.raw_constraints[0].a.insert(99, Fr::one());
+        assert_eq!(R1CSAdapter::new(Arc::new(unknown), None).err(), Some(CircuitError::UnknownVariable { index: 99 }));
+
+        let mut partial = witness.clone();
+        partial.remove(&r1cs.var_map["c"]);
+        assert_eq!(
+            R1CSAdapter::new(Arc::new(r1cs.clone()), Some(Arc::new(partial))).err(),
+            Some(CircuitError::MissingWitnessValue { name: "c".to_string(), index: r1cs.var_map["c"] })
+        );
+    }
+}
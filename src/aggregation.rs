@@ -0,0 +1,220 @@
+//Aggregation of Groth16 proofs for one verifying key: one multi-pairing with Fiat-Shamir weights
+//checks them all (N + 3 Miller loops). The aggregate still carries every proof, it is not succinct
+use std::fmt;
+
+use ark_bls12_381::{Bls12_381, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::pairing::Pairing;
+use ark_ec::CurveGroup;
+use ark_ff::{PrimeField, Zero};
+use ark_serialize::CanonicalSerialize;
+use sha2::{Digest, Sha256};
+
+use crate::{Proof, VerifyingKey};
+
+//Verifier side of the aggregation, derived from a circuit's verifying key
+#[derive(Clone)]
+pub struct AggregateVerifyingKey {
+    alpha_g1: G1Affine,
+    beta_g2: G2Affine,
+    gamma_g2: G2Affine,
+    delta_g2: G2Affine,
+    gamma_abc_g1: Vec<G1Affine>,
+}
+
+impl AggregateVerifyingKey {
+    pub fn new(vk: &VerifyingKey) -> Self {
+        let vk = vk.inner();
+        AggregateVerifyingKey {
+            alpha_g1: vk.alpha_g1,
+            beta_g2: vk.beta_g2,
+            gamma_g2: vk.gamma_g2,
+            delta_g2: vk.delta_g2,
+            gamma_abc_g1: vk.gamma_abc_g1.clone(),
+        }
+    }
+
+    //Number of public inputs each proof takes (ordered like verify(), without the constant one)
+    pub fn num_public_inputs(&self) -> usize {
+        self.gamma_abc_g1.len() - 2
+    }
+}
+
+//Proofs of one circuit bundled into a single statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateProof {
+    pub proofs: Vec<Proof>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AggregationError {
+    Empty,
+    //The number of public input vectors differs from the number of proofs
+    CountMismatch { proofs: usize, publics: usize },
+    //A public input vector has the wrong length for the verifying key
+    PublicInputCount { item: usize, expected: usize, found: usize },
+    //An item does not verify on its own, so it cannot be aggregated
+    InvalidProof { item: usize },
+}
+
+impl fmt::Display for AggregationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AggregationError::Empty => write!(f, "Nothing to aggregate"),
+            AggregationError::CountMismatch { proofs, publics } => {
+                write!(f, "{} proofs but {} public input vectors", proofs, publics)
+            }
+            AggregationError::PublicInputCount { item, expected, found } => {
+                write!(f, "Item {} has {} public inputs, expected {}", item, found, expected)
+            }
+            AggregationError::InvalidProof { item } => write!(f, "Proof {} does not verify", item),
+        }
+    }
+}
+
+impl std::error::Error for AggregationError {}
+
+fn check_shape(avk: &AggregateVerifyingKey, num_proofs: usize, all_publics: &[Vec<Fr>]) -> Result<(), AggregationError> {
+    if num_proofs == 0 {
+        return Err(AggregationError::Empty);
+    }
+    if num_proofs != all_publics.len() {
+        return Err(AggregationError::CountMismatch { proofs: num_proofs, publics: all_publics.len() });
+    }
+    for (item, publics) in all_publics.iter().enumerate() {
+        if publics.len() != avk.num_public_inputs() {
+            return Err(AggregationError::PublicInputCount { item, expected: avk.num_public_inputs(), found: publics.len() });
+        }
+    }
+    Ok(())
+}
+
+//Batch weights bound to every proof and public input, 128 bits each
+fn batch_weights(proofs: &[Proof], all_publics: &[Vec<Fr>]) -> Vec<Fr> {
+    let mut transcript = Sha256::new();
+    transcript.update(b"zk-framework/aggregate/v1");
+    for (proof, publics) in proofs.iter().zip(all_publics) {
+        let mut bytes = Vec::new();
+        proof.0.serialize_compressed(&mut bytes).expect("Serializing into a Vec cannot fail");
+        publics.serialize_compressed(&mut bytes).expect("Serializing into a Vec cannot fail");
+        transcript.update(&bytes);
+    }
+    let seed = transcript.finalize();
+    (0..proofs.len() as u64)
+        .map(|i| {
+            let digest = Sha256::new().chain_update(seed).chain_update(i.to_le_bytes()).finalize();
+            Fr::from_le_bytes_mod_order(&digest[..16])
+        })
+        .collect()
+}
+
+//Combined verification equation of the batch, true iff every proof verifies (w.h.p.)
+fn check_batch(avk: &AggregateVerifyingKey, proofs: &[Proof], all_publics: &[Vec<Fr>]) -> bool {
+    let weights = batch_weights(proofs, all_publics);
+    let mut g1 = Vec::with_capacity(proofs.len() + 3);
+    let mut g2 = Vec::with_capacity(proofs.len() + 3);
+    let mut weight_sum = Fr::zero();
+    let mut ic_sum = G1Projective::zero();
+    let mut c_sum = G1Projective::zero();
+    for ((proof, publics), r) in proofs.iter().zip(all_publics).zip(&weights) {
+        //gamma_abc_g1[1] belongs to the adapter's constant one
+        let mut ic = avk.gamma_abc_g1[0] + avk.gamma_abc_g1[1];
+        for (input, base) in publics.iter().zip(&avk.gamma_abc_g1[2..]) {
+            ic += *base * input;
+        }
+        g1.push((proof.0.a * r).into_affine());
+        g2.push(proof.0.b);
+        weight_sum += r;
+        ic_sum += ic * r;
+        c_sum += proof.0.c * r;
+    }
+    g1.push((-(avk.alpha_g1 * weight_sum)).into_affine());
+    g2.push(avk.beta_g2);
+    g1.push((-ic_sum).into_affine());
+    g2.push(avk.gamma_g2);
+    g1.push((-c_sum).into_affine());
+    g2.push(avk.delta_g2);
+    Bls12_381::multi_pairing(g1, g2).is_zero()
+}
+
+//Bundle proofs of the circuit behind vk; items that do not verify are rejected by index
+pub fn aggregate_proofs(vk: &VerifyingKey, items: &[(Proof, Vec<Fr>)]) -> Result<AggregateProof, AggregationError> {
+    let avk = AggregateVerifyingKey::new(vk);
+    let (proofs, all_publics): (Vec<Proof>, Vec<Vec<Fr>>) = items.iter().cloned().unzip();
+    check_shape(&avk, proofs.len(), &all_publics)?;
+    if !check_batch(&avk, &proofs, &all_publics) {
+        //Only pay for individual checks when the batch fails, to name the bad item
+        for (item, (proof, publics)) in items.iter().enumerate() {
+            if !check_batch(&avk, std::slice::from_ref(proof), std::slice::from_ref(publics)) {
+                return Err(AggregationError::InvalidProof { item });
+            }
+        }
+    }
+    Ok(AggregateProof { proofs })
+}
+
+//Check an aggregate against the public inputs of every proof, in aggregation order
+pub fn verify_aggregate(
+    avk: &AggregateVerifyingKey,
+    agg_proof: &AggregateProof,
+    all_publics: &[Vec<Fr>],
+) -> Result<bool, AggregationError> {
+    check_shape(avk, agg_proof.proofs.len(), all_publics)?;
+    Ok(check_batch(avk, &agg_proof.proofs, all_publics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_pipeline, Circuit, Gate, PipelineOptions};
+    use std::collections::HashMap;
+
+    fn transfer(note: i32) -> Circuit {
+        Circuit {
+            name: "transfer".to_string(),
+            inputs: HashMap::from([("alice".to_string(), 20), ("bob".to_string(), 1), ("note".to_string(), note)]),
+            gates: vec![
+                Gate::Sub("alice".to_string(), "transfer_amount_public".to_string(), "alice_after".to_string(), None),
+                Gate::Add("bob".to_string(), "transfer_amount_public".to_string(), "bob_after".to_string(), None),
+                Gate::Hash("note".to_string(), "note_hash".to_string()),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_aggregate_four_transfers() {
+        let keys = run_pipeline(&transfer(0), PipelineOptions { return_keys: true, verify: false, ..PipelineOptions::default() })
+            .expect("Setup failed")
+            .keys
+            .expect("Keys were requested");
+        let items: Vec<(Proof, Vec<Fr>)> = (1..=4)
+            .map(|note| {
+                let options = PipelineOptions { keys: Some((&keys.0, &keys.1)), verify: false, ..PipelineOptions::default() };
+                let result = run_pipeline(&transfer(note), options).expect("Proving failed");
+                (result.proof, result.public_inputs)
+            })
+            .collect();
+        let all_publics: Vec<Vec<Fr>> = items.iter().map(|(_, publics)| publics.clone()).collect();
+
+        let avk = AggregateVerifyingKey::new(&keys.1);
+        let aggregate = aggregate_proofs(&keys.1, &items).expect("Aggregation failed");
+        assert_eq!(verify_aggregate(&avk, &aggregate, &all_publics), Ok(true));
+
+        //Changing one proof's public inputs breaks the whole statement
+        let mut tampered = all_publics.clone();
+        tampered[2][1] += Fr::from(1u64);
+        assert_eq!(verify_aggregate(&avk, &aggregate, &tampered), Ok(false));
+
+        //A batch containing one invalid proof is rejected, naming the item
+        let mut bad_items = items.clone();
+        bad_items[3].1[2] += Fr::from(7u64);
+        assert_eq!(aggregate_proofs(&keys.1, &bad_items), Err(AggregationError::InvalidProof { item: 3 }));
+        assert_eq!(
+            verify_aggregate(&avk, &aggregate, &all_publics[..3]),
+            Err(AggregationError::CountMismatch { proofs: 4, publics: 3 })
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//Versioned header of the artifacts written to disk, rejecting files of another version, curve or circuit:
+//magic b"ZKFA" | version u16 | curve id u8 | kind u8 | has circuit hash u8 | circuit hash [u8; 32]
+use std::fmt;
+
+use ark_groth16::{Proof as ArkGroth16Proof, ProvingKey as ArkGroth16ProvingKey, VerifyingKey as ArkGroth16VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+
+use crate::{Proof, ProvingKey, VerifyingKey};
+
+pub const ARTIFACT_MAGIC: &[u8; 4] = b"ZKFA";
+pub const ARTIFACT_VERSION: u16 = 1;
+pub const CURVE_ID_BLS12_381: u8 = 1;
+pub const CURVE_NAME: &str = "bls12-381";
+pub const HEADER_LEN: usize = 4 + 2 + 1 + 1 + 1 + 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    ProvingKey = 1,
+    VerifyingKey = 2,
+    Proof = 3,
+    Witness = 4,
+    //Proving key in the uncompressed layout of ProvingKeyHandle
+    UncompressedProvingKey = 5,
+    //Proof with its circuit, verifying key fingerprint and public inputs (see envelope.rs)
+    ProofEnvelope = 6,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactHeader {
+    pub version: u16,
+    pub kind: ArtifactKind,
+    pub circuit_hash: Option<[u8; 32]>,
+}
+
+#[derive(Debug)]
+pub enum ArtifactError {
+    //Too short or without the magic bytes (e.g. a legacy headerless file)
+    MissingHeader,
+    UnsupportedVersion { found: u16 },
+    //Curve id of a binary header, or curve name of a JSON export
+    WrongCurve { found: String },
+    WrongKind { expected: ArtifactKind, found: u8 },
+    WrongCircuit { expected: String, found: String },
+    Serialization(SerializationError),
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::MissingHeader => write!(f, "No zk-framework artifact header (legacy or foreign file)"),
+            ArtifactError::UnsupportedVersion { found } => {
+                write!(f, "Unsupported artifact format version {} (this build reads {})", found, ARTIFACT_VERSION)
+            }
+            ArtifactError::WrongCurve { found } => write!(f, "Artifact is for curve '{}', expected {}", found, CURVE_NAME),
+            ArtifactError::WrongKind { expected, found } => write!(f, "Artifact kind {} is not a {:?}", found, expected),
+            ArtifactError::WrongCircuit { expected, found } => {
+                write!(f, "Artifact was made for circuit {}, expected circuit {}", found, expected)
+            }
+            ArtifactError::Serialization(e) => write!(f, "Invalid artifact payload: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+impl From<SerializationError> for ArtifactError {
+    fn from(e: SerializationError) -> Self {
+        ArtifactError::Serialization(e)
+    }
+}
+
+pub fn write_header(kind: ArtifactKind, circuit_hash: Option<&[u8; 32]>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    bytes.extend_from_slice(ARTIFACT_MAGIC);
+    bytes.extend_from_slice(&ARTIFACT_VERSION.to_le_bytes());
+    bytes.push(CURVE_ID_BLS12_381);
+    bytes.push(kind as u8);
+    bytes.push(circuit_hash.is_some() as u8);
+    bytes.extend_from_slice(circuit_hash.unwrap_or(&[0; 32]));
+    bytes
+}
+
+//Validate the header against the expected kind and (when given) circuit and return it with the
+//payload. Artifacts without a circuit hash are accepted for any circuit
+pub fn read_header<'a>(
+    bytes: &'a [u8],
+    kind: ArtifactKind,
+    expected_circuit: Option<&[u8; 32]>,
+) -> Result<(ArtifactHeader, &'a [u8]), ArtifactError> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != ARTIFACT_MAGIC {
+        return Err(ArtifactError::MissingHeader);
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != ARTIFACT_VERSION {
+        return Err(ArtifactError::UnsupportedVersion { found: version });
+    }
+    if bytes[6] != CURVE_ID_BLS12_381 {
+        return Err(ArtifactError::WrongCurve { found: bytes[6].to_string() });
+    }
+    if bytes[7] != kind as u8 {
+        return Err(ArtifactError::WrongKind { expected: kind, found: bytes[7] });
+    }
+    let circuit_hash: Option<[u8; 32]> = (bytes[8] != 0).then(|| bytes[9..HEADER_LEN].try_into().unwrap());
+    if let (Some(expected), Some(found)) = (expected_circuit, &circuit_hash) {
+        if expected != found {
+            return Err(ArtifactError::WrongCircuit { expected: hex::encode(expected), found: hex::encode(found) });
+        }
+    }
+    Ok((ArtifactHeader { version, kind, circuit_hash }, &bytes[HEADER_LEN..]))
+}
+
+//Payload of a headed artifact; with allow_legacy, files without a header are passed through
+//whole (for keys written before headers existed)
+pub fn payload<'a>(
+    bytes: &'a [u8],
+    kind: ArtifactKind,
+    expected_circuit: Option<&[u8; 32]>,
+    allow_legacy: bool,
+) -> Result<&'a [u8], ArtifactError> {
+    match read_header(bytes, kind, expected_circuit) {
+        Ok((_, payload)) => Ok(payload),
+        Err(ArtifactError::MissingHeader) if allow_legacy => Ok(bytes),
+        Err(e) => Err(e),
+    }
+}
+
+//to_artifact/from_artifact: the compressed canonical encoding behind an artifact header
+macro_rules! impl_artifact {
+    ($ty:ident, $inner:ty, $kind:expr) => {
+        impl $ty {
+            pub fn to_artifact(&self, circuit_hash: Option<&[u8; 32]>) -> Result<Vec<u8>, SerializationError> {
+                let mut bytes = write_header($kind, circuit_hash);
+                self.0.serialize_compressed(&mut bytes)?;
+                Ok(bytes)
+            }
+
+            pub fn from_artifact(
+                bytes: &[u8],
+                expected_circuit: Option<&[u8; 32]>,
+                allow_legacy: bool,
+            ) -> Result<Self, ArtifactError> {
+                let payload = payload(bytes, $kind, expected_circuit, allow_legacy)?;
+                Ok($ty(<$inner>::deserialize_compressed(payload)?))
+            }
+        }
+    };
+}
+
+impl_artifact!(ProvingKey, ArkGroth16ProvingKey<ark_bls12_381::Bls12_381>, ArtifactKind::ProvingKey);
+impl_artifact!(VerifyingKey, ArkGroth16VerifyingKey<ark_bls12_381::Bls12_381>, ArtifactKind::VerifyingKey);
+impl_artifact!(Proof, ArkGroth16Proof<ark_bls12_381::Bls12_381>, ArtifactKind::Proof);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mul_circuit;
+    use crate::{setup, Circuit, Gate};
+
+    fn circuit(name: &str, gates: Vec<Gate>) -> Circuit {
+        Circuit { gates, ..mul_circuit(name) }
+    }
+
+    #[test]
+    fn test_vk_for_another_circuit_is_rejected() {
+        let circuit_a = mul_circuit("a");
+        let circuit_b = circuit("b", vec![Gate::Add("a".to_string(), "b".to_string(), "c".to_string(), None)]);
+        let (hash_a, hash_b) = (circuit_a.canonical_hash(), circuit_b.canonical_hash());
+        let (_, vk) = setup(&circuit_a.to_r1cs_system()).expect("Setup failed");
+        let bytes = vk.to_artifact(Some(&hash_a)).unwrap();
+
+        let loaded = VerifyingKey::from_artifact(&bytes, Some(&hash_a), false).expect("Same circuit must load");
+        assert_eq!(loaded.to_bytes().unwrap(), vk.to_bytes().unwrap());
+        let err = VerifyingKey::from_artifact(&bytes, Some(&hash_b), false).err().expect("Other circuit must fail");
+        assert!(matches!(err, ArtifactError::WrongCircuit { .. }), "{}", err);
+        assert!(matches!(ProvingKey::from_artifact(&bytes, None, false), Err(ArtifactError::WrongKind { found: 2, .. })));
+
+        let mut other_curve = bytes.clone();
+        other_curve[6] = 2;
+        assert!(matches!(VerifyingKey::from_artifact(&other_curve, None, false), Err(ArtifactError::WrongCurve { .. })));
+        let mut future = bytes.clone();
+        future[4] = 9;
+        assert!(matches!(VerifyingKey::from_artifact(&future, None, false), Err(ArtifactError::UnsupportedVersion { found: 9 })));
+
+        //Headerless (legacy) keys only load behind the flag
+        let legacy = vk.to_bytes().unwrap();
+        assert!(matches!(VerifyingKey::from_artifact(&legacy, Some(&hash_b), false), Err(ArtifactError::MissingHeader)));
+        assert!(VerifyingKey::from_artifact(&legacy, Some(&hash_b), true).is_ok());
+    }
+
+    #[test]
+    fn test_r1cs_json_header() {
+        let circuit = mul_circuit("a");
+        let mut json = circuit.to_r1cs_system().to_json_struct();
+        json.circuit_hash = Some(hex::encode(circuit.canonical_hash()));
+        assert!(json.check_header(Some(&circuit.canonical_hash()), false).is_ok());
+        assert!(matches!(json.check_header(Some(&[0; 32]), false), Err(ArtifactError::WrongCircuit { .. })));
+
+        json.curve = Some("bn254".to_string());
+        assert!(matches!(json.check_header(None, false), Err(ArtifactError::WrongCurve { ref found }) if found == "bn254"));
+        json.format_version = None;
+        json.curve = None;
+        let legacy = serde_json::to_string(&json).unwrap();
+        assert!(crate::R1CSSystem::from_json(&legacy).unwrap_err().contains("No zk-framework artifact header"));
+        assert!(crate::R1CSSystem::from_json_checked(&legacy, None, true).is_ok());
+    }
+
+    #[test]
+    fn test_header_bytes_are_pinned() {
+        //Every file format builds on this header: a change to it must show up here and bump ARTIFACT_VERSION
+        let expected = hex::encode(b"ZKFA") + "0100" + "01" + "02" + "01" + &"ab".repeat(32);
+        assert_eq!(hex::encode(write_header(ArtifactKind::VerifyingKey, Some(&[0xab; 32]))), expected);
+        let expected = hex::encode(b"ZKFA") + "0100" + "01" + "03" + "00" + &"00".repeat(32);
+        assert_eq!(hex::encode(write_header(ArtifactKind::Proof, None)), expected);
+        assert_eq!(write_header(ArtifactKind::Proof, None).len(), HEADER_LEN);
+    }
+}
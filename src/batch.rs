@@ -0,0 +1,228 @@
+//Batch proving: prove one circuit against every inputs file in a directory
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::parser::parse_inputs;
+use crate::{run_pipeline, setup, Circuit, KeyCache, PipelineOptions, ProvingKey, PublicInputSource, VerifyingKey};
+
+//Keys of each distinct pinned_balances, and each case's inputs with its pinned balances
+type CaseKeys = HashMap<Vec<(String, i32)>, Result<(ProvingKey, VerifyingKey), String>>;
+type ParsedCase = Result<(HashMap<String, i32>, Vec<(String, i32)>), String>;
+
+//Result of proving a single inputs file
+#[derive(Debug, Clone)]
+pub struct BatchCase {
+    pub name: String,
+    pub passed: bool,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+//Summary of a whole batch run, cases are sorted by name
+#[derive(Debug, Clone)]
+pub struct BatchSummary {
+    pub cases: Vec<BatchCase>,
+    pub total_duration: Duration,
+}
+
+impl BatchSummary {
+    pub fn passed(&self) -> usize {
+        self.cases.iter().filter(|c| c.passed).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.cases.len() - self.passed()
+    }
+}
+
+//A copy of the circuit with the inputs applied
+pub fn case_circuit(circuit: &Circuit, inputs: &HashMap<String, i32>) -> Circuit {
+    let mut case_circuit = circuit.clone();
+    for (name, val) in inputs {
+        case_circuit.inputs.insert(name.clone(), *val);
+    }
+    case_circuit
+}
+
+//Apply the inputs to a copy of the circuit, then compute the witness, prove and verify with the given keys.
+//The keys must be for the copy: see pinned_balances for the inputs that change the constraint system
+pub fn prove_with_inputs(
+    circuit: &Circuit,
+    inputs: &HashMap<String, i32>,
+    pk: &ProvingKey,
+    vk: &VerifyingKey,
+) -> Result<bool, String> {
+    let options = PipelineOptions { keys: Some((pk, vk)), ..PipelineOptions::default() };
+    let result = run_pipeline(&case_circuit(circuit, inputs), options).map_err(|e| e.to_string())?;
+    Ok(result.verified.unwrap_or(false))
+}
+
+//Public balances are constants of their pin rows, so circuits differing in them need their own keys.
+//Cases with the same pinned balances share one setup
+pub fn pinned_balances(circuit: &Circuit) -> Vec<(String, i32)> {
+    circuit
+        .to_r1cs_system()
+        .public_input_sources
+        .iter()
+        .filter_map(|source| match source {
+            PublicInputSource::SenderBalance => Some(&circuit.sender),
+            PublicInputSource::ReceiverBalance => Some(&circuit.receiver),
+            _ => None,
+        })
+        .map(|party| (party.clone(), circuit.inputs[party]))
+        .collect()
+}
+
+//List the inputs files of a directory in a stable (sorted) order
+pub fn list_inputs_files(inputs_dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(inputs_dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+//Prove the circuit once per inputs file in inputs_dir, in parallel.
+//Keys are generated (or loaded from the cache) once per distinct pinned_balances and shared by the
+//cases with those balances; a failing case never aborts the rest of the batch.
+pub fn run_batch(circuit: &Circuit, inputs_dir: &Path, cache: Option<&KeyCache>) -> Result<BatchSummary, String> {
+    let start = Instant::now();
+    let files = list_inputs_files(inputs_dir).map_err(|e| format!("Cannot read inputs dir: {}", e))?;
+
+    //Inputs are parsed and keys set up before the workers start, a bad file only fails its own case
+    let mut keys = CaseKeys::new();
+    let mut parsed: Vec<ParsedCase> = Vec::with_capacity(files.len());
+    for path in &files {
+        let case = parse_inputs(&path.to_string_lossy()).map_err(|e| format!("Cannot parse inputs: {}", e)).map(|inputs| {
+            let case_circuit = case_circuit(circuit, &inputs);
+            let pinned = pinned_balances(&case_circuit);
+            keys.entry(pinned.clone()).or_insert_with(|| match cache {
+                Some(cache) => cache.get_or_setup(&case_circuit).map_err(|e| e.to_string()),
+                None => setup(&case_circuit.to_r1cs_system()).map_err(|e| format!("Setup failed: {}", e)),
+            });
+            (inputs, pinned)
+        });
+        parsed.push(case);
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<BatchCase>> = Mutex::new(Vec::with_capacity(files.len()));
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(files.len().max(1));
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(path) = files.get(i) else { break };
+                    let case = run_case(circuit, path, &parsed[i], &keys);
+                    results.lock().unwrap_or_else(|e| e.into_inner()).push(case);
+                })
+            })
+            .collect();
+        for handle in handles {
+            //A panicking worker only loses the case it was running, the others are already recorded
+            let _ = handle.join();
+        }
+    });
+
+    let mut cases = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    //Record any case lost to a panicking worker as a failure
+    for path in &files {
+        let name = case_name(path);
+        if !cases.iter().any(|c| c.name == name) {
+            cases.push(BatchCase { name, passed: false, duration: Duration::ZERO, error: Some("Prover panicked".to_string()) });
+        }
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(BatchSummary { cases, total_duration: start.elapsed() })
+}
+
+fn case_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path.display().to_string())
+}
+
+fn run_case(
+    circuit: &Circuit,
+    path: &Path,
+    case: &ParsedCase,
+    keys: &CaseKeys,
+) -> BatchCase {
+    let start = Instant::now();
+    let outcome = case.clone().and_then(|(inputs, pinned)| {
+        let (pk, vk) = keys[&pinned].as_ref().map_err(Clone::clone)?;
+        prove_with_inputs(circuit, &inputs, pk, vk)
+    });
+
+    let (passed, error) = match outcome {
+        Ok(true) => (true, None),
+        Ok(false) => (false, Some("Proof did not verify".to_string())),
+        Err(e) => (false, Some(e)),
+    };
+    BatchCase { name: case_name(path), passed, duration: start.elapsed(), error }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Gate;
+
+    #[test]
+    fn test_batch_summary_with_failing_case() {
+        let circuit = Circuit {
+            name: "batch_eq".to_string(),
+            inputs: HashMap::from([("a".to_string(), 4), ("b".to_string(), 4)]),
+            gates: vec![
+                Gate::Eq("a".to_string(), "b".to_string(), "diff".to_string()),
+                Gate::Mul("a".to_string(), "b".to_string(), "prod".to_string(), None),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+
+        let dir = std::env::temp_dir().join(format!("zk_batch_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("case1.txt"), "input a 7\ninput b 7\n").unwrap();
+        std::fs::write(dir.join("case2.txt"), "input a 7\ninput b 8\n").unwrap();
+        std::fs::write(dir.join("case3.txt"), "// unchanged inputs\n").unwrap();
+
+        let summary = run_batch(&circuit, &dir, None).expect("Batch failed");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(summary.cases.len(), 3);
+        assert_eq!(summary.passed(), 2);
+        assert_eq!(summary.failed(), 1);
+        assert!(summary.cases[0].passed);
+        assert!(!summary.cases[1].passed);
+        assert!(summary.cases[1].error.as_ref().unwrap().contains("Equality constraint failed"));
+        assert!(summary.cases[2].passed);
+    }
+
+    #[test]
+    fn test_batch_overriding_a_pinned_balance() {
+        let circuit = crate::parse_circuit_str("name batch_transfer\ninput alice 10\ninput bob 3\ninput x 2\nsender alice\nreceiver bob\namount 5\nmul x x y\n").unwrap();
+        let dir = std::env::temp_dir().join(format!("zk_batch_pinned_test_{}", std::process::id()));
+        let inputs_dir = dir.join("inputs");
+        std::fs::create_dir_all(&inputs_dir).unwrap();
+        std::fs::write(inputs_dir.join("case1.txt"), "input alice 20\n").unwrap();
+        std::fs::write(inputs_dir.join("case2.txt"), "input alice 20\ninput x 3\n").unwrap();
+        std::fs::write(inputs_dir.join("case3.txt"), "input x 4\n").unwrap();
+
+        //alice's balance is a constant of its pin row, the cases with 20 share keys of their own
+        let cache = KeyCache::new(dir.join("keys"));
+        let summary = run_batch(&circuit, &inputs_dir, Some(&cache)).expect("Batch failed");
+        assert_eq!(summary.passed(), 3, "{:?}", summary.cases);
+        assert_eq!(cache.setup_count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+//Constraint budget: the most rows and variables a circuit may compile to, checked while compiling
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstraintBudget {
+    pub max_constraints: usize,
+    //Counted like R1CSSystem::num_variables, the constant one included
+    pub max_variables: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    //The gate whose rows went over the budget, None for rows no gate emits (the transfer spec,
+    //input binding and wire type range checks)
+    pub gate_index: Option<usize>,
+    //Running totals at that point
+    pub constraints: usize,
+    pub variables: usize,
+    pub budget: ConstraintBudget,
+}
+
+impl ConstraintBudget {
+    //Err when the running totals are over the budget
+    pub(crate) fn check(&self, gate_index: Option<usize>, constraints: usize, variables: usize) -> Result<(), BudgetExceeded> {
+        if constraints > self.max_constraints || variables > self.max_variables {
+            return Err(BudgetExceeded { gate_index, constraints, variables, budget: *self });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.gate_index {
+            Some(gate_index) => write!(f, "Constraint budget exceeded at gate #{}", gate_index)?,
+            None => write!(f, "Constraint budget exceeded outside the gates")?,
+        }
+        write!(
+            f,
+            ": {} constraints (max {}), {} variables (max {})",
+            self.constraints, self.budget.max_constraints, self.variables, self.budget.max_variables
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples_support::TRANSFER_DSL;
+    use crate::{parse_circuit_str, run_pipeline, PipelineError, PipelineOptions};
+
+    #[test]
+    fn test_budget_reports_the_gate_that_exceeds_it() {
+        let circuit = parse_circuit_str("name chain
+input x 3
+amount 0
+mul x x x2
+mul x2 x x3
+mul x3 x x4
+mul x4 x x5
+").unwrap();
+        let full = circuit.to_r1cs_system();
+        let rows = full.raw_constraints.len();
+        let generous = ConstraintBudget { max_constraints: rows, max_variables: full.num_variables };
+        let bounded = circuit.to_r1cs_system_bounded(generous).unwrap();
+        assert_eq!(bounded.raw_constraints.len(), rows);
+        assert_eq!(bounded.var_map, full.var_map);
+
+        //One row short: the last gate is the one over
+        let tight = ConstraintBudget { max_constraints: rows - 1, ..generous };
+        let err = circuit.to_r1cs_system_bounded(tight).unwrap_err();
+        assert_eq!(err.gate_index, Some(3));
+        assert_eq!((err.constraints, err.variables), (rows, full.num_variables));
+        assert_eq!(err.budget, tight);
+
+        //Each mul allocates one variable, so two short stops at gate #2
+        let few_variables = ConstraintBudget { max_variables: full.num_variables - 2, ..generous };
+        let err = circuit.to_r1cs_system_bounded(few_variables).unwrap_err();
+        assert_eq!(err.gate_index, Some(2));
+        assert_eq!(err.variables, full.num_variables - 1);
+        assert_eq!(err.constraints, rows - 1);
+        assert_eq!(
+            err.to_string(),
+            format!("Constraint budget exceeded at gate #2: {} constraints (max {}), {} variables (max {})",
+                rows - 1, rows, full.num_variables - 1, full.num_variables - 2)
+        );
+
+        //The transfer spec rows come before any gate
+        let transfer = parse_circuit_str(TRANSFER_DSL).unwrap();
+        let err = transfer.to_r1cs_system_bounded(ConstraintBudget { max_constraints: 0, max_variables: usize::MAX }).unwrap_err();
+        assert_eq!(err.gate_index, None);
+
+        //The pipeline stops before setup
+        let options = PipelineOptions { budget: Some(tight), ..PipelineOptions::default() };
+        match run_pipeline(&circuit, options) {
+            Err(PipelineError::Budget(err)) => assert_eq!(err.gate_index, Some(3)),
+            other => panic!("Expected a budget error, got {:?}", other.map(|result| result.stats)),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//Proving/verifying key cache under <cache_dir>/<canonical circuit hash>/, keys are artifacts
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ark_serialize::SerializationError;
+use serde::{Deserialize, Serialize};
+
+use crate::{setup_with_progress, Circuit, NoProgress, ProgressSink, ProveError, ProvingKey, VerifyingKey};
+
+pub use crate::artifact::CURVE_NAME;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const PK_FILE: &str = "pk.bin";
+const VK_FILE: &str = "vk.bin";
+//Version of the rows to_r1cs_system emits for a circuit, bumped whenever the same circuit
+//compiles to other rows (2: exact duplicate rows are dropped, 3: private balances equal their
+//input wires), since keys of the old rows no longer prove
+const R1CS_FORMAT: u32 = 3;
+
+//Written next to the keys, a cache entry is only reused when every field matches
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheManifest {
+    crate_version: String,
+    curve: String,
+    circuit_hash: String,
+    //Missing in manifests written before the format was recorded, which then never match
+    r1cs_format: u32,
+}
+
+#[derive(Debug)]
+pub enum KeyCacheError {
+    Io(std::io::Error),
+    Serialization(SerializationError),
+    Setup(ProveError),
+}
+
+impl fmt::Display for KeyCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyCacheError::Io(e) => write!(f, "Key cache I/O error: {}", e),
+            KeyCacheError::Serialization(e) => write!(f, "Key serialization error: {}", e),
+            KeyCacheError::Setup(e) => write!(f, "Setup failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for KeyCacheError {}
+
+impl From<std::io::Error> for KeyCacheError {
+    fn from(e: std::io::Error) -> Self {
+        KeyCacheError::Io(e)
+    }
+}
+
+impl From<SerializationError> for KeyCacheError {
+    fn from(e: SerializationError) -> Self {
+        KeyCacheError::Serialization(e)
+    }
+}
+
+pub struct KeyCache {
+    dir: PathBuf,
+    //Number of setups run by this cache (i.e. cache misses)
+    setups: AtomicUsize,
+}
+
+impl KeyCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        KeyCache { dir: dir.into(), setups: AtomicUsize::new(0) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn setup_count(&self) -> usize {
+        self.setups.load(Ordering::SeqCst)
+    }
+
+    //Directory holding the keys of a circuit
+    pub fn entry_dir(&self, circuit: &Circuit) -> PathBuf {
+        self.dir.join(hex::encode(circuit.canonical_hash()))
+    }
+
+    //Return the cached keys for the circuit, running setup (and caching the result) on a miss
+    pub fn get_or_setup(&self, circuit: &Circuit) -> Result<(ProvingKey, VerifyingKey), KeyCacheError> {
+        self.get_or_setup_with_progress(circuit, &NoProgress)
+    }
+
+    pub fn get_or_setup_with_progress(
+        &self,
+        circuit: &Circuit,
+        progress: &dyn ProgressSink,
+    ) -> Result<(ProvingKey, VerifyingKey), KeyCacheError> {
+        self.fetch(circuit, progress).map(|(pk, vk, _)| (pk, vk))
+    }
+
+    //get_or_setup_with_progress, also telling whether the keys came from the cache
+    pub(crate) fn fetch(
+        &self,
+        circuit: &Circuit,
+        progress: &dyn ProgressSink,
+    ) -> Result<(ProvingKey, VerifyingKey, bool), KeyCacheError> {
+        let circuit_hash = circuit.canonical_hash();
+        let manifest = CacheManifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            curve: CURVE_NAME.to_string(),
+            circuit_hash: hex::encode(circuit_hash),
+            r1cs_format: R1CS_FORMAT,
+        };
+        let entry_dir = self.dir.join(&manifest.circuit_hash);
+
+        //Any unreadable, stale or corrupt entry (including headerless keys of older versions) is
+        //treated as a miss and overwritten
+        if let Some((pk, vk)) = load_entry(&entry_dir, &manifest, &circuit_hash) {
+            return Ok((pk, vk, true));
+        }
+
+        let r1cs = circuit.to_r1cs_system();
+        let (pk, vk) = setup_with_progress(&r1cs, progress).map_err(KeyCacheError::Setup)?;
+        self.setups.fetch_add(1, Ordering::SeqCst);
+
+        fs::create_dir_all(&entry_dir)?;
+        fs::write(entry_dir.join(PK_FILE), pk.to_artifact(Some(&circuit_hash))?)?;
+        fs::write(entry_dir.join(VK_FILE), vk.to_artifact(Some(&circuit_hash))?)?;
+        //The manifest is written last so a partially written entry is never considered valid
+        let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(std::io::Error::other)?;
+        fs::write(entry_dir.join(MANIFEST_FILE), manifest_json)?;
+
+        Ok((pk, vk, false))
+    }
+}
+
+fn load_entry(entry_dir: &Path, expected: &CacheManifest, circuit_hash: &[u8; 32]) -> Option<(ProvingKey, VerifyingKey)> {
+    let manifest: CacheManifest = serde_json::from_slice(&fs::read(entry_dir.join(MANIFEST_FILE)).ok()?).ok()?;
+    if &manifest != expected {
+        return None;
+    }
+    let pk = ProvingKey::from_artifact(&fs::read(entry_dir.join(PK_FILE)).ok()?, Some(circuit_hash), false).ok()?;
+    let vk = VerifyingKey::from_artifact(&fs::read(entry_dir.join(VK_FILE)).ok()?, Some(circuit_hash), false).ok()?;
+    Some((pk, vk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mul_circuit;
+    use crate::{run_pipeline, Gate, PipelineOptions};
+
+    #[test]
+    fn test_cache_hit_and_miss() {
+        let mut circuit = mul_circuit("cached");
+        let dir = std::env::temp_dir().join(format!("zk_key_cache_test_{}", std::process::id()));
+        let cache = KeyCache::new(&dir);
+
+        let (pk, vk) = cache.get_or_setup(&circuit).expect("Setup failed");
+        assert_eq!(cache.setup_count(), 1);
+        let (cached_pk, cached_vk) = cache.get_or_setup(&circuit).expect("Cache load failed");
+        assert_eq!(cache.setup_count(), 1, "Second call for the same circuit must hit the cache");
+        assert_eq!(vk.to_bytes().unwrap(), cached_vk.to_bytes().unwrap());
+        assert_eq!(pk.to_bytes().unwrap(), cached_pk.to_bytes().unwrap());
+
+        //Cached keys still prove and verify
+        let result = run_pipeline(&circuit, PipelineOptions { keys: Some((&cached_pk, &cached_vk)), ..PipelineOptions::default() })
+            .expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+        assert_eq!(result.timings.setup_or_cache, std::time::Duration::ZERO, "A cache hit runs no setup");
+
+        //The name is not part of the hash, any gate change is
+        circuit.name = "renamed".to_string();
+        cache.get_or_setup(&circuit).expect("Cache load failed");
+        assert_eq!(cache.setup_count(), 1);
+        circuit.gates[0] = Gate::Add("a".to_string(), "b".to_string(), "c".to_string(), None);
+        cache.get_or_setup(&circuit).expect("Setup failed");
+        assert_eq!(cache.setup_count(), 2, "A gate change must miss the cache");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+//Canonical form of an R1CS system: rows ordered by origin and variables renumbered by first use,
+//so the DSL and Circuit values built in code give the same keys
+use std::collections::{HashMap, HashSet};
+
+use ark_bls12_381::Fr;
+
+use crate::{R1CSSystem, _R1CSConstraintInternal};
+
+fn sorted_terms(terms: &HashMap<usize, Fr>) -> Vec<(usize, Fr)> {
+    let mut sorted: Vec<(usize, Fr)> = terms.iter().map(|(idx, coeff)| (*idx, *coeff)).collect();
+    sorted.sort_by(|(idx_a, coeff_a), (idx_b, coeff_b)| coeff_a.cmp(coeff_b).then(idx_a.cmp(idx_b)));
+    sorted
+}
+
+impl R1CSSystem {
+    //Canonical row order and the new index of every variable
+    fn canonical_order(&self) -> (Vec<usize>, HashMap<usize, usize>) {
+        let mut rows: Vec<usize> = (0..self.raw_constraints.len()).collect();
+        if self.constraint_origins.len() == rows.len() {
+            rows.sort_by_key(|row| self.constraint_origins[*row].gate_index.map_or(0, |gate_index| gate_index + 1));
+        }
+
+        let mut renumbered: HashMap<usize, usize> = HashMap::new();
+        let mut number = |idx: usize| {
+            let next = renumbered.len();
+            renumbered.entry(idx).or_insert(next);
+        };
+        let fixed = std::iter::once("1").chain(self.public_input_names.iter().map(String::as_str));
+        fixed.filter_map(|name| self.var_map.get(name)).for_each(|idx| number(*idx));
+        for row in &rows {
+            let constraint = &self.raw_constraints[*row];
+            for terms in [&constraint.a, &constraint.b, &constraint.c] {
+                sorted_terms(terms).into_iter().for_each(|(idx, _)| number(idx));
+            }
+        }
+        //Variables no constraint reads
+        let mut unused: Vec<usize> = self.var_map.values().copied().collect();
+        unused.sort_unstable();
+        unused.into_iter().for_each(number);
+        (rows, renumbered)
+    }
+
+    pub fn canonicalize(&self) -> R1CSSystem {
+        let (rows, renumbered) = self.canonical_order();
+        let public: HashSet<&String> = self.public_input_names.iter().collect();
+        let rename = |terms: &HashMap<usize, Fr>| terms.iter().map(|(idx, coeff)| (renumbered[idx], *coeff)).collect();
+        R1CSSystem {
+            raw_constraints: rows
+                .iter()
+                .map(|row| {
+                    let constraint = &self.raw_constraints[*row];
+                    _R1CSConstraintInternal { a: rename(&constraint.a), b: rename(&constraint.b), c: rename(&constraint.c) }
+                })
+                .collect(),
+            constraint_origins: if self.constraint_origins.len() == rows.len() {
+                rows.iter().map(|row| self.constraint_origins[*row].clone()).collect()
+            } else {
+                Vec::new()
+            },
+            var_map: self
+                .var_map
+                .iter()
+                .map(|(name, idx)| {
+                    let kept = name == "1" || public.contains(name);
+                    (if kept { name.clone() } else { format!("w{}", renumbered[idx]) }, renumbered[idx])
+                })
+                .collect(),
+            num_variables: self.num_variables,
+            num_public_inputs: self.num_public_inputs,
+            public_input_names: self.public_input_names.clone(),
+            public_input_sources: self.public_input_sources.clone(),
+            duplicate_rows: self.duplicate_rows,
+        }
+    }
+
+    //Move a witness of this system (e.g. from Circuit::compute_witness) to the numbering of canonicalize()
+    pub fn canonicalize_witness(&self, witness_by_idx: &HashMap<usize, Fr>) -> HashMap<usize, Fr> {
+        let (_, renumbered) = self.canonical_order();
+        witness_by_idx.iter().filter_map(|(idx, val)| renumbered.get(idx).map(|new_idx| (*new_idx, *val))).collect()
+    }
+
+    //Same constraints, variables and public inputs up to row order and private variable names
+    pub fn structural_eq(&self, other: &R1CSSystem) -> bool {
+        self.canonicalize().to_json_struct() == other.canonicalize().to_json_struct()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_circuit_str, prove, setup, verify, Circuit, Gate};
+
+    const DSL: &str = "name doubled_sum
+input a 10
+input b 20
+amount 5
+const two 2
+add a b sum
+mul sum two doubled
+";
+
+    fn built_circuit(second_gate: Gate) -> Circuit {
+        Circuit {
+            name: "doubled_sum".to_string(),
+            inputs: HashMap::from([("a".to_string(), 10), ("b".to_string(), 20)]),
+            gates: vec![Gate::Const("k".to_string(), 2), Gate::Add("b".to_string(), "a".to_string(), "s".to_string(), None), second_gate],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parser_and_built_circuit_share_keys() {
+        let parsed = parse_circuit_str(DSL).unwrap();
+        let built = built_circuit(Gate::Mul("s".to_string(), "k".to_string(), "out".to_string(), None));
+        let (parsed_r1cs, built_r1cs) = (parsed.to_r1cs_system(), built.to_r1cs_system());
+        //Other wire names and operand order give different raw systems
+        assert_ne!(parsed_r1cs.to_json_struct(), built_r1cs.to_json_struct());
+        assert!(parsed_r1cs.structural_eq(&built_r1cs));
+        assert!(!parsed_r1cs.structural_eq(&built_circuit(Gate::Add("s".to_string(), "k".to_string(), "out".to_string(), None)).to_r1cs_system()));
+
+        let parsed_canonical = parsed_r1cs.canonicalize();
+        let (pk, vk) = setup(&parsed_canonical).expect("Setup failed");
+        let built_canonical = built_r1cs.canonicalize();
+        let witness = built_r1cs.canonicalize_witness(&built.compute_witness(&built_r1cs.var_map).unwrap());
+        let public_inputs = built_canonical.public_inputs_from_witness(&witness).unwrap();
+        let proof = prove(&built_canonical, &pk, witness).expect("Proving failed");
+        assert!(verify(&vk, &proof, &public_inputs).unwrap());
+    }
+}
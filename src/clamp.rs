@@ -0,0 +1,132 @@
+//Clamp gate, out = min(x, cap) for a constant cap (DSL `clamp x 1000 y`), 66 rows
+use std::collections::HashMap;
+
+use ark_bls12_381::Fr;
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+
+use crate::{get_index, i64_to_fr, sum_terms, _R1CSConstraintInternal};
+
+//Bits of the comparison, the top one being the x >= cap flag
+pub const CLAMP_BITS: u32 = 64;
+
+//Rows emitted by one clamp gate
+pub fn constraint_cost() -> usize {
+    CLAMP_BITS as usize + 2
+}
+
+fn bit_name(out: &str, i: usize) -> String {
+    format!("{}_clamp_bit{}", out, i)
+}
+
+//2^63 - cap, the constant term of d
+fn offset(cap: i64) -> Fr {
+    Fr::from(1u64 << (CLAMP_BITS - 1)) - i64_to_fr(cap)
+}
+
+//Wires the gate assigns: the bits of d, then out
+pub(crate) fn clamp_witness(x: &str, cap: i64, out: &str, values: &HashMap<String, Fr>) -> Result<Vec<(String, Fr)>, String> {
+    let x_val = *values.get(x).ok_or_else(|| format!("Var {} not found", x))?;
+    let le_bits = (x_val + offset(cap)).into_bigint().to_bits_le();
+    if le_bits.iter().skip(CLAMP_BITS as usize).any(|bit| *bit) {
+        return Err(format!("Operand '{}' of clamp is {}, which is not within 2^63 of the cap {}", x, x_val, cap));
+    }
+    let mut assigned: Vec<(String, Fr)> = le_bits
+        .iter()
+        .take(CLAMP_BITS as usize)
+        .enumerate()
+        .map(|(i, bit)| (bit_name(out, i), if *bit { Fr::one() } else { Fr::zero() }))
+        .collect();
+    let above = le_bits[CLAMP_BITS as usize - 1];
+    assigned.push((out.to_string(), if above { i64_to_fr(cap) } else { x_val }));
+    Ok(assigned)
+}
+
+//Emit the constraint_cost() rows of one gate, in the order of clamp_roles
+pub(crate) fn emit_clamp_rows(
+    x: &str,
+    cap: i64,
+    out: &str,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    let one_idx = var_map["1"];
+    let x_idx = get_index(x, var_map, next_index);
+    let mut recomposition = Vec::with_capacity(CLAMP_BITS as usize);
+    let mut weight = Fr::one();
+    for i in 0..CLAMP_BITS as usize {
+        let idx = get_index(&bit_name(out, i), var_map, next_index);
+        constraints.push(_R1CSConstraintInternal {
+            a: HashMap::from([(idx, Fr::one())]),
+            b: HashMap::from([(idx, Fr::one())]),
+            c: HashMap::from([(idx, Fr::one())]),
+        });
+        recomposition.push((idx, weight));
+        weight += weight;
+    }
+    let above_idx = recomposition[CLAMP_BITS as usize - 1].0;
+    constraints.push(_R1CSConstraintInternal {
+        a: sum_terms(&recomposition),
+        b: HashMap::from([(one_idx, Fr::one())]),
+        c: sum_terms(&[(x_idx, Fr::one()), (one_idx, offset(cap))]),
+    });
+    let out_idx = get_index(out, var_map, next_index);
+    constraints.push(_R1CSConstraintInternal {
+        a: HashMap::from([(above_idx, Fr::one())]),
+        b: sum_terms(&[(one_idx, i64_to_fr(cap)), (x_idx, -Fr::one())]),
+        c: sum_terms(&[(out_idx, Fr::one()), (x_idx, -Fr::one())]),
+    });
+}
+
+//Role of each row emit_clamp_rows emits
+pub(crate) fn clamp_roles(x: &str, cap: i64) -> Vec<String> {
+    let mut roles: Vec<String> = (0..CLAMP_BITS).map(|i| format!("booleanity of comparison bit {}", i)).collect();
+    roles.push(format!("{} - {} + 2^63 recomposition", x, cap));
+    roles.push(format!("select {} or {}", x, cap));
+    roles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_circuit_str, run_pipeline, PipelineOptions};
+
+    const DSL: &str = "name rate_limit
+input x 1500
+public limited 1000
+clamp x 1000 y
+eq y limited
+";
+
+    #[test]
+    fn test_clamp_below_at_and_above_the_cap() {
+        let circuit = parse_circuit_str(DSL).unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        let rows = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(0)).count();
+        assert_eq!(rows, constraint_cost());
+        for (x, expected) in [(999, 999), (1000, 1000), (1500, 1000), (0, 0)] {
+            let mut case = circuit.clone();
+            case.inputs.insert("x".to_string(), x);
+            case.public_inputs[0].1 = i64_to_fr(expected);
+            let witness = case.compute_witness(&r1cs.var_map).unwrap();
+            assert_eq!(witness[&r1cs.var_map["y"]], i64_to_fr(expected), "clamp of {}", x);
+            assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None, "clamp of {}", x);
+        }
+
+        //The clamped output is public: the proof verifies for 1000 and not for the unclamped 1500
+        let options = PipelineOptions { return_keys: true, ..PipelineOptions::default() };
+        let result = run_pipeline(&circuit, options).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+        assert_eq!(result.public_inputs[1], i64_to_fr(1000));
+        let (_, vk) = result.keys.expect("Keys were requested");
+        let mut unclamped = result.public_inputs.clone();
+        unclamped[1] = i64_to_fr(1500);
+        assert!(!crate::verify(&vk, &result.proof, &unclamped).unwrap());
+
+        //Claiming the unclamped value breaks the select
+        let mut witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["y"], i64_to_fr(1500));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("y must be clamped");
+        assert!(r1cs.explain(failing).contains("select x or 1000"), "{}", r1cs.explain(failing));
+    }
+}
@@ -0,0 +1,180 @@
+//Pedersen commitments to amounts, C = value * G + blinding * H over BLS12-381 G1 (H is hashed
+//to the curve, so nobody knows log_G(H)), with Schnorr-style Fiat-Shamir opening proofs
+use std::ops::{Add, Sub};
+use std::sync::OnceLock;
+
+use ark_bls12_381::{Fq, Fr, G1Affine, G1Projective};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::rand::{CryptoRng, RngCore};
+use ark_std::UniformRand;
+use sha2::{Digest, Sha256};
+
+const H_DOMAIN_TAG: &[u8] = b"zk-framework/pedersen/H";
+const CHALLENGE_DOMAIN_TAG: &[u8] = b"zk-framework/pedersen/opening";
+
+pub struct PedersenParams {
+    pub g: G1Affine,
+    pub h: G1Affine,
+}
+
+pub fn params() -> &'static PedersenParams {
+    static PARAMS: OnceLock<PedersenParams> = OnceLock::new();
+    PARAMS.get_or_init(|| PedersenParams { g: G1Affine::generator(), h: hash_to_g1(H_DOMAIN_TAG) })
+}
+
+//Deterministic nothing-up-my-sleeve point: the first x = H(tag || counter) on the curve
+fn hash_to_g1(tag: &[u8]) -> G1Affine {
+    for counter in 0u64.. {
+        let digest = Sha256::new().chain_update(tag).chain_update(counter.to_le_bytes()).finalize();
+        let x = Fq::from_le_bytes_mod_order(&digest);
+        if let Some(point) = G1Affine::get_point_from_x_unchecked(x, false) {
+            let point = point.clear_cofactor();
+            if !point.is_zero() {
+                return point;
+            }
+        }
+    }
+    unreachable!("about half of all x coordinates are on the curve")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Commitment(pub G1Affine);
+
+impl Commitment {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = Vec::new();
+        self.0.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        Ok(Commitment(G1Affine::deserialize_compressed(bytes)?))
+    }
+
+    //Commitment to zero with zero blinding, the identity for homomorphic sums
+    pub fn zero() -> Self {
+        Commitment(G1Affine::zero())
+    }
+}
+
+//Homomorphic addition: commit(a, r) + commit(b, s) == commit(a + b, r + s)
+impl Add for Commitment {
+    type Output = Commitment;
+    fn add(self, other: Commitment) -> Commitment {
+        Commitment((self.0 + other.0).into_affine())
+    }
+}
+
+impl Sub for Commitment {
+    type Output = Commitment;
+    fn sub(self, other: Commitment) -> Commitment {
+        Commitment((self.0.into_group() - other.0).into_affine())
+    }
+}
+
+pub fn commit(value: Fr, blinding: Fr) -> Commitment {
+    let p = params();
+    Commitment((p.g * value + p.h * blinding).into_affine())
+}
+
+//Commit with a fresh random blinding factor, returning it for the later opening
+pub fn commit_random<R: RngCore + CryptoRng>(value: Fr, rng: &mut R) -> (Commitment, Fr) {
+    let blinding = Fr::rand(rng);
+    (commit(value, blinding), blinding)
+}
+
+pub fn verify_opening(commitment: &Commitment, value: Fr, blinding: Fr) -> bool {
+    commit(value, blinding) == *commitment
+}
+
+pub fn sum_commitments(commitments: &[Commitment]) -> Commitment {
+    let sum: G1Projective = commitments.iter().map(|c| c.0.into_group()).sum();
+    Commitment(sum.into_affine())
+}
+
+//Conservation check: inputs and outputs commit to the same total value, given that the
+//prover chose the blindings to balance as well (sum of input blindings == sum of output blindings)
+pub fn commitments_balance(inputs: &[Commitment], outputs: &[Commitment]) -> bool {
+    sum_commitments(inputs) == sum_commitments(outputs)
+}
+
+//Proof of knowledge of an opening (value, blinding) that reveals neither
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningProof {
+    pub t: G1Affine,
+    pub s_value: Fr,
+    pub s_blinding: Fr,
+}
+
+fn challenge(commitment: &Commitment, t: &G1Affine) -> Fr {
+    let mut bytes = CHALLENGE_DOMAIN_TAG.to_vec();
+    for point in [params().g, params().h, commitment.0, *t] {
+        point.serialize_compressed(&mut bytes).expect("Serializing into a Vec cannot fail");
+    }
+    Fr::from_le_bytes_mod_order(&Sha256::digest(&bytes))
+}
+
+pub fn prove_opening<R: RngCore + CryptoRng>(commitment: &Commitment, value: Fr, blinding: Fr, rng: &mut R) -> OpeningProof {
+    let p = params();
+    let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+    let t = (p.g * a + p.h * b).into_affine();
+    let e = challenge(commitment, &t);
+    OpeningProof { t, s_value: a + e * value, s_blinding: b + e * blinding }
+}
+
+//Checks s_value * G + s_blinding * H == T + e * C
+pub fn verify_opening_proof(commitment: &Commitment, proof: &OpeningProof) -> bool {
+    let p = params();
+    let e = challenge(commitment, &proof.t);
+    let lhs = p.g * proof.s_value + p.h * proof.s_blinding;
+    let rhs = proof.t.into_group() + commitment.0 * e;
+    lhs == rhs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_binding_hiding_and_homomorphism() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let r = Fr::rand(&mut rng);
+        let c = commit(Fr::from(100u64), r);
+
+        assert_eq!(params().h, hash_to_g1(H_DOMAIN_TAG), "H must be deterministic");
+        assert_ne!(params().g, params().h);
+        assert!(verify_opening(&c, Fr::from(100u64), r));
+        assert!(!verify_opening(&c, Fr::from(101u64), r), "Binding: another value must not open the commitment");
+        assert_ne!(c, commit(Fr::from(100u64), Fr::rand(&mut rng)), "Hiding: fresh blinding gives a fresh commitment");
+
+        let s = Fr::rand(&mut rng);
+        assert_eq!(c + commit(Fr::from(23u64), s), commit(Fr::from(123u64), r + s));
+        assert_eq!(commit(Fr::from(123u64), r + s) - c, commit(Fr::from(23u64), s));
+
+        //Balance 100 -> 60 + 40 with blindings that also balance
+        let r1 = Fr::rand(&mut rng);
+        let outputs = [commit(Fr::from(60u64), r1), commit(Fr::from(40u64), r - r1)];
+        assert!(commitments_balance(&[c], &outputs));
+        let minted = [commit(Fr::from(61u64), r1), commit(Fr::from(40u64), r - r1)];
+        assert!(!commitments_balance(&[c], &minted));
+
+        assert_eq!(Commitment::from_bytes(&c.to_bytes().unwrap()).unwrap(), c);
+        assert_eq!(c.to_bytes().unwrap().len(), 48);
+    }
+
+    #[test]
+    fn test_opening_proof() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let (c, r) = commit_random(Fr::from(5000u64), &mut rng);
+        let proof = prove_opening(&c, Fr::from(5000u64), r, &mut rng);
+        assert!(verify_opening_proof(&c, &proof));
+
+        let other = commit(Fr::from(5000u64), Fr::rand(&mut rng));
+        assert!(!verify_opening_proof(&other, &proof));
+        let forged = prove_opening(&c, Fr::from(4999u64), r, &mut rng);
+        assert!(!verify_opening_proof(&c, &forged));
+    }
+}
@@ -0,0 +1,113 @@
+//Upgrade check: does a circuit still compile to the system of an earlier R1CS JSON export?
+//Any change to the rows needs a new trusted setup
+use std::fmt;
+
+use crate::export::R1CSJson;
+use crate::Circuit;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatReport {
+    //Same variable count, public input count and rows: old keys and proofs stay valid
+    pub structure_equal: bool,
+    //Same public input names in the same order (and the same schema, when the old export has one)
+    pub public_inputs_equal: bool,
+    //None when the old export carries no circuit hash. A different hash alone keeps the keys
+    //valid, but artifacts tagged with the old hash fail checked loads against the new circuit
+    pub circuit_hash_equal: Option<bool>,
+    //One line per difference found
+    pub differences: Vec<String>,
+}
+
+impl CompatReport {
+    pub fn is_compatible(&self) -> bool {
+        self.structure_equal && self.public_inputs_equal
+    }
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.is_compatible() { "compatible" } else { "incompatible" };
+        write!(f, "Constraint system: {}", verdict)?;
+        for difference in &self.differences {
+            write!(f, "\n  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+//Compare an earlier R1CS JSON export with the system new_circuit compiles to now
+pub fn check(old_r1cs_json: &str, new_circuit: &Circuit) -> Result<CompatReport, String> {
+    let old: R1CSJson = serde_json::from_str(old_r1cs_json).map_err(|e| format!("Invalid R1CS JSON: {}", e))?;
+    old.check_header(None, true).map_err(|e| format!("Invalid R1CS JSON: {}", e))?;
+    let new = new_circuit.to_r1cs_system().to_json_struct();
+    let mut differences = Vec::new();
+
+    if old.num_variables != new.num_variables {
+        differences.push(format!("{} variables, was {}", new.num_variables, old.num_variables));
+    }
+    if old.num_public_inputs != new.num_public_inputs {
+        differences.push(format!("{} public inputs, was {}", new.num_public_inputs, old.num_public_inputs));
+    }
+    if old.constraints.len() != new.constraints.len() {
+        differences.push(format!("{} constraints, was {}", new.constraints.len(), old.constraints.len()));
+    }
+    let changed_rows = old.constraints.iter().zip(&new.constraints).filter(|(old_row, new_row)| old_row != new_row).count();
+    if let Some(first) = old.constraints.iter().zip(&new.constraints).position(|(old_row, new_row)| old_row != new_row) {
+        differences.push(format!("{} constraints changed, the first is constraint {}", changed_rows, first));
+    }
+    let structure_equal = differences.is_empty();
+
+    let mut public_inputs_equal = old.public_input_names == new.public_input_names;
+    if !public_inputs_equal {
+        differences.push(format!("public inputs [{}], were [{}]", new.public_input_names.join(", "), old.public_input_names.join(", ")));
+    } else if old.public_input_schema.is_some() && old.public_input_schema != new.public_input_schema {
+        public_inputs_equal = false;
+        differences.push("public input schema changed".to_string());
+    }
+
+    let new_hash = hex::encode(new_circuit.canonical_hash());
+    let circuit_hash_equal = old.circuit_hash.as_ref().map(|old_hash| *old_hash == new_hash);
+    if let (Some(false), Some(old_hash)) = (circuit_hash_equal, &old.circuit_hash) {
+        differences.push(format!("circuit hash {}, was {}", new_hash, old_hash));
+    }
+    Ok(CompatReport { structure_equal, public_inputs_equal, circuit_hash_equal, differences })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples_support::TRANSFER_DSL;
+    use crate::{parse_circuit_str, Gate};
+
+    const TRANSFER_R1CS: &str = include_str!("../fixtures/transfer/r1cs.json");
+
+    #[test]
+    fn test_changed_gate_breaks_compatibility() {
+        let circuit = parse_circuit_str(TRANSFER_DSL).unwrap();
+        let report = check(TRANSFER_R1CS, &circuit).unwrap();
+        assert!(report.is_compatible(), "{}", report);
+        assert_eq!(report.circuit_hash_equal, Some(true));
+        assert!(report.differences.is_empty());
+
+        //Exports without a circuit hash are compared on structure alone
+        let mut unhashed: serde_json::Value = serde_json::from_str(TRANSFER_R1CS).unwrap();
+        unhashed.as_object_mut().unwrap().remove("circuit_hash");
+        let report = check(&unhashed.to_string(), &circuit).unwrap();
+        assert!(report.is_compatible(), "{}", report);
+        assert_eq!(report.circuit_hash_equal, None);
+
+        //Adding instead of subtracting changes the sender's row
+        let mut changed = circuit.clone();
+        let Gate::Sub(a, b, c, m) = changed.gates[0].clone() else { panic!("transfer starts with a sub") };
+        changed.gates[0] = Gate::Add(a, b, c, m);
+        let report = check(TRANSFER_R1CS, &changed).unwrap();
+        assert!(!report.is_compatible());
+        assert!(!report.structure_equal);
+        assert!(report.public_inputs_equal);
+        assert!(report.to_string().starts_with("Constraint system: incompatible"), "{}", report);
+        assert!(report.differences[0].contains("constraints changed"), "{:?}", report.differences);
+        assert_eq!(report.circuit_hash_equal, Some(false));
+
+        assert!(check("{}", &circuit).unwrap_err().starts_with("Invalid R1CS JSON"));
+    }
+}
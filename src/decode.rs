@@ -0,0 +1,126 @@
+//Decode gate: an index wire to a one-hot vector (DSL `decode idx o0 o1 o2 o3`), an index
+//outside 0..n has no solution
+use std::collections::HashMap;
+
+use ark_bls12_381::Fr;
+use ark_ff::{One, Zero};
+
+use crate::{get_index, sum_terms, _R1CSConstraintInternal};
+
+//Rows emitted by a decode gate with n outputs
+pub fn constraint_cost(n: usize) -> usize {
+    n + 2
+}
+
+//Wires the gate assigns: out_i is 1 for i == index and 0 otherwise
+pub(crate) fn decode_witness(index: &str, outs: &[String], values: &HashMap<String, Fr>) -> Result<Vec<(String, Fr)>, String> {
+    let index_val = *values.get(index).ok_or_else(|| format!("Var {} not found", index))?;
+    let selected = (0..outs.len())
+        .position(|i| Fr::from(i as u64) == index_val)
+        .ok_or_else(|| format!("Index '{}' of decode is {}, outside 0..{}", index, index_val, outs.len()))?;
+    Ok(outs
+        .iter()
+        .enumerate()
+        .map(|(i, out)| (out.clone(), if i == selected { Fr::one() } else { Fr::zero() }))
+        .collect())
+}
+
+//Emit the constraint_cost() rows of one gate, in the order of decode_roles
+pub(crate) fn emit_decode_rows(
+    index: &str,
+    outs: &[String],
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    let one_idx = var_map["1"];
+    let index_idx = get_index(index, var_map, next_index);
+    let out_idxs: Vec<usize> = outs.iter().map(|out| get_index(out, var_map, next_index)).collect();
+    for idx in &out_idxs {
+        constraints.push(_R1CSConstraintInternal {
+            a: HashMap::from([(*idx, Fr::one())]),
+            b: HashMap::from([(*idx, Fr::one())]),
+            c: HashMap::from([(*idx, Fr::one())]),
+        });
+    }
+    let ones: Vec<(usize, Fr)> = out_idxs.iter().map(|idx| (*idx, Fr::one())).collect();
+    constraints.push(_R1CSConstraintInternal {
+        a: sum_terms(&ones),
+        b: HashMap::from([(one_idx, Fr::one())]),
+        c: HashMap::from([(one_idx, Fr::one())]),
+    });
+    //out_0 has weight 0 and drops out of the weighted sum
+    let weighted: Vec<(usize, Fr)> = out_idxs.iter().enumerate().skip(1).map(|(i, idx)| (*idx, Fr::from(i as u64))).collect();
+    constraints.push(_R1CSConstraintInternal {
+        a: sum_terms(&weighted),
+        b: HashMap::from([(one_idx, Fr::one())]),
+        c: HashMap::from([(index_idx, Fr::one())]),
+    });
+}
+
+//Role of each row emit_decode_rows emits
+pub(crate) fn decode_roles(index: &str, outs: &[String]) -> Vec<String> {
+    let mut roles: Vec<String> = outs.iter().map(|out| format!("booleanity of {}", out)).collect();
+    roles.push("outputs sum to one".to_string());
+    roles.push(format!("weighted sum of outputs equals {}", index));
+    roles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{i64_to_fr, parse_circuit_str, run_pipeline, PipelineOptions};
+
+    //Selects options[idx] as the dot product of the one-hot vector with the options
+    const DSL: &str = "name select
+input idx 2
+public selected 30
+decode idx s0 s1 s2 s3
+const v0 10
+const v1 20
+const v2 30
+const v3 40
+mul s0 v0 p0
+mul s1 v1 p1
+mul s2 v2 p2
+mul s3 v3 p3
+add p0 p1 p01
+add p01 p2 p012
+add p012 p3 picked
+eq picked selected
+";
+
+    #[test]
+    fn test_decode_selects_by_index() {
+        let circuit = parse_circuit_str(DSL).unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        let rows = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(0)).count();
+        assert_eq!(rows, constraint_cost(4));
+        for idx in 0..4 {
+            let mut case = circuit.clone();
+            case.inputs.insert("idx".to_string(), idx);
+            case.public_inputs[0].1 = i64_to_fr(10 * (idx as i64 + 1));
+            let witness = case.compute_witness(&r1cs.var_map).unwrap();
+            for (i, out) in ["s0", "s1", "s2", "s3"].iter().enumerate() {
+                let expected = if i == idx as usize { Fr::one() } else { Fr::zero() };
+                assert_eq!(witness[&r1cs.var_map[*out]], expected, "decode of {}", idx);
+            }
+            assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None, "decode of {}", idx);
+        }
+
+        let result = run_pipeline(&circuit, PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+
+        //An index past the last output has no witness
+        let mut out_of_range = circuit.clone();
+        out_of_range.inputs.insert("idx".to_string(), 4);
+        let err = out_of_range.compute_witness(&r1cs.var_map).unwrap_err();
+        assert!(err.contains("Index 'idx' of decode is 4, outside 0..4"), "{}", err);
+
+        //Two hot outputs break the sum even when the weighted sum still matches
+        let mut witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["s0"], Fr::one());
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("Only one output may be hot");
+        assert!(r1cs.explain(failing).contains("outputs sum to one"), "{}", r1cs.explain(failing));
+    }
+}
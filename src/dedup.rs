@@ -0,0 +1,104 @@
+//Exact duplicate rows of an R1CS system (e.g. every Xor re-asserts a * a = a), which
+//to_r1cs_system drops without changing the accepted witnesses
+use std::collections::HashSet;
+
+use ark_bls12_381::Fr;
+
+use crate::{R1CSSystem, _R1CSConstraintInternal};
+
+type RowKey = [Vec<(usize, Fr)>; 3];
+
+fn row_key(constraint: &_R1CSConstraintInternal) -> RowKey {
+    [&constraint.a, &constraint.b, &constraint.c].map(|terms| {
+        let mut sorted: Vec<(usize, Fr)> = terms.iter().map(|(idx, coeff)| (*idx, *coeff)).collect();
+        sorted.sort_unstable_by_key(|(idx, _)| *idx);
+        sorted
+    })
+}
+
+impl R1CSSystem {
+    //Drop every row equal to an earlier one, along with its origin, and add the count to duplicate_rows
+    pub(crate) fn drop_duplicate_rows(&mut self) {
+        let mut seen: HashSet<RowKey> = HashSet::with_capacity(self.raw_constraints.len());
+        let keep: Vec<bool> = self.raw_constraints.iter().map(|constraint| seen.insert(row_key(constraint))).collect();
+        let dropped = keep.iter().filter(|kept| !**kept).count();
+        if dropped == 0 {
+            return;
+        }
+        let mut kept = keep.iter();
+        self.raw_constraints.retain(|_| *kept.next().unwrap());
+        if self.constraint_origins.len() == keep.len() {
+            let mut kept = keep.iter();
+            self.constraint_origins.retain(|_| *kept.next().unwrap());
+        }
+        self.duplicate_rows += dropped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{random_circuit, CircuitConfig};
+    use crate::parse_circuit_str;
+    use ark_ff::One;
+    use std::collections::HashMap;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_shared_xor_operand_is_constrained_once() {
+        let circuit = parse_circuit_str("name shared_bit
+input a 1
+input b 0
+input c 1
+input d 1
+amount 5
+xor a b ab
+xor a c ac
+xor a d ad
+")
+        .unwrap();
+        let full = circuit.build_r1cs_system(false, None).unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(r1cs.duplicate_rows, 2);
+        assert_eq!(r1cs.raw_constraints.len(), full.raw_constraints.len() - 2);
+        assert_eq!(r1cs.constraint_origins.len(), r1cs.raw_constraints.len());
+        let booleanity_of_a = r1cs.constraint_origins.iter().filter(|origin| origin.role == "booleanity of a").count();
+        assert_eq!(booleanity_of_a, 1);
+
+        //The kept row still rejects a non-bit a
+        let mut witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["a"], Fr::from(2u64));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("a = 2 is not a bit");
+        assert_eq!(r1cs.constraint_origins[failing].gate_index, Some(0));
+    }
+
+    #[test]
+    fn test_dropping_duplicates_keeps_random_circuits_equivalent() {
+        let mut rng = StdRng::seed_from_u64(951);
+        for _ in 0..16 {
+            let config = CircuitConfig { num_inputs: 2, depth: 3, width: 4, ..CircuitConfig::default() };
+            let circuit = random_circuit(&mut rng, &config);
+            let full = circuit.build_r1cs_system(false, None).unwrap();
+            let r1cs = circuit.to_r1cs_system();
+            assert_eq!(r1cs.var_map, full.var_map);
+            assert_eq!(r1cs.raw_constraints.len() + r1cs.duplicate_rows, full.raw_constraints.len());
+            let rows = |system: &R1CSSystem| system.raw_constraints.iter().map(row_key).collect::<HashSet<RowKey>>();
+            assert_eq!(rows(&r1cs), rows(&full), "Same equations for {:?}", circuit);
+            assert_eq!(rows(&r1cs).len(), r1cs.raw_constraints.len(), "No duplicates left for {:?}", circuit);
+
+            //Both accept the witness and reject the same perturbations of it
+            let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+            let satisfied = |system: &R1CSSystem, witness: &HashMap<usize, Fr>| system.which_is_unsatisfied(witness).unwrap().is_none();
+            assert!(satisfied(&r1cs, &witness));
+            let mut vars: Vec<usize> = r1cs.var_map.values().copied().filter(|idx| *idx != r1cs.var_map["1"]).collect();
+            vars.sort_unstable();
+            for idx in vars.choose_multiple(&mut rng, 8) {
+                let mut perturbed = witness.clone();
+                *perturbed.get_mut(idx).unwrap() += Fr::one();
+                assert_eq!(satisfied(&r1cs, &perturbed), satisfied(&full, &perturbed));
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//Inner product gate, out = sum of w_i * x_i (DSL `dot (w0 x0) (w1 x1) -> out`). A pair with a
+//Const side only adds a term to the summation row, every other pair but the last costs a row
+use std::collections::HashMap;
+
+use ark_bls12_381::Fr;
+use ark_ff::{One, Zero};
+
+use crate::{get_index, sum_terms, _R1CSConstraintInternal};
+
+//Product wire of pair i
+fn term_name(out: &str, i: usize) -> String {
+    format!("{}_dot_term{}", out, i)
+}
+
+//Rows emitted for the pairs, constants being the values of the circuit's Const wires
+pub fn constraint_cost(pairs: &[(String, String)], constants: &HashMap<&str, Fr>) -> usize {
+    let products = pairs.iter().filter(|(w, x)| !constants.contains_key(w.as_str()) && !constants.contains_key(x.as_str())).count();
+    products.max(1)
+}
+
+//Wires the gate assigns: the product of every pair (to_r1cs_system keeps the ones it needs), then out
+pub(crate) fn dot_witness(pairs: &[(String, String)], out: &str, values: &HashMap<String, Fr>) -> Result<Vec<(String, Fr)>, String> {
+    let mut assigned = Vec::with_capacity(pairs.len() + 1);
+    let mut sum = Fr::zero();
+    for (i, (w, x)) in pairs.iter().enumerate() {
+        let w_val = *values.get(w.as_str()).ok_or_else(|| format!("Var {} not found", w))?;
+        let x_val = *values.get(x.as_str()).ok_or_else(|| format!("Var {} not found", x))?;
+        let term = w_val * x_val;
+        sum += term;
+        assigned.push((term_name(out, i), term));
+    }
+    assigned.push((out.to_string(), sum));
+    Ok(assigned)
+}
+
+//Emit the constraint_cost rows of one gate: the product rows, then the summation row
+pub(crate) fn emit_dot_rows(
+    pairs: &[(String, String)],
+    out: &str,
+    constants: &HashMap<&str, Fr>,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    let one_idx = var_map["1"];
+    //Terms of the summation row's C side besides out, negated
+    let mut rest: Vec<(usize, Fr)> = Vec::new();
+    let mut products: Vec<(usize, usize, usize)> = Vec::new();
+    for (i, (w, x)) in pairs.iter().enumerate() {
+        match (constants.get(w.as_str()), constants.get(x.as_str())) {
+            (Some(c), Some(d)) => rest.push((one_idx, -(*c * d))),
+            (Some(c), None) => rest.push((get_index(x, var_map, next_index), -*c)),
+            (None, Some(d)) => rest.push((get_index(w, var_map, next_index), -*d)),
+            (None, None) => products.push((i, get_index(w, var_map, next_index), get_index(x, var_map, next_index))),
+        }
+    }
+    let last = products.pop();
+    for (i, w_idx, x_idx) in products {
+        let term_idx = get_index(&term_name(out, i), var_map, next_index);
+        constraints.push(_R1CSConstraintInternal {
+            a: HashMap::from([(w_idx, Fr::one())]),
+            b: HashMap::from([(x_idx, Fr::one())]),
+            c: HashMap::from([(term_idx, Fr::one())]),
+        });
+        rest.push((term_idx, -Fr::one()));
+    }
+    let out_idx = get_index(out, var_map, next_index);
+    let (a, b) = match last {
+        Some((_, w_idx, x_idx)) => (HashMap::from([(w_idx, Fr::one())]), HashMap::from([(x_idx, Fr::one())])),
+        //All pairs linear: move them to the A side, (sum c_j * y_j) * 1 = out
+        None => {
+            let linear: Vec<(usize, Fr)> = rest.drain(..).map(|(idx, coeff)| (idx, -coeff)).collect();
+            (sum_terms(&linear), HashMap::from([(one_idx, Fr::one())]))
+        }
+    };
+    rest.push((out_idx, Fr::one()));
+    constraints.push(_R1CSConstraintInternal { a, b, c: sum_terms(&rest) });
+}
+
+//Role of each row emit_dot_rows emits
+pub(crate) fn dot_roles(out: &str, num_rows: usize) -> Vec<String> {
+    let mut roles = vec![format!("product term of {}", out); num_rows.saturating_sub(1)];
+    roles.push(format!("sum into {}", out));
+    roles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_circuit_str, run_pipeline, Circuit, Gate, PipelineOptions};
+
+    //16 weights and 16 features, with the score checked against a public value
+    fn linear_layer(const_weights: bool) -> Circuit {
+        let mut source = String::from("name linear_layer\namount 0\npublic expected 2856\n");
+        for i in 0..16 {
+            if const_weights {
+                source.push_str(&format!("const w{} {}\n", i, i + 1));
+            } else {
+                source.push_str(&format!("input w{} {}\n", i, i + 1));
+            }
+            source.push_str(&format!("input x{} {}\n", i, 2 * i + 1));
+        }
+        let pairs: Vec<String> = (0..16).map(|i| format!("(w{} x{})", i, i)).collect();
+        source.push_str(&format!("dot {} -> score\neq score expected\n", pairs.join(" ")));
+        parse_circuit_str(&source).unwrap()
+    }
+
+    //The same sum as a chain of Mul and Add gates
+    fn naive(circuit: &Circuit) -> Circuit {
+        let Some(Gate::DotProduct(pairs, out)) = circuit.gates.iter().find(|gate| gate.kind() == "dot") else { panic!("no dot gate") };
+        let mut expanded = circuit.clone();
+        expanded.gates.retain(|gate| gate.kind() != "dot");
+        let mut gates = Vec::new();
+        for (i, (w, x)) in pairs.iter().enumerate() {
+            gates.push(Gate::Mul(w.clone(), x.clone(), format!("p{}", i), None));
+        }
+        let mut acc = "p0".to_string();
+        for i in 1..pairs.len() {
+            let next = if i + 1 == pairs.len() { out.clone() } else { format!("acc{}", i) };
+            gates.push(Gate::Add(acc, format!("p{}", i), next.clone(), None));
+            acc = next;
+        }
+        let at = expanded.gates.iter().position(|gate| gate.kind() == "assert_eq").unwrap();
+        expanded.gates.splice(at..at, gates);
+        expanded
+    }
+
+    #[test]
+    fn test_dot_product_against_the_naive_expansion() {
+        //sum of (i + 1) * (2i + 1) for i < 16
+        for const_weights in [false, true] {
+            let circuit = linear_layer(const_weights);
+            let r1cs = circuit.to_r1cs_system();
+            let gate_index = circuit.gates.iter().position(|gate| gate.kind() == "dot").unwrap();
+            let rows = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(gate_index)).count();
+            let naive_r1cs = naive(&circuit).to_r1cs_system();
+            assert_eq!(naive_r1cs.raw_constraints.len() - r1cs.raw_constraints.len(), 31 - rows);
+            if const_weights {
+                //One row and no variables beyond score
+                assert_eq!(rows, 1);
+                assert!(!r1cs.var_map.keys().any(|name| name.contains("_dot_term")));
+            } else {
+                assert_eq!(rows, 16);
+                assert_eq!(r1cs.var_map.keys().filter(|name| name.contains("_dot_term")).count(), 15);
+            }
+            let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+            assert_eq!(witness[&r1cs.var_map["score"]], Fr::from(2856u64));
+            let result = run_pipeline(&circuit, PipelineOptions::default()).expect("Pipeline failed");
+            assert_eq!(result.verified, Some(true), "const weights: {}", const_weights);
+
+            //A wrong score breaks the summation row
+            let mut forged = witness.clone();
+            forged.insert(r1cs.var_map["score"], Fr::from(2857u64));
+            let failing = r1cs.which_is_unsatisfied(&forged).unwrap().expect("2857 is not the sum");
+            assert!(r1cs.explain(failing).contains("sum into score"), "{}", r1cs.explain(failing));
+        }
+    }
+
+    #[test]
+    fn test_mixed_pairs_and_repeated_wires() {
+        let circuit = parse_circuit_str("name mixed
+amount 0
+input a 3
+input b 4
+const two 2
+const five 5
+dot (a a) (two b) (five two) (b a) -> out
+").unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        //a * a and b * a are products, 2 * b and 5 * 2 are folded
+        let Gate::DotProduct(pairs, _) = &circuit.gates[2] else { panic!("gate 2 is the dot product") };
+        let constants = HashMap::from([("two", Fr::from(2u64)), ("five", Fr::from(5u64))]);
+        assert_eq!(constraint_cost(pairs, &constants), 2);
+        assert_eq!(r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(2)).count(), 2);
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        assert_eq!(witness[&r1cs.var_map["out"]], Fr::from(9u64 + 8 + 10 + 12));
+        assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None);
+    }
+}
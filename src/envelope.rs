@@ -0,0 +1,289 @@
+//Proof envelopes: a proof with its circuit, verifying key fingerprint, public inputs and
+//creation time, verify() refuses a key with another fingerprint
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ark_bls12_381::Fr;
+use ark_relations::r1cs::SynthesisError;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::artifact::{read_header, write_header, ArtifactError, ArtifactKind, ARTIFACT_MAGIC};
+use crate::{fr_codec, verify, Circuit, Proof, VerifyingKey};
+
+pub const ENVELOPE_VERSION: u16 = 1;
+//File name save_proof_files gives the JSON form
+pub const ENVELOPE_FILE: &str = "proof.envelope.json";
+
+const FR_BYTES: usize = 32;
+
+#[derive(Debug)]
+pub enum EnvelopeError {
+    Header(ArtifactError),
+    UnsupportedVersion { found: u16 },
+    Format(String),
+    //The envelope was made with another verifying key than the one loaded
+    VkMismatch { envelope: String, loaded: String },
+    Verification(SynthesisError),
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::Header(e) => write!(f, "Invalid proof envelope header: {}", e),
+            EnvelopeError::UnsupportedVersion { found } => {
+                write!(f, "Unsupported proof envelope version {} (this build reads {})", found, ENVELOPE_VERSION)
+            }
+            EnvelopeError::Format(e) => write!(f, "Malformed proof envelope: {}", e),
+            EnvelopeError::VkMismatch { envelope, loaded } => {
+                write!(f, "Proof was made for verifying key {}, the loaded key is {}", envelope, loaded)
+            }
+            EnvelopeError::Verification(e) => write!(f, "Verification failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProofEnvelope {
+    pub version: u16,
+    //Name of the circuit
+    pub circuit_id: String,
+    //Circuit::canonical_hash
+    pub circuit_hash: [u8; 32],
+    //VerifyingKey::fingerprint of the key the proof verifies under
+    pub vk_fingerprint: [u8; 32],
+    //Ordered like verify() expects, without the constant one
+    pub public_inputs: Vec<Fr>,
+    //Seconds since the Unix epoch
+    pub created_at: u64,
+    //Proof::to_bytes
+    pub proof_bytes: Vec<u8>,
+}
+
+//JSON form of ProofEnvelope
+#[derive(Serialize, Deserialize)]
+struct EnvelopeJson {
+    version: u16,
+    circuit_id: String,
+    circuit_hash: String,
+    vk_fingerprint: String,
+    public_inputs: Vec<String>,
+    created_at: u64,
+    proof_bytes: String,
+}
+
+impl VerifyingKey {
+    //SHA-256 of the compressed key, what a ProofEnvelope names its key by
+    pub fn fingerprint(&self) -> [u8; 32] {
+        Sha256::digest(self.to_bytes().expect("Serializing into a Vec cannot fail")).into()
+    }
+}
+
+impl ProofEnvelope {
+    //Envelope of a proof of circuit, created now
+    pub fn new(circuit: &Circuit, vk: &VerifyingKey, proof: &Proof, public_inputs: &[Fr]) -> Self {
+        ProofEnvelope {
+            version: ENVELOPE_VERSION,
+            circuit_id: circuit.name.clone(),
+            circuit_hash: circuit.canonical_hash(),
+            vk_fingerprint: vk.fingerprint(),
+            public_inputs: public_inputs.to_vec(),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            proof_bytes: proof.to_bytes().expect("Serializing into a Vec cannot fail"),
+        }
+    }
+
+    pub fn proof(&self) -> Result<Proof, EnvelopeError> {
+        Proof::from_bytes(&self.proof_bytes).map_err(|e| EnvelopeError::Format(format!("Invalid proof: {}", e)))
+    }
+
+    //Check the proof under vk, failing with VkMismatch without verifying when the envelope names
+    //another key
+    pub fn verify(&self, vk: &VerifyingKey) -> Result<bool, EnvelopeError> {
+        let loaded = vk.fingerprint();
+        if loaded != self.vk_fingerprint {
+            return Err(EnvelopeError::VkMismatch { envelope: hex::encode(self.vk_fingerprint), loaded: hex::encode(loaded) });
+        }
+        verify(vk, &self.proof()?, &self.public_inputs).map_err(EnvelopeError::Verification)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = write_header(ArtifactKind::ProofEnvelope, Some(&self.circuit_hash));
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&(self.circuit_id.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(self.circuit_id.as_bytes());
+        bytes.extend_from_slice(&self.vk_fingerprint);
+        bytes.extend_from_slice(&self.created_at.to_le_bytes());
+        bytes.extend_from_slice(&(self.public_inputs.len() as u64).to_le_bytes());
+        for val in &self.public_inputs {
+            val.serialize_compressed(&mut bytes).expect("Serializing into a Vec cannot fail");
+        }
+        bytes.extend_from_slice(&(self.proof_bytes.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.proof_bytes);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnvelopeError> {
+        let (header, mut reader) = read_header(bytes, ArtifactKind::ProofEnvelope, None).map_err(EnvelopeError::Header)?;
+        let circuit_hash = header.circuit_hash.ok_or_else(|| EnvelopeError::Format("No circuit hash in header".to_string()))?;
+        let version = u16::from_le_bytes(take_bytes(&mut reader, 2, "version")?.try_into().unwrap());
+        if version != ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion { found: version });
+        }
+        let id_len = take_u64(&mut reader, "circuit id length")? as usize;
+        let circuit_id = String::from_utf8(take_bytes(&mut reader, id_len, "circuit id")?.to_vec())
+            .map_err(|_| EnvelopeError::Format("Circuit id is not UTF-8".to_string()))?;
+        let vk_fingerprint = take_bytes(&mut reader, 32, "vk fingerprint")?.try_into().unwrap();
+        let created_at = take_u64(&mut reader, "created_at")?;
+        let count = take_u64(&mut reader, "public input count")? as usize;
+        let mut public_inputs = Vec::with_capacity(count.min(reader.len() / FR_BYTES));
+        for i in 0..count {
+            let val = Fr::deserialize_compressed(take_bytes(&mut reader, FR_BYTES, "public input")?)
+                .map_err(|e| EnvelopeError::Format(format!("Invalid public input #{}: {}", i, e)))?;
+            public_inputs.push(val);
+        }
+        let proof_len = take_u64(&mut reader, "proof length")? as usize;
+        let proof_bytes = take_bytes(&mut reader, proof_len, "proof")?.to_vec();
+        if !reader.is_empty() {
+            return Err(EnvelopeError::Format("Trailing bytes".to_string()));
+        }
+        Ok(ProofEnvelope { version, circuit_id, circuit_hash, vk_fingerprint, public_inputs, created_at, proof_bytes })
+    }
+
+    pub fn to_json(&self) -> String {
+        let json = EnvelopeJson {
+            version: self.version,
+            circuit_id: self.circuit_id.clone(),
+            circuit_hash: hex::encode(self.circuit_hash),
+            vk_fingerprint: hex::encode(self.vk_fingerprint),
+            public_inputs: self.public_inputs.iter().map(|val| format!("0x{}", fr_codec::fr_to_hex(val))).collect(),
+            created_at: self.created_at,
+            proof_bytes: hex::encode(&self.proof_bytes),
+        };
+        serde_json::to_string_pretty(&json).expect("Envelope JSON has no non-string keys")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, EnvelopeError> {
+        let json: EnvelopeJson = serde_json::from_str(json).map_err(|e| EnvelopeError::Format(e.to_string()))?;
+        if json.version != ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion { found: json.version });
+        }
+        let hash = |field: &str, value: &str| -> Result<[u8; 32], EnvelopeError> {
+            hex::decode(value).ok().and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| EnvelopeError::Format(format!("{} is not 32 bytes of hex", field)))
+        };
+        Ok(ProofEnvelope {
+            version: json.version,
+            circuit_id: json.circuit_id,
+            circuit_hash: hash("circuit_hash", &json.circuit_hash)?,
+            vk_fingerprint: hash("vk_fingerprint", &json.vk_fingerprint)?,
+            public_inputs: json
+                .public_inputs
+                .iter()
+                .map(|val| fr_codec::fr_from_hex_strict(val).map_err(|e| EnvelopeError::Format(e.to_string())))
+                .collect::<Result<_, _>>()?,
+            created_at: json.created_at,
+            proof_bytes: hex::decode(&json.proof_bytes).map_err(|e| EnvelopeError::Format(format!("proof_bytes: {}", e)))?,
+        })
+    }
+
+    //Either form, told apart by the artifact magic
+    pub fn decode(bytes: &[u8]) -> Result<Self, EnvelopeError> {
+        if bytes.starts_with(ARTIFACT_MAGIC) {
+            return ProofEnvelope::from_bytes(bytes);
+        }
+        let json = std::str::from_utf8(bytes).map_err(|_| EnvelopeError::Format("Neither binary nor JSON".to_string()))?;
+        ProofEnvelope::from_json(json)
+    }
+}
+
+//Split the next n bytes off the reader
+fn take_bytes<'a>(reader: &mut &'a [u8], n: usize, what: &str) -> Result<&'a [u8], EnvelopeError> {
+    if reader.len() < n {
+        return Err(EnvelopeError::Format(format!("Truncated {}", what)));
+    }
+    let (head, rest) = reader.split_at(n);
+    *reader = rest;
+    Ok(head)
+}
+
+fn take_u64(reader: &mut &[u8], what: &str) -> Result<u64, EnvelopeError> {
+    Ok(u64::from_le_bytes(take_bytes(reader, 8, what)?.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::HEADER_LEN;
+    use crate::{parse_circuit_str, run_pipeline, PipelineOptions};
+
+    #[test]
+    fn test_round_trip_and_vk_mismatch() {
+        let circuit = parse_circuit_str("name enveloped\ninput a 3\ninput b 4\npublic p 9\namount 5\nmul a b c\n").unwrap();
+        let result = run_pipeline(&circuit, PipelineOptions { return_keys: true, verify: false, ..PipelineOptions::default() })
+            .expect("Pipeline failed");
+        let (_, vk) = result.keys.expect("Keys were requested");
+        let envelope = ProofEnvelope::new(&circuit, &vk, &result.proof, &result.public_inputs);
+        assert_eq!(envelope.circuit_id, "enveloped");
+        assert_eq!(envelope.public_inputs.len(), 2);
+
+        assert_eq!(ProofEnvelope::from_bytes(&envelope.to_bytes()).unwrap(), envelope);
+        assert_eq!(ProofEnvelope::from_json(&envelope.to_json()).unwrap(), envelope);
+        assert_eq!(ProofEnvelope::decode(envelope.to_json().as_bytes()).unwrap(), envelope);
+        assert_eq!(ProofEnvelope::decode(&envelope.to_bytes()).unwrap(), envelope);
+        assert!(envelope.verify(&vk).unwrap());
+
+        let mut tampered = envelope.clone();
+        tampered.public_inputs[1] = Fr::from(10u64);
+        assert!(!tampered.verify(&vk).unwrap());
+
+        //Another key for the same circuit is refused before any pairing
+        let (_, other_vk) = crate::setup(&circuit.to_r1cs_system()).expect("Setup failed");
+        let err = envelope.verify(&other_vk).unwrap_err();
+        assert!(matches!(err, EnvelopeError::VkMismatch { ref loaded, .. } if *loaded == hex::encode(other_vk.fingerprint())), "{}", err);
+
+        let bytes = envelope.to_bytes();
+        assert!(matches!(ProofEnvelope::from_bytes(&bytes[..bytes.len() - 1]), Err(EnvelopeError::Format(_))));
+        assert!(matches!(ProofEnvelope::from_bytes(&bytes[HEADER_LEN..]), Err(EnvelopeError::Header(ArtifactError::MissingHeader))));
+        let mut future = bytes.clone();
+        future[HEADER_LEN] = 9;
+        assert!(matches!(ProofEnvelope::from_bytes(&future), Err(EnvelopeError::UnsupportedVersion { found: 9 })));
+        assert!(matches!(ProofEnvelope::decode(b"{\"version\": 1}"), Err(EnvelopeError::Format(_))));
+    }
+
+    #[test]
+    fn test_envelope_encodings_are_pinned() {
+        //A change to either form must show up here and bump ENVELOPE_VERSION
+        let envelope = ProofEnvelope {
+            version: ENVELOPE_VERSION,
+            circuit_id: "id".to_string(),
+            circuit_hash: [2; 32],
+            vk_fingerprint: [3; 32],
+            public_inputs: vec![Fr::from(7u64)],
+            created_at: 9,
+            proof_bytes: vec![0xaa, 0xbb],
+        };
+        let expected = [
+            hex::encode(b"ZKFA") + "0100" + "01" + "06" + "01" + &"02".repeat(32),
+            "0100".to_string(),
+            "0200000000000000".to_string() + &hex::encode("id"),
+            "03".repeat(32),
+            "0900000000000000".to_string(),
+            "0100000000000000".to_string() + "07" + &"00".repeat(31),
+            "0200000000000000".to_string() + "aabb",
+        ]
+        .concat();
+        assert_eq!(hex::encode(envelope.to_bytes()), expected);
+
+        let json = format!(
+            "{{\n  \"version\": 1,\n  \"circuit_id\": \"id\",\n  \"circuit_hash\": \"{}\",\n  \"vk_fingerprint\": \"{}\",\n  \"public_inputs\": [\n    \"0x07{}\"\n  ],\n  \"created_at\": 9,\n  \"proof_bytes\": \"aabb\"\n}}",
+            "02".repeat(32),
+            "03".repeat(32),
+            "00".repeat(31)
+        );
+        assert_eq!(envelope.to_json(), json);
+    }
+}
@@ -0,0 +1,141 @@
+//Randomized equivalence check of two circuits on the same sampled inputs, used to validate
+//optimizer passes (evidence, not proof)
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use ark_bls12_381::Fr;
+use rand::Rng;
+
+use crate::fr_codec::fr_to_hex;
+use crate::{Circuit, Gate, WireType};
+
+//Inputs on which the circuits disagree, with the first wire that differs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub inputs: BTreeMap<String, i32>,
+    //None when the circuits disagree on whether the witness can be computed at all
+    pub wire: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inputs: Vec<String> = self.inputs.iter().map(|(name, val)| format!("{}={}", name, val)).collect();
+        match &self.wire {
+            Some(wire) => write!(f, "Circuits differ on wire '{}' for inputs [{}]: {}", wire, inputs.join(", "), self.reason),
+            None => write!(f, "Circuits differ for inputs [{}]: {}", inputs.join(", "), self.reason),
+        }
+    }
+}
+
+impl std::error::Error for Counterexample {}
+
+//Half of the samples are bits, so gates that need boolean operands (Xor) are exercised too
+fn sample_input<R: Rng>(rng: &mut R, declared: Option<WireType>) -> i32 {
+    let max = match declared {
+        Some(WireType::Bool) => 1,
+        Some(WireType::UInt(bits)) if bits < 31 => (1i32 << bits) - 1,
+        _ => i32::MAX,
+    };
+    if max == 1 || rng.gen_bool(0.5) {
+        rng.gen_range(0..=1)
+    } else {
+        rng.gen_range(0..=max)
+    }
+}
+
+//Witness of the circuit with the given inputs applied, or the witness error
+fn witness_for(circuit: &Circuit, var_map: &HashMap<String, usize>, inputs: &BTreeMap<String, i32>) -> Result<HashMap<usize, Fr>, String> {
+    let mut assigned = circuit.clone();
+    assigned.inputs.extend(inputs.iter().map(|(name, val)| (name.clone(), *val)));
+    assigned.compute_witness(var_map)
+}
+
+//Compare a and b on `samples` random input assignments (declared wire types of a bound the
+//sampled values). Inputs of b that a lacks keep b's own values
+pub fn check_equivalent<R: Rng>(a: &Circuit, b: &Circuit, samples: usize, rng: &mut R) -> Result<(), Counterexample> {
+    let (r1cs_a, r1cs_b) = (a.to_r1cs_system(), b.to_r1cs_system());
+    let named: BTreeSet<&String> = a.inputs.keys().chain(a.gates.iter().flat_map(Gate::outputs)).collect();
+    let compared: Vec<(&String, usize, usize)> = named
+        .into_iter()
+        .filter_map(|wire| Some((wire, *r1cs_a.var_map.get(wire)?, *r1cs_b.var_map.get(wire)?)))
+        .collect();
+
+    let mut input_names: Vec<&String> = a.inputs.keys().collect();
+    input_names.sort();
+    for _ in 0..samples {
+        let inputs: BTreeMap<String, i32> =
+            input_names.iter().map(|name| ((*name).clone(), sample_input(rng, a.wire_types.get(*name).copied()))).collect();
+        let counterexample = |wire: Option<&String>, reason: String| Counterexample { inputs: inputs.clone(), wire: wire.cloned(), reason };
+        for output in a.outputs.keys().chain(b.outputs.keys()) {
+            if !r1cs_a.var_map.contains_key(output) || !r1cs_b.var_map.contains_key(output) {
+                return Err(counterexample(Some(output), "declared output is missing from one circuit".to_string()));
+            }
+        }
+
+        let (witness_a, witness_b) = match (witness_for(a, &r1cs_a.var_map, &inputs), witness_for(b, &r1cs_b.var_map, &inputs)) {
+            (Ok(witness_a), Ok(witness_b)) => (witness_a, witness_b),
+            //Both reject the assignment (e.g. a non-bit Xor operand), nothing to compare
+            (Err(_), Err(_)) => continue,
+            (Err(e), Ok(_)) => return Err(counterexample(None, format!("only the first circuit fails: {}", e))),
+            (Ok(_), Err(e)) => return Err(counterexample(None, format!("only the second circuit fails: {}", e))),
+        };
+        for (wire, idx_a, idx_b) in &compared {
+            let (val_a, val_b) = (witness_a[idx_a], witness_b[idx_b]);
+            if val_a != val_b {
+                return Err(counterexample(Some(wire), format!("0x{} vs 0x{}", fr_to_hex(&val_a), fr_to_hex(&val_b))));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    //A deliberately wrong "optimization": rewrites x - y as y - x
+    fn buggy_pass(circuit: &Circuit) -> Circuit {
+        let gates = circuit
+            .gates
+            .iter()
+            .map(|gate| match gate {
+                Gate::Sub(a, b, c, m) => Gate::Sub(b.clone(), a.clone(), c.clone(), *m),
+                other => other.clone(),
+            })
+            .collect();
+        Circuit { gates, ..circuit.clone() }
+    }
+
+    #[test]
+    fn test_buggy_pass_is_caught() {
+        let circuit = Circuit {
+            name: "equiv".to_string(),
+            inputs: HashMap::from([("x".to_string(), 7), ("y".to_string(), 2)]),
+            gates: vec![
+                Gate::Mul("x".to_string(), "y".to_string(), "p".to_string(), None),
+                Gate::Sub("p".to_string(), "x".to_string(), "d".to_string(), None),
+                Gate::Hash("d".to_string(), "h".to_string()),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(9);
+        assert_eq!(check_equivalent(&circuit, &circuit.clone(), 16, &mut rng), Ok(()));
+
+        let err = check_equivalent(&circuit, &buggy_pass(&circuit), 16, &mut rng).unwrap_err();
+        assert_eq!(err.wire.as_deref(), Some("d"));
+        assert_eq!(err.inputs.keys().collect::<Vec<_>>(), ["x", "y"]);
+        assert!(err.to_string().starts_with("Circuits differ on wire 'd' for inputs [x="), "{}", err);
+
+        let mut dropped_output = circuit.clone();
+        dropped_output.outputs.insert("h".to_string(), 0);
+        let err = check_equivalent(&dropped_output, &Circuit { gates: circuit.gates[..2].to_vec(), ..circuit.clone() }, 1, &mut rng)
+            .unwrap_err();
+        assert_eq!(err.reason, "declared output is missing from one circuit");
+    }
+}
@@ -0,0 +1,108 @@
+//Logic of the examples in examples/, kept in the library so the tests catch API changes
+use std::collections::HashMap;
+
+use crate::{parse_circuit_str, run_pipeline, Circuit, Gate, PipelineError, PipelineOptions};
+
+//Source of examples/transfer.txt
+pub const TRANSFER_DSL: &str = include_str!("../examples/transfer.txt");
+
+//Source of examples/merkle.txt
+pub const MERKLE_DSL: &str = include_str!("../examples/merkle.txt");
+
+//(a + b) * c, built from Gate values instead of the DSL
+pub fn arithmetic_circuit(a: i32, b: i32, c: i32) -> Circuit {
+    Circuit {
+        name: "arithmetic".to_string(),
+        inputs: HashMap::from([("a".to_string(), a), ("b".to_string(), b), ("c".to_string(), c)]),
+        gates: vec![
+            Gate::Add("a".to_string(), "b".to_string(), "sum".to_string(), None),
+            Gate::Mul("sum".to_string(), "c".to_string(), "product".to_string(), None),
+        ],
+        sender: "alice".to_string(),
+        receiver: "bob".to_string(),
+        transfer_amount: 0,
+        ..Default::default()
+    }
+}
+
+//Prove and verify arithmetic_circuit
+pub fn prove_arithmetic(a: i32, b: i32, c: i32) -> Result<bool, PipelineError> {
+    let result = run_pipeline(&arithmetic_circuit(a, b, c), PipelineOptions::default())?;
+    Ok(result.verified == Some(true))
+}
+
+//Prove the circuit once per input set (each overriding some of its inputs), running setup only
+//for the first proof and reusing its keys for the rest. One verification result per input set
+pub fn prove_with_key_reuse(circuit: &Circuit, input_sets: &[HashMap<String, i32>]) -> Result<Vec<bool>, PipelineError> {
+    let mut keys = None;
+    let mut verified = Vec::with_capacity(input_sets.len());
+    for inputs in input_sets {
+        let mut case = circuit.clone();
+        case.inputs.extend(inputs.iter().map(|(name, val)| (name.clone(), *val)));
+        let options = PipelineOptions { return_keys: true, ..PipelineOptions::default() };
+        let result = match &keys {
+            Some((pk, vk)) => run_pipeline(&case, PipelineOptions { keys: Some((pk, vk)), ..options })?,
+            None => {
+                let mut result = run_pipeline(&case, options)?;
+                keys = result.keys.take();
+                result
+            }
+        };
+        verified.push(result.verified == Some(true));
+    }
+    Ok(verified)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferOutcome {
+    pub verified: bool,
+    //Balances after the transfer is applied (unchanged when the proof does not verify)
+    pub sender_balance: i32,
+    pub receiver_balance: i32,
+}
+
+//Prove the transfer of examples/transfer.txt with the given balances and amount, then apply it
+//to the circuit's balances once the proof verifies
+pub fn transfer_flow(sender_balance: i32, receiver_balance: i32, amount: u64) -> Result<TransferOutcome, PipelineError> {
+    let mut circuit = parse_circuit_str(TRANSFER_DSL).expect("examples/transfer.txt parses");
+    circuit.inputs.insert(circuit.sender.clone(), sender_balance);
+    circuit.inputs.insert(circuit.receiver.clone(), receiver_balance);
+    circuit.transfer_amount = amount;
+    let verified = run_pipeline(&circuit, PipelineOptions::default())?.verified == Some(true);
+    if verified {
+        circuit.execute_transfer().map_err(PipelineError::Circuit)?;
+    }
+    Ok(TransferOutcome {
+        verified,
+        sender_balance: circuit.inputs[&circuit.sender],
+        receiver_balance: circuit.inputs[&circuit.receiver],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_circuit;
+
+    #[test]
+    fn test_example_gallery() {
+        assert!(prove_arithmetic(2, 3, 4).unwrap());
+
+        let circuit = parse_circuit(concat!(env!("CARGO_MANIFEST_DIR"), "/circuit.txt")).unwrap();
+        let input_sets: Vec<HashMap<String, i32>> = (5..8).map(|x| HashMap::from([("x".to_string(), x)])).collect();
+        assert_eq!(prove_with_key_reuse(&circuit, &input_sets).unwrap(), vec![true; 3]);
+
+        let outcome = transfer_flow(100, 20, 30).unwrap();
+        assert_eq!(outcome, TransferOutcome { verified: true, sender_balance: 70, receiver_balance: 50 });
+        //An overdraft leaves alice_after outside u32, so there is no witness to prove
+        assert!(matches!(transfer_flow(10, 20, 30), Err(PipelineError::Witness(_))));
+
+        let merkle = parse_circuit_str(MERKLE_DSL).unwrap();
+        assert_eq!(merkle.gates.iter().filter(|gate| gate.kind() == "condswap").count(), 3);
+        assert_eq!(run_pipeline(&merkle, PipelineOptions::default()).unwrap().verified, Some(true));
+        //The same leaf on another path position hashes to another root
+        let mut moved = merkle.clone();
+        moved.inputs.insert("dir1".to_string(), 1);
+        assert!(matches!(run_pipeline(&moved, PipelineOptions::default()), Err(PipelineError::Witness(_))));
+    }
+}
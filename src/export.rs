@@ -0,0 +1,125 @@
+//JSON export/import of R1CS systems, field elements are hex encoded (see fr_codec)
+use std::collections::{BTreeMap, HashMap};
+
+use ark_bls12_381::Fr;
+use serde::{Deserialize, Serialize};
+
+use crate::artifact::{ArtifactError, ARTIFACT_VERSION, CURVE_NAME};
+use crate::fr_codec::{fr_from_hex, fr_to_hex};
+use crate::{PublicInputDesc, R1CSSystem, _R1CSConstraintInternal};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct R1CSConstraintJson {
+    pub a: Vec<(usize, String)>,
+    pub b: Vec<(usize, String)>,
+    pub c: Vec<(usize, String)>,
+}
+
+//Serializable form of R1CSSystem with deterministic ordering (sorted var map and terms)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct R1CSJson {
+    //None only in legacy exports written before artifact headers
+    #[serde(default)]
+    pub format_version: Option<u16>,
+    #[serde(default)]
+    pub curve: Option<String>,
+    //Hex canonical hash of the circuit the system was compiled from, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_hash: Option<String>,
+    pub num_variables: usize,
+    pub num_public_inputs: usize,
+    pub public_input_names: Vec<String>,
+    //R1CSSystem::public_input_schema, absent from exports written before it existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_input_schema: Option<Vec<PublicInputDesc>>,
+    pub var_map: BTreeMap<String, usize>,
+    pub constraints: Vec<R1CSConstraintJson>,
+}
+
+impl R1CSJson {
+    //Same checks as artifact::read_header; a missing version marks a legacy export
+    pub fn check_header(&self, expected_circuit: Option<&[u8; 32]>, allow_legacy: bool) -> Result<(), ArtifactError> {
+        let version = match self.format_version {
+            Some(version) => version,
+            None if allow_legacy => return Ok(()),
+            None => return Err(ArtifactError::MissingHeader),
+        };
+        if version != ARTIFACT_VERSION {
+            return Err(ArtifactError::UnsupportedVersion { found: version });
+        }
+        if self.curve.as_deref() != Some(CURVE_NAME) {
+            return Err(ArtifactError::WrongCurve { found: self.curve.clone().unwrap_or_default() });
+        }
+        if let (Some(expected), Some(found)) = (expected_circuit, &self.circuit_hash) {
+            if &hex::encode(expected) != found {
+                return Err(ArtifactError::WrongCircuit { expected: hex::encode(expected), found: found.clone() });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn terms_to_json(terms: &HashMap<usize, Fr>) -> Vec<(usize, String)> {
+    let mut out: Vec<(usize, String)> = terms.iter().map(|(idx, coeff)| (*idx, fr_to_hex(coeff))).collect();
+    out.sort();
+    out
+}
+
+fn terms_from_json(terms: &[(usize, String)]) -> Result<HashMap<usize, Fr>, String> {
+    terms.iter().map(|(idx, coeff)| Ok((*idx, fr_from_hex(coeff)?))).collect()
+}
+
+impl R1CSSystem {
+    pub fn to_json_struct(&self) -> R1CSJson {
+        R1CSJson {
+            format_version: Some(ARTIFACT_VERSION),
+            curve: Some(CURVE_NAME.to_string()),
+            circuit_hash: None,
+            num_variables: self.num_variables,
+            num_public_inputs: self.num_public_inputs,
+            public_input_names: self.public_input_names.clone(),
+            public_input_schema: Some(self.public_input_schema()),
+            var_map: self.var_map.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            constraints: self.raw_constraints.iter()
+                .map(|c| R1CSConstraintJson { a: terms_to_json(&c.a), b: terms_to_json(&c.b), c: terms_to_json(&c.c) })
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_json_struct()).expect("R1CS JSON serialization cannot fail")
+    }
+
+    pub fn from_json(json: &str) -> Result<R1CSSystem, String> {
+        Self::from_json_checked(json, None, false)
+    }
+
+    //from_json, rejecting exports of another circuit and optionally accepting legacy exports
+    pub fn from_json_checked(json: &str, expected_circuit: Option<&[u8; 32]>, allow_legacy: bool) -> Result<R1CSSystem, String> {
+        let parsed: R1CSJson = serde_json::from_str(json).map_err(|e| format!("Invalid R1CS JSON: {}", e))?;
+        parsed.check_header(expected_circuit, allow_legacy).map_err(|e| format!("Invalid R1CS JSON: {}", e))?;
+        if let Some(schema) = &parsed.public_input_schema {
+            if !schema.iter().map(|desc| &desc.name).eq(parsed.public_input_names.iter()) {
+                return Err("Invalid R1CS JSON: public_input_schema does not match public_input_names".to_string());
+            }
+        }
+        let mut raw_constraints = Vec::with_capacity(parsed.constraints.len());
+        for c in &parsed.constraints {
+            raw_constraints.push(_R1CSConstraintInternal {
+                a: terms_from_json(&c.a)?,
+                b: terms_from_json(&c.b)?,
+                c: terms_from_json(&c.c)?,
+            });
+        }
+        Ok(R1CSSystem {
+            raw_constraints,
+            constraint_origins: Vec::new(),
+            var_map: parsed.var_map.into_iter().collect(),
+            num_variables: parsed.num_variables,
+            num_public_inputs: parsed.num_public_inputs,
+            public_input_sources: parsed.public_input_schema.iter().flatten().map(|desc| desc.source).collect(),
+            public_input_names: parsed.public_input_names,
+            duplicate_rows: 0,
+        })
+    }
+}
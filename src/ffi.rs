@@ -0,0 +1,303 @@
+//C ABI for setup, prove and verify (ffi feature). Buffers are freed with zk_free, a negative
+//return code has its message in zk_last_error_message()
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use ark_bls12_381::Fr;
+
+use crate::fr_codec::{fr_from_hex, fr_to_hex};
+use crate::{prove, setup, verify, Circuit, Proof, ProvingKey, R1CSSystem, VerifyingKey};
+
+pub const ZK_OK: i32 = 0;
+pub const ZK_ERR_NULL_POINTER: i32 = -1;
+pub const ZK_ERR_INVALID_ARGUMENT: i32 = -2;
+pub const ZK_ERR_PROVING: i32 = -3;
+pub const ZK_ERR_PANIC: i32 = -4;
+
+//Byte buffer handed to the caller, must be released with zk_free
+#[repr(C)]
+pub struct ZkBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl ZkBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        ZkBuffer { data, len }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+type FfiResult = Result<i32, (i32, String)>;
+
+//Run an entry point body, translating errors and panics into error codes
+fn ffi_call(body: impl FnOnce() -> FfiResult) -> i32 {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(code)) => code,
+        Ok(Err((code, message))) => {
+            set_last_error(message);
+            code
+        }
+        Err(panic) => {
+            let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(format!("Panic: {}", message));
+            ZK_ERR_PANIC
+        }
+    }
+}
+
+fn invalid(message: String) -> (i32, String) {
+    (ZK_ERR_INVALID_ARGUMENT, message)
+}
+
+unsafe fn read_str<'a>(s: *const c_char, what: &str) -> Result<&'a str, (i32, String)> {
+    if s.is_null() {
+        return Err((ZK_ERR_NULL_POINTER, format!("{} is null", what)));
+    }
+    CStr::from_ptr(s).to_str().map_err(|e| invalid(format!("{} is not UTF-8: {}", what, e)))
+}
+
+unsafe fn read_bytes<'a>(data: *const u8, len: usize, what: &str) -> Result<&'a [u8], (i32, String)> {
+    if data.is_null() {
+        return Err((ZK_ERR_NULL_POINTER, format!("{} is null", what)));
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+unsafe fn write_buffer(out: *mut ZkBuffer, bytes: Vec<u8>) {
+    *out = ZkBuffer::from_vec(bytes);
+}
+
+//Apply the optional JSON inputs map ({"name": value}) to a circuit given as JSON
+unsafe fn read_circuit(circuit_json: *const c_char, inputs_json: *const c_char) -> Result<Circuit, (i32, String)> {
+    let mut circuit: Circuit = serde_json::from_str(read_str(circuit_json, "circuit_json")?)
+        .map_err(|e| invalid(format!("Invalid circuit JSON: {}", e)))?;
+    if !inputs_json.is_null() {
+        let inputs: HashMap<String, i32> = serde_json::from_str(read_str(inputs_json, "inputs_json")?)
+            .map_err(|e| invalid(format!("Invalid inputs JSON: {}", e)))?;
+        circuit.inputs.extend(inputs);
+    }
+    Ok(circuit)
+}
+
+/// Run setup for an R1CS system exported as JSON, writing the compressed keys to out_pk/out_vk.
+///
+/// # Safety
+/// r1cs_json must be a valid NUL-terminated string, out_pk and out_vk valid writable pointers.
+#[no_mangle]
+pub unsafe extern "C" fn zk_setup(r1cs_json: *const c_char, out_pk: *mut ZkBuffer, out_vk: *mut ZkBuffer) -> i32 {
+    ffi_call(|| {
+        if out_pk.is_null() || out_vk.is_null() {
+            return Err((ZK_ERR_NULL_POINTER, "Output buffer is null".to_string()));
+        }
+        //Callers may still pass exports written before the JSON carried a format version
+        let r1cs = R1CSSystem::from_json_checked(read_str(r1cs_json, "r1cs_json")?, None, true).map_err(invalid)?;
+        let (pk, vk) = setup(&r1cs).map_err(|e| (ZK_ERR_PROVING, format!("Setup failed: {}", e)))?;
+        let pk_bytes = pk.to_bytes().map_err(|e| (ZK_ERR_PROVING, e.to_string()))?;
+        let vk_bytes = vk.to_bytes().map_err(|e| (ZK_ERR_PROVING, e.to_string()))?;
+        write_buffer(out_pk, pk_bytes);
+        write_buffer(out_vk, vk_bytes);
+        Ok(ZK_OK)
+    })
+}
+
+/// Prove a circuit given as JSON, inputs_json (may be null) overrides circuit inputs.
+///
+/// # Safety
+/// pk_data must point to pk_len readable bytes, the strings must be NUL-terminated and
+/// out_proof a valid writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn zk_prove(
+    pk_data: *const u8,
+    pk_len: usize,
+    circuit_json: *const c_char,
+    inputs_json: *const c_char,
+    out_proof: *mut ZkBuffer,
+) -> i32 {
+    ffi_call(|| {
+        if out_proof.is_null() {
+            return Err((ZK_ERR_NULL_POINTER, "Output buffer is null".to_string()));
+        }
+        let pk = ProvingKey::from_bytes(read_bytes(pk_data, pk_len, "pk")?)
+            .map_err(|e| invalid(format!("Invalid proving key: {}", e)))?;
+        let circuit = read_circuit(circuit_json, inputs_json)?;
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).map_err(|e| (ZK_ERR_PROVING, e))?;
+        let proof = prove(&r1cs, &pk, witness).map_err(|e| (ZK_ERR_PROVING, format!("Proof generation failed: {}", e)))?;
+        write_buffer(out_proof, proof.to_bytes().map_err(|e| (ZK_ERR_PROVING, e.to_string()))?);
+        Ok(ZK_OK)
+    })
+}
+
+/// Write the ordered public inputs of a circuit instance as a JSON array of hex strings.
+///
+/// # Safety
+/// The strings must be NUL-terminated (inputs_json may be null) and out_publics a valid writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn zk_public_inputs(
+    circuit_json: *const c_char,
+    inputs_json: *const c_char,
+    out_publics: *mut ZkBuffer,
+) -> i32 {
+    ffi_call(|| {
+        if out_publics.is_null() {
+            return Err((ZK_ERR_NULL_POINTER, "Output buffer is null".to_string()));
+        }
+        let circuit = read_circuit(circuit_json, inputs_json)?;
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).map_err(|e| (ZK_ERR_PROVING, e))?;
+        let publics = r1cs.public_inputs_from_witness(&witness).map_err(|e| (ZK_ERR_PROVING, e))?;
+        let hex: Vec<String> = publics.iter().map(fr_to_hex).collect();
+        write_buffer(out_publics, serde_json::to_vec(&hex).map_err(|e| (ZK_ERR_PROVING, e.to_string()))?);
+        Ok(ZK_OK)
+    })
+}
+
+/// Verify a proof, publics_json is a JSON array of hex field elements.
+/// Returns 1 for a valid proof, 0 for an invalid one and a negative error code otherwise.
+///
+/// # Safety
+/// vk_data/proof_data must point to vk_len/proof_len readable bytes and publics_json be NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn zk_verify(
+    vk_data: *const u8,
+    vk_len: usize,
+    proof_data: *const u8,
+    proof_len: usize,
+    publics_json: *const c_char,
+) -> i32 {
+    ffi_call(|| {
+        let vk = VerifyingKey::from_bytes(read_bytes(vk_data, vk_len, "vk")?)
+            .map_err(|e| invalid(format!("Invalid verifying key: {}", e)))?;
+        let proof = Proof::from_bytes(read_bytes(proof_data, proof_len, "proof")?)
+            .map_err(|e| invalid(format!("Invalid proof: {}", e)))?;
+        let hex: Vec<String> = serde_json::from_str(read_str(publics_json, "publics_json")?)
+            .map_err(|e| invalid(format!("Invalid public inputs JSON: {}", e)))?;
+        let publics = hex.iter().map(|s| fr_from_hex(s)).collect::<Result<Vec<Fr>, String>>().map_err(invalid)?;
+        let valid = verify(&vk, &proof, &publics).map_err(|e| (ZK_ERR_PROVING, format!("Verification failed: {}", e)))?;
+        Ok(if valid { 1 } else { 0 })
+    })
+}
+
+/// Release a buffer returned by this library.
+///
+/// # Safety
+/// buf must come from one of the zk_* functions and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn zk_free(buf: ZkBuffer) {
+    if !buf.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buf.data, buf.len)));
+    }
+}
+
+/// Message of the last error on the calling thread, or null if the last call succeeded.
+/// The pointer stays valid until the next zk_* call on the same thread.
+#[no_mangle]
+pub extern "C" fn zk_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mul_circuit;
+    use crate::Gate;
+
+    fn empty_buffer() -> ZkBuffer {
+        ZkBuffer { data: ptr::null_mut(), len: 0 }
+    }
+
+    unsafe fn buffer_bytes(buf: &ZkBuffer) -> Vec<u8> {
+        std::slice::from_raw_parts(buf.data, buf.len).to_vec()
+    }
+
+    fn last_error() -> String {
+        let msg = zk_last_error_message();
+        assert!(!msg.is_null());
+        unsafe { CStr::from_ptr(msg) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_ffi_setup_prove_verify() {
+        let circuit = mul_circuit("ffi");
+        let r1cs_json = CString::new(circuit.to_r1cs_system().to_json()).unwrap();
+        let circuit_json = CString::new(serde_json::to_string(&circuit).unwrap()).unwrap();
+        let inputs_json = CString::new(r#"{"a": 6}"#).unwrap();
+
+        unsafe {
+            let (mut pk, mut vk) = (empty_buffer(), empty_buffer());
+            assert_eq!(zk_setup(r1cs_json.as_ptr(), &mut pk, &mut vk), ZK_OK);
+            assert!(zk_last_error_message().is_null());
+
+            let mut proof = empty_buffer();
+            assert_eq!(zk_prove(pk.data, pk.len, circuit_json.as_ptr(), inputs_json.as_ptr(), &mut proof), ZK_OK);
+            let mut publics = empty_buffer();
+            assert_eq!(zk_public_inputs(circuit_json.as_ptr(), inputs_json.as_ptr(), &mut publics), ZK_OK);
+            let publics_json = CString::new(buffer_bytes(&publics)).unwrap();
+
+            assert_eq!(zk_verify(vk.data, vk.len, proof.data, proof.len, publics_json.as_ptr()), 1);
+
+            //A wrong public input is a clean "invalid", not an error
+            let wrong = serde_json::to_string(&vec![fr_to_hex(&Fr::from(6u64))]).unwrap();
+            let wrong = CString::new(wrong).unwrap();
+            assert_eq!(zk_verify(vk.data, vk.len, proof.data, proof.len, wrong.as_ptr()), 0);
+
+            //Truncated key bytes are rejected with a message
+            assert_eq!(zk_verify(vk.data, vk.len - 1, proof.data, proof.len, publics_json.as_ptr()), ZK_ERR_INVALID_ARGUMENT);
+            assert!(last_error().contains("Invalid verifying key"));
+
+            for buf in [pk, vk, proof, publics] {
+                zk_free(buf);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ffi_error_paths() {
+        unsafe {
+            let mut out = empty_buffer();
+            assert_eq!(zk_setup(ptr::null(), &mut out, &mut empty_buffer()), ZK_ERR_NULL_POINTER);
+            assert!(last_error().contains("r1cs_json is null"));
+
+            let bad_json = CString::new("{not json").unwrap();
+            assert_eq!(zk_setup(bad_json.as_ptr(), &mut out, &mut empty_buffer()), ZK_ERR_INVALID_ARGUMENT);
+            assert!(last_error().contains("Invalid R1CS JSON"));
+
+            //Witness errors (non-bit XOR input) surface as proving errors
+            let circuit = Circuit {
+                name: "ffi_xor".to_string(),
+                inputs: HashMap::from([("a".to_string(), 2), ("b".to_string(), 1)]),
+                gates: vec![Gate::Xor("a".to_string(), "b".to_string(), "c".to_string())],
+                sender: String::new(),
+                receiver: String::new(),
+                transfer_amount: 0,
+                ..Default::default()
+            };
+            let circuit_json = CString::new(serde_json::to_string(&circuit).unwrap()).unwrap();
+            assert_eq!(zk_public_inputs(circuit_json.as_ptr(), ptr::null(), &mut out), ZK_ERR_PROVING);
+            assert!(last_error().contains("not a bit"));
+
+            assert_eq!(ffi_call(|| panic!("boom")), ZK_ERR_PANIC);
+            assert_eq!(last_error(), "Panic: boom");
+
+            //Freeing an empty buffer is a no-op
+            zk_free(empty_buffer());
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//Regression fixtures under fixtures/, regenerate with ZK_UPDATE_FIXTURES=1 cargo test --lib fixtures
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use ark_std::rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use crate::envelope::ENVELOPE_FILE;
+use crate::examples_support::TRANSFER_DSL;
+use crate::{parse_circuit_str, prove_with_rng, save_proof_files, setup_with_rng, Circuit, Gate, NoProgress, ProofEnvelope};
+
+const SEED: u64 = 0x5eed;
+
+fn test_add() -> Circuit {
+    Circuit {
+        name: "test_add".to_string(),
+        inputs: HashMap::from([("a".to_string(), 10), ("b".to_string(), 20)]),
+        gates: vec![
+            Gate::Add("a".to_string(), "b".to_string(), "c".to_string(), None),
+            Gate::Add("c".to_string(), "transfer_amount_public".to_string(), "d".to_string(), None),
+        ],
+        sender: "alice".to_string(),
+        receiver: "bob".to_string(),
+        transfer_amount: 5,
+        ..Default::default()
+    }
+}
+
+//Every artifact of the circuit by file name, from seeded setup and proving
+fn artifacts(circuit: &Circuit) -> BTreeMap<String, Vec<u8>> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let r1cs = circuit.to_r1cs_system();
+    let (pk, vk) = setup_with_rng(&r1cs, &mut rng, &NoProgress).expect("Setup failed");
+    let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+    let public_inputs = r1cs.public_inputs_from_witness(&witness).unwrap();
+    let proof = prove_with_rng(&r1cs, &pk, witness, &mut rng, &NoProgress).expect("Proving failed");
+
+    let dir = std::env::temp_dir().join(format!("zk_fixtures_test_{}_{}", circuit.name, std::process::id()));
+    save_proof_files(&dir, circuit, &vk, &proof, &public_inputs).unwrap();
+    let mut artifacts: BTreeMap<String, Vec<u8>> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            (path.file_name().unwrap().to_string_lossy().into_owned(), std::fs::read(&path).unwrap())
+        })
+        .collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    //The envelope is pinned with its creation time cleared
+    let mut envelope = ProofEnvelope::decode(&artifacts[ENVELOPE_FILE]).unwrap();
+    envelope.created_at = 0;
+    artifacts.insert(ENVELOPE_FILE.to_string(), envelope.to_json().into_bytes());
+    artifacts.insert("circuit_hash.hex".to_string(), format!("{}\n", hex::encode(circuit.canonical_hash())).into_bytes());
+    artifacts
+}
+
+fn fixture_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join(name)
+}
+
+#[test]
+fn test_artifacts_match_fixtures() {
+    let update = std::env::var("ZK_UPDATE_FIXTURES").is_ok_and(|v| !v.is_empty());
+    let circuits = [test_add(), parse_circuit_str(TRANSFER_DSL).unwrap()];
+    let mut diverged = Vec::new();
+    for circuit in &circuits {
+        let dir = fixture_dir(&circuit.name);
+        let generated = artifacts(circuit);
+        //Seeded setup and proving must be reproducible before they can be pinned
+        assert_eq!(generated, artifacts(circuit), "artifacts of {} differ between runs", circuit.name);
+        if update {
+            std::fs::create_dir_all(&dir).unwrap();
+            for (file, bytes) in &generated {
+                std::fs::write(dir.join(file), bytes).unwrap();
+            }
+            continue;
+        }
+        for (file, bytes) in &generated {
+            if std::fs::read(dir.join(file)).ok().as_ref() != Some(bytes) {
+                diverged.push(format!("{}/{}", circuit.name, file));
+            }
+        }
+    }
+    assert!(
+        diverged.is_empty(),
+        "Artifacts diverged from fixtures/: {}. If the change is intentional, regenerate them with \
+         `ZK_UPDATE_FIXTURES=1 cargo test --lib fixtures` and commit the result",
+        diverged.join(", ")
+    );
+}
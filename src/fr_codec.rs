@@ -0,0 +1,197 @@
+//Text encodings of field elements, strict: values must be canonical (below the modulus)
+use std::fmt;
+
+use ark_bls12_381::Fr;
+use ark_ff::{BigInt, PrimeField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+const FR_BYTES: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrParseError {
+    //Not hex (or not decimal) at all
+    Malformed { input: String, reason: String },
+    //Hex encodings must be exactly 32 bytes
+    WrongLength { input: String, bytes: usize },
+    //The value is at least the field modulus
+    NotCanonical { input: String },
+}
+
+impl fmt::Display for FrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrParseError::Malformed { input, reason } => write!(f, "Invalid field element '{}': {}", input, reason),
+            FrParseError::WrongLength { input, bytes } => {
+                write!(f, "Invalid field element '{}': expected {} bytes of hex, got {}", input, FR_BYTES, bytes)
+            }
+            FrParseError::NotCanonical { input } => {
+                write!(f, "Invalid field element '{}': value is not below the field modulus", input)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrParseError {}
+
+//Hex of the canonical (little-endian, 32 byte) encoding of a field element
+pub fn fr_to_hex(val: &Fr) -> String {
+    let mut bytes = Vec::with_capacity(FR_BYTES);
+    val.serialize_compressed(&mut bytes).expect("Serializing into a Vec cannot fail");
+    hex::encode(bytes)
+}
+
+//Strict inverse of fr_to_hex, an optional 0x prefix is accepted
+pub fn fr_from_hex_strict(s: &str) -> Result<Fr, FrParseError> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    let bytes = hex::decode(digits).map_err(|e| FrParseError::Malformed { input: s.to_string(), reason: e.to_string() })?;
+    if bytes.len() != FR_BYTES {
+        return Err(FrParseError::WrongLength { input: s.to_string(), bytes: bytes.len() });
+    }
+    Fr::deserialize_compressed(bytes.as_slice()).map_err(|_| FrParseError::NotCanonical { input: s.to_string() })
+}
+
+//Decimal representation of a value in [0, modulus)
+pub fn fr_from_decimal_strict(s: &str) -> Result<Fr, FrParseError> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(FrParseError::Malformed { input: s.to_string(), reason: "expected decimal digits".to_string() });
+    }
+    //Accumulate into 256-bit little-endian limbs, anything wider is certainly too large
+    let mut limbs = [0u64; 4];
+    for digit in s.bytes().map(|b| (b - b'0') as u128) {
+        let mut carry = digit;
+        for limb in limbs.iter_mut() {
+            let wide = (*limb as u128) * 10 + carry;
+            *limb = wide as u64;
+            carry = wide >> 64;
+        }
+        if carry != 0 {
+            return Err(FrParseError::NotCanonical { input: s.to_string() });
+        }
+    }
+    Fr::from_bigint(BigInt(limbs)).ok_or_else(|| FrParseError::NotCanonical { input: s.to_string() })
+}
+
+//Externally supplied field element: 0x-prefixed hex (fr_to_hex layout) or decimal
+pub fn fr_from_str_strict(s: &str) -> Result<Fr, FrParseError> {
+    let s = s.trim();
+    if s.starts_with("0x") {
+        fr_from_hex_strict(s)
+    } else {
+        fr_from_decimal_strict(s)
+    }
+}
+
+//What parse_field_literal does with a well-formed literal at or above the modulus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldLiteralPolicy {
+    //Reject it (FrParseError::NotCanonical)
+    #[default]
+    Strict,
+    //Reduce it modulo the field order and emit a tracing warning, since the proven statement
+    //then differs from the literal's text
+    Reduce,
+}
+
+//Field literal of a circuit file or inputs file: 0x-prefixed hex (fr_to_hex layout) or decimal.
+//Malformed text and hex of the wrong length are rejected under either policy
+pub fn parse_field_literal(s: &str, policy: FieldLiteralPolicy) -> Result<Fr, FrParseError> {
+    match fr_from_str_strict(s) {
+        Err(FrParseError::NotCanonical { .. }) if policy == FieldLiteralPolicy::Reduce => {
+            let s = s.trim();
+            let reduced = match s.strip_prefix("0x") {
+                //Only valid hex of the right length gets this far
+                Some(digits) => Fr::from_le_bytes_mod_order(&hex::decode(digits).unwrap_or_default()),
+                None => s.bytes().fold(Fr::from(0u64), |acc, digit| acc * Fr::from(10u64) + Fr::from((digit - b'0') as u64)),
+            };
+            tracing::warn!(literal = s, reduced = %fr_to_hex(&reduced), "Field literal is not below the modulus and was reduced");
+            Ok(reduced)
+        }
+        result => result,
+    }
+}
+
+//Inverse of fr_to_hex (strict), with the error as a message for the String-based entry points
+pub fn fr_from_hex(s: &str) -> Result<Fr, String> {
+    fr_from_hex_strict(s).map_err(|e| e.to_string())
+}
+
+//Serde form of named field elements as (name, fr_to_hex) pairs, for Circuit::public_inputs
+pub mod named_hex {
+    use ark_bls12_381::Fr;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[(String, Fr)], serializer: S) -> Result<S::Ok, S::Error> {
+        let pairs: Vec<(&String, String)> = values.iter().map(|(name, val)| (name, super::fr_to_hex(val))).collect();
+        pairs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, Fr)>, D::Error> {
+        let pairs: Vec<(String, String)> = Vec::deserialize(deserializer)?;
+        pairs.into_iter().map(|(name, hex)| Ok((name, super::fr_from_hex(&hex).map_err(D::Error::custom)?))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::{BigInteger, One};
+
+    #[test]
+    fn test_non_canonical_encodings_are_rejected() {
+        let modulus = Fr::MODULUS;
+        let modulus_plus_one = {
+            let mut m = modulus;
+            m.add_with_carry(&BigInt::from(1u64));
+            m
+        };
+        assert!(matches!(
+            fr_from_str_strict(&modulus_plus_one.to_string()),
+            Err(FrParseError::NotCanonical { .. })
+        ));
+        assert!(matches!(fr_from_decimal_strict(&modulus.to_string()), Err(FrParseError::NotCanonical { .. })));
+        assert!(matches!(fr_from_decimal_strict(&"9".repeat(100)), Err(FrParseError::NotCanonical { .. })));
+        let below = fr_from_decimal_strict(&(Fr::from(0u64) - Fr::one()).into_bigint().to_string()).unwrap();
+        assert_eq!(below, -Fr::one());
+
+        let hex = fr_to_hex(&Fr::from(42u64));
+        assert_eq!(fr_from_hex_strict(&format!("0x{}", hex)), Ok(Fr::from(42u64)));
+        let err = fr_from_hex_strict(&format!("{}00", hex)).unwrap_err();
+        assert!(matches!(err, FrParseError::WrongLength { bytes: 33, .. }), "{}", err);
+        assert!(err.to_string().contains("expected 32 bytes of hex, got 33"));
+        let modulus_hex = hex::encode(modulus.to_bytes_le());
+        assert!(matches!(fr_from_hex_strict(&modulus_hex), Err(FrParseError::NotCanonical { .. })));
+        assert!(matches!(fr_from_str_strict("12a"), Err(FrParseError::Malformed { .. })));
+        assert_eq!(fr_from_str_strict(" 42 "), Ok(Fr::from(42u64)));
+    }
+
+    #[test]
+    fn test_field_literal_policies_at_the_modulus() {
+        let modulus = Fr::MODULUS;
+        let mut modulus_minus_one = modulus;
+        modulus_minus_one.sub_with_borrow(&BigInt::from(1u64));
+        let mut modulus_plus_one = modulus;
+        modulus_plus_one.add_with_carry(&BigInt::from(1u64));
+
+        for (literal, reduced) in [(modulus_minus_one, -Fr::one()), (modulus, Fr::from(0u64)), (modulus_plus_one, Fr::one())] {
+            let strict = parse_field_literal(&literal.to_string(), FieldLiteralPolicy::Strict);
+            if literal == modulus_minus_one {
+                assert_eq!(strict, Ok(reduced));
+            } else {
+                assert!(matches!(strict, Err(FrParseError::NotCanonical { .. })), "{}", literal);
+            }
+            assert_eq!(parse_field_literal(&literal.to_string(), FieldLiteralPolicy::Reduce), Ok(reduced));
+            let hex_literal = format!("0x{}", hex::encode(literal.to_bytes_le()));
+            assert_eq!(parse_field_literal(&hex_literal, FieldLiteralPolicy::Reduce), Ok(reduced));
+        }
+        assert_eq!(FieldLiteralPolicy::default(), FieldLiteralPolicy::Strict);
+
+        //Circuit files use the same policies for `public` lines
+        let source = format!("name p\namount 0\npublic d {}\n", modulus_plus_one);
+        let err = crate::parse_circuit_str(&source).unwrap_err();
+        assert!(err.to_string().contains("not below the field modulus"), "{}", err);
+        let circuit = crate::parse_circuit_str_with_policy(&source, FieldLiteralPolicy::Reduce).unwrap();
+        assert_eq!(circuit.public_inputs, vec![("d".to_string(), Fr::one())]);
+        assert!(matches!(parse_field_literal("0x1234", FieldLiteralPolicy::Reduce), Err(FrParseError::WrongLength { .. })));
+    }
+}
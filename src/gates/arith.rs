@@ -0,0 +1,220 @@
+//Field arithmetic: add, mul, sub and div over two wires, and constants
+use std::io;
+use std::ops::Neg;
+
+use ark_bls12_381::Fr;
+use ark_ff::{Field, One};
+
+use super::{value, GateImpl, R1csBuilder, WireValues};
+use crate::parser::{invalid_data, ParseContext};
+use crate::{i64_to_fr, Gate};
+
+pub(crate) struct Add<'a> {
+    pub a: &'a str,
+    pub b: &'a str,
+    pub c: &'a str,
+}
+
+pub(crate) struct Mul<'a> {
+    pub a: &'a str,
+    pub b: &'a str,
+    pub c: &'a str,
+}
+
+pub(crate) struct Sub<'a> {
+    pub a: &'a str,
+    pub b: &'a str,
+    pub c: &'a str,
+}
+
+pub(crate) struct Div<'a> {
+    pub a: &'a str,
+    pub b: &'a str,
+    pub c: &'a str,
+}
+
+pub(crate) struct Const<'a> {
+    pub name: &'a str,
+    pub val: i64,
+}
+
+//(a + b) * 1 = c
+impl GateImpl for Add<'_> {
+    fn kind(&self) -> &'static str {
+        "add"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let a_idx = builder.index(self.a);
+        let b_idx = builder.index(self.b);
+        let c_idx = builder.index(self.c);
+        let one = builder.one();
+        builder.push(&[(a_idx, Fr::one()), (b_idx, Fr::one())], &[(one, Fr::one())], &[(c_idx, Fr::one())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        Ok(vec![(self.c.to_string(), value(values, self.a)? + value(values, self.b)?)])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec!["output equation".to_string()]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["add", a, b, c] => Some(Ok(Gate::Add(a.to_string(), b.to_string(), c.to_string(), None))),
+            _ => None,
+        }
+    }
+}
+
+//a * b = c
+impl GateImpl for Mul<'_> {
+    fn kind(&self) -> &'static str {
+        "mul"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let a_idx = builder.index(self.a);
+        let b_idx = builder.index(self.b);
+        let c_idx = builder.index(self.c);
+        builder.push(&[(a_idx, Fr::one())], &[(b_idx, Fr::one())], &[(c_idx, Fr::one())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        Ok(vec![(self.c.to_string(), value(values, self.a)? * value(values, self.b)?)])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec!["output equation".to_string()]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["mul", a, b, c] => Some(Ok(Gate::Mul(a.to_string(), b.to_string(), c.to_string(), None))),
+            _ => None,
+        }
+    }
+}
+
+//(a - b) * 1 = c
+impl GateImpl for Sub<'_> {
+    fn kind(&self) -> &'static str {
+        "sub"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let a_idx = builder.index(self.a);
+        let b_idx = builder.index(self.b);
+        let c_idx = builder.index(self.c);
+        let one = builder.one();
+        builder.push(&[(a_idx, Fr::one()), (b_idx, Fr::one().neg())], &[(one, Fr::one())], &[(c_idx, Fr::one())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        Ok(vec![(self.c.to_string(), value(values, self.a)? - value(values, self.b)?)])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec!["output equation".to_string()]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["sub", a, b, c] => Some(Ok(Gate::Sub(a.to_string(), b.to_string(), c.to_string(), None))),
+            _ => None,
+        }
+    }
+}
+
+//b * c = a, field division. The row holds for any c when a = b = 0, so pair a divisor that
+//can be zero with assert_nonzero
+impl GateImpl for Div<'_> {
+    fn kind(&self) -> &'static str {
+        "div"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let a_idx = builder.index(self.a);
+        let b_idx = builder.index(self.b);
+        let c_idx = builder.index(self.c);
+        builder.push(&[(b_idx, Fr::one())], &[(c_idx, Fr::one())], &[(a_idx, Fr::one())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let b_val = value(values, self.b)?;
+        let inverse = b_val
+            .inverse()
+            .ok_or_else(|| format!("Division by zero: wire '{}' is zero in {} = {} / {}", self.b, self.c, self.a, self.b))?;
+        Ok(vec![(self.c.to_string(), value(values, self.a)? * inverse)])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec!["output equation".to_string()]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["div", a, b, c] => Some(Ok(Gate::Div(a.to_string(), b.to_string(), c.to_string(), None))),
+            _ => None,
+        }
+    }
+}
+
+//val * 1 = name
+impl GateImpl for Const<'_> {
+    fn kind(&self) -> &'static str {
+        "const"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let idx = builder.index(self.name);
+        let one = builder.one();
+        builder.push(&[(one, i64_to_fr(self.val))], &[(one, Fr::one())], &[(idx, Fr::one())]);
+    }
+
+    fn evaluate(&self, _values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        Ok(vec![(self.name.to_string(), i64_to_fr(self.val))])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec!["constant assignment".to_string()]
+    }
+
+    fn parse(line: &str, parts: &[&str], ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["const", name, val] => match val.parse::<i64>() {
+                Ok(value) => Some(ctx.typed_name(name).map(|name| Gate::Const(name, value))),
+                Err(_) => Some(Err(invalid_data(format!("Invalid constant value: {}", line)))),
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{i32_to_fr, parse_circuit_str, run_pipeline, PipelineOptions};
+
+    #[test]
+    fn test_div_proves_and_verifies() {
+        let circuit = parse_circuit_str("name ratio\ninput a 7\ninput b 2\namount 0\ndiv a b c\nmul c b back\n").unwrap();
+        assert!(matches!(&circuit.gates[0], Gate::Div(a, b, c, None) if (a.as_str(), b.as_str(), c.as_str()) == ("a", "b", "c")));
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        //Field division: c is 7 times the inverse of 2, and multiplying back gives 7
+        assert_eq!(witness[&r1cs.var_map["c"]], i32_to_fr(7) * i32_to_fr(2).inverse().unwrap());
+        assert_eq!(witness[&r1cs.var_map["back"]], i32_to_fr(7));
+        let result = run_pipeline(&circuit, PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+    }
+
+    #[test]
+    fn test_div_by_zero_is_an_error() {
+        let circuit = parse_circuit_str("name ratio\ninput a 7\ninput b 0\namount 0\ndiv a b c\n").unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(circuit.compute_witness(&r1cs.var_map).unwrap_err(), "Division by zero: wire 'b' is zero in c = a / b");
+        assert!(run_pipeline(&circuit, PipelineOptions::default()).is_err());
+    }
+}
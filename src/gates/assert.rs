@@ -0,0 +1,250 @@
+//Equality, non-zero and upper bound checks, which assign no wire other than the always-zero
+//output of iseq
+use std::io;
+use std::ops::Neg;
+
+use ark_bls12_381::Fr;
+use ark_ff::{BigInteger, Field, One, PrimeField, Zero};
+
+use super::{value, GateImpl, R1csBuilder, WireValues};
+use crate::parser::{invalid_data, ParseContext};
+use crate::Gate;
+
+pub(crate) struct Eq<'a> {
+    pub a: &'a str,
+    pub b: &'a str,
+    pub out: &'a str,
+}
+
+pub(crate) struct AssertEq<'a> {
+    pub a: &'a str,
+    pub b: &'a str,
+}
+
+pub(crate) struct AssertNonZero<'a> {
+    pub x: &'a str,
+}
+
+pub(crate) struct AssertLeConst<'a> {
+    pub x: &'a str,
+    pub limit: u64,
+}
+
+//Witness variable holding the inverse that proves a wire is non-zero
+fn nonzero_inverse_name(wire: &str) -> String {
+    format!("{}_nonzero_inv", wire)
+}
+
+//Bits of limit - x, as many as limit has (none for a zero limit, which pins x to zero)
+fn limit_bits(limit: u64) -> usize {
+    (u64::BITS - limit.leading_zeros()) as usize
+}
+
+fn le_bit_name(x: &str, limit: u64, i: usize) -> String {
+    format!("{}_le{}_bit{}", x, limit, i)
+}
+
+impl GateImpl for Eq<'_> {
+    fn kind(&self) -> &'static str {
+        "eq"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let a_idx = builder.index(self.a);
+        let b_idx = builder.index(self.b);
+        let out_idx = builder.index(self.out);
+        let one = builder.one();
+        //First constraint: a - b = diff
+        builder.push(&[(a_idx, Fr::one()), (b_idx, Fr::one().neg())], &[(one, Fr::one())], &[(out_idx, Fr::one())]);
+        //Second constraint: diff * diff = 0 (enforces diff = 0)
+        builder.push(&[(out_idx, Fr::one())], &[(out_idx, Fr::one())], &[(one, Fr::zero())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let a_val = value(values, self.a)?;
+        let b_val = value(values, self.b)?;
+        //For equality to hold, a_val must equal b_val
+        if a_val != b_val {
+            return Err(format!("Equality constraint failed: {} ({:?}) != {} ({:?})", self.a, a_val, self.b, b_val));
+        }
+        Ok(vec![(self.out.to_string(), Fr::zero())])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec![format!("difference {} - {}", self.a, self.b), "difference is zero".to_string()]
+    }
+
+    fn parse(line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["iseq", a, b, out] => Some(Ok(Gate::Eq(a.to_string(), b.to_string(), out.to_string()))),
+            //Three-operand eq from before `iseq`, kept working as Gate::Eq
+            ["eq", a, b, out] => {
+                tracing::warn!(line, "Three-operand eq is deprecated, use `eq a b` to assert equality or `iseq a b out`");
+                Some(Ok(Gate::Eq(a.to_string(), b.to_string(), out.to_string())))
+            }
+            _ => None,
+        }
+    }
+}
+
+//(a - b) * 1 = 0
+impl GateImpl for AssertEq<'_> {
+    fn kind(&self) -> &'static str {
+        "assert_eq"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let a_idx = builder.index(self.a);
+        let b_idx = builder.index(self.b);
+        let one = builder.one();
+        builder.push(&[(a_idx, Fr::one()), (b_idx, Fr::one().neg())], &[(one, Fr::one())], &[]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let a_val = value(values, self.a)?;
+        let b_val = value(values, self.b)?;
+        if a_val != b_val {
+            return Err(format!("Assertion failed: wire '{}' is {} but wire '{}' is {}", self.a, a_val, self.b, b_val));
+        }
+        Ok(vec![])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec![format!("{} equals {}", self.a, self.b)]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["eq", a, b] => Some(Ok(Gate::AssertEq(a.to_string(), b.to_string()))),
+            _ => None,
+        }
+    }
+}
+
+//x * inv = 1 has a solution only when x is non-zero
+impl GateImpl for AssertNonZero<'_> {
+    fn kind(&self) -> &'static str {
+        "assert_nonzero"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let x_idx = builder.index(self.x);
+        let inv_idx = builder.index(&nonzero_inverse_name(self.x));
+        let one = builder.one();
+        builder.push(&[(x_idx, Fr::one())], &[(inv_idx, Fr::one())], &[(one, Fr::one())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let val = value(values, self.x)?;
+        let inv = val.inverse().ok_or_else(|| format!("Assertion failed: wire '{}' is zero", self.x))?;
+        Ok(vec![(nonzero_inverse_name(self.x), inv)])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec![format!("{} times its inverse is one", self.x)]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["assert_nonzero", x] => Some(Ok(Gate::AssertNonZero(x.to_string()))),
+            _ => None,
+        }
+    }
+}
+
+//x <= limit: limit - x decomposed into limit_bits(limit) bits. Only sound for a range checked x,
+//a wrapped negative can pass
+impl GateImpl for AssertLeConst<'_> {
+    fn kind(&self) -> &'static str {
+        "assert_le"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let x_idx = builder.index(self.x);
+        let mut recomposition = Vec::with_capacity(limit_bits(self.limit));
+        let mut weight = Fr::one();
+        for i in 0..limit_bits(self.limit) {
+            let idx = builder.index(&le_bit_name(self.x, self.limit, i));
+            builder.push(&[(idx, Fr::one())], &[(idx, Fr::one())], &[(idx, Fr::one())]);
+            recomposition.push((idx, weight));
+            weight += weight;
+        }
+        let one = builder.one();
+        builder.push(&recomposition, &[(one, Fr::one())], &[(one, Fr::from(self.limit)), (x_idx, Fr::one().neg())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let x_val = value(values, self.x)?;
+        let le_bits = (Fr::from(self.limit) - x_val).into_bigint().to_bits_le();
+        if le_bits.iter().skip(limit_bits(self.limit)).any(|bit| *bit) {
+            return Err(format!("Assertion failed: wire '{}' is {}, above the limit {}", self.x, x_val, self.limit));
+        }
+        Ok(le_bits
+            .iter()
+            .take(limit_bits(self.limit))
+            .enumerate()
+            .map(|(i, bit)| (le_bit_name(self.x, self.limit, i), if *bit { Fr::one() } else { Fr::zero() }))
+            .collect())
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        let mut roles: Vec<String> = (0..limit_bits(self.limit)).map(|i| format!("booleanity of bound bit {}", i)).collect();
+        roles.push(format!("{} - {} recomposition", self.limit, self.x));
+        roles
+    }
+
+    fn parse(line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["assert_le", x, limit] => Some(match limit.parse::<u64>() {
+                Ok(limit) => Ok(Gate::AssertLeConst(x.to_string(), limit)),
+                Err(_) => Err(invalid_data(format!("Invalid assert_le limit, expected an integer from 0 to 2^64 - 1: {}", line))),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gates::exhaustive_check;
+    use crate::{parse_circuit_str, run_pipeline, PipelineOptions};
+
+    //Rows emitted by one assert_le gate
+    fn assert_le_cost(limit: u64) -> usize {
+        limit_bits(limit) + 1
+    }
+
+    #[test]
+    fn test_assert_le_at_below_and_above_the_limit() {
+        let circuit = parse_circuit_str("name capped\ninput amount:u32 1000000\namount 0\nassert_le amount 1000000\n").unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        let rows = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(0)).count();
+        assert_eq!(rows, assert_le_cost(1_000_000));
+        for amount in [1_000_000, 999_999, 0] {
+            let mut case = circuit.clone();
+            case.inputs.insert("amount".to_string(), amount);
+            let witness = case.compute_witness(&r1cs.var_map).unwrap();
+            assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None, "amount {}", amount);
+        }
+        let mut above = circuit.clone();
+        above.inputs.insert("amount".to_string(), 1_000_001);
+        assert_eq!(
+            above.compute_witness(&r1cs.var_map).unwrap_err(),
+            "Assertion failed: wire 'amount' is 1000001, above the limit 1000000"
+        );
+        let result = run_pipeline(&circuit, PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+
+        //Rows and evaluation agree around small limits, zero included
+        for limit in [0u64, 1, 5, 8] {
+            let domain = (0..limit + 3).map(Fr::from).collect();
+            assert_eq!(exhaustive_check(&Gate::AssertLeConst("x".to_string(), limit), &[("x".to_string(), domain)]), Ok(()));
+        }
+
+        //No comparison gate yields a bit for x <= limit, the nearest is clamp's 64-bit comparison
+        let limit = (1 << 20) - 1;
+        assert_eq!(limit_bits(limit), 20);
+        assert!(assert_le_cost(limit) < crate::clamp::constraint_cost(), "{} rows", assert_le_cost(limit));
+    }
+}
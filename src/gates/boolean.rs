@@ -0,0 +1,205 @@
+//Gates over bits: xor, booleanity assertions, conditional swaps and conditional disclosure
+use std::io;
+use std::ops::Neg;
+
+use ark_bls12_381::Fr;
+use ark_ff::{One, Zero};
+
+use super::{value, GateImpl, R1csBuilder, WireValues};
+use crate::parser::ParseContext;
+use crate::{i32_to_fr, Gate};
+
+pub(crate) struct Xor<'a> {
+    pub a: &'a str,
+    pub b: &'a str,
+    pub c: &'a str,
+}
+
+pub(crate) struct AssertBool<'a> {
+    pub b: &'a str,
+}
+
+pub(crate) struct CondSwap<'a> {
+    pub bit: &'a str,
+    pub x: &'a str,
+    pub y: &'a str,
+    pub left: &'a str,
+    pub right: &'a str,
+}
+
+pub(crate) struct RevealIf<'a> {
+    pub flag: &'a str,
+    pub value: &'a str,
+    pub disclosed: &'a str,
+}
+
+impl GateImpl for Xor<'_> {
+    fn kind(&self) -> &'static str {
+        "xor"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let a_idx = builder.index(self.a);
+        let b_idx = builder.index(self.b);
+        let ab_idx = builder.index(&format!("{}_xor_prod_{}", self.a, self.b));
+        let c_idx = builder.index(self.c);
+        let one = builder.one();
+        //a*b = ab
+        builder.push(&[(a_idx, Fr::one())], &[(b_idx, Fr::one())], &[(ab_idx, Fr::one())]);
+        //a + b - 2*ab = c
+        builder.push(&[(a_idx, Fr::one()), (b_idx, Fr::one()), (ab_idx, i32_to_fr(-2))], &[(one, Fr::one())], &[(c_idx, Fr::one())]);
+        //boolean constraints to ensure a and b are either 0 or 1
+        builder.push(&[(a_idx, Fr::one())], &[(a_idx, Fr::one())], &[(a_idx, Fr::one())]);
+        builder.push(&[(b_idx, Fr::one())], &[(b_idx, Fr::one())], &[(b_idx, Fr::one())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let a_val = *values.get(self.a).ok_or_else(|| format!("Var {} not found for XOR", self.a))?;
+        let b_val = *values.get(self.b).ok_or_else(|| format!("Var {} not found for XOR", self.b))?;
+        if !(a_val.is_zero() || a_val.is_one()) {
+            return Err(format!("XOR input {} is not a bit (0 or 1)", self.a));
+        }
+        if !(b_val.is_zero() || b_val.is_one()) {
+            return Err(format!("XOR input {} is not a bit (0 or 1)", self.b));
+        }
+        let ab_val = a_val * b_val;
+        let c_val = a_val + b_val - (i32_to_fr(2) * ab_val);
+        Ok(vec![(format!("{}_xor_prod_{}", self.a, self.b), ab_val), (self.c.to_string(), c_val)])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec![
+            format!("product {}*{}", self.a, self.b),
+            "output equation".to_string(),
+            format!("booleanity of {}", self.a),
+            format!("booleanity of {}", self.b),
+        ]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["xor", a, b, c] => Some(Ok(Gate::Xor(a.to_string(), b.to_string(), c.to_string()))),
+            _ => None,
+        }
+    }
+}
+
+//b * b = b holds only for 0 and 1
+impl GateImpl for AssertBool<'_> {
+    fn kind(&self) -> &'static str {
+        "assert_bool"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let b_idx = builder.index(self.b);
+        builder.push(&[(b_idx, Fr::one())], &[(b_idx, Fr::one())], &[(b_idx, Fr::one())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let val = value(values, self.b)?;
+        if !(val.is_zero() || val.is_one()) {
+            return Err(format!("Assertion failed: wire '{}' is {}, not a bit (0 or 1)", self.b, val));
+        }
+        Ok(vec![])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec![format!("booleanity of {}", self.b)]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["assert_bool", b] => Some(Ok(Gate::AssertBool(b.to_string()))),
+            _ => None,
+        }
+    }
+}
+
+//b * b = b, b * (y - x) = left - x and b * (x - y) = right - y
+impl GateImpl for CondSwap<'_> {
+    fn kind(&self) -> &'static str {
+        "condswap"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let bit_idx = builder.index(self.bit);
+        let x_idx = builder.index(self.x);
+        let y_idx = builder.index(self.y);
+        let left_idx = builder.index(self.left);
+        let right_idx = builder.index(self.right);
+        builder.push(&[(bit_idx, Fr::one())], &[(bit_idx, Fr::one())], &[(bit_idx, Fr::one())]);
+        for (from, to, out) in [(x_idx, y_idx, left_idx), (y_idx, x_idx, right_idx)] {
+            builder.push(
+                &[(bit_idx, Fr::one())],
+                &[(to, Fr::one()), (from, Fr::one().neg())],
+                &[(out, Fr::one()), (from, Fr::one().neg())],
+            );
+        }
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let bit_val = value(values, self.bit)?;
+        let x_val = value(values, self.x)?;
+        let y_val = value(values, self.y)?;
+        if !(bit_val.is_zero() || bit_val.is_one()) {
+            return Err(format!("Selector '{}' of condswap is {}, not a bit (0 or 1)", self.bit, bit_val));
+        }
+        let (left_val, right_val) = if bit_val.is_one() { (y_val, x_val) } else { (x_val, y_val) };
+        Ok(vec![(self.left.to_string(), left_val), (self.right.to_string(), right_val)])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec![
+            format!("booleanity of {}", self.bit),
+            format!("select {}", self.left),
+            format!("select {}", self.right),
+        ]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["condswap", bit, x, y, left, right] => {
+                Some(Ok(Gate::CondSwap(bit.to_string(), x.to_string(), y.to_string(), left.to_string(), right.to_string())))
+            }
+            _ => None,
+        }
+    }
+}
+
+//flag * flag = flag and flag * value = disclosed
+impl GateImpl for RevealIf<'_> {
+    fn kind(&self) -> &'static str {
+        "reveal_if"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let flag_idx = builder.index(self.flag);
+        let value_idx = builder.index(self.value);
+        let disclosed_idx = builder.index(self.disclosed);
+        builder.push(&[(flag_idx, Fr::one())], &[(flag_idx, Fr::one())], &[(flag_idx, Fr::one())]);
+        builder.push(&[(flag_idx, Fr::one())], &[(value_idx, Fr::one())], &[(disclosed_idx, Fr::one())]);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let flag_val = value(values, self.flag)?;
+        let value_val = value(values, self.value)?;
+        if !(flag_val.is_zero() || flag_val.is_one()) {
+            return Err(format!("Flag '{}' of reveal_if is {}, not a bit (0 or 1)", self.flag, flag_val));
+        }
+        Ok(vec![(self.disclosed.to_string(), flag_val * value_val)])
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        vec![
+            format!("booleanity of {}", self.flag),
+            format!("{} is {} or zero", self.disclosed, self.value),
+        ]
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["reveal_if", flag, value, disclosed] => Some(Ok(Gate::RevealIf(flag.to_string(), value.to_string(), disclosed.to_string()))),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//Exhaustive spot checks of one gate: its rows must hold for exactly the assignments its
+//evaluate accepts, over small domains of the listed wires
+use std::collections::HashMap;
+use std::fmt;
+
+use ark_bls12_381::Fr;
+use ark_ff::Zero;
+
+use crate::{Circuit, Gate, R1CSSystem};
+
+//An assignment on which the rows and native evaluation disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub assignment: Vec<(String, Fr)>,
+    //Whether evaluate accepts the assignment (and computes the listed outputs)
+    pub native_accepts: bool,
+    //The first row that fails, None when the rows accept an assignment evaluation refuses
+    pub failing_row: Option<usize>,
+    pub reason: String,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let assignment: Vec<String> = self.assignment.iter().map(|(name, val)| format!("{}={}", name, val)).collect();
+        write!(f, "Rows and evaluation disagree for [{}]: {}", assignment.join(", "), self.reason)
+    }
+}
+
+impl std::error::Error for Counterexample {}
+
+//The gate alone, in a circuit without transfer parties
+fn single_gate_circuit(gate: &Gate) -> Circuit {
+    Circuit {
+        name: "exhaustive_check".to_string(),
+        inputs: HashMap::new(),
+        gates: vec![gate.clone()],
+        sender: String::new(),
+        receiver: String::new(),
+        transfer_amount: 0,
+        ..Default::default()
+    }
+}
+
+//Check the gate's rows against its evaluate on every assignment of the domains. Every operand
+//needs a domain, outputs may have one
+pub fn exhaustive_check(gate: &Gate, domains: &[(String, Vec<Fr>)]) -> Result<(), Counterexample> {
+    let circuit = single_gate_circuit(gate);
+    check_rows(&circuit, &circuit.to_r1cs_system(), domains)
+}
+
+fn check_rows(circuit: &Circuit, r1cs: &R1CSSystem, domains: &[(String, Vec<Fr>)]) -> Result<(), Counterexample> {
+    let gate = &circuit.gates[0];
+    for operand in gate.operands() {
+        assert!(domains.iter().any(|(name, _)| name == operand), "exhaustive_check needs a domain for operand '{}'", operand);
+    }
+    if domains.iter().any(|(_, domain)| domain.is_empty()) {
+        return Ok(());
+    }
+    let outputs = gate.outputs();
+    let implementation = gate.implementation();
+
+    //Odometer over the domains, the last wire turning fastest
+    let mut digits = vec![0usize; domains.len()];
+    loop {
+        let assignment: Vec<(String, Fr)> = domains.iter().zip(&digits).map(|((name, domain), digit)| (name.clone(), domain[*digit])).collect();
+        let mut values = circuit.input_wire_values();
+        values.extend(assignment.iter().filter(|(name, _)| !outputs.contains(&name)).cloned());
+
+        let native = implementation.evaluate(&values);
+        let native_accepts = match &native {
+            Ok(assigned) => assignment.iter().filter(|(name, _)| outputs.contains(&name)).all(|(name, val)| {
+                assigned.iter().any(|(wire, computed)| wire == name && computed == val)
+            }),
+            Err(_) => false,
+        };
+        //Listed values win over computed ones
+        if let Ok(assigned) = native {
+            for (wire, val) in assigned {
+                values.entry(wire).or_insert(val);
+            }
+        }
+        values.extend(assignment.iter().cloned());
+        let witness: HashMap<usize, Fr> =
+            r1cs.var_map.iter().map(|(name, idx)| (*idx, values.get(name).copied().unwrap_or_else(Fr::zero))).collect();
+        let failing_row = r1cs.which_is_unsatisfied(&witness).expect("Every variable has a value");
+
+        if native_accepts != failing_row.is_none() {
+            let reason = match failing_row {
+                Some(row) => format!("evaluation accepts it but {} fails", r1cs.explain(row)),
+                None => "evaluation refuses it but every row holds".to_string(),
+            };
+            return Err(Counterexample { assignment, native_accepts, failing_row, reason });
+        }
+
+        //Next assignment
+        let mut position = digits.len();
+        loop {
+            if position == 0 {
+                return Ok(());
+            }
+            position -= 1;
+            digits[position] += 1;
+            if digits[position] < domains[position].1.len() {
+                break;
+            }
+            digits[position] = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uint::UIntOp;
+
+    fn w(name: &str) -> String {
+        name.to_string()
+    }
+
+    fn range(names: &[&str], values: std::ops::Range<u64>) -> Vec<(String, Vec<Fr>)> {
+        names.iter().map(|name| (w(name), values.clone().map(Fr::from).collect())).collect()
+    }
+
+    #[test]
+    fn test_boolean_gates_match_their_rows() {
+        //2 is outside every boolean domain, the rows must refuse it wherever evaluation does
+        let cases = [
+            (Gate::Xor(w("a"), w("b"), w("c")), range(&["a", "b", "c"], 0..3)),
+            (Gate::AssertBool(w("b")), range(&["b"], 0..3)),
+            (Gate::AssertNonZero(w("x")), range(&["x"], 0..3)),
+            (Gate::AssertEq(w("a"), w("b")), range(&["a", "b"], 0..3)),
+            (Gate::Eq(w("a"), w("b"), w("d")), range(&["a", "b", "d"], 0..3)),
+            (Gate::CondSwap(w("s"), w("x"), w("y"), w("l"), w("r")), range(&["s", "x", "y", "l", "r"], 0..3)),
+            (Gate::RevealIf(w("f"), w("v"), w("d")), range(&["f", "v", "d"], 0..3)),
+            (Gate::Decode(w("i"), vec![w("o0"), w("o1"), w("o2")]), [range(&["i"], 0..4), range(&["o0", "o1", "o2"], 0..2)].concat()),
+            (Gate::Const(w("k"), 1), range(&["k"], 0..3)),
+        ];
+        for (gate, domains) in cases {
+            assert_eq!(exhaustive_check(&gate, &domains), Ok(()), "{:?}", gate);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_and_comparison_gates_match_their_rows() {
+        let cases = [
+            (Gate::Add(w("a"), w("b"), w("c"), None), range(&["a", "b", "c"], 0..5)),
+            (Gate::Sub(w("a"), w("b"), w("c"), None), range(&["a", "b", "c"], 0..5)),
+            (Gate::Mul(w("a"), w("b"), w("c"), None), range(&["a", "b", "c"], 0..5)),
+            //A zero divisor is refused by evaluation but not by the row when a is zero too
+            (Gate::Div(w("a"), w("b"), w("c"), None), [range(&["a", "c"], 0..5), range(&["b"], 1..5)].concat()),
+            (Gate::Clamp(w("x"), 4, w("m")), range(&["x", "m"], 0..8)),
+            (Gate::UInt(UIntOp::Add, 32, w("a"), w("b"), w("s"), w("carry")), [
+                vec![(w("a"), vec![Fr::from(0u64), Fr::from(1u64), Fr::from(u32::MAX)])],
+                vec![(w("b"), vec![Fr::from(1u64), Fr::from(u32::MAX)])],
+                range(&["carry"], 0..3),
+            ].concat()),
+        ];
+        for (gate, domains) in cases {
+            assert_eq!(exhaustive_check(&gate, &domains), Ok(()), "{:?}", gate);
+        }
+    }
+
+    #[test]
+    fn test_missing_row_is_a_counterexample() {
+        //Without its booleanity rows Xor accepts a non-bit operand, the first is b = 2 with c = a + b - 2ab
+        let gate = Gate::Xor(w("a"), w("b"), w("c"));
+        let circuit = single_gate_circuit(&gate);
+        let mut r1cs = circuit.to_r1cs_system();
+        let kept = r1cs.raw_constraints.len() - 2;
+        r1cs.raw_constraints.truncate(kept);
+        r1cs.constraint_origins.truncate(kept);
+        let counterexample = check_rows(&circuit, &r1cs, &range(&["a", "b", "c"], 0..3)).unwrap_err();
+        assert_eq!(counterexample.assignment, vec![(w("a"), Fr::zero()), (w("b"), Fr::from(2u64)), (w("c"), Fr::from(2u64))]);
+        assert!(!counterexample.native_accepts);
+        assert_eq!(counterexample.failing_row, None);
+        assert_eq!(counterexample.to_string(), "Rows and evaluation disagree for [a=0, b=2, c=2]: evaluation refuses it but every row holds");
+    }
+}
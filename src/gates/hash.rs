@@ -0,0 +1,172 @@
+//Poseidon gates: hash, hash_bytes, hashn and nullifier (rows in poseidon.rs and nullifier.rs)
+use std::io;
+
+use ark_bls12_381::Fr;
+
+use super::{poseidon_roles, value, GateImpl, R1csBuilder, WireValues};
+use crate::parser::{byte_wire, invalid_data, ParseContext};
+use crate::{nullifier, poseidon, Gate};
+
+pub(crate) struct Hash<'a> {
+    pub input: &'a str,
+    pub output: &'a str,
+}
+
+pub(crate) struct HashBytes<'a> {
+    pub bytes: &'a [String],
+    pub output: &'a str,
+}
+
+pub(crate) struct HashN<'a> {
+    pub inputs: &'a [String],
+    pub output: &'a str,
+}
+
+pub(crate) struct Nullifier<'a> {
+    pub secret: &'a str,
+    pub nonce: &'a str,
+    pub output: &'a str,
+}
+
+//Prefix of the permutation state wires of a hash into output
+fn state_prefix(output: &str) -> String {
+    format!("{}_hash", output)
+}
+
+//Values of the wires a gate reads
+fn values_of(values: &WireValues, wires: &[String]) -> Result<Vec<Fr>, String> {
+    wires.iter().map(|wire| value(values, wire)).collect()
+}
+
+//output = poseidon_hash([input])
+impl GateImpl for Hash<'_> {
+    fn kind(&self) -> &'static str {
+        "hash"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let input_idx = builder.index(self.input);
+        let output_idx = builder.index(self.output);
+        poseidon::emit_poseidon_rows(&[input_idx], output_idx, &state_prefix(self.output),
+            builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let in_val = value(values, self.input)?;
+        let (out_val, mut assigned) = poseidon::poseidon_witness(&[in_val], &state_prefix(self.output));
+        assigned.push((self.output.to_string(), out_val));
+        Ok(assigned)
+    }
+
+    fn roles(&self, num_rows: usize) -> Vec<String> {
+        poseidon_roles(num_rows)
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["hash", input, output] => Some(Ok(Gate::Hash(input.to_string(), output.to_string()))),
+            _ => None,
+        }
+    }
+}
+
+impl GateImpl for HashBytes<'_> {
+    fn kind(&self) -> &'static str {
+        "hash_bytes"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let byte_idxs: Vec<usize> = self.bytes.iter().map(|byte| builder.index(byte)).collect();
+        let output_idx = builder.index(self.output);
+        poseidon::emit_hash_bytes_rows(&byte_idxs, output_idx, &state_prefix(self.output),
+            builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let byte_vals = values_of(values, self.bytes)?;
+        let (out_val, mut assigned) = poseidon::hash_bytes_witness(&byte_vals, &state_prefix(self.output));
+        assigned.push((self.output.to_string(), out_val));
+        Ok(assigned)
+    }
+
+    fn roles(&self, num_rows: usize) -> Vec<String> {
+        poseidon_roles(num_rows)
+    }
+
+    fn parse(_line: &str, parts: &[&str], ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["hash_bytes", input, output] => Some(match ctx.byte_inputs.get(*input) {
+                Some(len) => Ok(Gate::HashBytes((0..*len).map(|i| byte_wire(input, i)).collect(), output.to_string())),
+                None => Err(invalid_data(format!("hash_bytes needs a byte-string input, '{}' is not one", input))),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl GateImpl for HashN<'_> {
+    fn kind(&self) -> &'static str {
+        "hashn"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let input_idxs: Vec<usize> = self.inputs.iter().map(|input| builder.index(input)).collect();
+        let output_idx = builder.index(self.output);
+        poseidon::emit_poseidon_rows(&input_idxs, output_idx, &state_prefix(self.output),
+            builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let in_vals = values_of(values, self.inputs)?;
+        let (out_val, mut assigned) = poseidon::poseidon_witness(&in_vals, &state_prefix(self.output));
+        assigned.push((self.output.to_string(), out_val));
+        Ok(assigned)
+    }
+
+    fn roles(&self, num_rows: usize) -> Vec<String> {
+        poseidon_roles(num_rows)
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["hashn", inputs @ .., "->", output] if !inputs.is_empty() => {
+                Some(Ok(Gate::HashN(inputs.iter().map(|input| input.to_string()).collect(), output.to_string())))
+            }
+            _ => None,
+        }
+    }
+}
+
+//output = nullifier::derive(secret, nonce)
+impl GateImpl for Nullifier<'_> {
+    fn kind(&self) -> &'static str {
+        "nullifier"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        let secret_idx = builder.index(self.secret);
+        let nonce_idx = builder.index(self.nonce);
+        let output_idx = builder.index(self.output);
+        nullifier::emit_nullifier_rows(secret_idx, nonce_idx, output_idx, &state_prefix(self.output),
+            builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        let secret_val = value(values, self.secret)?;
+        let nonce_val = value(values, self.nonce)?;
+        let (out_val, mut assigned) = nullifier::nullifier_witness(secret_val, nonce_val, &state_prefix(self.output));
+        assigned.push((self.output.to_string(), out_val));
+        Ok(assigned)
+    }
+
+    fn roles(&self, num_rows: usize) -> Vec<String> {
+        poseidon_roles(num_rows)
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["nullifier", secret, nonce, output] => Some(Ok(Gate::Nullifier(secret.to_string(), nonce.to_string(), output.to_string()))),
+            _ => None,
+        }
+    }
+}
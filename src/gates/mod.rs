@@ -0,0 +1,176 @@
+//One implementation per gate (parsing, rows and evaluation). A new gate is a struct in one of
+//these files, an arm in Gate::implementation and an entry in PARSERS
+use std::collections::HashMap;
+use std::io;
+
+use ark_bls12_381::Fr;
+
+use crate::parser::ParseContext;
+use crate::{get_index, sum_terms, Gate, _R1CSConstraintInternal};
+
+mod arith;
+mod assert;
+mod boolean;
+mod check;
+mod hash;
+mod numeric;
+mod vector;
+
+pub use check::{exhaustive_check, Counterexample};
+
+//Wire values by name, as compute_witness assigns them
+pub type WireValues = HashMap<String, Fr>;
+
+pub trait GateImpl {
+    //Name of the gate's kind, one of GATE_KINDS
+    fn kind(&self) -> &'static str;
+
+    //Append the gate's rows, allocating its wires in the order of their first use
+    fn emit_constraints(&self, builder: &mut R1csBuilder);
+
+    //The wires the gate assigns from the current values, in order; Err when the gate cannot hold
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String>;
+
+    //Role of each of the num_rows rows emit_constraints appended, in emission order
+    fn roles(&self, num_rows: usize) -> Vec<String>;
+
+    //The gate of a split DSL line (keyword first), None when the line is not this gate's
+    fn parse(line: &str, parts: &[&str], ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>>
+    where
+        Self: Sized;
+}
+
+//The state a gate emits its rows into
+pub struct R1csBuilder<'a> {
+    pub(crate) var_map: &'a mut HashMap<String, usize>,
+    pub(crate) next_index: &'a mut usize,
+    pub(crate) constraints: &'a mut Vec<_R1CSConstraintInternal>,
+    //Values of the circuit's Const wires, which dot products fold into coefficients
+    pub(crate) constants: &'a HashMap<&'a str, Fr>,
+}
+
+impl R1csBuilder<'_> {
+    //Index of a wire's variable, allocated on first use
+    pub fn index(&mut self, wire: &str) -> usize {
+        get_index(wire, self.var_map, self.next_index)
+    }
+
+    //Index of the constant one
+    pub fn one(&self) -> usize {
+        self.var_map["1"]
+    }
+
+    //Append the row a * b = c, each side a sum of (variable, coefficient) terms
+    pub fn push(&mut self, a: &[(usize, Fr)], b: &[(usize, Fr)], c: &[(usize, Fr)]) {
+        self.constraints.push(_R1CSConstraintInternal { a: sum_terms(a), b: sum_terms(b), c: sum_terms(c) });
+    }
+}
+
+//Value of a wire the gate reads
+fn value(values: &WireValues, wire: &str) -> Result<Fr, String> {
+    values.get(wire).copied().ok_or_else(|| format!("Var {} not found", wire))
+}
+
+//Roles of the Poseidon gates: the permutation rows, then the row that assigns the output
+fn poseidon_roles(num_rows: usize) -> Vec<String> {
+    let mut roles = vec!["Poseidon permutation".to_string(); num_rows.saturating_sub(1)];
+    roles.push("output equation".to_string());
+    roles
+}
+
+type ParseFn = fn(&str, &[&str], &mut ParseContext) -> Option<Result<Gate, io::Error>>;
+
+//The DSL parser of every gate, tried in order
+const PARSERS: &[ParseFn] = &[
+    <arith::Add as GateImpl>::parse,
+    <arith::Mul as GateImpl>::parse,
+    <arith::Sub as GateImpl>::parse,
+    <arith::Div as GateImpl>::parse,
+    <assert::AssertEq as GateImpl>::parse,
+    <assert::Eq as GateImpl>::parse,
+    <arith::Const as GateImpl>::parse,
+    <boolean::Xor as GateImpl>::parse,
+    <hash::Hash as GateImpl>::parse,
+    <hash::Nullifier as GateImpl>::parse,
+    <hash::HashBytes as GateImpl>::parse,
+    <hash::HashN as GateImpl>::parse,
+    <vector::Decode as GateImpl>::parse,
+    <numeric::Clamp as GateImpl>::parse,
+    <vector::DotProduct as GateImpl>::parse,
+    <boolean::RevealIf as GateImpl>::parse,
+    <boolean::CondSwap as GateImpl>::parse,
+    <numeric::PowMod as GateImpl>::parse,
+    <assert::AssertNonZero as GateImpl>::parse,
+    <boolean::AssertBool as GateImpl>::parse,
+    <assert::AssertLeConst as GateImpl>::parse,
+    <numeric::UInt as GateImpl>::parse,
+];
+
+//The gate of a DSL line, None when no gate claims it
+pub(crate) fn parse_gate(line: &str, parts: &[&str], ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+    PARSERS.iter().find_map(|parse| parse(line, parts, ctx))
+}
+
+impl Gate {
+    pub fn implementation(&self) -> Box<dyn GateImpl + '_> {
+        match self {
+            Gate::Add(a, b, c, _) => Box::new(arith::Add { a, b, c }),
+            Gate::Mul(a, b, c, _) => Box::new(arith::Mul { a, b, c }),
+            Gate::Sub(a, b, c, _) => Box::new(arith::Sub { a, b, c }),
+            Gate::Div(a, b, c, _) => Box::new(arith::Div { a, b, c }),
+            Gate::Const(name, val) => Box::new(arith::Const { name, val: *val }),
+            Gate::Xor(a, b, c) => Box::new(boolean::Xor { a, b, c }),
+            Gate::AssertBool(b) => Box::new(boolean::AssertBool { b }),
+            Gate::CondSwap(bit, x, y, left, right) => Box::new(boolean::CondSwap { bit, x, y, left, right }),
+            Gate::RevealIf(flag, value, disclosed) => Box::new(boolean::RevealIf { flag, value, disclosed }),
+            Gate::Eq(a, b, out) => Box::new(assert::Eq { a, b, out }),
+            Gate::AssertEq(a, b) => Box::new(assert::AssertEq { a, b }),
+            Gate::AssertNonZero(x) => Box::new(assert::AssertNonZero { x }),
+            Gate::AssertLeConst(x, limit) => Box::new(assert::AssertLeConst { x, limit: *limit }),
+            Gate::Hash(input, output) => Box::new(hash::Hash { input, output }),
+            Gate::HashBytes(bytes, output) => Box::new(hash::HashBytes { bytes, output }),
+            Gate::HashN(inputs, output) => Box::new(hash::HashN { inputs, output }),
+            Gate::Nullifier(secret, nonce, output) => Box::new(hash::Nullifier { secret, nonce, output }),
+            Gate::Clamp(x, cap, out) => Box::new(numeric::Clamp { x, cap: *cap, out }),
+            Gate::PowMod(base, e, n, out) => Box::new(numeric::PowMod { base, e: *e, n: *n, out }),
+            Gate::UInt(op, bits, a, b, low, high) => Box::new(numeric::UInt { op: *op, bits: *bits, a, b, low, high }),
+            Gate::Decode(index, outs) => Box::new(vector::Decode { index, outs }),
+            Gate::DotProduct(pairs, out) => Box::new(vector::DotProduct { pairs, out }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_circuit_str, GATE_KINDS};
+
+    #[test]
+    fn test_every_kind_is_registered() {
+        //One line per kind, each must be claimed by the parser of its own kind
+        let lines = [
+            "add a b c", "mul a b c", "sub a b c", "xor a b c", "const k 7", "hash a h", "iseq a b d",
+            "assert_nonzero a", "assert_bool a", "eq a b", "clamp a 4 m", "nullifier a b n", "decode a o0 o1",
+            "u32add a b s carry", "hash_bytes msg hb", "hashn a b -> hn", "powmod a 3 5 p",
+            "condswap f a b l r", "dot (a b) (b a) -> d", "reveal_if f a r", "assert_le a 1000",
+            "div a b c",
+        ];
+        let mut ctx = ParseContext { byte_inputs: HashMap::from([("msg".to_string(), 2)]), wire_types: HashMap::new() };
+        let kinds: Vec<&str> = lines.iter().map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            parse_gate(line, &parts, &mut ctx).unwrap().unwrap().kind()
+        }).collect();
+        assert_eq!(kinds, GATE_KINDS);
+        assert!(parse_gate("frobnicate a", &["frobnicate", "a"], &mut ctx).is_none());
+        assert!(parse_gate("add a b", &["add", "a", "b"], &mut ctx).is_none());
+
+        //Every gate gives each of its rows a role
+        let circuit = parse_circuit_str("name kinds\ninput a 1\ninput b 1\ninput f 1\ninput msg bytes 0102\namount 0\n\
+            add a b c\nhash a h\nhashn a b -> hn\nxor a b x\ndot (a b) (b a) -> d\n").unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        for (gate_index, gate) in circuit.gates.iter().enumerate() {
+            let rows = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(gate_index)).count();
+            assert_eq!(gate.implementation().roles(rows).len(), rows, "{:?}", gate);
+        }
+    }
+}
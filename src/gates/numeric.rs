@@ -0,0 +1,119 @@
+//Integer gates over range-checked wires: clamp, powmod and the machine-integer operations. Their
+//rows and witnesses live in clamp.rs, powmod.rs and uint.rs
+use std::io;
+
+use ark_bls12_381::Fr;
+
+use super::{GateImpl, R1csBuilder, WireValues};
+use crate::parser::{invalid_data, ParseContext};
+use crate::uint::UIntOp;
+use crate::{clamp, powmod, uint, Gate};
+
+pub(crate) struct Clamp<'a> {
+    pub x: &'a str,
+    pub cap: i64,
+    pub out: &'a str,
+}
+
+pub(crate) struct PowMod<'a> {
+    pub base: &'a str,
+    pub e: u64,
+    pub n: u64,
+    pub out: &'a str,
+}
+
+pub(crate) struct UInt<'a> {
+    pub op: UIntOp,
+    pub bits: u32,
+    pub a: &'a str,
+    pub b: &'a str,
+    pub low: &'a str,
+    pub high: &'a str,
+}
+
+impl GateImpl for Clamp<'_> {
+    fn kind(&self) -> &'static str {
+        "clamp"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        clamp::emit_clamp_rows(self.x, self.cap, self.out, builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        clamp::clamp_witness(self.x, self.cap, self.out, values)
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        clamp::clamp_roles(self.x, self.cap)
+    }
+
+    fn parse(line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["clamp", x, cap, out] => Some(match cap.parse::<i64>() {
+                Ok(cap) => Ok(Gate::Clamp(x.to_string(), cap, out.to_string())),
+                Err(_) => Err(invalid_data(format!("Invalid clamp cap: {}", line))),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl GateImpl for PowMod<'_> {
+    fn kind(&self) -> &'static str {
+        "powmod"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        powmod::emit_powmod_rows(self.base, self.e, self.n, self.out, builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        powmod::powmod_witness(self.base, self.e, self.n, self.out, values)
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        powmod::powmod_roles(self.base, self.e, self.n)
+    }
+
+    fn parse(line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        let ["powmod", base, e, n, out] = parts else {
+            return None;
+        };
+        let Ok(e) = e.parse::<u64>() else {
+            return Some(Err(invalid_data(format!("Invalid powmod exponent: {}", line))));
+        };
+        match n.parse::<u64>().ok().filter(|n| *n >= 2) {
+            Some(n) => Some(Ok(Gate::PowMod(base.to_string(), e, n, out.to_string()))),
+            None => Some(Err(invalid_data(format!("Invalid powmod modulus (must be at least 2): {}", line)))),
+        }
+    }
+}
+
+impl GateImpl for UInt<'_> {
+    fn kind(&self) -> &'static str {
+        "uint"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        uint::emit_uint_rows(self.op, self.bits, (self.a, self.b, self.low, self.high),
+            builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        uint::uint_witness(self.op, self.bits, (self.a, self.b, self.low, self.high), values)
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        uint::uint_roles(self.op, self.bits, self.a, self.b)
+    }
+
+    //u<bits>add, u<bits>sub or u<bits>mul for each of UINT_WIDTHS
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        let [keyword, a, b, low, high] = parts else {
+            return None;
+        };
+        let (op, bits) = UIntOp::parse_keyword(keyword)?;
+        Some(Ok(Gate::UInt(op, bits, a.to_string(), b.to_string(), low.to_string(), high.to_string())))
+    }
+}
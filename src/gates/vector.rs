@@ -0,0 +1,88 @@
+//Gates over lists of wires: one-hot decode and dot products (rows in decode.rs and dot.rs)
+use std::io;
+
+use ark_bls12_381::Fr;
+
+use super::{GateImpl, R1csBuilder, WireValues};
+use crate::parser::{invalid_data, ParseContext};
+use crate::{decode, dot, Gate};
+
+pub(crate) struct Decode<'a> {
+    pub index: &'a str,
+    pub outs: &'a [String],
+}
+
+pub(crate) struct DotProduct<'a> {
+    pub pairs: &'a [(String, String)],
+    pub out: &'a str,
+}
+
+//The (w x) pairs of a dot line, split on whitespace as "(w" and "x)"
+fn dot_pairs(terms: &[&str]) -> Option<Vec<(String, String)>> {
+    terms
+        .chunks(2)
+        .map(|pair| match pair {
+            [w, x] => {
+                let w = w.strip_prefix('(').filter(|w| !w.is_empty())?;
+                let x = x.strip_suffix(')').filter(|x| !x.is_empty())?;
+                Some((w.to_string(), x.to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+impl GateImpl for Decode<'_> {
+    fn kind(&self) -> &'static str {
+        "decode"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        decode::emit_decode_rows(self.index, self.outs, builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        decode::decode_witness(self.index, self.outs, values)
+    }
+
+    fn roles(&self, _num_rows: usize) -> Vec<String> {
+        decode::decode_roles(self.index, self.outs)
+    }
+
+    fn parse(_line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["decode", index, outs @ ..] if !outs.is_empty() => {
+                Some(Ok(Gate::Decode(index.to_string(), outs.iter().map(|out| out.to_string()).collect())))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl GateImpl for DotProduct<'_> {
+    fn kind(&self) -> &'static str {
+        "dot"
+    }
+
+    fn emit_constraints(&self, builder: &mut R1csBuilder) {
+        dot::emit_dot_rows(self.pairs, self.out, builder.constants, builder.var_map, builder.next_index, builder.constraints);
+    }
+
+    fn evaluate(&self, values: &WireValues) -> Result<Vec<(String, Fr)>, String> {
+        dot::dot_witness(self.pairs, self.out, values)
+    }
+
+    fn roles(&self, num_rows: usize) -> Vec<String> {
+        dot::dot_roles(self.out, num_rows)
+    }
+
+    fn parse(line: &str, parts: &[&str], _ctx: &mut ParseContext) -> Option<Result<Gate, io::Error>> {
+        match parts {
+            ["dot", terms @ .., "->", out] if !terms.is_empty() => Some(match dot_pairs(terms) {
+                Some(pairs) => Ok(Gate::DotProduct(pairs, out.to_string())),
+                None => Err(invalid_data(format!("Invalid dot terms, expected (w x) pairs: {}", line))),
+            }),
+            _ => None,
+        }
+    }
+}
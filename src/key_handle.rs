@@ -0,0 +1,153 @@
+//Cloneable handles to one proving key, so parallel proofs share it, loaded lazily from an
+//uncompressed artifact file
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use ark_groth16::ProvingKey as ArkGroth16ProvingKey;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+
+use crate::artifact::{read_header, write_header, ArtifactError, ArtifactKind, HEADER_LEN};
+use crate::ProvingKey;
+
+#[derive(Debug)]
+pub enum KeyHandleError {
+    Io { path: PathBuf, error: std::io::Error },
+    Serialization { path: PathBuf, error: SerializationError },
+    Header { path: PathBuf, error: ArtifactError },
+}
+
+impl fmt::Display for KeyHandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyHandleError::Io { path, error } => write!(f, "Proving key file {}: {}", path.display(), error),
+            KeyHandleError::Serialization { path, error } => {
+                write!(f, "Proving key file {} is not a valid key: {}", path.display(), error)
+            }
+            KeyHandleError::Header { path, error } => write!(f, "Proving key file {}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for KeyHandleError {}
+
+struct Shared {
+    //None for handles created from a key already in memory
+    path: Option<PathBuf>,
+    //Circuit the key file must have been saved for, when known
+    circuit_hash: Option<[u8; 32]>,
+    key: OnceLock<ProvingKey>,
+    //Serializes the first load so concurrent callers do not each read the file
+    loading: Mutex<()>,
+}
+
+#[derive(Clone)]
+pub struct ProvingKeyHandle {
+    shared: Arc<Shared>,
+}
+
+impl ProvingKeyHandle {
+    //Share a key that is already in memory
+    pub fn new(pk: ProvingKey) -> Self {
+        let key = OnceLock::new();
+        let _ = key.set(pk);
+        ProvingKeyHandle { shared: Arc::new(Shared { path: None, circuit_hash: None, key, loading: Mutex::new(()) }) }
+    }
+
+    //Handle to a key file written by save; nothing is read until the key is first needed. With a
+    //circuit hash, a key saved for another circuit fails to load with ArtifactError::WrongCircuit
+    pub fn open(path: impl Into<PathBuf>, circuit_hash: Option<[u8; 32]>) -> Self {
+        ProvingKeyHandle {
+            shared: Arc::new(Shared { path: Some(path.into()), circuit_hash, key: OnceLock::new(), loading: Mutex::new(()) }),
+        }
+    }
+
+    //Write a key in the uncompressed handle layout, streaming it to the file
+    pub fn save(pk: &ProvingKey, circuit_hash: Option<&[u8; 32]>, path: &Path) -> Result<(), KeyHandleError> {
+        let io_err = |error| KeyHandleError::Io { path: path.to_path_buf(), error };
+        let mut writer = BufWriter::new(File::create(path).map_err(io_err)?);
+        writer.write_all(&write_header(ArtifactKind::UncompressedProvingKey, circuit_hash)).map_err(io_err)?;
+        pk.0.serialize_uncompressed(&mut writer)
+            .map_err(|error| KeyHandleError::Serialization { path: path.to_path_buf(), error })?;
+        writer.flush().map_err(io_err)
+    }
+
+    //The shared key, loading it on the first call
+    pub fn get(&self) -> Result<&ProvingKey, KeyHandleError> {
+        if let Some(pk) = self.shared.key.get() {
+            return Ok(pk);
+        }
+        let _loading = self.shared.loading.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pk) = self.shared.key.get() {
+            return Ok(pk);
+        }
+        let path = self.shared.path.as_ref().expect("In-memory handles always hold their key");
+        let io_err = |error| KeyHandleError::Io { path: path.clone(), error };
+        let mut reader = BufReader::new(File::open(path).map_err(io_err)?);
+        //A file shorter than the header is reported as a missing header, not an I/O error
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        reader.by_ref().take(HEADER_LEN as u64).read_to_end(&mut header).map_err(io_err)?;
+        read_header(&header, ArtifactKind::UncompressedProvingKey, self.shared.circuit_hash.as_ref())
+            .map_err(|error| KeyHandleError::Header { path: path.clone(), error })?;
+        let pk = ArkGroth16ProvingKey::deserialize_uncompressed(reader)
+            .map_err(|error| KeyHandleError::Serialization { path: path.clone(), error })?;
+        Ok(self.shared.key.get_or_init(|| ProvingKey(pk)))
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.shared.key.get().is_some()
+    }
+
+    //Number of handles sharing this key
+    pub fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.shared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mul_circuit;
+    use crate::{prove, setup, verify};
+
+    #[test]
+    fn test_prove_twice_from_one_handle() {
+        let circuit = mul_circuit("handle");
+        let r1cs = circuit.to_r1cs_system();
+        let (pk, vk) = setup(&r1cs).expect("Setup failed");
+        let dir = std::env::temp_dir().join(format!("zk-key-handle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pk.uncompressed");
+        let hash = circuit.canonical_hash();
+        ProvingKeyHandle::save(&pk, Some(&hash), &path).unwrap();
+        drop(pk);
+
+        let handle = ProvingKeyHandle::open(&path, Some(hash));
+        assert!(!handle.is_loaded());
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        let public_inputs = r1cs.public_inputs_from_witness(&witness).unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..2 {
+                let handle = handle.clone();
+                let (r1cs, witness, vk, public_inputs) = (&r1cs, witness.clone(), &vk, &public_inputs);
+                scope.spawn(move || {
+                    let proof = prove(r1cs, handle.get().unwrap(), witness).expect("Proving failed");
+                    assert!(verify(vk, &proof, public_inputs).unwrap());
+                });
+            }
+        });
+        assert!(handle.is_loaded());
+        assert_eq!(handle.handle_count(), 1);
+        let first: *const ProvingKey = handle.get().unwrap();
+        assert!(std::ptr::eq(first, handle.clone().get().unwrap()), "Clones must share one key");
+
+        let err = ProvingKeyHandle::open(&path, Some([7; 32])).get().err().expect("Other circuit must not load");
+        assert!(matches!(err, KeyHandleError::Header { error: ArtifactError::WrongCircuit { .. }, .. }), "{}", err);
+        std::fs::write(&path, b"not a key").unwrap();
+        let err = ProvingKeyHandle::open(&path, None).get().err().expect("Garbage must not load");
+        assert!(matches!(err, KeyHandleError::Header { error: ArtifactError::MissingHeader, .. }), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,14 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 use std::ops::Neg;
 // Arkworks imports - v0.5.0
 use ark_bls12_381::{Bls12_381, Fr};
 use ark_ff::{One, Zero};
 use ark_relations::r1cs::{
-    ConstraintSynthesizer, ConstraintSystemRef,
-    LinearCombination, SynthesisError, Variable,
+    ConstraintSynthesizer, ConstraintSystem, SynthesisError, SynthesisMode,
 };
 //zk-SNARK imports
 use ark_groth16::{
@@ -15,16 +18,200 @@ use ark_groth16::{
     Proof as ArkGroth16Proof,
 };
 use ark_crypto_primitives::snark::SNARK;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use ark_std::rand::rngs::OsRng;
+use ark_std::rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
 
 //Parser imports
 pub mod parser;
-pub use parser::parse_circuit;
+pub use parser::{parse_circuit, parse_circuit_str, parse_circuit_str_with_policy, parse_inputs};
+
+//Batch proving
+pub mod batch;
+
+//Progress reporting
+pub mod progress;
+pub use progress::{NoProgress, ProgressEvent, ProgressSink};
+
+//Poseidon hash shared by native code and circuits
+pub mod poseidon;
+pub use poseidon::poseidon_hash;
+
+//Field element text encodings
+pub mod fr_codec;
+pub use fr_codec::FieldLiteralPolicy;
+
+//JSON export of R1CS systems
+pub mod export;
+
+//Raw A, B and C matrices of R1CS systems in CSR form
+pub mod matrices;
+pub use matrices::SparseMatrix;
+
+//C ABI for other languages
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+//Browser bindings
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+//Key cache keyed by the canonical circuit hash
+pub mod cache;
+pub use cache::KeyCache;
+
+//Public ConstraintSynthesizer over an R1CSSystem
+pub mod adapter;
+pub use adapter::{CircuitError, R1CSAdapter};
+
+//Pedersen commitments to amounts
+pub mod commitment;
+
+//Random circuit generation and pipeline assertions for tests and benches
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+//Schnorr signatures over the embedded Bandersnatch curve
+pub mod signature;
+
+//Binary witness files (.wit)
+pub mod witness_file;
+pub use witness_file::{load_witness, save_witness, WitnessFile, WitnessFileError};
+
+//Optional wire types and the type-checking pass
+pub mod types;
+pub use types::{TypeError, WireType};
+
+//Batched verification of many proofs for one verifying key
+#[cfg(feature = "aggregation")]
+pub mod aggregation;
+
+//Incremental witness recomputation for changing inputs
+pub mod witness_engine;
+pub use witness_engine::WitnessEngine;
+
+//Gate-level optimizer passes
+pub mod optimize;
+pub use optimize::{eliminate_common_subexpressions, CseReport};
+
+//Randomized equivalence checks of optimizer output
+pub mod equivalence;
+pub use equivalence::{check_equivalent, Counterexample};
+
+//Verifying keys for several circuits, selected by id
+pub mod registry;
+pub use registry::{CircuitMetadata, CircuitRegistry, RegistryError};
+
+//End-to-end proving pipeline
+pub mod pipeline;
+pub use pipeline::{run_pipeline, run_pipeline_str, PipelineError, PipelineOptions, PipelineResult, Timings};
+
+//Setup/prove/verify times and key and proof sizes
+pub mod perf;
+pub use perf::{measure, PerfReport, SerializedSize};
+
+//Proving keys shared across concurrent proofs, optionally loaded lazily from disk
+pub mod key_handle;
+pub use key_handle::{KeyHandleError, ProvingKeyHandle};
+
+//Versioned headers of keys, proofs and witnesses written to disk
+pub mod artifact;
+pub use artifact::{ArtifactError, ArtifactKind};
+
+//Machine-integer gates (add with carry, sub with borrow, mul into low/high words)
+pub mod uint;
+pub use uint::UIntOp;
+
+//Clamp gate, min of a wire and a constant cap
+pub mod clamp;
+
+//Nullifier derivation shared by native code and the nullifier gate
+pub mod nullifier;
+
+//Decode gate, one-hot vector from an index
+pub mod decode;
+
+//Modular exponentiation gate by constants, for RSA-style checks
+pub mod powmod;
+
+//Inner product gate with constant weights folded into one row
+pub mod dot;
+
+//Reusable prover for many witnesses of one R1CS, proving concurrently without copying the keys
+pub mod prover;
+pub use prover::Prover;
+
+//Canonical R1CS form, so systems of one logical circuit built by different front ends share keys
+pub mod canonical;
+
+//Dropping exact duplicate rows from compiled systems
+mod dedup;
+
+//Logic of the examples/ gallery, shared with its smoke tests
+pub mod examples_support;
+
+//Committed test vectors guarding constraint generation and serialization against silent changes
+#[cfg(test)]
+mod fixtures;
+
+//Warnings for public inputs that reveal private values
+pub mod privacy;
+pub use privacy::{LeakReason, LeakWarning};
+
+//Variables a prover may be able to set freely
+pub mod soundness;
+pub use soundness::{UnderconstrainedReason, UnderconstrainedWarning};
+
+//Deny/allow lists and count limits on the gate kinds of proved circuits
+pub mod policy;
+pub use policy::{GatePolicy, PolicyError, PolicyViolation};
+
+//Whether a circuit still compiles to the constraint system of an earlier R1CS export
+pub mod compatibility;
+pub use compatibility::CompatReport;
+
+//Fail fast when a circuit compiles to more rows or variables than allowed
+pub mod budget;
+pub use budget::{BudgetExceeded, ConstraintBudget};
+
+//Per-gate DSL syntax, rows and witness evaluation, and exhaustive checks of the two
+pub mod gates;
+pub use gates::{GateImpl, R1csBuilder};
+
+//Witness maps that wipe their values when dropped
+pub mod secret;
+pub use secret::SecretWitness;
+
+//Opt-in subscriber setup for the tracing spans of the pipeline stages
+#[cfg(feature = "logging")]
+pub mod logging;
+#[cfg(feature = "logging")]
+pub use logging::init_logging;
+
+//Proofs with their circuit, verifying key fingerprint, public inputs and creation time
+pub mod envelope;
+pub use envelope::{EnvelopeError, ProofEnvelope};
+
+//Interpreter behind the repl subcommand, building a circuit one DSL line at a time
+pub mod repl;
+pub use repl::Repl;
 
 //Helper function for converting i32 to Fr
 pub fn i32_to_fr(val: i32) -> Fr {
+    i64_to_fr(val as i64)
+}
+
+//Helper function for converting monetary u64 values to Fr
+pub fn u64_to_fr(val: u64) -> Fr {
+    Fr::from(val)
+}
+
+//Helper function for converting i64 to Fr (negative values map to p - |val|)
+pub fn i64_to_fr(val: i64) -> Fr {
     if val < 0 {
-        Fr::from((-val) as u64).neg()
+        Fr::from(val.unsigned_abs()).neg()
     } else {
         Fr::from(val as u64)
     }
@@ -42,20 +229,155 @@ fn get_index(var: &str, var_index: &mut HashMap<String, usize>, next_index: &mut
     }
 }
 
+//Helper function for building a linear combination, summing the coefficients of repeated
+//variables (e.g. Add(x, x, y) must give 2*x, not x)
+fn sum_terms(terms: &[(usize, Fr)]) -> HashMap<usize, Fr> {
+    let mut lc: HashMap<usize, Fr> = HashMap::new();
+    for (idx, coeff) in terms {
+        *lc.entry(*idx).or_insert_with(Fr::zero) += coeff;
+    }
+    lc
+}
+
+//Helpers for length-prefixed canonical encodings
+fn write_len(bytes: &mut Vec<u8>, len: usize) {
+    bytes.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn write_str(bytes: &mut Vec<u8>, s: &str) {
+    write_len(bytes, s.len());
+    bytes.extend_from_slice(s.as_bytes());
+}
+
+//A balance kept out of the public inputs is still the party's input wire: (initial - input) * 1 = 0,
+//so the sufficiency check and the input digest are about the balance the gates read, not a free witness
+fn private_balance_row(party: &str, var_map: &mut HashMap<String, usize>, next_index: &mut usize) -> (_R1CSConstraintInternal, ConstraintOrigin) {
+    let initial_name = format!("{}_initial_balance", party);
+    let initial_idx = get_index(&initial_name, var_map, next_index);
+    let input_idx = get_index(party, var_map, next_index);
+    let row = _R1CSConstraintInternal {
+        a: sum_terms(&[(initial_idx, Fr::one()), (input_idx, Fr::one().neg())]),
+        b: vec![(var_map["1"], Fr::one())].into_iter().collect(),
+        c: HashMap::new(),
+    };
+    let origin = ConstraintOrigin {
+        gate_index: None,
+        gate: format!("transfer spec ({})", initial_name),
+        role: format!("private balance equals the input wire {}", party),
+    };
+    (row, origin)
+}
+
+//Every name Gate::kind returns
+pub const GATE_KINDS: [&str; 22] = [
+    "add", "mul", "sub", "xor", "const", "hash", "eq", "assert_nonzero", "assert_bool", "assert_eq",
+    "clamp", "nullifier", "decode", "uint", "hash_bytes", "hashn", "powmod", "condswap", "dot",
+    "reveal_if", "assert_le", "div",
+];
+
 //Enum for the gates (define the types of gate supported by the circuit)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Gate {
     Add(String, String, String, Option<i32>),
     Mul(String, String, String, Option<i32>),
     Sub(String, String, String, Option<i32>),
+    //Field division c = a / b, the single row b * c = a (see gates/arith.rs)
+    Div(String, String, String, Option<i32>),
     Xor(String, String, String),
-    Const(String, i32),
+    //Constants are i64 so field-sized (beyond i32) values can be declared
+    Const(String, i64),
     Hash(String, String),
+    //Checks a == b and assigns out = a - b, which is always zero (the DSL's `iseq`)
     Eq(String, String, String),
+    //Assertions on existing wires, they assign no output wire
+    AssertNonZero(String),
+    AssertBool(String),
+    //Hard equality of two wires: the single row (a - b) * 1 = 0
+    AssertEq(String, String),
+    //out = min(x, cap) for a constant cap (see clamp.rs)
+    Clamp(String, i64, String),
+    //out = nullifier::derive(secret, nonce)
+    Nullifier(String, String, String),
+    //One-hot decode of an index into n outputs, out_i = 1 exactly for i == index (see decode.rs)
+    Decode(String, Vec<String>),
+    //Poseidon hash of any number of wires, output = poseidon_hash(inputs)
+    HashN(Vec<String>, String),
+    //Machine-integer operation of the given width (see uint.rs): a, b, low word and carry,
+    //borrow or high word
+    UInt(UIntOp, u32, String, String, String, String),
+    //Poseidon hash of byte wires (range checked to u8 elsewhere) into one output wire, see
+    //poseidon::hash_bytes
+    HashBytes(Vec<String>, String),
+    //out = base^e mod n for constants e and n below 2^64 (see powmod.rs)
+    PowMod(String, u64, u64, String),
+    //Conditional swap on a bit b: (left, right) = (x, y) for b = 0 and (y, x) for b = 1, e.g. to
+    //order a Merkle node and its sibling by the path's direction bit
+    CondSwap(String, String, String, String, String),
+    //out = sum of w_i * x_i over the (w_i, x_i) pairs, pairs with a Const wire are folded into
+    //coefficients (see dot.rs)
+    DotProduct(Vec<(String, String)>, String),
+    //Conditional disclosure on a private bit flag: the public input disclosed = flag * value, so
+    //value is published when flag is 1 and zero is published otherwise
+    RevealIf(String, String, String),
+    //x <= limit for a constant limit, with no output wire: limit - x is decomposed into as many
+    //bits as limit has (see gates/assert.rs)
+    AssertLeConst(String, u64),
 }
 
-//Struct for the circuit (define the circuit structure)
-#[derive(Debug, Clone)]
+impl Gate {
+    //The wires the gate assigns, none for assertions
+    pub fn outputs(&self) -> Vec<&String> {
+        match self {
+            Gate::Add(_, _, c, _) | Gate::Mul(_, _, c, _) | Gate::Sub(_, _, c, _) | Gate::Div(_, _, c, _) | Gate::Xor(_, _, c) | Gate::Eq(_, _, c) | Gate::Nullifier(_, _, c) => vec![c],
+            Gate::Const(name, _) => vec![name],
+            Gate::Hash(_, output) | Gate::HashBytes(_, output) | Gate::HashN(_, output) | Gate::Clamp(_, _, output) | Gate::PowMod(_, _, _, output) => vec![output],
+            Gate::DotProduct(_, out) | Gate::RevealIf(_, _, out) => vec![out],
+            Gate::AssertNonZero(_) | Gate::AssertBool(_) | Gate::AssertEq(..) | Gate::AssertLeConst(..) => vec![],
+            Gate::UInt(_, _, _, _, low, high) | Gate::CondSwap(_, _, _, low, high) => vec![low, high],
+            Gate::Decode(_, outs) => outs.iter().collect(),
+        }
+    }
+
+    //The wires the gate reads
+    pub fn operands(&self) -> Vec<&String> {
+        match self {
+            Gate::Add(a, b, _, _) | Gate::Mul(a, b, _, _) | Gate::Sub(a, b, _, _) | Gate::Div(a, b, _, _) | Gate::Xor(a, b, _) | Gate::Eq(a, b, _) | Gate::Nullifier(a, b, _) => vec![a, b],
+            Gate::UInt(_, _, a, b, _, _) | Gate::AssertEq(a, b) | Gate::RevealIf(a, b, _) => vec![a, b],
+            Gate::Hash(input, _) | Gate::AssertNonZero(input) | Gate::AssertBool(input) | Gate::Clamp(input, _, _) | Gate::Decode(input, _) | Gate::PowMod(input, _, _, _) => vec![input],
+            Gate::AssertLeConst(input, _) => vec![input],
+            Gate::HashBytes(inputs, _) | Gate::HashN(inputs, _) => inputs.iter().collect(),
+            Gate::CondSwap(bit, x, y, _, _) => vec![bit, x, y],
+            Gate::DotProduct(pairs, _) => pairs.iter().flat_map(|(w, x)| [w, x]).collect(),
+            Gate::Const(..) => vec![],
+        }
+    }
+
+    //Name of the gate's kind, used in reports and gate policies (one of GATE_KINDS)
+    pub fn kind(&self) -> &'static str {
+        self.implementation().kind()
+    }
+
+    //Every wire the gate names, operands then outputs, for renaming in place
+    pub fn wires_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Gate::Add(a, b, c, _) | Gate::Mul(a, b, c, _) | Gate::Sub(a, b, c, _) | Gate::Div(a, b, c, _) | Gate::Xor(a, b, c) | Gate::Eq(a, b, c) | Gate::Nullifier(a, b, c) => vec![a, b, c],
+            Gate::RevealIf(flag, value, disclosed) => vec![flag, value, disclosed],
+            Gate::UInt(_, _, a, b, low, high) => vec![a, b, low, high],
+            Gate::CondSwap(bit, x, y, left, right) => vec![bit, x, y, left, right],
+            Gate::Hash(input, output) | Gate::Clamp(input, _, output) | Gate::PowMod(input, _, _, output) => vec![input, output],
+            Gate::HashBytes(inputs, output) | Gate::HashN(inputs, output) => inputs.iter_mut().chain([output]).collect(),
+            Gate::Decode(index, outs) => std::iter::once(index).chain(outs.iter_mut()).collect(),
+            Gate::DotProduct(pairs, out) => pairs.iter_mut().flat_map(|(w, x)| [w, x]).chain([out]).collect(),
+            Gate::AssertNonZero(x) | Gate::AssertBool(x) | Gate::AssertLeConst(x, _) => vec![x],
+            Gate::AssertEq(a, b) => vec![a, b],
+            Gate::Const(name, _) => vec![name],
+        }
+    }
+}
+
+//Struct for the circuit (define the circuit structure). The default is an empty circuit with no
+//transfer parties, so literals only spell out the fields they care about
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Circuit {
     pub name: String,
     pub inputs: HashMap<String, i32>,
@@ -63,7 +385,59 @@ pub struct Circuit {
     pub gates: Vec<Gate>,
     pub sender: String,
     pub receiver: String,
-    pub transfer_amount: i32,
+    //A u64, and validate_gates rejects negative sender and receiver balances, so no monetary
+    //value reaches the field as p - x
+    pub transfer_amount: u64,
+    //Declared wire types (`input flag:bool 1`), wires not listed are untyped field elements
+    #[serde(default)]
+    pub wire_types: HashMap<String, WireType>,
+    //Field-valued public inputs (`public digest 0x..`), verified after the transfer spec in
+    //declaration order. Only the prover's witness takes these values, verifiers supply their own
+    #[serde(default, with = "fr_codec::named_hex")]
+    pub public_inputs: Vec<(String, Fr)>,
+    //Keep the initial balances private (`balances private`): instead of publishing them, the
+    //proof shows the sender's balance covers the transfer amount (see privacy.rs). Needs
+    //bind_inputs, the digest is what ties the proof to the actual balance
+    #[serde(default)]
+    pub private_balances: bool,
+    //Commit to every input with one public Poseidon digest (`inputs bound`), the last public
+    //input, named INPUT_DIGEST_WIRE (see Circuit::input_digest)
+    #[serde(default)]
+    pub bind_inputs: bool,
+    //Which transfer spec values are pinned as public inputs (`transfer_options hide_amount`)
+    #[serde(default)]
+    pub transfer_options: TransferOptions,
+}
+
+//The transfer spec publishes the sender's balance, the receiver's balance and the amount, each
+//pinned to its value by one constraint. A hidden value stays a witness wire with no pin, so only
+//the gates reading it constrain it. Balances are never exposed with private_balances
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferOptions {
+    pub expose_sender_balance: bool,
+    pub expose_receiver_balance: bool,
+    pub expose_amount: bool,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        TransferOptions { expose_sender_balance: true, expose_receiver_balance: true, expose_amount: true }
+    }
+}
+
+//Wire of the input digest of circuits with bind_inputs
+pub const INPUT_DIGEST_WIRE: &str = "input_digest";
+
+//Bits of the sender's balance after a transfer with private balances, whose range check shows
+//the balance covers the amount (validate_gates keeps balances non-negative i32, so 32 bits suffice)
+pub const SUFFICIENCY_BITS: u32 = 32;
+
+//Bits of the range check on a hidden transfer amount, which no public pin ties to its u64 value
+pub const AMOUNT_BITS: u32 = 64;
+
+fn balance_after_name(sender: &str) -> String {
+    format!("{}_balance_after", sender)
 }
 
 //Functions for the circuit struct
@@ -71,39 +445,296 @@ impl Circuit {
     //Validate the transfer (check if the sender has enough balance)
     pub fn validate_transfer(&self) -> bool {
         if let Some(sender_balance) = self.inputs.get(&self.sender) {
-            *sender_balance >= self.transfer_amount
+            u64::try_from(*sender_balance).is_ok_and(|balance| balance >= self.transfer_amount)
         } else {
             false
         }
     }
 
-    //Execute the transfer (subtract the transfer amount from the sender's balance and add it to the receiver's balance)
-    pub fn execute_transfer(&mut self) {
-        if self.validate_transfer() {
-            if let Some(sender_balance) = self.inputs.get_mut(&self.sender) {
-                *sender_balance -= self.transfer_amount;
+    //Each Const gate must assign its own wire: a second definition or a Const on an input
+    //would add a conflicting constraint and make the system unsatisfiable
+    pub fn check_constants(&self) -> Result<(), CircuitError> {
+        let mut defined = std::collections::HashSet::new();
+        for gate in &self.gates {
+            if let Gate::Const(name, _) = gate {
+                if self.inputs.contains_key(name) {
+                    return Err(CircuitError::ConstOnInput { wire: name.clone() });
+                }
+                if !defined.insert(name) {
+                    return Err(CircuitError::DuplicateConst { wire: name.clone() });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //Wire behind a well-known DSL name: `$one` is the constant one, `$transfer_amount`,
+    //`$sender_balance` and `$receiver_balance` the transfer spec wires (the balances only when
+    //that party is a circuit input, private with private_balances) and `$input_digest` the
+    //digest of bind_inputs. None for any other name
+    pub fn well_known_wire(&self, name: &str) -> Option<String> {
+        let balance = |party: &String| self.inputs.contains_key(party).then(|| format!("{}_initial_balance", party));
+        match name {
+            "$one" => Some("1".to_string()),
+            "$transfer_amount" => Some("transfer_amount_public".to_string()),
+            "$sender_balance" => balance(&self.sender),
+            "$receiver_balance" => balance(&self.receiver),
+            "$input_digest" => self.bind_inputs.then(|| INPUT_DIGEST_WIRE.to_string()),
+            _ => None,
+        }
+    }
+
+    //Inputs in the order bind_inputs hashes them: sorted by name
+    fn bound_inputs(&self) -> Vec<(&String, &i32)> {
+        let mut inputs: Vec<(&String, &i32)> = self.inputs.iter().collect();
+        inputs.sort();
+        inputs
+    }
+
+    //Poseidon digest of the inputs (values in name order), the public input of circuits with
+    //bind_inputs. Verifiers holding the inputs recompute it to check a proof is about them
+    pub fn input_digest(&self) -> Fr {
+        let values: Vec<Fr> = self.bound_inputs().into_iter().map(|(_, val)| i32_to_fr(*val)).collect();
+        poseidon::poseidon_hash(&values)
+    }
+
+    //A transfer moves value between two parties and may be of zero (e.g. to prove a balance
+    //without moving any), a party transferring to itself is a CircuitError::SelfTransfer.
+    //Every wire must have a single writer: gate outputs may not be operands of their own gate,
+    //outputs of another gate, or inputs (including the transfer spec wires). Reassigning a wire
+    //would emit a second constraint on the same variable (`add t x t` forces x = 0). Repeating
+    //an operand is fine (`mul x x sq`, `add x x double`), as is any number of readers; an
+    //accumulator is written as a chain of fresh wires (`add acc0 x acc1`, `add acc1 y acc2`)
+    pub fn validate_gates(&self) -> Result<(), CircuitError> {
+        self.check_constants()?;
+        //Circuits without a transfer leave both parties empty
+        if !self.sender.is_empty() && self.sender == self.receiver {
+            return Err(CircuitError::SelfTransfer { party: self.sender.clone() });
+        }
+        for party in [&self.sender, &self.receiver] {
+            if let Some(&balance) = self.inputs.get(party).filter(|balance| **balance < 0) {
+                return Err(CircuitError::NegativeBalance { party: party.clone(), balance });
+            }
+        }
+        //Without the digest a private balance is any witness the prover likes
+        if self.private_balances && !self.bind_inputs {
+            return Err(CircuitError::UnboundPrivateBalances);
+        }
+        let reserved = |name: &str| {
+            name.starts_with('$')
+                || name == "1"
+                || name == "transfer_amount_public"
+                || (self.bind_inputs && name == INPUT_DIGEST_WIRE)
+                || [&self.sender, &self.receiver].iter().any(|party| name == format!("{}_initial_balance", party))
+        };
+        let mut declared: Vec<String> = Vec::new();
+        for name in self.inputs.keys().chain(self.public_inputs.iter().map(|(name, _)| name)) {
+            if reserved(name) {
+                return Err(CircuitError::ReservedName { wire: name.clone() });
+            }
+            if declared.contains(name) {
+                return Err(CircuitError::DuplicateInput { wire: name.clone() });
+            }
+            declared.push(name.clone());
+        }
+        let fixed = self.input_wire_values();
+        let mut writers: HashMap<&String, usize> = HashMap::new();
+        for (gate_index, gate) in self.gates.iter().enumerate() {
+            //Well-known names the parser did not resolve (or a circuit built in code used verbatim)
+            if let Some(wire) = gate.operands().into_iter().chain(gate.outputs()).find(|wire| wire.starts_with('$')) {
+                return Err(CircuitError::ReservedName { wire: wire.clone() });
+            }
+            for output in gate.outputs() {
+                if gate.operands().contains(&output) {
+                    return Err(CircuitError::SelfReferential { gate_index, wire: output.clone() });
+                }
+                if fixed.contains_key(output) {
+                    return Err(CircuitError::OverwritesInput { gate_index, wire: output.clone() });
+                }
+                if let Some(first_gate) = writers.insert(output, gate_index) {
+                    return Err(CircuitError::MultipleWriters { wire: output.clone(), first_gate, second_gate: gate_index });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //Execute the transfer (subtract the transfer amount from the sender's balance and add it to the receiver's balance),
+    //an invalid transfer or a self-transfer changes nothing. A receiver balance the amount would take past
+    //i32::MAX is a CircuitError::BalanceOverflow and a negative one a CircuitError::NegativeBalance,
+    //both leave the balances as they were
+    pub fn execute_transfer(&mut self) -> Result<(), CircuitError> {
+        if !self.validate_transfer() || self.sender == self.receiver {
+            return Ok(());
+        }
+        let overflow = || CircuitError::BalanceOverflow { party: self.receiver.clone(), amount: self.transfer_amount };
+        //Balances are non-negative (validate_transfer checked the sender's), so the sums are u64
+        let receiver_balance = match self.inputs.get(&self.receiver) {
+            Some(&balance) => {
+                let balance = u64::try_from(balance).map_err(|_| CircuitError::NegativeBalance { party: self.receiver.clone(), balance })?;
+                Some(balance.checked_add(self.transfer_amount).and_then(|sum| i32::try_from(sum).ok()).ok_or_else(overflow)?)
+            }
+            None => None,
+        };
+        if let Some(sender_balance) = self.inputs.get_mut(&self.sender) {
+            //At most the old balance, which is an i32
+            *sender_balance = (*sender_balance as u64 - self.transfer_amount) as i32;
+        }
+        if let Some(balance) = receiver_balance {
+            self.inputs.insert(self.receiver.clone(), balance);
+        }
+        Ok(())
+    }
+
+    //Hash of canonical_bytes
+    pub fn canonical_hash(&self) -> [u8; 32] {
+        Sha256::digest(self.canonical_bytes()).into()
+    }
+
+    //Normalized serialization of everything that shapes the constraint system: sorted inputs,
+    //gates in order and the transfer spec (the circuit name and outputs are ignored). Fixed field
+    //order, length-prefixed strings and compressed field elements, never serde_json or Debug
+    //output, so the bytes only change with the encoding's version tag (v2: the amount takes 8
+    //bytes). Hash or sign these
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"zk-framework/circuit/v2".to_vec();
+
+        let mut inputs: Vec<(&String, &i32)> = self.inputs.iter().collect();
+        inputs.sort();
+        write_len(&mut bytes, inputs.len());
+        for (name, val) in inputs {
+            write_str(&mut bytes, name);
+            bytes.extend_from_slice(&val.to_le_bytes());
+        }
+
+        write_len(&mut bytes, self.gates.len());
+        for gate in &self.gates {
+            let (tag, wires, values): (u8, Vec<&String>, Vec<Option<i64>>) = match gate {
+                Gate::Add(a, b, c, m) => (0, vec![a, b, c], vec![m.map(i64::from)]),
+                Gate::Mul(a, b, c, m) => (1, vec![a, b, c], vec![m.map(i64::from)]),
+                Gate::Sub(a, b, c, m) => (2, vec![a, b, c], vec![m.map(i64::from)]),
+                Gate::Xor(a, b, c) => (3, vec![a, b, c], vec![]),
+                Gate::Const(name, val) => (4, vec![name], vec![Some(*val)]),
+                Gate::Hash(input, output) => (5, vec![input, output], vec![]),
+                Gate::Eq(a, b, out) => (6, vec![a, b, out], vec![]),
+                Gate::AssertNonZero(x) => (7, vec![x], vec![]),
+                Gate::AssertBool(x) => (8, vec![x], vec![]),
+                Gate::UInt(op, bits, a, b, low, high) => (9, vec![a, b, low, high], vec![Some(*op as i64), Some(*bits as i64)]),
+                Gate::HashBytes(bytes, output) => (10, bytes.iter().chain([output]).collect(), vec![Some(bytes.len() as i64)]),
+                Gate::AssertEq(a, b) => (11, vec![a, b], vec![]),
+                Gate::Clamp(x, cap, out) => (12, vec![x, out], vec![Some(*cap)]),
+                Gate::Nullifier(secret, nonce, out) => (13, vec![secret, nonce, out], vec![]),
+                Gate::Decode(index, outs) => (14, std::iter::once(index).chain(outs).collect(), vec![Some(outs.len() as i64)]),
+                Gate::HashN(inputs, output) => (15, inputs.iter().chain([output]).collect(), vec![Some(inputs.len() as i64)]),
+                //Constants above i64::MAX keep their bits
+                Gate::PowMod(base, e, n, out) => (16, vec![base, out], vec![Some(*e as i64), Some(*n as i64)]),
+                Gate::CondSwap(bit, x, y, left, right) => (17, vec![bit, x, y, left, right], vec![]),
+                Gate::DotProduct(pairs, out) => (18, pairs.iter().flat_map(|(w, x)| [w, x]).chain([out]).collect(), vec![Some(pairs.len() as i64)]),
+                Gate::RevealIf(flag, value, disclosed) => (19, vec![flag, value, disclosed], vec![]),
+                Gate::AssertLeConst(x, limit) => (20, vec![x], vec![Some(*limit as i64)]),
+                Gate::Div(a, b, c, m) => (21, vec![a, b, c], vec![m.map(i64::from)]),
+            };
+            bytes.push(tag);
+            for wire in wires {
+                write_str(&mut bytes, wire);
+            }
+            for val in values {
+                match val {
+                    Some(v) => {
+                        bytes.push(1);
+                        bytes.extend_from_slice(&v.to_le_bytes());
+                    }
+                    None => bytes.push(0),
+                }
+            }
+        }
+
+        write_str(&mut bytes, &self.sender);
+        write_str(&mut bytes, &self.receiver);
+        bytes.extend_from_slice(&self.transfer_amount.to_le_bytes());
+
+        //Declared types add range constraints; untyped circuits keep their previous hash
+        if !self.wire_types.is_empty() {
+            let mut wire_types: Vec<(&String, &WireType)> = self.wire_types.iter().collect();
+            wire_types.sort_by_key(|(name, _)| *name);
+            write_len(&mut bytes, wire_types.len());
+            for (name, ty) in wire_types {
+                write_str(&mut bytes, name);
+                write_str(&mut bytes, &ty.to_string());
+            }
+        }
+
+        //Likewise for circuits without declared public inputs
+        if !self.public_inputs.is_empty() {
+            bytes.push(b'p');
+            write_len(&mut bytes, self.public_inputs.len());
+            for (name, val) in &self.public_inputs {
+                write_str(&mut bytes, name);
+                val.serialize_compressed(&mut bytes).expect("Writing to a Vec cannot fail");
             }
-            if let Some(receiver_balance) = self.inputs.get_mut(&self.receiver) {
-                *receiver_balance += self.transfer_amount;
+        }
+        if self.private_balances {
+            bytes.push(b'b');
+        }
+        if self.bind_inputs {
+            bytes.push(b'd');
+        }
+        for (exposed, tag) in [
+            (self.transfer_options.expose_sender_balance, b's'),
+            (self.transfer_options.expose_receiver_balance, b'r'),
+            (self.transfer_options.expose_amount, b'a'),
+        ] {
+            if !exposed {
+                bytes.push(tag);
             }
         }
+        bytes
     }
 
     //Convert the circuit to an R1CS system for zk-SNARK
     pub fn to_r1cs_system(&self) -> R1CSSystem {
+        self.build_r1cs_system(true, None).expect("Only a budget can stop the conversion")
+    }
+
+    //to_r1cs_system, stopping at the first gate that takes the system over the budget
+    pub fn to_r1cs_system_bounded(&self, budget: ConstraintBudget) -> Result<R1CSSystem, BudgetExceeded> {
+        self.build_r1cs_system(true, Some(budget))
+    }
+
+    //to_r1cs_system, optionally keeping exact duplicate rows (tests compare both) and checking a budget
+    pub(crate) fn build_r1cs_system(&self, drop_duplicates: bool, budget: Option<ConstraintBudget>) -> Result<R1CSSystem, BudgetExceeded> {
+        let span = tracing::info_span!(
+            "to_r1cs_system",
+            circuit = %self.name,
+            num_gates = self.gates.len(),
+            num_constraints = tracing::field::Empty,
+            num_variables = tracing::field::Empty,
+            elapsed_us = tracing::field::Empty,
+        );
+        let _guard = span.enter();
+        let start = stage_start();
+
         //Initialize the variable map
         let mut var_map = HashMap::new();
         var_map.insert("1".to_string(), 0);
         let mut next_r1cs_idx = 1;
 
-        //Initialize the constraints vector
+        //Initialize the constraints vector and the gate each constraint comes from
         let mut temp_constraints: Vec<_R1CSConstraintInternal> = Vec::new();
+        let mut constraint_origins: Vec<ConstraintOrigin> = Vec::new();
         let mut public_input_names: Vec<String> = Vec::new();
+        let mut public_input_sources: Vec<PublicInputSource> = Vec::new();
 
         //Add the sender's initial balance to the constraints
-        if self.inputs.contains_key(&self.sender) {
+        let options = self.transfer_options;
+        if self.inputs.contains_key(&self.sender) && (self.private_balances || !options.expose_sender_balance) {
+            let (row, origin) = private_balance_row(&self.sender, &mut var_map, &mut next_r1cs_idx);
+            temp_constraints.push(row);
+            constraint_origins.push(origin);
+        } else if self.inputs.contains_key(&self.sender) {
             let public_var_name = format!("{}_initial_balance", self.sender);
             public_input_names.push(public_var_name.clone());
+            public_input_sources.push(PublicInputSource::SenderBalance);
             let sender_initial_idx = get_index(&public_var_name, &mut var_map, &mut next_r1cs_idx);
             let val_fr = i32_to_fr(*self.inputs.get(&self.sender).unwrap_or(&0));
             temp_constraints.push(_R1CSConstraintInternal {
@@ -111,12 +742,18 @@ impl Circuit {
                 b: vec![(var_map["1"], Fr::one())].into_iter().collect(),
                 c: vec![(var_map["1"], val_fr)].into_iter().collect(),
             });
+            constraint_origins.push(ConstraintOrigin::transfer_spec(&public_var_name));
         }
 
         //Add the receiver's initial balance to the constraints
-        if self.inputs.contains_key(&self.receiver) {
+        if self.inputs.contains_key(&self.receiver) && (self.private_balances || !options.expose_receiver_balance) {
+            let (row, origin) = private_balance_row(&self.receiver, &mut var_map, &mut next_r1cs_idx);
+            temp_constraints.push(row);
+            constraint_origins.push(origin);
+        } else if self.inputs.contains_key(&self.receiver) {
             let public_var_name = format!("{}_initial_balance", self.receiver);
             public_input_names.push(public_var_name.clone());
+            public_input_sources.push(PublicInputSource::ReceiverBalance);
             let receiver_initial_idx = get_index(&public_var_name, &mut var_map, &mut next_r1cs_idx);
             let val_fr = i32_to_fr(*self.inputs.get(&self.receiver).unwrap_or(&0));
             temp_constraints.push(_R1CSConstraintInternal {
@@ -124,134 +761,147 @@ impl Circuit {
                 b: vec![(var_map["1"], Fr::one())].into_iter().collect(),
                 c: vec![(var_map["1"], val_fr)].into_iter().collect(),
             });
+            constraint_origins.push(ConstraintOrigin::transfer_spec(&public_var_name));
         }
 
         //Add the transfer amount to the constraints
         let transfer_amount_var_name = "transfer_amount_public".to_string();
-        public_input_names.push(transfer_amount_var_name.clone());
         let transfer_amount_idx = get_index(&transfer_amount_var_name, &mut var_map, &mut next_r1cs_idx);
-        temp_constraints.push(_R1CSConstraintInternal {
-            a: vec![(transfer_amount_idx, Fr::one())].into_iter().collect(),
-            b: vec![(var_map["1"], Fr::one())].into_iter().collect(),
-            c: vec![(var_map["1"], i32_to_fr(self.transfer_amount))].into_iter().collect(),
-        });
+        if options.expose_amount {
+            public_input_names.push(transfer_amount_var_name.clone());
+            public_input_sources.push(PublicInputSource::TransferAmount);
+            temp_constraints.push(_R1CSConstraintInternal {
+                a: vec![(transfer_amount_idx, Fr::one())].into_iter().collect(),
+                b: vec![(var_map["1"], Fr::one())].into_iter().collect(),
+                c: vec![(var_map["1"], u64_to_fr(self.transfer_amount))].into_iter().collect(),
+            });
+            constraint_origins.push(ConstraintOrigin::transfer_spec(&transfer_amount_var_name));
+        } else {
+            //A hidden amount is a witness, its range check keeps it a u64
+            types::emit_bit_decomposition(
+                &transfer_amount_var_name,
+                AMOUNT_BITS,
+                &mut var_map,
+                &mut next_r1cs_idx,
+                &mut temp_constraints,
+                &mut constraint_origins,
+                |role| ConstraintOrigin { gate_index: None, gate: format!("transfer spec ({})", transfer_amount_var_name), role: format!("amount range: {}", role) },
+            );
+        }
 
-        //Add the gates to the constraints
-        for gate_ref in &self.gates {
-            match gate_ref {
-                Gate::Add(a, b, c, _modulus) => {
-                    let a_idx = get_index(a, &mut var_map, &mut next_r1cs_idx);
-                    let b_idx = get_index(b, &mut var_map, &mut next_r1cs_idx);
-                    let c_idx = get_index(c, &mut var_map, &mut next_r1cs_idx);
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(a_idx,Fr::one()),(b_idx,Fr::one())].into_iter().collect(),
-                        b: vec![(var_map["1"],Fr::one())].into_iter().collect(),
-                        c: vec![(c_idx,Fr::one())].into_iter().collect(),
-                    });
-                }
-                Gate::Mul(a, b, c, _modulus) => {
-                    let a_idx = get_index(a, &mut var_map, &mut next_r1cs_idx);
-                    let b_idx = get_index(b, &mut var_map, &mut next_r1cs_idx);
-                    let c_idx = get_index(c, &mut var_map, &mut next_r1cs_idx);
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(a_idx,Fr::one())].into_iter().collect(),
-                        b: vec![(b_idx,Fr::one())].into_iter().collect(),
-                        c: vec![(c_idx,Fr::one())].into_iter().collect(),
-                    });
-                }
-                Gate::Sub(a, b, c, _modulus) => {
-                    let a_idx = get_index(a, &mut var_map, &mut next_r1cs_idx);
-                    let b_idx = get_index(b, &mut var_map, &mut next_r1cs_idx);
-                    let c_idx = get_index(c, &mut var_map, &mut next_r1cs_idx);
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(a_idx,Fr::one()),(b_idx,Fr::one().neg())].into_iter().collect(),
-                        b: vec![(var_map["1"],Fr::one())].into_iter().collect(),
-                        c: vec![(c_idx,Fr::one())].into_iter().collect(),
-                    });
-                }
-                Gate::Eq(a, b, out) => {
-                    let a_idx = get_index(a, &mut var_map, &mut next_r1cs_idx);
-                    let b_idx = get_index(b, &mut var_map, &mut next_r1cs_idx);
-                    let out_idx = get_index(out, &mut var_map, &mut next_r1cs_idx);
-                    // First constraint: a - b = diff
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(a_idx,Fr::one()),(b_idx,Fr::one().neg())].into_iter().collect(),
-                        b: vec![(var_map["1"],Fr::one())].into_iter().collect(),
-                        c: vec![(out_idx,Fr::one())].into_iter().collect(),
-                    });
-                    // Second constraint: diff * diff = 0 (enforces diff = 0)
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(out_idx,Fr::one())].into_iter().collect(),
-                        b: vec![(out_idx,Fr::one())].into_iter().collect(),
-                        c: vec![(var_map["1"],Fr::zero())].into_iter().collect(),
-                    });
-                }
-                //Hash gate
-                Gate::Hash(input, output) => {
-                    let input_idx = get_index(input, &mut var_map, &mut next_r1cs_idx);
-                    let output_idx = get_index(output, &mut var_map, &mut next_r1cs_idx);
-
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(input_idx,Fr::one())].into_iter().collect(),
-                        //Multiply the input by 7
-                        b: vec![(var_map["1"],i32_to_fr(7))].into_iter().collect(),
-                        c: vec![(output_idx,Fr::one())].into_iter().collect(),
-                    });
-                }
-                Gate::Const(name, val) => {
-                    let idx = get_index(name, &mut var_map, &mut next_r1cs_idx);
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(var_map["1"],i32_to_fr(*val))].into_iter().collect(),//convert the constant to Fr
-                        b: vec![(var_map["1"],Fr::one())].into_iter().collect(),//multiply by 1
-                        c: vec![(idx,Fr::one())].into_iter().collect(),//assign to the variable
-                    });
-                }
-                Gate::Xor(a, b, c) => {
-                    let a_idx = get_index(a, &mut var_map, &mut next_r1cs_idx);
-                    let b_idx = get_index(b, &mut var_map, &mut next_r1cs_idx);
-                    let ab_var_name = format!("{}_xor_prod_{}", a, b);
-                    let ab_idx = get_index(&ab_var_name, &mut var_map, &mut next_r1cs_idx);
-                    let c_idx = get_index(c, &mut var_map, &mut next_r1cs_idx);
-                    //a*b = ab
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(a_idx,Fr::one())].into_iter().collect(),
-                        b: vec![(b_idx,Fr::one())].into_iter().collect(),
-                        c: vec![(ab_idx,Fr::one())].into_iter().collect(),
-                    });
-                    //a*b - 2*a*b = c
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(a_idx,Fr::one()), (b_idx, Fr::one()), (ab_idx, i32_to_fr(-2))].into_iter().collect(),
-                        b: vec![(var_map["1"],Fr::one())].into_iter().collect(),
-                        c: vec![(c_idx,Fr::one())].into_iter().collect(),
-                    });
-                    //boolean constraints to ensure a and b are either 0 or 1
-                    //a*a = a
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(a_idx, Fr::one())].into_iter().collect(),
-                        b: vec![(a_idx, Fr::one())].into_iter().collect(),
-                        c: vec![(a_idx, Fr::one())].into_iter().collect(),
-                    });
-                    //b*b = b
-                    temp_constraints.push(_R1CSConstraintInternal {
-                        a: vec![(b_idx, Fr::one())].into_iter().collect(),
-                        b: vec![(b_idx, Fr::one())].into_iter().collect(),
-                        c: vec![(b_idx, Fr::one())].into_iter().collect(),
-                    });
-                }
+        //Private balances: sender balance - amount = balance after, range checked to SUFFICIENCY_BITS
+        if self.inputs.contains_key(&self.sender) && self.private_balances {
+            let sender_initial_idx = var_map[&format!("{}_initial_balance", self.sender)];
+            let after_name = balance_after_name(&self.sender);
+            let after_idx = get_index(&after_name, &mut var_map, &mut next_r1cs_idx);
+            temp_constraints.push(_R1CSConstraintInternal {
+                a: sum_terms(&[(sender_initial_idx, Fr::one()), (transfer_amount_idx, Fr::one().neg())]),
+                b: vec![(var_map["1"], Fr::one())].into_iter().collect(),
+                c: vec![(after_idx, Fr::one())].into_iter().collect(),
+            });
+            let origin = |role: String| ConstraintOrigin { gate_index: None, gate: format!("balance sufficiency ({})", after_name), role };
+            constraint_origins.push(origin("sender balance minus the amount".to_string()));
+            types::emit_bit_decomposition(
+                &after_name,
+                SUFFICIENCY_BITS,
+                &mut var_map,
+                &mut next_r1cs_idx,
+                &mut temp_constraints,
+                &mut constraint_origins,
+                origin,
+            );
+        }
+
+        //Declared public inputs are unconstrained here, gates (e.g. an eq) tie them to the circuit
+        for (name, _) in &self.public_inputs {
+            public_input_names.push(name.clone());
+            public_input_sources.push(PublicInputSource::UserDeclared);
+            get_index(name, &mut var_map, &mut next_r1cs_idx);
+        }
+
+        //Input binding: input_digest = poseidon_hash(inputs in name order)
+        if self.bind_inputs {
+            public_input_names.push(INPUT_DIGEST_WIRE.to_string());
+            public_input_sources.push(PublicInputSource::InputDigest);
+            let digest_idx = get_index(INPUT_DIGEST_WIRE, &mut var_map, &mut next_r1cs_idx);
+            let input_idxs: Vec<usize> =
+                self.bound_inputs().into_iter().map(|(name, _)| get_index(name, &mut var_map, &mut next_r1cs_idx)).collect();
+            let first_row = temp_constraints.len();
+            poseidon::emit_poseidon_rows(&input_idxs, digest_idx, &format!("{}_hash", INPUT_DIGEST_WIRE),
+                &mut var_map, &mut next_r1cs_idx, &mut temp_constraints);
+            let num_rows = temp_constraints.len() - first_row;
+            constraint_origins.extend((0..num_rows).map(|row| ConstraintOrigin {
+                gate_index: None,
+                gate: format!("input binding ({})", INPUT_DIGEST_WIRE),
+                role: if row + 1 == num_rows { "output equation" } else { "Poseidon permutation" }.to_string(),
+            }));
+        }
+
+        //The output of every reveal_if gate is public, in gate order, its gate ties it to the circuit
+        for gate in &self.gates {
+            if let Gate::RevealIf(_, _, disclosed) = gate {
+                public_input_names.push(disclosed.clone());
+                public_input_sources.push(PublicInputSource::Disclosure);
+                get_index(disclosed, &mut var_map, &mut next_r1cs_idx);
             }
         }
-        //Return the R1CS system
-        R1CSSystem {
+        let check_budget = |gate_index: Option<usize>, constraints: usize, variables: usize| match &budget {
+            Some(budget) => budget.check(gate_index, constraints, variables),
+            None => Ok(()),
+        };
+        check_budget(None, temp_constraints.len(), next_r1cs_idx)?;
+
+        //Values of the Const wires, which dot products fold into coefficients
+        let constants: HashMap<&str, Fr> = self.gates.iter()
+            .filter_map(|gate| match gate {
+                Gate::Const(name, val) => Some((name.as_str(), i64_to_fr(*val))),
+                _ => None,
+            })
+            .collect();
+
+        //Add the gates to the constraints
+        for (gate_index, gate_ref) in self.gates.iter().enumerate() {
+            let first_row = temp_constraints.len();
+            let implementation = gate_ref.implementation();
+            implementation.emit_constraints(&mut gates::R1csBuilder {
+                var_map: &mut var_map,
+                next_index: &mut next_r1cs_idx,
+                constraints: &mut temp_constraints,
+                constants: &constants,
+            });
+            let roles = implementation.roles(temp_constraints.len() - first_row);
+            constraint_origins.extend(roles.into_iter().map(|role| ConstraintOrigin {
+                gate_index: Some(gate_index),
+                gate: format!("{:?}", gate_ref),
+                role,
+            }));
+            check_budget(Some(gate_index), temp_constraints.len(), next_r1cs_idx)?;
+        }
+        //Range constraints of the declared bounded wire types
+        types::emit_range_checks(self, &mut var_map, &mut next_r1cs_idx, &mut temp_constraints, &mut constraint_origins);
+        check_budget(None, temp_constraints.len(), next_r1cs_idx)?;
+
+        let mut system = R1CSSystem {
             raw_constraints: temp_constraints,
+            constraint_origins,
             var_map: var_map.clone(),
             num_variables: next_r1cs_idx,
             num_public_inputs: 1 + public_input_names.len(),
             public_input_names,
+            public_input_sources,
+            duplicate_rows: 0,
+        };
+        if drop_duplicates {
+            system.drop_duplicate_rows();
         }
+        span.record("num_constraints", system.raw_constraints.len());
+        span.record("num_variables", next_r1cs_idx);
+        finish_stage(&span, start);
+        Ok(system)
     }
 
-    //Compute the witness for the circuit
-    pub fn compute_witness(&self, r1cs_var_map: &HashMap<String, usize>) -> Result<HashMap<usize, Fr>, String> {
+    //Wire values fixed before any gate runs: the inputs, "1" and the transfer spec wires
+    pub(crate) fn input_wire_values(&self) -> HashMap<String, Fr> {
         let mut wire_values_by_name: HashMap<String, Fr> = HashMap::new();
 
         //Add the inputs to the wire values
@@ -259,6 +909,9 @@ impl Circuit {
             wire_values_by_name.insert(name.clone(), i32_to_fr(*val));
         }
         wire_values_by_name.insert("1".to_string(), Fr::one());
+        for (name, val) in &self.public_inputs {
+            wire_values_by_name.insert(name.clone(), *val);
+        }
 
         //Add the sender's initial balance to the wire values
         let sender_initial_var_name = format!("{}_initial_balance", self.sender);
@@ -282,65 +935,67 @@ impl Circuit {
         let transfer_amount_public_var_name = "transfer_amount_public".to_string();
         wire_values_by_name.insert(
             transfer_amount_public_var_name.clone(),
-            i32_to_fr(self.transfer_amount)
+            u64_to_fr(self.transfer_amount)
         );
+        if !self.transfer_options.expose_amount {
+            let amount = u64_to_fr(self.transfer_amount);
+            wire_values_by_name.extend(types::bit_values(&transfer_amount_public_var_name, &amount, AMOUNT_BITS).unwrap_or_default());
+        }
 
-        //Add the gates to the wire values
-        for gate_ref in &self.gates {
-            match gate_ref {
-                Gate::Add(a_name, b_name, c_name, _) => {
-                    let a_val = wire_values_by_name.get(a_name.as_str()).ok_or_else(|| format!("Var {} not found", a_name))?;
-                    let b_val = wire_values_by_name.get(b_name.as_str()).ok_or_else(|| format!("Var {} not found", b_name))?;
-                    wire_values_by_name.insert(c_name.clone(), *a_val + *b_val);
-                }
-                Gate::Mul(a_name, b_name, c_name, _) => {
-                    let a_val = wire_values_by_name.get(a_name.as_str()).ok_or_else(|| format!("Var {} not found", a_name))?;
-                    let b_val = wire_values_by_name.get(b_name.as_str()).ok_or_else(|| format!("Var {} not found", b_name))?;
-                    wire_values_by_name.insert(c_name.clone(), *a_val * *b_val);
-                }
-                Gate::Sub(a_name, b_name, c_name, _) => {
-                    let a_val = wire_values_by_name.get(a_name.as_str()).ok_or_else(|| format!("Var {} not found", a_name))?;
-                    let b_val = wire_values_by_name.get(b_name.as_str()).ok_or_else(|| format!("Var {} not found", b_name))?;
-                    wire_values_by_name.insert(c_name.clone(), *a_val - *b_val);
-                }
-                Gate::Eq(a_name, b_name, out_name) => {
-                    let a_val = wire_values_by_name.get(a_name.as_str()).ok_or_else(|| format!("Var {} not found", a_name))?;
-                    let b_val = wire_values_by_name.get(b_name.as_str()).ok_or_else(|| format!("Var {} not found", b_name))?;
-                    // For equality to hold, a_val must equal b_val
-                    if *a_val != *b_val {
-                        return Err(format!("Equality constraint failed: {} ({:?}) != {} ({:?})", 
-                            a_name, a_val, b_name, b_val));
-                    }
-                    wire_values_by_name.insert(out_name.clone(), Fr::zero());
-                }
-                Gate::Hash(in_name, out_name) => {
-                    let in_val = wire_values_by_name.get(in_name.as_str()).ok_or_else(|| format!("Var {} not found", in_name))?;
-                    wire_values_by_name.insert(out_name.clone(), *in_val * i32_to_fr(7));
-                }
-                Gate::Const(name, val) => {
-                    wire_values_by_name.insert(name.clone(), i32_to_fr(*val));
-                }
-                Gate::Xor(a_name, b_name, c_name) => {
-                    let a_val = *wire_values_by_name.get(a_name.as_str()).ok_or_else(|| format!("Var {} not found for XOR", a_name))?;
-                    let b_val = *wire_values_by_name.get(b_name.as_str()).ok_or_else(|| format!("Var {} not found for XOR", b_name))?;
-                    if !(a_val.is_zero() || a_val.is_one()) { return Err(format!("XOR input {} is not a bit (0 or 1)", a_name));} // Keep this
-                    if !(b_val.is_zero() || b_val.is_one()) { return Err(format!("XOR input {} is not a bit (0 or 1)", b_name));} // Keep this
-
-                    let ab_var_name_string = format!("{}_xor_prod_{}", a_name, b_name);
-                    let ab_val = a_val * b_val;
-                    wire_values_by_name.insert(ab_var_name_string.clone(), ab_val);
-
-                    let c_val = a_val + b_val - (i32_to_fr(2) * ab_val);
-                    wire_values_by_name.insert(c_name.clone(), c_val);
-                }
+        //Balance after the transfer and its bits for private balances (no bits when the balance
+        //does not cover the amount, compute_witness reports that)
+        if let (Some(balance), true) = (self.inputs.get(&self.sender), self.private_balances) {
+            let after_name = balance_after_name(&self.sender);
+            let after = i32_to_fr(*balance) - u64_to_fr(self.transfer_amount);
+            wire_values_by_name.extend(types::bit_values(&after_name, &after, SUFFICIENCY_BITS).unwrap_or_default());
+            wire_values_by_name.insert(after_name, after);
+        }
+
+        //Input digest and its Poseidon intermediates
+        if self.bind_inputs {
+            let values: Vec<Fr> = self.bound_inputs().into_iter().map(|(_, val)| i32_to_fr(*val)).collect();
+            let (digest, assigned) = poseidon::poseidon_witness(&values, &format!("{}_hash", INPUT_DIGEST_WIRE));
+            wire_values_by_name.extend(assigned);
+            wire_values_by_name.insert(INPUT_DIGEST_WIRE.to_string(), digest);
+        }
+        wire_values_by_name
+    }
+
+    //Compute the witness for the circuit
+    pub fn compute_witness(&self, r1cs_var_map: &HashMap<String, usize>) -> Result<HashMap<usize, Fr>, String> {
+        let span = tracing::info_span!(
+            "compute_witness",
+            circuit = %self.name,
+            num_variables = r1cs_var_map.len(),
+            elapsed_us = tracing::field::Empty,
+        );
+        let _guard = span.enter();
+        let start = stage_start();
+
+        self.validate_gates().map_err(|e| e.to_string())?;
+        if self.private_balances && self.inputs.contains_key(&self.sender) && !self.validate_transfer() {
+            return Err(format!("Balance of sender '{}' is below the transfer amount {}", self.sender, self.transfer_amount));
+        }
+        //Wiped when it goes out of scope, on error returns too
+        let mut wire_values_by_name = SecretWitness::new(self.input_wire_values());
+        wire_values_by_name.reserve(r1cs_var_map.len());
+
+        //Add the gates to the wire values, an output is never overwritten
+        for (gate_index, gate_ref) in self.gates.iter().enumerate() {
+            if let Some(wire) = gate_ref.outputs().into_iter().find(|wire| wire_values_by_name.expose().contains_key(*wire)) {
+                return Err(format!("Gate #{} would overwrite the already assigned wire '{}'", gate_index, wire));
             }
+            let assigned = eval_gate(gate_ref, wire_values_by_name.expose())?;
+            wire_values_by_name.absorb(assigned);
         }
+        let bits = self.range_check_values(wire_values_by_name.expose())?;
+        wire_values_by_name.absorb(bits);
 
         //Add the witness to the wire values
-        let mut witness_by_idx: HashMap<usize, Fr> = HashMap::new();
-        for (name, val_fr) in wire_values_by_name {
-            if let Some(idx) = r1cs_var_map.get(&name) {
-                witness_by_idx.insert(*idx, val_fr);
+        let mut witness_by_idx: HashMap<usize, Fr> = HashMap::with_capacity(r1cs_var_map.len());
+        for (name, val_fr) in wire_values_by_name.expose() {
+            if let Some(idx) = r1cs_var_map.get(name) {
+                witness_by_idx.insert(*idx, *val_fr);
             }
         }
         //Check if all the variables in the R1CS var_map have a witness value
@@ -349,10 +1004,16 @@ impl Circuit {
                 return Err(format!("Variable '{}' (index {}) is in R1CS var_map but has no computed witness value.", name, idx));
             }
         }
+        finish_stage(&span, start);
         Ok(witness_by_idx)
     }
 }
 
+//Evaluate one gate against the current wire values, returning the wires it assigns in order
+pub(crate) fn eval_gate(gate_ref: &Gate, wire_values_by_name: &HashMap<String, Fr>) -> Result<Vec<(String, Fr)>, String> {
+    gate_ref.implementation().evaluate(wire_values_by_name)
+}
+
 #[derive(Debug, Clone)]
 pub struct _R1CSConstraintInternal { // Added pub as per previous fix
     pub a: HashMap<usize, Fr>,
@@ -360,13 +1021,200 @@ pub struct _R1CSConstraintInternal { // Added pub as per previous fix
     pub c: HashMap<usize, Fr>,
 }
 
+//Where a constraint comes from: the gate that emitted it and what the row enforces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintOrigin {
+    //Index into Circuit::gates, None for the transfer spec rows emitted before the gates
+    pub gate_index: Option<usize>,
+    //Debug form of the gate (or the public variable for transfer spec rows)
+    pub gate: String,
+    pub role: String,
+}
+
+impl ConstraintOrigin {
+    //Emitted by the transfer spec before any gate: a public value pin or the balance sufficiency
+    //check of private_balances
+    pub fn is_transfer_preamble(&self) -> bool {
+        self.gate_index.is_none() && (self.gate.starts_with("transfer spec (") || self.gate.starts_with("balance sufficiency ("))
+    }
+
+    fn transfer_spec(public_var_name: &str) -> Self {
+        ConstraintOrigin {
+            gate_index: None,
+            gate: format!("transfer spec ({})", public_var_name),
+            role: "public value equals its constant".to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct R1CSSystem {
     pub raw_constraints: Vec<_R1CSConstraintInternal>,
+    //Parallel to raw_constraints, empty for systems imported without origins (e.g. from JSON)
+    pub constraint_origins: Vec<ConstraintOrigin>,
     pub var_map: HashMap<String, usize>,
     pub num_variables: usize,
+    //Instance variables, the constant one included: verify() takes one input fewer
     pub num_public_inputs: usize,
+    //Ordered like verify()'s inputs after the constant one, see public_input_schema
     pub public_input_names: Vec<String>,
+    //Parallel to public_input_names, empty for systems imported without a schema (legacy JSON)
+    pub public_input_sources: Vec<PublicInputSource>,
+    //Exact duplicate rows to_r1cs_system dropped (e.g. the booleanity rows of Xor gates sharing
+    //an operand), 0 for imported systems
+    pub duplicate_rows: usize,
+}
+
+//Where a public input comes from. The order of public_input_names is part of the verifier
+//interface and stable across releases: sender balance and receiver balance (each only when that
+//party is a circuit input, balances are not private and the transfer options expose it), the
+//transfer amount (unless hidden), then the circuit's `public` declarations in declaration order,
+//the input digest and the reveal_if outputs in gate order. test_public_input_schema_is_stable pins it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PublicInputSource {
+    SenderBalance,
+    ReceiverBalance,
+    TransferAmount,
+    UserDeclared,
+    //Digest of the inputs of a circuit with bind_inputs
+    InputDigest,
+    //Output of a reveal_if gate
+    Disclosure,
+}
+
+impl PublicInputSource {
+    //Name gates use for this input in the DSL, resolved by Circuit::well_known_wire
+    pub fn dsl_name(&self) -> Option<&'static str> {
+        match self {
+            PublicInputSource::SenderBalance => Some("$sender_balance"),
+            PublicInputSource::ReceiverBalance => Some("$receiver_balance"),
+            PublicInputSource::TransferAmount => Some("$transfer_amount"),
+            PublicInputSource::UserDeclared => None,
+            PublicInputSource::InputDigest => Some("$input_digest"),
+            PublicInputSource::Disclosure => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicInputDesc {
+    pub name: String,
+    pub description: String,
+    pub source: PublicInputSource,
+    //Position in verify()'s public inputs
+    pub index: usize,
+}
+
+impl R1CSSystem {
+    //What each verify() input means, in order
+    pub fn public_input_schema(&self) -> Vec<PublicInputDesc> {
+        let transfer_position = self.public_input_names.iter().position(|name| name == "transfer_amount_public");
+        self.public_input_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                //Legacy imports: balances precede the transfer amount, a lone one is taken as the sender's
+                let source = self.public_input_sources.get(i).copied().unwrap_or(match transfer_position {
+                    Some(t) if i == t => PublicInputSource::TransferAmount,
+                    Some(t) if i < t => [PublicInputSource::SenderBalance, PublicInputSource::ReceiverBalance][i.min(1)],
+                    _ => PublicInputSource::UserDeclared,
+                });
+                let mut description = match source {
+                    PublicInputSource::SenderBalance => "Initial balance of the sender".to_string(),
+                    PublicInputSource::ReceiverBalance => "Initial balance of the receiver".to_string(),
+                    PublicInputSource::TransferAmount => "Amount transferred from the sender to the receiver".to_string(),
+                    PublicInputSource::UserDeclared => format!("Public input '{}' declared by the circuit", name),
+                    PublicInputSource::InputDigest => "Poseidon digest of every circuit input, in name order".to_string(),
+                    PublicInputSource::Disclosure => format!("'{}', set by a reveal_if gate to its value or zero", name),
+                };
+                if let Some(dsl_name) = source.dsl_name() {
+                    description.push_str(&format!(" (`{}` in the DSL)", dsl_name));
+                }
+                PublicInputDesc { name: name.clone(), description, source, index: i }
+            })
+            .collect()
+    }
+
+    //Build the ordered verification inputs: the value of each public input name (verify() adds
+    //the constant one itself)
+    pub fn public_inputs_from_witness(&self, witness_by_idx: &HashMap<usize, Fr>) -> Result<Vec<Fr>, String> {
+        let mut public_inputs = Vec::with_capacity(self.public_input_names.len());
+        for name in &self.public_input_names {
+            let idx = self.var_map.get(name)
+                .ok_or_else(|| format!("Public input name '{}' not found in var_map", name))?;
+            let val = witness_by_idx.get(idx)
+                .ok_or_else(|| format!("Witness value for public input '{}' (index {}) not found", name, idx))?;
+            public_inputs.push(*val);
+        }
+        Ok(public_inputs)
+    }
+
+    //Index of the first constraint the witness violates, None when all hold
+    pub fn which_is_unsatisfied(&self, witness_by_idx: &HashMap<usize, Fr>) -> Result<Option<usize>, String> {
+        let eval = |lc: &HashMap<usize, Fr>| -> Result<Fr, String> {
+            lc.iter().try_fold(Fr::zero(), |acc, (idx, coeff)| {
+                //"1" is the constant one whether or not the witness carries it
+                let val = if Some(idx) == self.var_map.get("1") {
+                    Fr::one()
+                } else {
+                    *witness_by_idx.get(idx).ok_or_else(|| format!("Witness value for index {} not found", idx))?
+                };
+                Ok(acc + *coeff * val)
+            })
+        };
+        for (i, constraint) in self.raw_constraints.iter().enumerate() {
+            if eval(&constraint.a)? * eval(&constraint.b)? != eval(&constraint.c)? {
+                return Ok(Some(i));
+            }
+        }
+        Ok(None)
+    }
+
+    //Non-zero counts and row lengths of the A/B/C matrices, with a rough proving cost estimate
+    pub fn density(&self) -> DensityReport {
+        let mut non_zero = [0usize; 3];
+        let mut max_row_len = 0;
+        for constraint in &self.raw_constraints {
+            for (count, lc) in non_zero.iter_mut().zip([&constraint.a, &constraint.b, &constraint.c]) {
+                //Explicit zero coefficients (e.g. the "= 0" side of Eq) are not matrix entries
+                let len = lc.values().filter(|coeff| !coeff.is_zero()).count();
+                *count += len;
+                max_row_len = max_row_len.max(len);
+            }
+        }
+        let num_constraints = self.raw_constraints.len();
+        //The arkworks system adds its own constant one next to our "1"
+        let num_instance = 1 + self.num_public_inputs;
+        let num_witness = self.num_variables.saturating_sub(self.num_public_inputs);
+        let domain_size = (num_constraints + num_instance).next_power_of_two();
+        DensityReport {
+            num_constraints,
+            num_variables: self.num_variables,
+            a_non_zero: non_zero[0],
+            b_non_zero: non_zero[1],
+            c_non_zero: non_zero[2],
+            avg_row_len: non_zero.iter().sum::<usize>() as f64 / (3 * num_constraints).max(1) as f64,
+            max_row_len,
+            domain_size,
+            //A and B (G1) over all variables, H over the domain, L over the witness
+            msm_g1_size: 2 * (num_instance + num_witness) + domain_size + num_witness,
+            //B (G2) over all variables
+            msm_g2_size: num_instance + num_witness,
+        }
+    }
+
+    //Human readable description of the gate and role behind a constraint
+    pub fn explain(&self, constraint_index: usize) -> String {
+        match self.constraint_origins.get(constraint_index) {
+            Some(ConstraintOrigin { gate_index: Some(gate_index), gate, role }) => {
+                format!("constraint {}: {} of gate #{} {}", constraint_index, role, gate_index, gate)
+            }
+            Some(ConstraintOrigin { gate_index: None, gate, role }) => {
+                format!("constraint {}: {} of {}", constraint_index, role, gate)
+            }
+            None => format!("constraint {} (origin unknown)", constraint_index),
+        }
+    }
 }
 
 // Wrapper structs using the CORRECT types from ark_groth16 v0.5.0 (assuming root export)
@@ -382,86 +1230,137 @@ impl VerifyingKey {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Proof(ArkGroth16Proof<Bls12_381>);
 
-#[derive(Clone)]
-struct Groth16CircuitAdapter {
-    r1cs_system: R1CSSystem,
-    witness_assignment: Option<HashMap<usize, Fr>>,
-}
+//Compressed canonical byte encodings of the keys and proofs
+impl ProvingKey {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = Vec::new();
+        self.0.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
 
-//Implement the ConstraintSynthesizer trait for the Groth16CircuitAdapter
-impl ConstraintSynthesizer<Fr> for Groth16CircuitAdapter {
-    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
-        let mut cs_vars: HashMap<usize, Variable> = HashMap::new();
-        
-        // Allocate constant 1
-        let one_original_idx = *self.r1cs_system.var_map.get("1").ok_or_else(|| {
-            eprintln!("[Setup/Prove Allocation Error] Variable '1' not found in var_map.");
-            SynthesisError::AssignmentMissing
-        })?;
-        let one_cs_var = cs.new_input_variable(|| Ok(Fr::one()))?;
-        cs_vars.insert(one_original_idx, one_cs_var);
-
-        // Allocate public inputs
-        for name in &self.r1cs_system.public_input_names {
-            let original_idx = *self.r1cs_system.var_map.get(name).ok_or_else(|| {
-                eprintln!("[Setup/Prove Allocation Error] Public input name '{}' not found in var_map.", name);
-                SynthesisError::AssignmentMissing
-            })?;
-            
-            let val = self.witness_assignment.as_ref()
-                .and_then(|w| w.get(&original_idx).cloned())
-                .unwrap_or_else(|| Fr::zero());
-            
-            let cs_var = cs.new_input_variable(|| Ok(val))?;
-            cs_vars.insert(original_idx, cs_var);
-        }
-
-        // Allocate witness variables
-        for (name, original_idx) in &self.r1cs_system.var_map {
-            if name != "1" && !self.r1cs_system.public_input_names.contains(name) {
-                let val = self.witness_assignment.as_ref()
-                    .and_then(|w| w.get(original_idx).cloned())
-                    .unwrap_or_else(|| Fr::zero());
-                
-                let cs_var = cs.new_witness_variable(|| Ok(val))?;
-                cs_vars.insert(*original_idx, cs_var);
-            }
-        }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        Ok(ProvingKey(ArkGroth16ProvingKey::deserialize_compressed(bytes)?))
+    }
+}
 
-        // Enforce constraints
-        for r1cs_constraint_internal in &self.r1cs_system.raw_constraints {
-            let mut lc_a = LinearCombination::zero();
-            let mut lc_b = LinearCombination::zero();
-            let mut lc_c = LinearCombination::zero();
+impl VerifyingKey {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = Vec::new();
+        self.0.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
 
-            for (original_idx, coeff) in &r1cs_constraint_internal.a {
-                lc_a += (*coeff, cs_vars[original_idx]);
-            }
-            for (original_idx, coeff) in &r1cs_constraint_internal.b {
-                lc_b += (*coeff, cs_vars[original_idx]);
-            }
-            for (original_idx, coeff) in &r1cs_constraint_internal.c {
-                lc_c += (*coeff, cs_vars[original_idx]);
-            }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        Ok(VerifyingKey(ArkGroth16VerifyingKey::deserialize_compressed(bytes)?))
+    }
+}
+
+impl Proof {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = Vec::new();
+        self.0.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
 
-            cs.enforce_constraint(lc_a, lc_b, lc_c)?;
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        Ok(Proof(ArkGroth16Proof::deserialize_compressed(bytes)?))
+    }
+}
+
+//Errors of setup and proving: invalid circuits are reported before synthesis starts
+#[derive(Debug)]
+pub enum ProveError {
+    //A variable the constraint system needs ("1" or a public input) is not in var_map
+    MissingVariable { name: String },
+    //Any other validation failure of the R1CS system or witness
+    InvalidCircuit(CircuitError),
+    //Failure inside arkworks synthesis or the Groth16 prover
+    Synthesis(SynthesisError),
+}
+
+impl fmt::Display for ProveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProveError::MissingVariable { name } => write!(f, "Variable '{}' not found in var_map", name),
+            ProveError::InvalidCircuit(e) => write!(f, "Invalid circuit: {}", e),
+            ProveError::Synthesis(e) => write!(f, "Synthesis failed: {}", e),
         }
+    }
+}
 
-        Ok(())
+impl std::error::Error for ProveError {}
+
+impl From<CircuitError> for ProveError {
+    fn from(e: CircuitError) -> Self {
+        match e {
+            CircuitError::MissingOneVariable => ProveError::MissingVariable { name: "1".to_string() },
+            CircuitError::MissingPublicInput { name } => ProveError::MissingVariable { name },
+            other => ProveError::InvalidCircuit(other),
+        }
     }
 }
-//setup and initialize proving key and verifying key
-pub fn setup(r1cs_system: &R1CSSystem) -> Result<(ProvingKey, VerifyingKey), SynthesisError> {
-    let rng = &mut OsRng;
-    let circuit = Groth16CircuitAdapter {
-        r1cs_system: r1cs_system.clone(),
-        witness_assignment: None,
+
+impl From<SynthesisError> for ProveError {
+    fn from(e: SynthesisError) -> Self {
+        ProveError::Synthesis(e)
+    }
+}
+
+//Span of a Groth16 stage over an R1CS system, elapsed_us is recorded by finish_stage
+macro_rules! stage_span {
+    ($name:literal, $r1cs_system:expr) => {
+        tracing::info_span!(
+            $name,
+            num_constraints = $r1cs_system.raw_constraints.len(),
+            num_variables = $r1cs_system.num_variables,
+            elapsed_us = tracing::field::Empty,
+        )
     };
+}
+
+//When a stage started, None on wasm32 where Instant::now panics (there is no clock in the
+//browser), so verify_proof never touches it
+fn stage_start() -> Option<Instant> {
+    (!cfg!(target_arch = "wasm32")).then(Instant::now)
+}
+
+//Record how long a stage took on its span and log its completion
+fn finish_stage(span: &tracing::Span, start: Option<Instant>) {
+    let Some(start) = start else { return };
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    span.record("elapsed_us", elapsed_us);
+    tracing::debug!(elapsed_us, "stage finished");
+}
+
+//setup and initialize proving key and verifying key
+pub fn setup(r1cs_system: &R1CSSystem) -> Result<(ProvingKey, VerifyingKey), ProveError> {
+    setup_with_progress(r1cs_system, &NoProgress)
+}
+//setup, reporting stage events to the progress sink
+pub fn setup_with_progress(
+    r1cs_system: &R1CSSystem,
+    progress: &dyn ProgressSink,
+) -> Result<(ProvingKey, VerifyingKey), ProveError> {
+    setup_with_rng(r1cs_system, &mut OsRng, progress)
+}
+//setup with a caller supplied rng (e.g. a seeded rng for reproducible keys)
+pub fn setup_with_rng<R: RngCore + CryptoRng>(
+    r1cs_system: &R1CSSystem,
+    rng: &mut R,
+    progress: &dyn ProgressSink,
+) -> Result<(ProvingKey, VerifyingKey), ProveError> {
+    let span = stage_span!("setup", r1cs_system);
+    let _guard = span.enter();
+    let start = stage_start();
+    let circuit = R1CSAdapter::new(Arc::new(r1cs_system.clone()), None)?.with_progress(progress);
 
+    progress.on_event(ProgressEvent::SetupStarted);
     let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(circuit, rng)?;
+    progress.on_event(ProgressEvent::SetupFinished);
+    finish_stage(&span, start);
     Ok((ProvingKey(pk), VerifyingKey(vk)))
 }
 // to generate the proof
@@ -469,37 +1368,387 @@ pub fn prove(
     r1cs_system: &R1CSSystem,
     pk: &ProvingKey,
     witness_by_original_idx: HashMap<usize, Fr>,
-) -> Result<Proof, SynthesisError> {
-    let rng = &mut OsRng;
-    let circuit = Groth16CircuitAdapter {
-        r1cs_system: r1cs_system.clone(),
-        witness_assignment: Some(witness_by_original_idx),
-    };
-
-    let proof = Groth16::<Bls12_381>::prove(&pk.0, circuit, rng)?;
+) -> Result<Proof, ProveError> {
+    prove_with_progress(r1cs_system, pk, witness_by_original_idx, &NoProgress)
+}
+// to generate the proof, reporting stage events to the progress sink
+pub fn prove_with_progress(
+    r1cs_system: &R1CSSystem,
+    pk: &ProvingKey,
+    witness_by_original_idx: HashMap<usize, Fr>,
+    progress: &dyn ProgressSink,
+) -> Result<Proof, ProveError> {
+    prove_with_rng(r1cs_system, pk, witness_by_original_idx, &mut OsRng, progress)
+}
+// to generate the proof with a caller supplied rng
+pub fn prove_with_rng<R: RngCore + CryptoRng>(
+    r1cs_system: &R1CSSystem,
+    pk: &ProvingKey,
+    witness_by_original_idx: HashMap<usize, Fr>,
+    rng: &mut R,
+    progress: &dyn ProgressSink,
+) -> Result<Proof, ProveError> {
+    prove_shared(Arc::new(r1cs_system.clone()), pk, witness_by_original_idx, rng, progress)
+}
+//Prove against a system that is already shared, without copying it (see prover::Prover)
+pub(crate) fn prove_shared<R: RngCore + CryptoRng>(
+    r1cs_system: Arc<R1CSSystem>,
+    pk: &ProvingKey,
+    witness_by_original_idx: HashMap<usize, Fr>,
+    rng: &mut R,
+    progress: &dyn ProgressSink,
+) -> Result<Proof, ProveError> {
+    let span = stage_span!("prove", r1cs_system);
+    let _guard = span.enter();
+    let start = stage_start();
+    let witness = Arc::new(witness_by_original_idx);
+    let proof = R1CSAdapter::new(r1cs_system, Some(Arc::clone(&witness))).map_err(ProveError::from).and_then(|circuit| {
+        progress.on_event(ProgressEvent::ProvingStarted);
+        Groth16::<Bls12_381>::prove(&pk.0, circuit.with_progress(progress), rng).map_err(ProveError::from)
+    });
+    //The adapter is gone, wipe the witness whether or not proving succeeded
+    if let Ok(witness) = Arc::try_unwrap(witness) {
+        drop(SecretWitness::new(witness));
+    }
+    let proof = proof?;
+    progress.on_event(ProgressEvent::ProvingFinished);
+    finish_stage(&span, start);
     Ok(Proof(proof))
 }
-//function to use the verifying key
+//Check a witness against the synthesized constraint system without proving
+//(Groth16 proofs of an unsatisfied system never verify)
+pub fn is_satisfied(
+    r1cs_system: &R1CSSystem,
+    witness_by_original_idx: HashMap<usize, Fr>,
+) -> Result<bool, ProveError> {
+    let circuit = R1CSAdapter::new(Arc::new(r1cs_system.clone()), Some(Arc::new(witness_by_original_idx)))?;
+    let cs = ConstraintSystem::<Fr>::new_ref();
+    circuit.generate_constraints(cs.clone())?;
+    Ok(cs.is_satisfied()?)
+}
+//Counts taken from the arkworks constraint system the adapter synthesizes, which can differ
+//from the R1CSSystem counts once gates allocate their own variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsStats {
+    pub num_constraints: usize,
+    //Includes the arkworks constant one as well as our "1" variable
+    pub num_instance_variables: usize,
+    pub num_witness_variables: usize,
+    pub a_non_zero: usize,
+    pub b_non_zero: usize,
+    pub c_non_zero: usize,
+}
+
+impl CsStats {
+    //Fraction of non-zero entries in the A, B and C matrices
+    pub fn densities(&self) -> [f64; 3] {
+        let cells = (self.num_constraints * (self.num_instance_variables + self.num_witness_variables)).max(1) as f64;
+        [self.a_non_zero, self.b_non_zero, self.c_non_zero].map(|nnz| nnz as f64 / cells)
+    }
+
+    //Differences against the counts of the R1CSSystem, empty when they agree
+    pub fn mismatches(&self, r1cs_system: &R1CSSystem) -> Vec<String> {
+        let expected = [
+            ("constraints", r1cs_system.raw_constraints.len(), self.num_constraints),
+            ("instance variables", 1 + r1cs_system.num_public_inputs, self.num_instance_variables),
+            ("witness variables", r1cs_system.num_variables - r1cs_system.num_public_inputs, self.num_witness_variables),
+        ];
+        expected
+            .iter()
+            .filter(|(_, ours, synthesized)| ours != synthesized)
+            .map(|(what, ours, synthesized)| format!("{}: R1CSSystem has {}, synthesized has {}", what, ours, synthesized))
+            .collect()
+    }
+}
+
+//Rows longer than this many terms make the witness map and setup expensive
+//(the longest Poseidon rows have about 60)
+pub const DENSE_ROW_WARN_LEN: usize = 128;
+//Average terms per matrix row above which the system is unusually dense (arithmetic gates
+//average 1-2, hash-heavy circuits about 9)
+pub const AVG_ROW_WARN_LEN: f64 = 16.0;
+
+//Matrix density of an R1CSSystem and the sizes that dominate Groth16 proving time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DensityReport {
+    pub num_constraints: usize,
+    pub num_variables: usize,
+    pub a_non_zero: usize,
+    pub b_non_zero: usize,
+    pub c_non_zero: usize,
+    //Average non-zero terms per row over the three matrices
+    pub avg_row_len: f64,
+    //Longest single row of any matrix
+    pub max_row_len: usize,
+    //Evaluation domain of the QAP (FFT size)
+    pub domain_size: usize,
+    //Points in the prover's G1 and G2 multi-scalar multiplications
+    pub msm_g1_size: usize,
+    pub msm_g2_size: usize,
+}
+
+impl DensityReport {
+    //Rough proving cost in G1 scalar multiplications (a G2 one counts as three, and every
+    //matrix entry as one field multiplication, about a hundredth of a scalar multiplication)
+    pub fn estimated_cost(&self) -> usize {
+        self.msm_g1_size + 3 * self.msm_g2_size + (self.a_non_zero + self.b_non_zero + self.c_non_zero) / 100
+    }
+
+    //Warnings for rows or averages above the thresholds, empty for typical systems
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.max_row_len > DENSE_ROW_WARN_LEN {
+            warnings.push(format!("a constraint row has {} terms (threshold {})", self.max_row_len, DENSE_ROW_WARN_LEN));
+        }
+        if self.avg_row_len > AVG_ROW_WARN_LEN {
+            warnings.push(format!("rows average {:.2} terms (threshold {})", self.avg_row_len, AVG_ROW_WARN_LEN));
+        }
+        warnings
+    }
+}
+
+//Synthesize the constraint system in setup mode and report its statistics
+pub fn synthesize_stats(r1cs_system: &R1CSSystem) -> Result<CsStats, ProveError> {
+    let circuit = R1CSAdapter::new(Arc::new(r1cs_system.clone()), None)?;
+    let cs = ConstraintSystem::<Fr>::new_ref();
+    cs.set_mode(SynthesisMode::Setup);
+    circuit.generate_constraints(cs.clone())?;
+    cs.finalize();
+    let matrices = cs.to_matrices().ok_or(ProveError::Synthesis(SynthesisError::MissingCS))?;
+    Ok(CsStats {
+        num_constraints: matrices.num_constraints,
+        num_instance_variables: matrices.num_instance_variables,
+        num_witness_variables: matrices.num_witness_variables,
+        a_non_zero: matrices.a_num_non_zero,
+        b_non_zero: matrices.b_num_non_zero,
+        c_non_zero: matrices.c_num_non_zero,
+    })
+}
+//Verify a proof. public_inputs are the values of the system's public_input_names, in order
+//(public_inputs_from_witness); the constant one that R1CSAdapter allocates as the first instance
+//variable is supplied here, never by the caller. A wrong number of inputs is an error, not false
 pub fn verify(
     vk: &VerifyingKey,
     proof: &Proof,
-    public_inputs_ordered: &[Fr],
+    public_inputs: &[Fr],
 ) -> Result<bool, SynthesisError> {
+    let span = tracing::info_span!(
+        "verify",
+        num_public_inputs = public_inputs.len(),
+        valid = tracing::field::Empty,
+        elapsed_us = tracing::field::Empty,
+    );
+    let _guard = span.enter();
+    let start = stage_start();
     let processed_vk = Groth16::<Bls12_381>::process_vk(vk.inner())?;
-    let result = Groth16::<Bls12_381>::verify_with_processed_vk(&processed_vk, public_inputs_ordered, &proof.0)?;
+    let result = Groth16::<Bls12_381>::verify_with_processed_vk(&processed_vk, &with_constant_one(public_inputs), &proof.0)?;
+    span.record("valid", result);
+    finish_stage(&span, start);
     Ok(result)
 }
 
+//The arkworks instance of a proof: the adapter's constant one, then the named public inputs
+pub(crate) fn with_constant_one(public_inputs: &[Fr]) -> Vec<Fr> {
+    std::iter::once(Fr::one()).chain(public_inputs.iter().copied()).collect()
+}
+
+//Named public inputs a verifying key takes (its instance size minus arkworks' own one and the
+//adapter's constant one)
+pub(crate) fn num_named_public_inputs(vk: &ark_groth16::VerifyingKey<Bls12_381>) -> usize {
+    vk.gamma_abc_g1.len().saturating_sub(2)
+}
+
+//verify() for callers still passing Fr::one() before the public inputs: a leading one that makes
+//the count one too many is dropped with a warning, anything else is verified as given
+#[deprecated(note = "verify() supplies the constant one itself, pass only the public input values")]
+pub fn verify_with_explicit_one(vk: &VerifyingKey, proof: &Proof, public_inputs: &[Fr]) -> Result<bool, SynthesisError> {
+    match public_inputs.split_first() {
+        Some((first, rest)) if first.is_one() && rest.len() == num_named_public_inputs(vk.inner()) => {
+            tracing::warn!("Public inputs start with the constant one, which verify() now supplies itself; drop it");
+            verify(vk, proof, rest)
+        }
+        _ => verify(vk, proof, public_inputs),
+    }
+}
+//Byte-oriented verification for callers without the Rust types (e.g. the wasm bindings).
+//Public inputs are hex encoded and ordered like verify() expects.
+pub fn verify_bytes(vk_bytes: &[u8], proof_bytes: &[u8], public_inputs_hex: &[String]) -> Result<bool, String> {
+    let vk = VerifyingKey::from_bytes(vk_bytes).map_err(|e| format!("Invalid verifying key: {}", e))?;
+    let proof = Proof::from_bytes(proof_bytes).map_err(|e| format!("Invalid proof: {}", e))?;
+    let public_inputs = public_inputs_hex.iter()
+        .map(|s| fr_codec::fr_from_hex(s))
+        .collect::<Result<Vec<Fr>, String>>()?;
+    verify(&vk, &proof, &public_inputs).map_err(|e| format!("Verification failed: {}", e))
+}
+
+//Errors of verify_from_files; every malformed file is reported, never a panic
+#[derive(Debug)]
+pub enum VerifyFilesError {
+    Io { path: PathBuf, error: std::io::Error },
+    //The verifying key or proof file is not a valid artifact of its kind
+    Artifact { path: PathBuf, error: ArtifactError },
+    //The public inputs (or schema) file is not valid JSON of the expected shape
+    MalformedPublicInputs(String),
+    //A named public input map that does not match the schema's names
+    PublicInputNames { missing: Vec<String>, unknown: Vec<String> },
+    PublicInputCount { expected: usize, found: usize },
+    Verification(SynthesisError),
+    //The proof envelope is malformed or names another verifying key
+    Envelope(EnvelopeError),
+}
+
+impl fmt::Display for VerifyFilesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyFilesError::Io { path, error } => write!(f, "Cannot read {}: {}", path.display(), error),
+            VerifyFilesError::Artifact { path, error } => write!(f, "Invalid file {}: {}", path.display(), error),
+            VerifyFilesError::MalformedPublicInputs(e) => write!(f, "Malformed public inputs: {}", e),
+            VerifyFilesError::PublicInputNames { missing, unknown } => {
+                write!(f, "Public inputs do not match the schema (missing: [{}], unknown: [{}])", missing.join(", "), unknown.join(", "))
+            }
+            VerifyFilesError::PublicInputCount { expected, found } => {
+                write!(f, "Expected {} public inputs, got {}", expected, found)
+            }
+            VerifyFilesError::Verification(e) => write!(f, "Verification failed: {}", e),
+            VerifyFilesError::Envelope(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for VerifyFilesError {}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, VerifyFilesError> {
+    std::fs::read(path).map_err(|error| VerifyFilesError::Io { path: path.to_path_buf(), error })
+}
+
+//A public input value: a hex ("0x..") or decimal string, or a non-negative JSON integer
+fn public_input_value(name: &str, value: &serde_json::Value, policy: FieldLiteralPolicy) -> Result<Fr, VerifyFilesError> {
+    let malformed = |reason: String| VerifyFilesError::MalformedPublicInputs(format!("{}: {}", name, reason));
+    match value {
+        serde_json::Value::String(s) => fr_codec::parse_field_literal(s, policy).map_err(|e| malformed(e.to_string())),
+        serde_json::Value::Number(n) => n.as_u64().map(Fr::from).ok_or_else(|| malformed(format!("{} is not a non-negative integer", n))),
+        other => Err(malformed(format!("expected a string or integer, got {}", other))),
+    }
+}
+
+//Files written by save_proof_files (and the prove subcommand's --out-dir)
+pub const VK_FILE: &str = "vk.bin";
+pub const PROOF_FILE: &str = "proof.bin";
+pub const PUBLIC_INPUTS_FILE: &str = "public_inputs.json";
+pub const SCHEMA_FILE: &str = "r1cs.json";
+
+//Write what a verifier needs into dir: the vk and proof as artifacts tagged with the circuit
+//hash, the public inputs (ordered like verify(), without the constant one) as a JSON array of
+//hex strings, the R1CS JSON export as the schema naming them, and the proof with its public
+//inputs as a JSON ProofEnvelope
+pub fn save_proof_files(
+    dir: &Path,
+    circuit: &Circuit,
+    vk: &VerifyingKey,
+    proof: &Proof,
+    public_inputs: &[Fr],
+) -> Result<(), std::io::Error> {
+    let circuit_hash = circuit.canonical_hash();
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(VK_FILE), vk.to_artifact(Some(&circuit_hash)).map_err(std::io::Error::other)?)?;
+    std::fs::write(dir.join(PROOF_FILE), proof.to_artifact(Some(&circuit_hash)).map_err(std::io::Error::other)?)?;
+    let hex: Vec<String> = public_inputs.iter().map(|val| format!("0x{}", fr_codec::fr_to_hex(val))).collect();
+    std::fs::write(dir.join(PUBLIC_INPUTS_FILE), serde_json::to_vec_pretty(&hex).map_err(std::io::Error::other)?)?;
+    let mut schema = circuit.to_r1cs_system().to_json_struct();
+    schema.circuit_hash = Some(hex::encode(circuit_hash));
+    std::fs::write(dir.join(SCHEMA_FILE), serde_json::to_vec_pretty(&schema).map_err(std::io::Error::other)?)?;
+    std::fs::write(dir.join(envelope::ENVELOPE_FILE), ProofEnvelope::new(circuit, vk, proof, public_inputs).to_json())
+}
+
+//Verify a proof envelope (binary or JSON) under the verifying key at vk_path. A vk artifact
+//tagged with another circuit, or a key whose fingerprint differs from the envelope's, is an
+//error before any verification
+pub fn verify_envelope_file(vk_path: &Path, envelope_path: &Path) -> Result<bool, VerifyFilesError> {
+    let envelope = ProofEnvelope::decode(&read_file(envelope_path)?).map_err(VerifyFilesError::Envelope)?;
+    let vk = VerifyingKey::from_artifact(&read_file(vk_path)?, Some(&envelope.circuit_hash), true)
+        .map_err(|error| VerifyFilesError::Artifact { path: vk_path.to_path_buf(), error })?;
+    match envelope.verify(&vk) {
+        Err(EnvelopeError::Verification(e)) => Err(VerifyFilesError::Verification(e)),
+        result => result.map_err(VerifyFilesError::Envelope),
+    }
+}
+
+//Verify a received proof without the circuit or its witness. The vk and proof files are
+//artifacts (headerless keys and proofs, e.g. from the FFI, are accepted too). The public inputs
+//file is JSON, either an array ordered like verify() expects or a map from public input name to
+//value, ordered by the public_input_names of the R1CS JSON export at schema_path. Arrays written
+//before verify() supplied the constant one start with it and are still accepted. Values at or
+//above the field modulus are rejected
+pub fn verify_from_files(
+    vk_path: &Path,
+    proof_path: &Path,
+    public_inputs_path: &Path,
+    schema_path: Option<&Path>,
+) -> Result<bool, VerifyFilesError> {
+    verify_from_files_with_policy(vk_path, proof_path, public_inputs_path, schema_path, FieldLiteralPolicy::Strict)
+}
+
+//verify_from_files with a policy for public input values at or above the field modulus
+pub fn verify_from_files_with_policy(
+    vk_path: &Path,
+    proof_path: &Path,
+    public_inputs_path: &Path,
+    schema_path: Option<&Path>,
+    policy: FieldLiteralPolicy,
+) -> Result<bool, VerifyFilesError> {
+    let artifact_err = |path: &Path| {
+        let path = path.to_path_buf();
+        move |error| VerifyFilesError::Artifact { path, error }
+    };
+    let vk = VerifyingKey::from_artifact(&read_file(vk_path)?, None, true).map_err(artifact_err(vk_path))?;
+    let proof = Proof::from_artifact(&read_file(proof_path)?, None, true).map_err(artifact_err(proof_path))?;
+    let json: serde_json::Value = serde_json::from_slice(&read_file(public_inputs_path)?)
+        .map_err(|e| VerifyFilesError::MalformedPublicInputs(e.to_string()))?;
+
+    let public_inputs = match json {
+        serde_json::Value::Array(values) => values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| public_input_value(&format!("#{}", i), value, policy))
+            .collect::<Result<Vec<Fr>, _>>()?,
+        serde_json::Value::Object(named) => {
+            let schema_path = schema_path.ok_or_else(|| {
+                VerifyFilesError::MalformedPublicInputs("named public inputs need the R1CS JSON schema".to_string())
+            })?;
+            let schema_json = String::from_utf8_lossy(&read_file(schema_path)?).into_owned();
+            let schema: export::R1CSJson = serde_json::from_str(&schema_json)
+                .map_err(|e| VerifyFilesError::MalformedPublicInputs(format!("schema {}: {}", schema_path.display(), e)))?;
+            schema.check_header(None, true).map_err(artifact_err(schema_path))?;
+            let names = &schema.public_input_names;
+            let missing: Vec<String> = names.iter().filter(|name| !named.contains_key(*name)).cloned().collect();
+            let mut unknown: Vec<String> = named.keys().filter(|name| !names.contains(name)).cloned().collect();
+            unknown.sort();
+            if !missing.is_empty() || !unknown.is_empty() {
+                return Err(VerifyFilesError::PublicInputNames { missing, unknown });
+            }
+            names.iter().map(|name| public_input_value(name, &named[name], policy)).collect::<Result<Vec<Fr>, _>>()?
+        }
+        other => return Err(VerifyFilesError::MalformedPublicInputs(format!("expected an array or object, got {}", other))),
+    };
+
+    //Files written with the constant one in front are one too many, not silently corrected
+    let expected = num_named_public_inputs(vk.inner());
+    if public_inputs.len() != expected {
+        return Err(VerifyFilesError::PublicInputCount { expected, found: public_inputs.len() });
+    }
+    verify(&vk, &proof, &public_inputs).map_err(VerifyFilesError::Verification)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ark_ff::Field;
 
     #[test]
     fn test_simple_circuit_groth16() {
         let circuit = Circuit {
             name: "test_add".to_string(),
             inputs: HashMap::from([("a".to_string(), 10), ("b".to_string(), 20)]),
-            outputs: HashMap::new(),
             gates: vec![
                 Gate::Add("a".to_string(), "b".to_string(), "c".to_string(), None),
                 Gate::Add("c".to_string(), "transfer_amount_public".to_string(), "d".to_string(), None),
@@ -507,6 +1756,7 @@ mod tests {
             sender: "alice".to_string(),
             receiver: "bob".to_string(),
             transfer_amount: 5,
+            ..Default::default()
         };
 
         println!("Generating R1CS...");
@@ -522,10 +1772,6 @@ mod tests {
         assert!(r1cs.var_map.contains_key("transfer_amount_public"));
 
 
-        println!("Running Groth16 Setup...");
-        let (pk, vk) = setup(&r1cs).expect("Setup failed");
-        println!("Setup complete.");
-
         println!("Computing witness...");
         let witness_map_by_idx = circuit.compute_witness(&r1cs.var_map).expect("Witness computation failed");
         println!("Witness computed. {} assignments.", witness_map_by_idx.len());
@@ -539,29 +1785,632 @@ mod tests {
         let transfer_public_idx = *r1cs.var_map.get("transfer_amount_public").unwrap();
         assert_eq!(witness_map_by_idx.get(&transfer_public_idx), Some(&i32_to_fr(5)));
 
+        println!("Running pipeline (setup, prove, verify)...");
+        let result = run_pipeline(&circuit, PipelineOptions::default()).expect("Pipeline failed");
+
+        assert_eq!(result.stats.num_constraints, r1cs.raw_constraints.len());
+        assert_eq!(result.public_inputs, vec![i32_to_fr(5)]);
+        assert_eq!(result.public_inputs.len() + 1, r1cs.num_public_inputs, "Mismatch in number of public inputs for verification.");
+
+        let is_valid = result.verified.expect("Verification was requested");
+        println!("Proof verification result: {}", is_valid);
+        assert!(is_valid, "Proof should be valid!");
+    }
+
+    #[test]
+    fn test_verify_bytes_entry_point() {
+        let circuit = testing::mul_circuit("bytes");
+        let result = run_pipeline(&circuit, PipelineOptions { return_keys: true, ..PipelineOptions::default() })
+            .expect("Pipeline failed");
+        let (_, vk) = result.keys.expect("Keys were requested");
+        let vk_bytes = vk.to_bytes().unwrap();
+        let proof_bytes = result.proof.to_bytes().unwrap();
+
+        let mut public_inputs_hex: Vec<String> = result.public_inputs.iter().map(fr_codec::fr_to_hex).collect();
+        assert_eq!(verify_bytes(&vk_bytes, &proof_bytes, &public_inputs_hex), Ok(true));
+
+        public_inputs_hex[0] = fr_codec::fr_to_hex(&i32_to_fr(6));
+        assert_eq!(verify_bytes(&vk_bytes, &proof_bytes, &public_inputs_hex), Ok(false));
+        assert!(verify_bytes(&vk_bytes[1..], &proof_bytes, &public_inputs_hex).is_err());
+        assert!(verify_bytes(&vk_bytes, &proof_bytes, &["zz".to_string()]).is_err());
+    }
 
-        println!("Generating Groth16 Proof...");
-        let proof_obj = prove(&r1cs, &pk, witness_map_by_idx.clone()).expect("Proof generation failed");
-        println!("Proof generated.");
+    #[test]
+    #[allow(deprecated)]
+    fn test_verify_supplies_the_constant_one() {
+        let circuit = Circuit {
+            public_inputs: vec![("tag".to_string(), Fr::one())],
+            ..testing::mul_circuit("constant_one")
+        };
+        let result = run_pipeline(&circuit, PipelineOptions { return_keys: true, ..PipelineOptions::default() })
+            .expect("Pipeline failed");
+        let (_, vk) = result.keys.expect("Keys were requested");
+        assert_eq!(result.public_inputs, vec![i32_to_fr(5), Fr::one()]);
+        assert!(verify(&vk, &result.proof, &result.public_inputs).unwrap());
+
+        //A leading one is an input too many, never silently shifted into place
+        let explicit = with_constant_one(&result.public_inputs);
+        assert!(verify(&vk, &result.proof, &explicit).is_err());
+        assert!(verify(&vk, &result.proof, &result.public_inputs[1..]).is_err());
+        //Swapping the values the one could be confused with does not verify either
+        assert!(!verify(&vk, &result.proof, &[Fr::one(), i32_to_fr(5)]).unwrap());
+
+        //The shim drops the leading one only when it makes the count one too many
+        assert!(verify_with_explicit_one(&vk, &result.proof, &explicit).unwrap());
+        assert!(verify_with_explicit_one(&vk, &result.proof, &result.public_inputs).unwrap());
+        assert!(verify_with_explicit_one(&vk, &result.proof, &with_constant_one(&explicit)).is_err());
+    }
+
+    #[test]
+    fn test_verify_from_saved_files() {
+        let circuit = Circuit {
+            name: "files".to_string(),
+            inputs: HashMap::from([("alice".to_string(), 20), ("bob".to_string(), 1)]),
+            gates: vec![Gate::Sub("alice".to_string(), "transfer_amount_public".to_string(), "left".to_string(), None)],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        let result = run_pipeline(&circuit, PipelineOptions { return_keys: true, ..PipelineOptions::default() })
+            .expect("Pipeline failed");
+        let (_, vk) = result.keys.expect("Keys were requested");
+        let dir = std::env::temp_dir().join(format!("zk_verify_files_test_{}", std::process::id()));
+        save_proof_files(&dir, &circuit, &vk, &result.proof, &result.public_inputs).unwrap();
+        let (vk_path, proof_path, schema_path) = (dir.join(VK_FILE), dir.join(PROOF_FILE), dir.join(SCHEMA_FILE));
+        let verify_json = |json: &str, schema: Option<&Path>| {
+            let path = dir.join("inputs.json");
+            std::fs::write(&path, json).unwrap();
+            verify_from_files(&vk_path, &proof_path, &path, schema)
+        };
 
-        let mut public_inputs_for_verification_ordered: Vec<Fr> = Vec::new();
-        public_inputs_for_verification_ordered.push(Fr::one());
+        assert!(verify_from_files(&vk_path, &proof_path, &dir.join(PUBLIC_INPUTS_FILE), None).unwrap());
+        //Named values are ordered by the schema
+        let named = r#"{"transfer_amount_public": 5, "bob_initial_balance": "1", "alice_initial_balance": "20"}"#;
+        assert!(verify_json(named, Some(&schema_path)).unwrap());
+        assert!(!verify_json(r#"{"transfer_amount_public": 6, "bob_initial_balance": 1, "alice_initial_balance": 20}"#, Some(&schema_path)).unwrap());
 
-        for name in &r1cs.public_input_names {
-            let original_idx = *r1cs.var_map.get(name)
-                .unwrap_or_else(|| panic!("Public input name {} not in r1cs.var_map", name));
-            let val = witness_map_by_idx.get(&original_idx)
-                .unwrap_or_else(|| panic!("Value for public input {} (idx {}) not in witness map", name, original_idx));
-            public_inputs_for_verification_ordered.push(*val);
+        //Arrays saved with the constant one in front fail closed
+        let err = verify_json(r#"["1", "20", "1", "5"]"#, None).unwrap_err();
+        assert!(matches!(err, VerifyFilesError::PublicInputCount { expected: 3, found: 4 }), "{}", err);
+        let err = verify_json(r#"["1", "20"]"#, None).unwrap_err();
+        assert!(matches!(err, VerifyFilesError::PublicInputCount { expected: 3, found: 2 }), "{}", err);
+        let err = verify_json(r#"{"alice_initial_balance": 20, "carol": 1}"#, Some(&schema_path)).unwrap_err();
+        assert!(matches!(err, VerifyFilesError::PublicInputNames { ref unknown, .. } if unknown == &["carol"]), "{}", err);
+        assert!(matches!(verify_json(named, None), Err(VerifyFilesError::MalformedPublicInputs(_))));
+        assert!(matches!(verify_json(r#"["1", -3]"#, None), Err(VerifyFilesError::MalformedPublicInputs(_))));
+        assert!(matches!(verify_json("not json", None), Err(VerifyFilesError::MalformedPublicInputs(_))));
+        let err = verify_from_files(&proof_path, &proof_path, &dir.join(PUBLIC_INPUTS_FILE), None).unwrap_err();
+        assert!(matches!(err, VerifyFilesError::Artifact { error: ArtifactError::WrongKind { .. }, .. }), "{}", err);
+
+        //The envelope carries the public inputs and names the key
+        let envelope_path = dir.join(envelope::ENVELOPE_FILE);
+        assert!(verify_envelope_file(&vk_path, &envelope_path).unwrap());
+        let mut envelope = ProofEnvelope::decode(&std::fs::read(&envelope_path).unwrap()).unwrap();
+        assert_eq!(envelope.vk_fingerprint, vk.fingerprint());
+        envelope.vk_fingerprint[0] ^= 1;
+        std::fs::write(&envelope_path, envelope.to_bytes()).unwrap();
+        let err = verify_envelope_file(&vk_path, &envelope_path).unwrap_err();
+        assert!(matches!(err, VerifyFilesError::Envelope(EnvelopeError::VkMismatch { .. })), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prove_reports_missing_public_input() {
+        let circuit = testing::mul_chain(1);
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        let (pk, _) = setup(&r1cs).expect("Setup failed");
+
+        let mut broken = r1cs.clone();
+        broken.var_map.remove("transfer_amount_public");
+        let err = prove(&broken, &pk, witness).unwrap_err();
+        assert!(matches!(&err, ProveError::MissingVariable { name } if name == "transfer_amount_public"), "{:?}", err);
+        assert_eq!(err.to_string(), "Variable 'transfer_amount_public' not found in var_map");
+        assert!(matches!(setup(&broken), Err(ProveError::MissingVariable { .. })));
+    }
+
+    #[test]
+    fn test_synthesized_stats_match_r1cs() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(9);
+        let mut circuits = vec![testing::mul_chain(5), testing::merkle_path(2)];
+        circuits.extend((0..20).map(|_| testing::random_circuit(&mut rng, &testing::CircuitConfig::default())));
+
+        for circuit in circuits {
+            let r1cs = circuit.to_r1cs_system();
+            let stats = synthesize_stats(&r1cs).expect("Synthesis failed");
+            assert_eq!(stats.mismatches(&r1cs), Vec::<String>::new(), "{:?}", circuit.gates);
+            assert!(stats.densities().iter().all(|d| *d > 0.0 && *d <= 1.0));
         }
-        
-        assert_eq!(public_inputs_for_verification_ordered.len(), r1cs.num_public_inputs, "Mismatch in number of public inputs for verification.");
+    }
 
-        println!("Verifying Proof with {} public inputs...", public_inputs_for_verification_ordered.len());
-        let is_valid = verify(&vk, &proof_obj, &public_inputs_for_verification_ordered)
-            .expect("Verification threw error");
+    #[test]
+    fn test_explain_names_failing_gate() {
+        let circuit = Circuit {
+            name: "xor".to_string(),
+            inputs: HashMap::from([("a".to_string(), 1), ("b".to_string(), 0)]),
+            gates: vec![
+                Gate::Const("k".to_string(), 3),
+                Gate::Xor("a".to_string(), "b".to_string(), "c".to_string()),
+                Gate::Hash("c".to_string(), "h".to_string()),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(r1cs.constraint_origins.len(), r1cs.raw_constraints.len());
+        let mut witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        assert_eq!(r1cs.which_is_unsatisfied(&witness), Ok(None));
 
-        println!("Proof verification result: {}", is_valid);
-        assert!(is_valid, "Proof should be valid!");
+        //a = 2 with the product and output rows kept consistent (b = 0), so only booleanity breaks
+        witness.insert(r1cs.var_map["a"], Fr::from(2u64));
+        witness.insert(r1cs.var_map["c"], Fr::from(2u64));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("Corrupted witness must fail");
+        let explanation = r1cs.explain(failing);
+        assert!(explanation.contains("booleanity of a"), "{}", explanation);
+        assert!(explanation.contains("gate #1 Xor(\"a\", \"b\", \"c\")"), "{}", explanation);
+        assert!(r1cs.explain(0).contains("transfer spec"));
+        assert_eq!(r1cs.explain(r1cs.raw_constraints.len()), format!("constraint {} (origin unknown)", r1cs.raw_constraints.len()));
+    }
+
+    #[test]
+    fn test_constants_are_single_assignment_and_wide() {
+        let big = 1i64 << 40;
+        let mut circuit = Circuit {
+            name: "consts".to_string(),
+            inputs: HashMap::from([("alice".to_string(), 10), ("x".to_string(), 3)]),
+            gates: vec![
+                Gate::Const("k".to_string(), big),
+                Gate::Mul("x".to_string(), "k".to_string(), "y".to_string(), None),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        let result = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        assert_eq!(witness[&r1cs.var_map["y"]], Fr::from(3u64 << 40));
+
+        circuit.gates.push(Gate::Const("k".to_string(), 2));
+        assert_eq!(circuit.check_constants(), Err(CircuitError::DuplicateConst { wire: "k".to_string() }));
+        let err = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).err().expect("Duplicate constant must fail");
+        assert_eq!(err.to_string(), "Invalid circuit: Constant 'k' is defined more than once");
+        assert!(circuit.compute_witness(&r1cs.var_map).unwrap_err().contains("'k'"));
+
+        circuit.gates[2] = Gate::Const("x".to_string(), 2);
+        assert_eq!(circuit.check_constants(), Err(CircuitError::ConstOnInput { wire: "x".to_string() }));
+    }
+
+    #[test]
+    fn test_bound_inputs_commit_to_every_input() {
+        let circuit = parse_circuit_str("name bound
+input alice 100
+input bob 20
+input secret 7
+inputs bound
+balances private
+amount 30
+mul secret secret sq
+").unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(r1cs.public_input_names, vec!["transfer_amount_public".to_string(), INPUT_DIGEST_WIRE.to_string()]);
+        assert_eq!(r1cs.public_input_schema()[1].source, PublicInputSource::InputDigest);
+        let digest = circuit.input_digest();
+        assert_eq!(digest, poseidon::poseidon_hash(&[i32_to_fr(100), i32_to_fr(20), i32_to_fr(7)]));
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        assert_eq!(witness[&r1cs.var_map[INPUT_DIGEST_WIRE]], digest);
+
+        let result = run_pipeline(&circuit, PipelineOptions { return_keys: true, ..PipelineOptions::default() })
+            .expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+        assert_eq!(result.public_inputs, vec![i32_to_fr(30), digest]);
+        let (_, vk) = result.keys.expect("Keys were requested");
+
+        //Any other input gives another digest, which the old proof does not verify against
+        for name in ["alice", "bob", "secret"] {
+            let mut changed = circuit.clone();
+            *changed.inputs.get_mut(name).unwrap() += 1;
+            let other = changed.input_digest();
+            assert_ne!(other, digest, "{}", name);
+            assert!(!verify(&vk, &result.proof, &[i32_to_fr(30), other]).unwrap(), "{}", name);
+        }
+
+        //The digest wire is reserved and binding changes the circuit's identity
+        let mut unbound = circuit.clone();
+        unbound.bind_inputs = false;
+        assert_ne!(unbound.canonical_hash(), circuit.canonical_hash());
+        let mut clash = circuit.clone();
+        clash.inputs.insert(INPUT_DIGEST_WIRE.to_string(), 1);
+        assert_eq!(clash.validate_gates(), Err(CircuitError::ReservedName { wire: INPUT_DIGEST_WIRE.to_string() }));
+    }
+
+    #[test]
+    fn test_public_input_schema_is_stable() {
+        //The order is part of the verifier interface: a change here must be deliberate
+        let circuit = Circuit {
+            name: "test_add".to_string(),
+            inputs: HashMap::from([("alice".to_string(), 10), ("bob".to_string(), 20), ("a".to_string(), 1)]),
+            gates: vec![Gate::Add("a".to_string(), "transfer_amount_public".to_string(), "c".to_string(), None)],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            public_inputs: vec![("zeta".to_string(), Fr::from(7u64)), ("digest".to_string(), Fr::from(9u64))],
+            ..Default::default()
+        };
+        let r1cs = circuit.to_r1cs_system();
+        let schema: Vec<(String, PublicInputSource, usize)> =
+            r1cs.public_input_schema().into_iter().map(|desc| (desc.name, desc.source, desc.index)).collect();
+        assert_eq!(
+            schema,
+            vec![
+                ("alice_initial_balance".to_string(), PublicInputSource::SenderBalance, 0),
+                ("bob_initial_balance".to_string(), PublicInputSource::ReceiverBalance, 1),
+                ("transfer_amount_public".to_string(), PublicInputSource::TransferAmount, 2),
+                ("zeta".to_string(), PublicInputSource::UserDeclared, 3),
+                ("digest".to_string(), PublicInputSource::UserDeclared, 4),
+            ]
+        );
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        let public_inputs = r1cs.public_inputs_from_witness(&witness).unwrap();
+        assert_eq!(public_inputs, [10u64, 20, 5, 7, 9].map(Fr::from).to_vec());
+
+        //The JSON export carries the schema, legacy exports infer it from the names
+        let mut json = r1cs.to_json_struct();
+        let imported = R1CSSystem::from_json(&serde_json::to_string(&json).unwrap()).unwrap();
+        assert_eq!(imported.public_input_schema(), r1cs.public_input_schema());
+        json.public_input_schema = None;
+        let legacy = R1CSSystem::from_json(&serde_json::to_string(&json).unwrap()).unwrap();
+        assert_eq!(legacy.public_input_schema(), r1cs.public_input_schema());
+    }
+
+    #[test]
+    fn test_transfer_options_hide_each_pin() {
+        let source = crate::examples_support::TRANSFER_DSL;
+        for mask in 0..8u8 {
+            let hidden: Vec<&str> = ["hide_sender_balance", "hide_receiver_balance", "hide_amount"]
+                .into_iter()
+                .enumerate()
+                .filter(|(bit, _)| mask & (1 << bit) != 0)
+                .map(|(_, option)| option)
+                .collect();
+            let dsl = if hidden.is_empty() { source.to_string() } else { format!("transfer_options {}\n{}", hidden.join(" "), source) };
+            let circuit = crate::parse_circuit_str(&dsl).unwrap();
+            let r1cs = circuit.to_r1cs_system();
+            let expected: Vec<PublicInputSource> = [PublicInputSource::SenderBalance, PublicInputSource::ReceiverBalance, PublicInputSource::TransferAmount]
+                .into_iter()
+                .enumerate()
+                .filter(|(bit, _)| mask & (1 << bit) == 0)
+                .map(|(_, source)| source)
+                .collect();
+            let sources: Vec<PublicInputSource> = r1cs.public_input_schema().into_iter().map(|desc| desc.source).collect();
+            assert_eq!(sources, expected, "hidden {:?}", hidden);
+            assert_eq!(r1cs.num_public_inputs, 1 + expected.len());
+            //A hidden amount is range checked instead of pinned, a hidden balance equals its input wire
+            let amount_range = if mask & 4 != 0 { AMOUNT_BITS as usize + 1 } else { 0 };
+            let hidden_balances = (mask & 3).count_ones() as usize;
+            let preamble = r1cs.constraint_origins.iter().filter(|origin| origin.is_transfer_preamble()).count();
+            assert_eq!(preamble, expected.len() + amount_range + hidden_balances);
+
+            let result = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).expect("Pipeline failed");
+            assert_eq!(result.public_inputs.len(), expected.len(), "hidden {:?}", hidden);
+            assert_eq!(result.verified, Some(true), "hidden {:?}", hidden);
+        }
+        let err = crate::parse_circuit_str("transfer_options hide_everything\nname x\n").unwrap_err();
+        assert!(err.to_string().contains("Unknown transfer option 'hide_everything'"), "{}", err);
+    }
+
+    #[test]
+    fn test_amounts_are_u64() {
+        let source = format!("name max_amount\ninput x 1\namount {}\nadd $transfer_amount x total\n", u64::MAX);
+        for hide in [false, true] {
+            let dsl = if hide { format!("transfer_options hide_amount\n{}", source) } else { source.clone() };
+            let circuit = crate::parse_circuit_str(&dsl).unwrap();
+            assert_eq!(circuit.transfer_amount, u64::MAX);
+            let result = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).expect("Pipeline failed");
+            assert_eq!(result.verified, Some(true), "hidden amount: {}", hide);
+        }
+
+        //The range check rejects a hidden amount above u64::MAX
+        let circuit = crate::parse_circuit_str(&format!("transfer_options hide_amount\n{}", source)).unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        let mut witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["transfer_amount_public"], u64_to_fr(u64::MAX) + Fr::one());
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("2^64 is out of range");
+        assert!(r1cs.explain(failing).contains("amount range"), "{}", r1cs.explain(failing));
+
+        for amount in ["-5", "18446744073709551616", "ten"] {
+            let err = crate::parse_circuit_str(&format!("name bad\namount {}\n", amount)).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains(&format!("Invalid transfer amount '{}'", amount)), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_gate_graph_has_single_writers() {
+        let wire = |name: &str| name.to_string();
+        let mut circuit = Circuit {
+            name: "writers".to_string(),
+            inputs: HashMap::from([("alice".to_string(), 10), ("x".to_string(), 3), ("y".to_string(), 4)]),
+            //Repeated operands are allowed, accumulators chain fresh wires
+            gates: vec![
+                Gate::Mul(wire("x"), wire("x"), wire("acc0"), None),
+                Gate::Add(wire("acc0"), wire("y"), wire("acc1"), None),
+                Gate::Add(wire("acc1"), wire("acc1"), wire("acc2"), None),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        assert_eq!(circuit.validate_gates(), Ok(()));
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(circuit.compute_witness(&r1cs.var_map).unwrap()[&r1cs.var_map["acc2"]], Fr::from(26u64));
+
+        circuit.gates[2] = Gate::Add(wire("acc1"), wire("y"), wire("acc1"), None);
+        assert_eq!(circuit.validate_gates(), Err(CircuitError::SelfReferential { gate_index: 2, wire: wire("acc1") }));
+        assert_eq!(circuit.compute_witness(&r1cs.var_map).unwrap_err(), "Gate #2 reads its own output wire 'acc1'");
+
+        circuit.gates[2] = Gate::Mul(wire("x"), wire("y"), wire("acc0"), None);
+        assert_eq!(
+            circuit.validate_gates(),
+            Err(CircuitError::MultipleWriters { wire: wire("acc0"), first_gate: 0, second_gate: 2 })
+        );
+        let err = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).err().expect("Second writer must fail");
+        assert_eq!(err.to_string(), "Invalid circuit: Wire 'acc0' is assigned by gate #0 and again by gate #2");
+
+        circuit.gates[2] = Gate::UInt(crate::uint::UIntOp::Add, 32, wire("x"), wire("y"), wire("s"), wire("s"));
+        assert_eq!(circuit.validate_gates(), Err(CircuitError::MultipleWriters { wire: wire("s"), first_gate: 2, second_gate: 2 }));
+
+        circuit.gates[2] = Gate::Add(wire("acc0"), wire("acc1"), wire("alice_initial_balance"), None);
+        assert_eq!(
+            circuit.validate_gates(),
+            Err(CircuitError::OverwritesInput { gate_index: 2, wire: wire("alice_initial_balance") })
+        );
+    }
+
+    #[test]
+    fn test_zero_amount_and_self_transfers() {
+        //A zero amount is a valid transfer, with public or private balances
+        for balances in ["", "balances private\n"] {
+            let source = format!("name zero\ninput alice 10\ninput bob 3\nsender alice\nreceiver bob\namount 0\n{}", balances);
+            let mut circuit = crate::parse_circuit_str(&source).unwrap();
+            assert!(circuit.validate_transfer());
+            let result = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).expect("Pipeline failed");
+            assert_eq!(result.verified, Some(true), "{}", source);
+            let amount = circuit.to_r1cs_system().public_input_names.iter().position(|name| name == "transfer_amount_public");
+            assert_eq!(amount.map(|i| result.public_inputs[i]), Some(Fr::zero()));
+            circuit.execute_transfer().unwrap();
+            assert_eq!((circuit.inputs["alice"], circuit.inputs["bob"]), (10, 3));
+        }
+
+        //A receiver balance past i32::MAX is an error that moves nothing
+        let mut circuit = crate::parse_circuit_str(&format!("name overflow\ninput alice 10\ninput bob {}\nsender alice\nreceiver bob\namount 4\n", i32::MAX - 3)).unwrap();
+        assert_eq!(circuit.execute_transfer(), Err(CircuitError::BalanceOverflow { party: "bob".to_string(), amount: 4 }));
+        assert_eq!((circuit.inputs["alice"], circuit.inputs["bob"]), (10, i32::MAX - 3));
+        circuit.transfer_amount = 3;
+        circuit.execute_transfer().unwrap();
+        assert_eq!((circuit.inputs["alice"], circuit.inputs["bob"]), (7, i32::MAX));
+
+        let err = crate::parse_circuit_str("name self\ninput alice 10\nsender alice\nreceiver alice\namount 4\n").unwrap_err();
+        assert_eq!(err.to_string(), "'alice' is both the sender and the receiver of the transfer");
+        let mut circuit = crate::parse_circuit_str("name self\ninput alice 10\nsender alice\nreceiver bob\namount 4\n").unwrap();
+        circuit.receiver = "alice".to_string();
+        assert_eq!(circuit.validate_gates(), Err(CircuitError::SelfTransfer { party: "alice".to_string() }));
+        circuit.execute_transfer().unwrap();
+        assert_eq!(circuit.inputs["alice"], 10);
+        let err = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).err().expect("Self-transfer must fail");
+        assert!(matches!(err, crate::PipelineError::Circuit(CircuitError::SelfTransfer { .. })));
+    }
+
+    #[test]
+    fn test_forged_private_balance_is_rejected() {
+        //alice has 10 and sends 30: the honest witness refuses, a forged initial balance must too
+        let circuit = crate::parse_circuit_str("name forged\ninput alice 10\ninput bob 3\nsender alice\nreceiver bob\nbalances private\namount 30\n").unwrap();
+        assert!(circuit.bind_inputs, "balances private implies inputs bound");
+        let r1cs = circuit.to_r1cs_system();
+        assert!(circuit.compute_witness(&r1cs.var_map).is_err());
+        let mut unbound = circuit.clone();
+        unbound.bind_inputs = false;
+        assert_eq!(unbound.validate_gates(), Err(CircuitError::UnboundPrivateBalances));
+
+        //Every balance-derived wire taken from a sender with 100, only the input wire keeps the real 10
+        let mut rich = circuit.clone();
+        rich.inputs.insert("alice".to_string(), 100);
+        let mut witness = rich.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["alice"], i32_to_fr(10));
+        assert_eq!(witness[&r1cs.var_map["alice_initial_balance"]], i32_to_fr(100));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("The forged balance must violate a row");
+        assert!(r1cs.explain(failing).contains("private balance equals the input wire alice"), "{}", r1cs.explain(failing));
+
+        //Forging the input wire as well only moves the failure to the sufficiency check
+        let mut witness = rich.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["alice_initial_balance"], i32_to_fr(10));
+        witness.insert(r1cs.var_map["alice"], i32_to_fr(10));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("The forged balance must violate a row");
+        assert!(r1cs.explain(failing).contains("balance sufficiency"), "{}", r1cs.explain(failing));
+
+        //A consistent witness for 100 proves, but only against the digest of 100, not the committed 10
+        let result = run_pipeline(&rich, PipelineOptions { return_keys: true, ..PipelineOptions::default() }).expect("Pipeline failed");
+        let (_, vk) = result.keys.expect("Keys were requested");
+        assert_eq!(result.public_inputs, vec![u64_to_fr(30), rich.input_digest()]);
+        assert!(verify(&vk, &result.proof, &result.public_inputs).unwrap());
+        assert!(!verify(&vk, &result.proof, &[u64_to_fr(30), circuit.input_digest()]).unwrap());
+    }
+
+    #[test]
+    fn test_standalone_assertions() {
+        let source = "name asserts\ninput alice 10\ninput x 3\ninput flag 1\nsub x flag d\nassert_nonzero d\nassert_bool flag\n";
+        let mut circuit = crate::parse_circuit_str(source).unwrap();
+        assert!(matches!(circuit.gates[1], Gate::AssertNonZero(ref x) if x == "d"));
+        assert!(matches!(circuit.gates[2], Gate::AssertBool(ref b) if b == "flag"));
+        let result = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+
+        //d = x - flag = 0 has no inverse
+        circuit.inputs.insert("x".to_string(), 1);
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(circuit.compute_witness(&r1cs.var_map).unwrap_err(), "Assertion failed: wire 'd' is zero");
+        //A prover skipping the check still cannot satisfy the system
+        let mut witness = crate::parse_circuit_str(source).unwrap().compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["x"], Fr::from(1u64));
+        witness.insert(r1cs.var_map["d"], Fr::zero());
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("Zero must violate x * inv = 1");
+        assert!(r1cs.explain(failing).contains("d times its inverse is one"), "{}", r1cs.explain(failing));
+
+        circuit.inputs.insert("x".to_string(), 5);
+        circuit.inputs.insert("flag".to_string(), 2);
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(circuit.compute_witness(&r1cs.var_map).unwrap_err(), "Assertion failed: wire 'flag' is 2, not a bit (0 or 1)");
+        circuit.inputs.insert("flag".to_string(), 1);
+        let mut witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["flag"], Fr::from(2u64));
+        witness.insert(r1cs.var_map["d"], Fr::from(3u64));
+        witness.insert(r1cs.var_map["d_nonzero_inv"], Fr::from(3u64).inverse().unwrap());
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("2 is not a bit");
+        assert!(r1cs.explain(failing).contains("booleanity of flag"), "{}", r1cs.explain(failing));
+    }
+
+    #[test]
+    fn test_assert_eq_is_one_row() {
+        let source = "name assert_eq\ninput x 3\ninput y 4\nconst seven 7\nadd x y sum\neq sum seven\niseq x x zero\neq y y old\n";
+        let mut circuit = crate::parse_circuit_str(source).unwrap();
+        assert!(matches!(&circuit.gates[2], Gate::AssertEq(a, b) if a == "sum" && b == "seven"));
+        //iseq and the deprecated three-operand eq both build the two-row Eq
+        assert!(matches!(&circuit.gates[3], Gate::Eq(_, _, out) if out == "zero"));
+        assert!(matches!(&circuit.gates[4], Gate::Eq(_, _, out) if out == "old"));
+        let r1cs = circuit.to_r1cs_system();
+        let rows: Vec<_> = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(2)).collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].role, "sum equals seven");
+        let result = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+
+        circuit.inputs.insert("y".to_string(), 5);
+        assert_eq!(circuit.compute_witness(&r1cs.var_map).unwrap_err(), "Assertion failed: wire 'sum' is 8 but wire 'seven' is 7");
+        //A prover skipping the check still cannot satisfy the system
+        let mut witness = crate::parse_circuit_str(source).unwrap().compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["y"], Fr::from(5u64));
+        witness.insert(r1cs.var_map["sum"], Fr::from(8u64));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("8 != 7");
+        assert!(r1cs.explain(failing).contains("sum equals seven"), "{}", r1cs.explain(failing));
+    }
+
+    #[test]
+    fn test_condswap_orders_a_pair() {
+        let source = "name condswap\ninput b:bool 0\ninput x 3\ninput y 9\ncondswap b x y left right\n";
+        let mut circuit = crate::parse_circuit_str(source).unwrap();
+        assert!(matches!(&circuit.gates[0], Gate::CondSwap(b, _, _, l, r) if b == "b" && l == "left" && r == "right"));
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(0)).count(), 3);
+        for (bit, expected) in [(0, (3u64, 9u64)), (1, (9, 3))] {
+            circuit.inputs.insert("b".to_string(), bit);
+            let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+            assert_eq!((witness[&r1cs.var_map["left"]], witness[&r1cs.var_map["right"]]), (Fr::from(expected.0), Fr::from(expected.1)));
+            assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None);
+        }
+
+        circuit.inputs.insert("b".to_string(), 2);
+        assert_eq!(circuit.compute_witness(&r1cs.var_map).unwrap_err(), "Selector 'b' of condswap is 2, not a bit (0 or 1)");
+        //A prover skipping the check cannot pass off a non-bit selector
+        circuit.inputs.insert("b".to_string(), 0);
+        let mut witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["b"], Fr::from(2u64));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("2 is not a bit");
+        assert!(r1cs.explain(failing).contains("booleanity of b"), "{}", r1cs.explain(failing));
+    }
+
+    #[test]
+    fn test_reveal_if_discloses_the_amount_only_when_flagged() {
+        let source = "name compliance\namount 250\ntransfer_options hide_amount\ninput flag:bool 1\nreveal_if flag $transfer_amount disclosed\n";
+        let mut circuit = crate::parse_circuit_str(source).unwrap();
+        assert!(matches!(&circuit.gates[0], Gate::RevealIf(f, v, d) if f == "flag" && v == "transfer_amount_public" && d == "disclosed"));
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(r1cs.public_input_names, vec!["disclosed".to_string()]);
+        assert_eq!(r1cs.public_input_schema()[0].source, PublicInputSource::Disclosure);
+        let (pk, vk) = setup(&r1cs).unwrap();
+        for (flag, expected) in [(1, 250u64), (0, 0)] {
+            circuit.inputs.insert("flag".to_string(), flag);
+            let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+            let public_inputs = r1cs.public_inputs_from_witness(&witness).unwrap();
+            assert_eq!(public_inputs, vec![Fr::from(expected)]);
+            let proof = prove(&r1cs, &pk, witness).unwrap();
+            assert!(verify(&vk, &proof, &public_inputs).unwrap());
+            //The proof does not verify against another disclosed value
+            assert!(!verify(&vk, &proof, &[Fr::from(expected + 1)]).unwrap(), "flag {}", flag);
+        }
+
+        circuit.inputs.insert("flag".to_string(), 2);
+        assert_eq!(circuit.compute_witness(&r1cs.var_map).unwrap_err(), "Flag 'flag' of reveal_if is 2, not a bit (0 or 1)");
+    }
+
+    #[test]
+    fn test_canonical_bytes_are_pinned() {
+        //The encoding is what gets hashed: a change to it must show up here and bump the version tag
+        let circuit = parse_circuit_str("name pinned\ninput b 7\ninput a 3\nconst k 5\nadd a k c\npublic p 9\nsender a\nreceiver b\namount 2\n").unwrap();
+        let expected = [
+            hex::encode(b"zk-framework/circuit/v2"),
+            //Inputs sorted by name, u64 length prefixes and little-endian values
+            "0200000000000000".to_string() + "010000000000000061" + "03000000" + "010000000000000062" + "07000000",
+            //Gates in order: tag, wires, then each constant behind a presence byte
+            "0200000000000000".to_string() + "04" + "01000000000000006b" + "01" + "0500000000000000",
+            "00".to_string() + "010000000000000061" + "01000000000000006b" + "010000000000000063" + "00",
+            //Transfer spec
+            "010000000000000061".to_string() + "010000000000000062" + "0200000000000000",
+            //Public inputs, values as compressed field elements
+            "70".to_string() + "0100000000000000" + "010000000000000070" + "09" + &"00".repeat(31),
+        ]
+        .concat();
+        assert_eq!(hex::encode(circuit.canonical_bytes()), expected);
+        assert_eq!(hex::encode(circuit.canonical_hash()), "998f3939c92b1de3ed0f339833b82b30017a7f3fb31efaba77dbce1e7707ec57");
+
+        //Insertion order of the inputs does not matter, the name does not either
+        let mut reordered = circuit.clone();
+        reordered.name = "renamed".to_string();
+        reordered.inputs = HashMap::from([("b".to_string(), 7), ("a".to_string(), 3)]);
+        assert_eq!(reordered.canonical_bytes(), circuit.canonical_bytes());
+    }
+
+    #[test]
+    fn test_density_report_is_pinned() {
+        //Pinned so changes to the constraint builders show up as density changes
+        let circuit = Circuit {
+            name: "density".to_string(),
+            inputs: HashMap::from([("alice".to_string(), 10), ("a".to_string(), 1), ("b".to_string(), 0), ("x".to_string(), 3)]),
+            gates: vec![
+                Gate::Add("x".to_string(), "x".to_string(), "y".to_string(), None),
+                Gate::Mul("x".to_string(), "y".to_string(), "z".to_string(), None),
+                Gate::Sub("z".to_string(), "y".to_string(), "w".to_string(), None),
+                Gate::Xor("a".to_string(), "b".to_string(), "c".to_string()),
+                Gate::Eq("c".to_string(), "a".to_string(), "d".to_string()),
+                Gate::Hash("w".to_string(), "h".to_string()),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        let r1cs = circuit.to_r1cs_system();
+        let report = r1cs.density();
+        assert_eq!(
+            (report.num_constraints, report.num_variables, report.a_non_zero, report.b_non_zero, report.c_non_zero),
+            (255, 256, 2263, 4259, 254)
+        );
+        assert_eq!((report.max_row_len, report.domain_size, report.msm_g1_size, report.msm_g2_size), (61, 512, 1279, 257));
+        assert!((report.avg_row_len - 8.8575).abs() < 1e-4);
+        assert_eq!(report.estimated_cost(), 2117);
+        assert!(report.warnings().is_empty());
+
+        //A single row summing 200 variables trips the row length warning
+        let mut dense = r1cs.clone();
+        dense.raw_constraints[0].a = (1..201).map(|idx| (idx, Fr::one())).collect();
+        let warnings = dense.density().warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("200 terms"), "{:?}", warnings);
     }
 }
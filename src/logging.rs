@@ -0,0 +1,14 @@
+//Opt-in log output, the library only emits tracing spans and events
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+
+//Filter used by the CLI when RUST_LOG is not set
+pub const DEFAULT_FILTER: &str = "info,ark_relations::r1cs::trace=trace";
+
+//Install a global fmt subscriber with the given EnvFilter directives (e.g. "info,zk_framework=debug").
+//Fails on invalid directives or when a global subscriber is already set
+pub fn init_logging(filter: &str) -> Result<(), String> {
+    let env_filter = EnvFilter::try_new(filter).map_err(|e| format!("Invalid log filter '{}': {}", filter, e))?;
+    //No with_max_level: it would replace the env filter and log everything at trace
+    let subscriber = FmtSubscriber::builder().with_env_filter(env_filter).finish();
+    tracing::subscriber::set_global_default(subscriber).map_err(|e| format!("Cannot install log subscriber: {}", e))
+}
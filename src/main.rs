@@ -1,85 +1,602 @@
 use std::collections::HashMap;
-use zk_framework::{Circuit, ProvingKey, VerifyingKey, parse_circuit, setup, prove, verify};
-use ark_bls12_381::Fr;
-use ark_ff::One;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use zk_framework::{Circuit, KeyCache, Repl, PipelineOptions, parse_circuit, parse_inputs, run_pipeline, setup_with_progress, synthesize_stats};
+use zk_framework::{eliminate_common_subexpressions, load_witness, save_witness};
+use zk_framework::{ProgressEvent, ProgressSink};
+use zk_framework::{measure, GatePolicy, PipelineError, SerializedSize};
+use zk_framework::{save_proof_files, verify_envelope_file, verify_from_files};
+use ark_std::rand::rngs::OsRng;
+use zk_framework::logging::{init_logging, DEFAULT_FILTER};
+use zk_framework::batch::run_batch;
+use zk_framework::compatibility;
+use notify::{RecursiveMode, Watcher};
+use indicatif::{ProgressBar, ProgressStyle};
 
+const USAGE: &str = "Usage: cargo run -- <path_to_circuit_file>
+       cargo run -- prove <path_to_circuit_file> [--inputs <file> [--witness <file.wit>] [--out-dir <dir>] | --inputs-dir <dir>] [--cache-dir <dir>] [--watch]
+       cargo run -- verify <vk.bin> <proof.bin> <public_inputs.json> [--schema <r1cs.json>]
+       cargo run -- verify <vk.bin> <proof.envelope.json>
+       cargo run -- witness <path_to_circuit_file> [--inputs <file>] --out <file.wit>
+       cargo run -- inspect <path_to_circuit_file> [--policy <policy.json>] [--strict-soundness]
+       cargo run -- bench <path_to_circuit_file>
+       cargo run -- compat <old_r1cs.json> <path_to_circuit_file>
+       cargo run -- repl [--cache-dir <dir>]";
 
 fn main() {
-    circuit_main();
-}
-
-#[allow(dead_code)]
-fn circuit_main() {
-    //setting up logging
-    use tracing_subscriber::{EnvFilter, FmtSubscriber}; 
-    let subscriber = FmtSubscriber::builder() 
-        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info,ark_relations::r1cs::trace=trace"))) // Corrected filter
-        .with_max_level(tracing::Level::TRACE)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("setting default subscriber failed");
+    //setting up logging, a non-empty RUST_LOG overrides the default filter
+    let filter = std::env::var("RUST_LOG").ok().filter(|f| !f.is_empty()).unwrap_or_else(|| DEFAULT_FILTER.to_string());
+    if let Err(e) = init_logging(&filter) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 
     //check for command line arguments
     let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: cargo run -- <path_to_circuit_file>");
+    match args.get(1).map(String::as_str) {
+        Some("prove") => prove_main(&args[2..]),
+        Some("witness") => witness_main(&args[2..]),
+        Some("verify") => verify_main(&args[2..]),
+        Some("inspect") => match parse_inspect_args(&args[2..]) {
+            Ok(inspect) => inspect_main(&inspect),
+            Err(e) => eprintln!("{}\n{}", e, USAGE),
+        },
+        Some("bench") => match args.get(2) {
+            Some(path) => bench_main(path),
+            None => eprintln!("{}", USAGE),
+        },
+        Some("compat") => match (args.get(2), args.get(3)) {
+            (Some(old_export), Some(path)) => compat_main(old_export, path),
+            _ => eprintln!("{}", USAGE),
+        },
+        Some("repl") => repl_main(&args[2..]),
+        Some(path) => circuit_main(path),
+        None => eprintln!("{}", USAGE),
+    }
+}
+
+//Options of the prove subcommand
+struct ProveArgs {
+    circuit_path: String,
+    inputs: Option<String>,
+    inputs_dir: Option<String>,
+    witness: Option<String>,
+    cache_dir: Option<String>,
+    //Directory receiving the vk, proof, public inputs and R1CS schema for verify
+    out_dir: Option<String>,
+    watch: bool,
+}
+
+fn parse_prove_args(args: &[String]) -> Result<ProveArgs, String> {
+    let mut circuit_path = None;
+    let mut inputs = None;
+    let mut inputs_dir = None;
+    let mut witness = None;
+    let mut cache_dir = None;
+    let mut out_dir = None;
+    let mut watch = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--inputs" => inputs = Some(iter.next().ok_or("--inputs needs a file")?.clone()),
+            "--inputs-dir" => inputs_dir = Some(iter.next().ok_or("--inputs-dir needs a directory")?.clone()),
+            "--witness" => witness = Some(iter.next().ok_or("--witness needs a file")?.clone()),
+            "--cache-dir" => cache_dir = Some(iter.next().ok_or("--cache-dir needs a directory")?.clone()),
+            "--out-dir" => out_dir = Some(iter.next().ok_or("--out-dir needs a directory")?.clone()),
+            "--watch" => watch = true,
+            other if circuit_path.is_none() && !other.starts_with("--") => circuit_path = Some(other.to_string()),
+            other => return Err(format!("Unexpected argument: {}", other)),
+        }
+    }
+    if inputs_dir.is_some() && (inputs.is_some() || witness.is_some() || out_dir.is_some()) {
+        return Err("--inputs-dir cannot be combined with --inputs, --witness or --out-dir".to_string());
+    }
+    Ok(ProveArgs { circuit_path: circuit_path.ok_or("Missing circuit file")?, inputs, inputs_dir, witness, cache_dir, out_dir, watch })
+}
+
+//prove subcommand: single run, batch over a directory, optionally re-run on file changes
+fn prove_main(args: &[String]) {
+    let prove_args = match parse_prove_args(args) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let ok = run_prove_once(&prove_args);
+    if !prove_args.watch {
+        if !ok {
+            std::process::exit(1);
+        }
         return;
     }
+
+    let mut watched = vec![prove_args.circuit_path.clone()];
+    watched.extend(prove_args.inputs.clone());
+    watched.extend(prove_args.inputs_dir.clone());
+    watched.extend(prove_args.witness.clone());
+    if let Err(e) = watch_loop(&watched, || {
+        run_prove_once(&prove_args);
+    }) {
+        eprintln!("Watch failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+//Run parse/witness/prove/verify once and print the results, returns whether everything passed
+fn run_prove_once(prove_args: &ProveArgs) -> bool {
+    let start = Instant::now();
+    let circuit = match parse_circuit(&prove_args.circuit_path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to parse circuit: {}", e);
+            return false;
+        }
+    };
+    let parse_time = start.elapsed();
+    warn_privacy_leaks(&circuit);
+
+    let cache = prove_args.cache_dir.as_ref().map(KeyCache::new);
+
+    if let Some(dir) = &prove_args.inputs_dir {
+        return match run_batch(&circuit, Path::new(dir), cache.as_ref()) {
+            Ok(summary) => {
+                for case in &summary.cases {
+                    let status = if case.passed { "PASS" } else { "FAIL" };
+                    println!("[{}] {} ({:.2?}){}", status, case.name, case.duration,
+                        case.error.as_ref().map(|e| format!(": {}", e)).unwrap_or_default());
+                }
+                println!("Batch: {} passed, {} failed in {:.2?}", summary.passed(), summary.failed(), summary.total_duration);
+                summary.failed() == 0
+            }
+            Err(e) => {
+                eprintln!("Batch failed: {}", e);
+                false
+            }
+        };
+    }
+
+    let inputs = match &prove_args.inputs {
+        Some(path) => match parse_inputs(path) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                eprintln!("Failed to parse inputs: {}", e);
+                return false;
+            }
+        },
+        None => HashMap::new(),
+    };
+
+    //A precomputed witness is checked against the circuit (with the same inputs it was computed
+    //for) before any setup or proving
+    let mut witness_circuit = circuit.clone();
+    witness_circuit.inputs.extend(inputs.clone());
+    let witness = match &prove_args.witness {
+        Some(path) => {
+            let r1cs = witness_circuit.to_r1cs_system();
+            let loaded = load_witness(path)
+                .and_then(|file| file.check(&witness_circuit.canonical_hash(), &r1cs).map(|_| file))
+                .map_err(|e| e.to_string())
+                .and_then(|file| match r1cs.which_is_unsatisfied(&file.values)? {
+                    Some(failing) => Err(format!("witness does not satisfy {}", r1cs.explain(failing))),
+                    None => Ok(file),
+                });
+            match loaded {
+                Ok(file) => Some(file.values),
+                Err(e) => {
+                    eprintln!("Cannot use witness {}: {}", path, e);
+                    return false;
+                }
+            }
+        }
+        None => None,
+    };
+
+    //Keys for the circuit with the inputs applied, they may change the public balances it pins
+    let setups = cache.as_ref().map(KeyCache::setup_count);
+    let start = Instant::now();
+    let keys = match &cache {
+        Some(cache) => cache.get_or_setup_with_progress(&witness_circuit, &SpinnerProgress::new()).map_err(|e| e.to_string()),
+        None => setup_with_progress(&witness_circuit.to_r1cs_system(), &SpinnerProgress::new()).map_err(|e| format!("Setup failed: {}", e)),
+    };
+    let setup_time = start.elapsed();
+    let result = keys.and_then(|(pk, vk)| {
+        let options = PipelineOptions { keys: Some((&pk, &vk)), witness: witness.as_ref(), ..PipelineOptions::default() };
+        let mut result = run_pipeline(&witness_circuit, options).map_err(|e| e.to_string())?;
+        //Keys come from the cache or setup above, outside the pipeline
+        result.timings.parse = parse_time;
+        //A cache that ran no setup loaded the keys
+        if setups.is_some() && cache.as_ref().map(KeyCache::setup_count) == setups {
+            result.timings.cache_load = setup_time;
+        } else {
+            result.timings.setup_or_cache = setup_time;
+        }
+        println!("Timings: {}", result.timings);
+        if let Some(dir) = &prove_args.out_dir {
+            save_proof_files(Path::new(dir), &witness_circuit, &vk, &result.proof, &result.public_inputs)
+                .map_err(|e| format!("Cannot write proof files to {}: {}", dir, e))?;
+            println!("Wrote proof files to {}", dir);
+        }
+        Ok(result.verified.unwrap_or(false))
+    });
+    match result {
+        Ok(valid) => {
+            println!("Verification Result: {}", valid);
+            valid
+        }
+        Err(e) => {
+            eprintln!("Proving failed: {}", e);
+            false
+        }
+    }
+}
+
+//verify subcommand: check a received proof against its public inputs, without the circuit
+fn verify_main(args: &[String]) {
+    let mut paths = Vec::new();
+    let mut schema = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--schema" => schema = iter.next().cloned(),
+            other if !other.starts_with("--") => paths.push(other.to_string()),
+            _ => paths.clear(),
+        }
+    }
+    let result = match paths.as_slice() {
+        [vk, proof, public_inputs] => {
+            verify_from_files(Path::new(vk), Path::new(proof), Path::new(public_inputs), schema.as_deref().map(Path::new))
+        }
+        [vk, envelope] if schema.is_none() => verify_envelope_file(Path::new(vk), Path::new(envelope)),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    match result {
+        Ok(valid) => {
+            println!("Verification Result: {}", valid);
+            if !valid {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+//Block forever, calling on_change whenever one of the paths is modified
+fn watch_loop(paths: &[String], mut on_change: impl FnMut()) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for path in paths {
+        watcher.watch(Path::new(path), RecursiveMode::NonRecursive)?;
+    }
+    println!("Watching {:?} for changes...", paths);
+
+    while let Ok(event) = rx.recv() {
+        if !matches!(event, Ok(ref e) if e.kind.is_modify() || e.kind.is_create() || e.kind.is_remove()) {
+            continue;
+        }
+        //Editors usually emit several events per save, drain them before re-running
+        while rx.recv_timeout(Duration::from_millis(200)).is_ok() {}
+        println!("Change detected, re-running...");
+        on_change();
+    }
+    Ok(())
+}
+
+//Renders progress events as a spinner on the terminal
+struct SpinnerProgress {
+    bar: ProgressBar,
+}
+
+impl SpinnerProgress {
+    fn new() -> Self {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(ProgressStyle::with_template("{spinner} [{elapsed}] {msg}").unwrap_or_else(|_| ProgressStyle::default_spinner()));
+        bar.enable_steady_tick(Duration::from_millis(100));
+        SpinnerProgress { bar }
+    }
+}
+
+impl ProgressSink for SpinnerProgress {
+    fn on_event(&self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::SetupStarted => self.bar.set_message("Running setup..."),
+            ProgressEvent::ProvingStarted => self.bar.set_message("Generating proof..."),
+            ProgressEvent::SynthesisStarted { num_constraints, num_variables } => {
+                self.bar.set_message(format!("Synthesizing {} constraints over {} variables...", num_constraints, num_variables))
+            }
+            ProgressEvent::SynthesisFinished { num_constraints, .. } => {
+                self.bar.set_message(format!("Synthesized {} constraints, running MSMs...", num_constraints))
+            }
+            ProgressEvent::SetupFinished | ProgressEvent::ProvingFinished => self.bar.finish_and_clear(),
+        }
+    }
+}
+
+//witness subcommand: compute the witness once and write it to a .wit file
+fn witness_main(args: &[String]) {
+    let mut circuit_path = None;
+    let mut inputs_path = None;
+    let mut out = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--inputs" => inputs_path = iter.next().cloned(),
+            "--out" => out = iter.next().cloned(),
+            other if circuit_path.is_none() && !other.starts_with("--") => circuit_path = Some(other.to_string()),
+            _ => circuit_path = None,
+        }
+    }
+    let (Some(circuit_path), Some(out)) = (circuit_path, out) else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let result = parse_circuit(&circuit_path)
+        .map_err(|e| format!("Failed to parse circuit: {}", e))
+        .and_then(|mut circuit| {
+            if let Some(path) = &inputs_path {
+                let inputs = parse_inputs(path).map_err(|e| format!("Failed to parse inputs: {}", e))?;
+                circuit.inputs.extend(inputs);
+            }
+            let r1cs = circuit.to_r1cs_system();
+            let witness = circuit.compute_witness(&r1cs.var_map)?;
+            save_witness(&out, &witness, circuit.canonical_hash()).map_err(|e| e.to_string())?;
+            Ok(witness.len())
+        });
+    match result {
+        Ok(count) => println!("Wrote {} witness values to {}", count, out),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+//inspect subcommand: our R1CS counts next to the ones of the synthesized arkworks system
+//Options of the inspect subcommand
+struct InspectArgs {
+    circuit_path: String,
+    policy: Option<String>,
+    //Exit with an error when underconstrained_report flags anything
+    strict_soundness: bool,
+}
+
+fn parse_inspect_args(args: &[String]) -> Result<InspectArgs, String> {
+    let mut circuit_path = None;
+    let mut policy = None;
+    let mut strict_soundness = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--policy" => policy = Some(iter.next().ok_or("--policy needs a file")?.clone()),
+            "--strict-soundness" => strict_soundness = true,
+            other if circuit_path.is_none() && !other.starts_with("--") => circuit_path = Some(other.to_string()),
+            other => return Err(format!("Unexpected argument: {}", other)),
+        }
+    }
+    Ok(InspectArgs { circuit_path: circuit_path.ok_or("Missing circuit file")?, policy, strict_soundness })
+}
+
+fn inspect_main(args: &InspectArgs) {
+    let path = args.circuit_path.as_str();
+    let circuit = match parse_circuit(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to parse circuit {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(policy_path) = &args.policy {
+        let violations = match GatePolicy::load(policy_path) {
+            Ok(policy) => policy.check(&circuit),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        for violation in &violations {
+            println!("POLICY {}", violation);
+        }
+        if !violations.is_empty() {
+            std::process::exit(1);
+        }
+    }
+    let r1cs = circuit.to_r1cs_system();
+    for warning in r1cs.privacy_report(&circuit) {
+        eprintln!("Warning: {}", warning);
+    }
+    let stats = match synthesize_stats(&r1cs) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Synthesis failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Circuit: {}", circuit.name);
+    println!("R1CSSystem:  {} constraints, {} variables, {} public inputs",
+        r1cs.raw_constraints.len(), r1cs.num_variables, r1cs.num_public_inputs);
+    println!("Transfer preamble: {} constraints", r1cs.constraint_origins.iter().filter(|origin| origin.is_transfer_preamble()).count());
+    println!("Synthesized: {} constraints, {} instance variables, {} witness variables",
+        stats.num_constraints, stats.num_instance_variables, stats.num_witness_variables);
+    let [a, b, c] = stats.densities();
+    println!("Non-zero entries: A {} ({:.4}), B {} ({:.4}), C {} ({:.4})",
+        stats.a_non_zero, a, stats.b_non_zero, b, stats.c_non_zero, c);
+
+    let density = r1cs.density();
+    println!("Density: {:.2} terms per row on average, longest row {} terms", density.avg_row_len, density.max_row_len);
+    println!("Proving cost: domain {}, MSM sizes G1 {} / G2 {}, estimate {} G1 scalar multiplications",
+        density.domain_size, density.msm_g1_size, density.msm_g2_size, density.estimated_cost());
+    for warning in density.warnings() {
+        println!("WARNING dense constraint system: {}", warning);
+    }
+
+    let (_, cse) = eliminate_common_subexpressions(&circuit);
+    let by_op: Vec<String> = cse.eliminated.iter().map(|(op, n)| format!("{} {}", op, n)).collect();
+    println!("Duplicate gates (CSE): {}{}", cse.total(),
+        if by_op.is_empty() { String::new() } else { format!(" ({})", by_op.join(", ")) });
+    println!("Duplicate rows dropped: {}", r1cs.duplicate_rows);
+
+    let underconstrained = r1cs.underconstrained_report();
+    for warning in &underconstrained {
+        match warning.row {
+            Some(row) => println!("UNDERCONSTRAINED {} ({})", warning, r1cs.explain(row)),
+            None => println!("UNDERCONSTRAINED {}", warning),
+        }
+    }
+
+    let mismatches = stats.mismatches(&r1cs);
+    for mismatch in &mismatches {
+        println!("MISMATCH {}", mismatch);
+    }
+    if !mismatches.is_empty() || (args.strict_soundness && !underconstrained.is_empty()) {
+        std::process::exit(1);
+    }
+}
+
+//Exits with an error when the circuit no longer compiles to the exported constraint system
+fn compat_main(old_export: &str, path: &str) {
+    let circuit = match parse_circuit(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to parse circuit {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let report = match std::fs::read_to_string(old_export).map_err(|e| e.to_string()).and_then(|json| compatibility::check(&json, &circuit)) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Failed to read R1CS export {}: {}", old_export, e);
+            std::process::exit(1);
+        }
+    };
+    println!("{}", report);
+    if !report.is_compatible() {
+        std::process::exit(1);
+    }
+}
+
+//Read DSL statements and : commands from stdin until :quit or end of input
+fn repl_main(args: &[String]) {
+    let cache_dir = match args {
+        [] => std::env::temp_dir().join("zk_repl_keys"),
+        [flag, dir] if flag == "--cache-dir" => dir.into(),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let mut repl = Repl::new(KeyCache::new(cache_dir));
+    let stdin = std::io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        let _ = std::io::stdout().flush();
+        line.clear();
+        match stdin.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) if line.trim() == ":quit" => break,
+            Ok(_) => match repl.eval(&line) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => println!("{}", output),
+                Err(e) => eprintln!("Error: {}", e),
+            },
+            Err(e) => {
+                eprintln!("Failed to read stdin: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn bench_main(path: &str) {
+    let start = Instant::now();
+    let circuit = match parse_circuit(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to parse circuit {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let parse_time = start.elapsed();
+    let start = Instant::now();
+    let validated = circuit.validate_gates();
+    let validate_time = start.elapsed();
+    if let Err(e) = validated {
+        eprintln!("Invalid circuit {}: {}", path, e);
+        std::process::exit(1);
+    }
+    let start = Instant::now();
+    let r1cs = circuit.to_r1cs_system();
+    let r1cs_time = start.elapsed();
+    let start = Instant::now();
+    let witness = circuit.compute_witness(&r1cs.var_map);
+    let witness_time = start.elapsed();
+    let report = witness.map_err(PipelineError::Witness).and_then(|witness| measure(&r1cs, Some(&witness), &mut OsRng));
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Benchmark failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let size = |s: SerializedSize| format!("{} bytes ({} uncompressed)", s.compressed, s.uncompressed);
+    println!("Circuit: {}", circuit.name);
+    println!("R1CSSystem: {} constraints, {} variables, {} public inputs",
+        report.stats.num_constraints, report.stats.num_variables, report.stats.num_public_inputs);
+    println!("Parse:    {:?}", parse_time);
+    println!("Validate: {:?}", validate_time);
+    println!("R1CS:     {:?}", r1cs_time);
+    println!("Witness:  {:?}", witness_time);
+    println!("Setup:    {:?}", report.setup_time);
+    println!("Prove:    {:?}", report.prove_time);
+    println!("Verify:   {:?} ({})", report.verify_time, if report.verified { "valid" } else { "INVALID" });
+    println!("Proving key:   {}", size(report.proving_key_size));
+    println!("Verifying key: {}", size(report.verifying_key_size));
+    println!("Proof:         {}", size(report.proof_size));
+}
+
+//Print the public inputs that reveal private values (privacy_report)
+fn warn_privacy_leaks(circuit: &Circuit) {
+    for warning in circuit.to_r1cs_system().privacy_report(circuit) {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+fn circuit_main(path: &str) {
     //parse the circuit to obtain circuit file name and create a circuit object
-    let path = &args[1];
     println!("Parsing circuit from: {}", path);
+    let start = Instant::now();
     let circuit: Circuit = parse_circuit(path).expect("Failed to parse circuit");
+    let parse_time = start.elapsed();
     println!("Parsed Circuit: {:?}", circuit.name);
+    warn_privacy_leaks(&circuit);
 
-    println!("Converting circuit to R1CS system...");
-    //convert to r1cs system
-    let r1cs = circuit.to_r1cs_system();
+    //convert to r1cs, generate keys, compute the witness, prove and verify
+    println!("Running proving pipeline (r1cs, setup, witness, prove, verify)...");
+    let progress = SpinnerProgress::new();
+    let options = PipelineOptions { progress: &progress, ..PipelineOptions::default() };
+    let mut result = run_pipeline(&circuit, options).expect("Proving pipeline failed");
+    result.timings.parse = parse_time;
+    println!("Timings: {}", result.timings);
     println!("Circuit parsed: {} ({} constraints, {} variables)", 
-        circuit.name, r1cs.raw_constraints.len(), r1cs.num_variables);
-    println!("Public input names (excluding implicit '1'): {:?}", r1cs.public_input_names);
-
-    println!("Generating Groth16 proving and verifying keys (setup)...");
-    //generate cryptographic keys using Groth16
-    let (pk, vk): (ProvingKey, VerifyingKey) = setup(&r1cs).expect("Failed to generate keys (setup)");
-    println!("Keys generated successfully.");
-
-    println!("Computing witness for the circuit instance...");
-    //Compute values satisfying the circuit
-    let witness_by_idx: HashMap<usize, Fr> = circuit.compute_witness(&r1cs.var_map)
-        .expect("Failed to compute witness");
-    println!("Witness computed with {} assignments.", witness_by_idx.len());
-
-    println!("Generating Groth16 proof...");
-    //Generate zero knowledge proof 
-    let proof = prove(&r1cs, &pk, witness_by_idx.clone())
-        .expect("Failed to generate proof");
-    println!("Proof generated: {:?}", proof); // Proof struct is a wrapper, debug might not be very informative
-
-    // Prepare public inputs for verification
-    // The order must be: Fr::one(), then values for each name in r1cs.public_input_names
-    let mut public_inputs_for_verification = Vec::with_capacity(r1cs.num_public_inputs);
-    public_inputs_for_verification.push(Fr::one()); // The first public input is always 1(constant)
-
-    for name in &r1cs.public_input_names { //iterate thru all public inputs
-        let original_idx = r1cs.var_map.get(name).unwrap_or_else(|| { //get the index of the public input
-            panic!("Public input name '{}' from r1cs.public_input_names not found in r1cs.var_map", name);
-        });
-        let value = witness_by_idx.get(original_idx).unwrap_or_else(|| { //get the value of the public input
-            panic!("Witness value for public input '{}' (original_idx {}) not found", name, original_idx);
-        });
-        public_inputs_for_verification.push(*value); //add the value to the vector
-    }
-    //check if the number of public inputs is correct
-    if public_inputs_for_verification.len() != r1cs.num_public_inputs {
-         panic!("Error: Expected {} public inputs for verification, but got {}. Names: {:?}, Values: {:?}",
-                r1cs.num_public_inputs, public_inputs_for_verification.len(), r1cs.public_input_names, public_inputs_for_verification);
-    }
+        circuit.name, result.stats.num_constraints, result.stats.num_variables);
+    println!("Proof generated: {:?}", result.proof); // Proof struct is a wrapper, debug might not be very informative
 
     //Verifies the proof and returns true if valid
-    println!("Verifying proof with public inputs: {:?}", public_inputs_for_verification);
-    let is_valid = verify(&vk, &proof, &public_inputs_for_verification)
-        .expect("Verification failed");
-
+    println!("Verified proof with public inputs: {:?}", result.public_inputs);
+    let is_valid = result.verified.unwrap_or(false);
     println!("Verification Result: {}", is_valid);
 
     if is_valid {
@@ -88,6 +605,3 @@ fn circuit_main() {
         println!("Proof is INVALID!");
     }
 }
-
-/* Code was wriiten mainly using other Groth16 implementation examples,and the documentation of the zk_framework crate. */
-/* Debugging was done mainly by checking the logs and the values of the variables. */
@@ -0,0 +1,183 @@
+//Raw A, B and C matrices of an R1CS system in CSR form, row i is constraint i and column j the
+//var_map index j (column 0 is the constant one)
+use std::collections::HashMap;
+
+use ark_bls12_381::Fr;
+
+use crate::{R1CSSystem, _R1CSConstraintInternal};
+
+//Compressed sparse row matrix: the entries of row i are col_idx/values[row_ptr[i]..row_ptr[i + 1]]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseMatrix {
+    pub row_ptr: Vec<usize>,
+    pub col_idx: Vec<usize>,
+    pub values: Vec<Fr>,
+    num_cols: usize,
+}
+
+impl SparseMatrix {
+    //CSR form of the given rows, columns sorted within each row
+    fn from_rows<'a>(rows: impl Iterator<Item = &'a HashMap<usize, Fr>>, num_cols: usize) -> Self {
+        let mut matrix = SparseMatrix { row_ptr: vec![0], col_idx: Vec::new(), values: Vec::new(), num_cols };
+        for row in rows {
+            let mut entries: Vec<(usize, Fr)> = row.iter().map(|(col, value)| (*col, *value)).collect();
+            entries.sort_unstable_by_key(|(col, _)| *col);
+            for (col, value) in entries {
+                matrix.col_idx.push(col);
+                matrix.values.push(value);
+            }
+            matrix.row_ptr.push(matrix.col_idx.len());
+        }
+        matrix
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.row_ptr.len() - 1
+    }
+
+    pub fn num_cols(&self) -> usize {
+        self.num_cols
+    }
+
+    //Number of stored entries
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    //(column, value) entries of one row, in column order
+    pub fn row(&self, i: usize) -> impl Iterator<Item = (usize, &Fr)> {
+        let range = self.row_ptr[i]..self.row_ptr[i + 1];
+        self.col_idx[range.clone()].iter().copied().zip(&self.values[range])
+    }
+
+    //Structural checks from_matrices relies on
+    fn check(&self, label: &str) -> Result<(), String> {
+        let monotonic = self.row_ptr.first() == Some(&0) && self.row_ptr.windows(2).all(|w| w[0] <= w[1]);
+        if !monotonic || self.row_ptr.last() != Some(&self.col_idx.len()) || self.col_idx.len() != self.values.len() {
+            return Err(format!("Matrix {} is not valid CSR", label));
+        }
+        if let Some(col) = self.col_idx.iter().find(|col| **col >= self.num_cols) {
+            return Err(format!("Matrix {} has column {} but only {} columns", label, col, self.num_cols));
+        }
+        Ok(())
+    }
+}
+
+impl R1CSSystem {
+    //The A, B and C matrices, num_constraints rows by num_variables columns each
+    pub fn to_matrices(&self) -> (SparseMatrix, SparseMatrix, SparseMatrix) {
+        let rows = &self.raw_constraints;
+        (
+            SparseMatrix::from_rows(rows.iter().map(|row| &row.a), self.num_variables),
+            SparseMatrix::from_rows(rows.iter().map(|row| &row.b), self.num_variables),
+            SparseMatrix::from_rows(rows.iter().map(|row| &row.c), self.num_variables),
+        )
+    }
+
+    //Name of every variable, by index (the var_names from_matrices takes)
+    pub fn var_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.num_variables];
+        for (name, idx) in &self.var_map {
+            names[*idx] = name.clone();
+        }
+        names
+    }
+
+    //A system built from external matrices. var_names names every column and must start with
+    //the constant "1"; public_input_names are the columns verify() takes, in order. Like JSON
+    //imports, the system has no constraint origins
+    pub fn from_matrices(
+        (a, b, c): (SparseMatrix, SparseMatrix, SparseMatrix),
+        var_names: Vec<String>,
+        public_input_names: Vec<String>,
+    ) -> Result<R1CSSystem, String> {
+        for (label, matrix) in [("A", &a), ("B", &b), ("C", &c)] {
+            matrix.check(label)?;
+            if matrix.num_cols != var_names.len() {
+                return Err(format!("Matrix {} has {} columns for {} variable names", label, matrix.num_cols, var_names.len()));
+            }
+            if matrix.num_rows() != a.num_rows() {
+                return Err(format!("Matrix {} has {} rows, A has {}", label, matrix.num_rows(), a.num_rows()));
+            }
+        }
+        if var_names.first().map(String::as_str) != Some("1") {
+            return Err("The first variable must be the constant \"1\"".to_string());
+        }
+        let mut var_map = HashMap::with_capacity(var_names.len());
+        for (idx, name) in var_names.iter().enumerate() {
+            if var_map.insert(name.clone(), idx).is_some() {
+                return Err(format!("Variable name '{}' is used twice", name));
+            }
+        }
+        if let Some(unknown) = public_input_names.iter().find(|name| !var_map.contains_key(*name) || *name == "1") {
+            return Err(format!("Public input '{}' is not a variable", unknown));
+        }
+        let to_lc = |matrix: &SparseMatrix, i: usize| matrix.row(i).map(|(col, value)| (col, *value)).collect();
+        let raw_constraints = (0..a.num_rows())
+            .map(|i| _R1CSConstraintInternal { a: to_lc(&a, i), b: to_lc(&b, i), c: to_lc(&c, i) })
+            .collect();
+        Ok(R1CSSystem {
+            raw_constraints,
+            constraint_origins: Vec::new(),
+            num_variables: var_names.len(),
+            num_public_inputs: 1 + public_input_names.len(),
+            var_map,
+            public_input_names,
+            public_input_sources: Vec::new(),
+            duplicate_rows: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_circuit_str, prove, setup, verify};
+
+    #[test]
+    fn test_matrices_round_trip() {
+        let circuit = parse_circuit_str("name matrices
+input alice 50
+input x 3
+public y 27
+amount 5
+mul x x sq
+mul sq x cube
+eq cube y
+").unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        let (a, b, c) = r1cs.to_matrices();
+        for matrix in [&a, &b, &c] {
+            assert_eq!((matrix.num_rows(), matrix.num_cols()), (r1cs.raw_constraints.len(), r1cs.num_variables));
+        }
+        //Row of mul x x sq: x in A and B, sq in C
+        let mul_row = r1cs.constraint_origins.iter().position(|origin| origin.gate_index == Some(0)).unwrap();
+        assert_eq!(a.row(mul_row).collect::<Vec<_>>(), vec![(r1cs.var_map["x"], &Fr::from(1u64))]);
+        assert_eq!(c.row(mul_row).map(|(col, _)| col).collect::<Vec<_>>(), vec![r1cs.var_map["sq"]]);
+        let density = r1cs.density();
+        assert_eq!((a.nnz(), b.nnz(), c.nnz()), (density.a_non_zero, density.b_non_zero, density.c_non_zero));
+
+        let rebuilt = R1CSSystem::from_matrices(r1cs.to_matrices(), r1cs.var_names(), r1cs.public_input_names.clone()).unwrap();
+        assert_eq!(rebuilt.to_matrices(), (a.clone(), b.clone(), c.clone()));
+        assert_eq!(rebuilt.var_map, r1cs.var_map);
+        assert_eq!(rebuilt.num_public_inputs, r1cs.num_public_inputs);
+        let witness = circuit.compute_witness(&rebuilt.var_map).unwrap();
+        assert_eq!(rebuilt.which_is_unsatisfied(&witness).unwrap(), None);
+        let (pk, vk) = setup(&rebuilt).expect("Setup failed");
+        let public_inputs = rebuilt.public_inputs_from_witness(&witness).unwrap();
+        let proof = prove(&rebuilt, &pk, witness).expect("Proving failed");
+        assert!(verify(&vk, &proof, &public_inputs).unwrap());
+
+        //Shape errors are reported, not panics
+        let names = r1cs.var_names();
+        let publics = r1cs.public_input_names.clone();
+        let mut short = a.clone();
+        short.row_ptr.pop();
+        let err = R1CSSystem::from_matrices((short, b.clone(), c.clone()), names.clone(), publics.clone()).unwrap_err();
+        assert!(err.contains("not valid CSR"), "{}", err);
+        let err = R1CSSystem::from_matrices((a.clone(), b.clone(), c.clone()), names[..names.len() - 1].to_vec(), publics.clone()).unwrap_err();
+        assert!(err.contains("variable names"), "{}", err);
+        let err = R1CSSystem::from_matrices((a, b, c), names, vec!["carol".to_string()]).unwrap_err();
+        assert!(err.contains("'carol' is not a variable"), "{}", err);
+    }
+}
@@ -0,0 +1,87 @@
+//Nullifiers, poseidon_hash([domain, secret, nonce]), shared by native code and the `nullifier` gate
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use ark_bls12_381::Fr;
+use ark_ff::One;
+
+use crate::poseidon::{self, emit_sponge_rows, hash_bytes, poseidon_hash, poseidon_witness};
+use crate::_R1CSConstraintInternal;
+
+pub const NULLIFIER_TAG: &[u8] = b"zk-framework/nullifier/v1";
+
+//First sponge input of every nullifier
+pub fn domain() -> Fr {
+    static DOMAIN: OnceLock<Fr> = OnceLock::new();
+    *DOMAIN.get_or_init(|| hash_bytes(NULLIFIER_TAG))
+}
+
+//Nullifier of a secret for a leaf index or nonce, the value a nullifier gate assigns
+pub fn derive(secret: Fr, nonce: Fr) -> Fr {
+    poseidon_hash(&[domain(), secret, nonce])
+}
+
+//Constraints of one nullifier gate (two permutations)
+pub fn num_constraints() -> usize {
+    poseidon::num_constraints(3)
+}
+
+//derive with the values of the auxiliary wires emit_nullifier_rows allocates under prefix
+pub(crate) fn nullifier_witness(secret: Fr, nonce: Fr, prefix: &str) -> (Fr, Vec<(String, Fr)>) {
+    poseidon_witness(&[domain(), secret, nonce], prefix)
+}
+
+//Emit the rows constraining output = derive(secret, nonce), the domain being a constant term
+pub(crate) fn emit_nullifier_rows(
+    secret: usize,
+    nonce: usize,
+    output: usize,
+    prefix: &str,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    let input_lcs = [
+        HashMap::from([(var_map["1"], domain())]),
+        HashMap::from([(secret, Fr::one())]),
+        HashMap::from([(nonce, Fr::one())]),
+    ];
+    emit_sponge_rows(&input_lcs, output, prefix, var_map, next_index, constraints);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fr_codec::fr_to_hex, i32_to_fr, parse_circuit_str, run_pipeline, PipelineOptions};
+
+    #[test]
+    fn test_native_nullifier_matches_the_gate() {
+        let expected = derive(Fr::from(123456789u64), Fr::from(3u64));
+        assert_ne!(expected, poseidon_hash(&[Fr::from(123456789u64), Fr::from(3u64)]));
+        assert_ne!(expected, derive(Fr::from(123456789u64), Fr::from(4u64)));
+
+        //A transfer publishing the nullifier of its private secret
+        let source = format!(
+            "name spend\ninput alice 100\ninput secret 123456789\ninput nonce 3\nsender alice\nreceiver bob\namount 30\n\
+             balances private\npublic nf 0x{}\nnullifier secret nonce derived\neq derived nf\n",
+            fr_to_hex(&expected)
+        );
+        let circuit = parse_circuit_str(&source).unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        let rows = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(0)).count();
+        assert_eq!(rows, num_constraints());
+        let options = PipelineOptions { return_keys: true, ..PipelineOptions::default() };
+        let result = run_pipeline(&circuit, options).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+        assert_eq!(result.public_inputs, vec![i32_to_fr(30), expected, circuit.input_digest()]);
+
+        //Another nonce derives another nullifier, which the public one rejects
+        let mut reused = circuit.clone();
+        reused.inputs.insert("nonce".to_string(), 4);
+        assert!(reused.compute_witness(&r1cs.var_map).unwrap_err().contains("Assertion failed: wire 'derived'"));
+        let (_, vk) = result.keys.expect("Keys were requested");
+        let mut other = result.public_inputs.clone();
+        other[1] = derive(Fr::from(123456789u64), Fr::from(4u64));
+        assert!(!crate::verify(&vk, &result.proof, &other).unwrap());
+    }
+}
@@ -0,0 +1,257 @@
+//Circuit-level optimizer passes, run on the gate list before R1CS conversion
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{Circuit, Gate};
+
+//What a common subexpression elimination run removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CseReport {
+    //Eliminated gates per operation ("add", "mul", ...)
+    pub eliminated: BTreeMap<&'static str, usize>,
+    //Outputs of eliminated gates and the wire that replaced them
+    pub rewired: Vec<(String, String)>,
+}
+
+impl CseReport {
+    pub fn total(&self) -> usize {
+        self.eliminated.values().sum()
+    }
+}
+
+//Gate with its output removed and commutative operands sorted; two gates with the same key
+//compute the same value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum GateKey {
+    Add(String, String, Option<i32>),
+    Mul(String, String, Option<i32>),
+    Sub(String, String, Option<i32>),
+    Xor(String, String),
+    Const(i64),
+    Hash(String),
+    Clamp(String, i64),
+    PowMod(String, u64, u64),
+    Nullifier(String, String),
+    //Sponge inputs keep their order
+    HashN(Vec<String>),
+    DotProduct(Vec<(String, String)>),
+}
+
+fn sorted(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+//Canonical key and output wire of a gate; Eq, Div and the assertions check a side condition and are
+//never merged, nor are the multi-output integer, decode and condswap gates, byte hashes and
+//disclosures (their output is a public input)
+fn canonicalize(gate: &Gate) -> Option<(GateKey, &String)> {
+    match gate {
+        Gate::Add(a, b, c, m) => {
+            let (a, b) = sorted(a, b);
+            Some((GateKey::Add(a, b, *m), c))
+        }
+        Gate::Mul(a, b, c, m) => {
+            let (a, b) = sorted(a, b);
+            Some((GateKey::Mul(a, b, *m), c))
+        }
+        Gate::Sub(a, b, c, m) => Some((GateKey::Sub(a.clone(), b.clone(), *m), c)),
+        Gate::Xor(a, b, c) => {
+            let (a, b) = sorted(a, b);
+            Some((GateKey::Xor(a, b), c))
+        }
+        Gate::Const(name, val) => Some((GateKey::Const(*val), name)),
+        Gate::Hash(input, output) => Some((GateKey::Hash(input.clone()), output)),
+        Gate::Clamp(x, cap, out) => Some((GateKey::Clamp(x.clone(), *cap), out)),
+        Gate::PowMod(base, e, n, out) => Some((GateKey::PowMod(base.clone(), *e, *n), out)),
+        Gate::Nullifier(secret, nonce, out) => Some((GateKey::Nullifier(secret.clone(), nonce.clone()), out)),
+        Gate::HashN(inputs, output) => Some((GateKey::HashN(inputs.clone()), output)),
+        Gate::DotProduct(pairs, out) => Some((GateKey::DotProduct(pairs.iter().map(|(w, x)| sorted(w, x)).collect()), out)),
+        Gate::Eq(..) | Gate::Div(..) | Gate::AssertNonZero(_) | Gate::AssertBool(_) | Gate::AssertEq(..) | Gate::UInt(..) | Gate::HashBytes(..) | Gate::Decode(..) | Gate::CondSwap(..) | Gate::RevealIf(..) | Gate::AssertLeConst(..) => None,
+    }
+}
+
+//Rewrite the operands of a gate through the rename map, outputs are left alone
+fn rename_operands(gate: &Gate, renames: &HashMap<String, String>) -> Gate {
+    let r = |name: &String| renames.get(name).unwrap_or(name).clone();
+    match gate {
+        Gate::Add(a, b, c, m) => Gate::Add(r(a), r(b), c.clone(), *m),
+        Gate::Mul(a, b, c, m) => Gate::Mul(r(a), r(b), c.clone(), *m),
+        Gate::Sub(a, b, c, m) => Gate::Sub(r(a), r(b), c.clone(), *m),
+        Gate::Div(a, b, c, m) => Gate::Div(r(a), r(b), c.clone(), *m),
+        Gate::Xor(a, b, c) => Gate::Xor(r(a), r(b), c.clone()),
+        Gate::Const(name, val) => Gate::Const(name.clone(), *val),
+        Gate::Hash(input, output) => Gate::Hash(r(input), output.clone()),
+        Gate::Eq(a, b, out) => Gate::Eq(r(a), r(b), out.clone()),
+        Gate::AssertNonZero(x) => Gate::AssertNonZero(r(x)),
+        Gate::AssertBool(b) => Gate::AssertBool(r(b)),
+        Gate::AssertEq(a, b) => Gate::AssertEq(r(a), r(b)),
+        Gate::AssertLeConst(x, limit) => Gate::AssertLeConst(r(x), *limit),
+        Gate::Clamp(x, cap, out) => Gate::Clamp(r(x), *cap, out.clone()),
+        Gate::PowMod(base, e, n, out) => Gate::PowMod(r(base), *e, *n, out.clone()),
+        Gate::CondSwap(bit, x, y, left, right) => Gate::CondSwap(r(bit), r(x), r(y), left.clone(), right.clone()),
+        Gate::RevealIf(flag, value, disclosed) => Gate::RevealIf(r(flag), r(value), disclosed.clone()),
+        Gate::Nullifier(secret, nonce, out) => Gate::Nullifier(r(secret), r(nonce), out.clone()),
+        Gate::Decode(index, outs) => Gate::Decode(r(index), outs.clone()),
+        Gate::HashN(inputs, output) => Gate::HashN(inputs.iter().map(r).collect(), output.clone()),
+        Gate::DotProduct(pairs, out) => Gate::DotProduct(pairs.iter().map(|(w, x)| (r(w), r(x))).collect(), out.clone()),
+        Gate::UInt(op, bits, a, b, low, high) => Gate::UInt(*op, *bits, r(a), r(b), low.clone(), high.clone()),
+        Gate::HashBytes(bytes, output) => Gate::HashBytes(bytes.iter().map(r).collect(), output.clone()),
+    }
+}
+
+//Common subexpression elimination: when two gates compute the same value, later references
+//to the duplicate's output are rewired to the first output and the duplicate is dropped.
+//Wires written more than once, inputs, declared outputs and typed (range checked) wires are
+//never merged away, since the name carries meaning beyond the single gate.
+pub fn eliminate_common_subexpressions(circuit: &Circuit) -> (Circuit, CseReport) {
+    let mut writes: HashMap<&String, usize> = HashMap::new();
+    for output in circuit.gates.iter().flat_map(Gate::outputs) {
+        *writes.entry(output).or_insert(0) += 1;
+    }
+    let mergeable = |wire: &String| {
+        writes.get(wire) == Some(&1)
+            && !circuit.inputs.contains_key(wire)
+            && !circuit.outputs.contains_key(wire)
+            && !circuit.wire_types.contains_key(wire)
+    };
+
+    let mut report = CseReport::default();
+    let mut renames: HashMap<String, String> = HashMap::new();
+    let mut first_output: HashMap<GateKey, String> = HashMap::new();
+    let mut gates = Vec::with_capacity(circuit.gates.len());
+    for gate in &circuit.gates {
+        let gate = rename_operands(gate, &renames);
+        if let Some((key, output)) = canonicalize(&gate) {
+            if mergeable(output) {
+                match first_output.get(&key) {
+                    Some(first) => {
+                        *report.eliminated.entry(gate.kind()).or_insert(0) += 1;
+                        report.rewired.push((output.clone(), first.clone()));
+                        renames.insert(output.clone(), first.clone());
+                        continue;
+                    }
+                    None => {
+                        first_output.insert(key, output.clone());
+                    }
+                }
+            }
+        }
+        gates.push(gate);
+    }
+
+    (Circuit { gates, ..circuit.clone() }, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check_equivalent;
+    use crate::testing::{assert_roundtrip, random_circuit, CircuitConfig};
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn circuit(gates: Vec<Gate>) -> Circuit {
+        Circuit {
+            name: "cse".to_string(),
+            inputs: HashMap::from([("x".to_string(), 3), ("y".to_string(), 4)]),
+            gates,
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        }
+    }
+
+    //Both circuits verify and agree on every wire that survives optimization, for the
+    //circuit's own inputs and for random ones
+    fn assert_equivalent(original: &Circuit, optimized: &Circuit) {
+        if let Err(counterexample) = check_equivalent(original, optimized, 16, &mut StdRng::seed_from_u64(0)) {
+            panic!("CSE changed the circuit: {}", counterexample);
+        }
+        let (r1cs, opt_r1cs) = (original.to_r1cs_system(), optimized.to_r1cs_system());
+        let witness = original.compute_witness(&r1cs.var_map).expect("Original witness failed");
+        let opt_witness = optimized.compute_witness(&opt_r1cs.var_map).expect("Optimized witness failed");
+        for (name, idx) in &opt_r1cs.var_map {
+            if let Some(orig_idx) = r1cs.var_map.get(name) {
+                assert_eq!(witness[orig_idx], opt_witness[idx], "Wire '{}' differs after CSE", name);
+            }
+        }
+        assert_roundtrip(original);
+        assert_roundtrip(optimized);
+    }
+
+    #[test]
+    fn test_duplicates_are_merged() {
+        let original = circuit(vec![
+            Gate::Mul("x".to_string(), "x".to_string(), "sq".to_string(), None),
+            Gate::Mul("x".to_string(), "x".to_string(), "sq2".to_string(), None),
+            Gate::Add("x".to_string(), "y".to_string(), "s".to_string(), None),
+            Gate::Add("y".to_string(), "x".to_string(), "t".to_string(), None),
+            Gate::Sub("sq2".to_string(), "t".to_string(), "d".to_string(), None),
+            Gate::Sub("sq".to_string(), "s".to_string(), "e".to_string(), None),
+            //Eq keeps its own row even though it repeats
+            Gate::Eq("d".to_string(), "e".to_string(), "z".to_string()),
+            Gate::Eq("d".to_string(), "e".to_string(), "z2".to_string()),
+        ]);
+        let (optimized, report) = eliminate_common_subexpressions(&original);
+        assert_eq!(report.total(), 3);
+        assert_eq!(report.eliminated, BTreeMap::from([("add", 1), ("mul", 1), ("sub", 1)]));
+        assert_eq!(report.rewired[0], ("sq2".to_string(), "sq".to_string()));
+        assert_eq!(optimized.gates.len(), 5);
+        assert_equivalent(&original, &optimized);
+    }
+
+    #[test]
+    fn test_sub_is_not_commutative() {
+        let original = circuit(vec![
+            Gate::Sub("x".to_string(), "y".to_string(), "a".to_string(), None),
+            Gate::Sub("y".to_string(), "x".to_string(), "b".to_string(), None),
+        ]);
+        let (optimized, report) = eliminate_common_subexpressions(&original);
+        assert_eq!(report.total(), 0);
+        assert_eq!(optimized.gates.len(), 2);
+    }
+
+    //Random circuit with copies of some gates (fresh outputs) that later gates also read
+    fn with_duplicates<R: Rng>(rng: &mut R, circuit: &Circuit) -> Circuit {
+        let mut gates = Vec::new();
+        for (i, gate) in circuit.gates.iter().enumerate() {
+            gates.push(gate.clone());
+            let Some(output) = gate.outputs().first().copied() else { continue };
+            if matches!(gate, Gate::Eq(..)) || !rng.gen_bool(0.5) {
+                continue;
+            }
+            let dup = format!("dup{}", i);
+            let copy = match gate {
+                Gate::Add(a, b, _, m) => Gate::Add(b.clone(), a.clone(), dup.clone(), *m),
+                Gate::Mul(a, b, _, m) => Gate::Mul(b.clone(), a.clone(), dup.clone(), *m),
+                Gate::Sub(a, b, _, m) => Gate::Sub(a.clone(), b.clone(), dup.clone(), *m),
+                Gate::Xor(a, b, _) => Gate::Xor(b.clone(), a.clone(), dup.clone()),
+                Gate::Const(_, val) => Gate::Const(dup.clone(), *val),
+                Gate::Hash(input, _) => Gate::Hash(input.clone(), dup.clone()),
+                Gate::Eq(..) | Gate::AssertNonZero(_) | Gate::AssertBool(_) | Gate::AssertEq(..) | Gate::UInt(..) | Gate::HashBytes(..) | Gate::Clamp(..) | Gate::Nullifier(..) | Gate::Decode(..) | Gate::HashN(..) | Gate::PowMod(..) | Gate::CondSwap(..) | Gate::DotProduct(..) | Gate::RevealIf(..) | Gate::AssertLeConst(..) | Gate::Div(..) => unreachable!(),
+            };
+            gates.push(copy);
+            gates.push(Gate::Add(dup.clone(), output.clone(), format!("{}_use", dup), None));
+        }
+        Circuit { gates, ..circuit.clone() }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(4))]
+
+        #[test]
+        fn prop_cse_preserves_witness(seed in any::<u64>()) {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let base = random_circuit(&mut rng, &CircuitConfig::default());
+            let original = with_duplicates(&mut rng, &base);
+            let (optimized, report) = eliminate_common_subexpressions(&original);
+            prop_assert_eq!(optimized.gates.len() + report.total(), original.gates.len());
+            assert_equivalent(&original, &optimized);
+        }
+    }
+}
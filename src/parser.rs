@@ -1,20 +1,65 @@
 use std::fs; //for reading the file
 use std::collections::HashMap; //for storing inputs and outputs
-use crate::{Gate, Circuit}; 
+use crate::{Gate, Circuit, CircuitError, TransferOptions, WireType}; 
+use crate::fr_codec::{parse_field_literal, FieldLiteralPolicy};
+use crate::gates::parse_gate;
+use crate::types::parse_typed_name;
+
+pub(crate) fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+//Wire holding byte i of a byte-string input
+pub fn byte_wire(input: &str, i: usize) -> String {
+    format!("{}_byte{}", input, i)
+}
+
+//What gate parsers need from the lines before theirs
+pub struct ParseContext {
+    //Length of each byte-string input, for hash_bytes
+    pub(crate) byte_inputs: HashMap<String, usize>,
+    pub(crate) wire_types: HashMap<String, WireType>,
+}
+
+impl ParseContext {
+    //Name of an optional `name:type` annotation, recording the type
+    pub(crate) fn typed_name(&mut self, decl: &str) -> Result<String, std::io::Error> {
+        let (name, ty) = parse_typed_name(decl).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if let Some(ty) = ty {
+            self.wire_types.insert(name.to_string(), ty);
+        }
+        Ok(name.to_string())
+    }
+}
 
 pub fn parse_circuit(file_path: &str) -> Result<Circuit, std::io::Error> {
     // Open the file and wrap it with a buf reader
-    let content = fs::read_to_string(file_path).expect("Cannot read circuit file");
+    let content = fs::read_to_string(file_path)?;
+    parse_circuit_str(&content)
+}
 
+//Parse circuit source text; wire types are checked once the whole circuit is read
+pub fn parse_circuit_str(content: &str) -> Result<Circuit, std::io::Error> {
+    parse_circuit_str_with_policy(content, FieldLiteralPolicy::Strict)
+}
+
+//parse_circuit_str with a policy for field literals at or above the modulus (`public` lines)
+pub fn parse_circuit_str_with_policy(content: &str, policy: FieldLiteralPolicy) -> Result<Circuit, std::io::Error> {
     // Storing circuit parts
     let mut circuit_name_from_file = String::new();
     let mut inputs = HashMap::new();
     let mut outputs = HashMap::new();
     let mut gates = Vec::new();
+    // Optional `name:type` annotations of input, output and const lines, and byte-string inputs
+    let mut ctx = ParseContext { byte_inputs: HashMap::new(), wire_types: HashMap::new() };
 
     let mut sender = String::new();
     let mut receiver = String::new();
     let mut transfer_amount = 0;
+    let mut public_inputs = Vec::new();
+    let mut private_balances = false;
+    let mut bind_inputs = false;
+    let mut transfer_options = TransferOptions::default();
 
     // Parsing line by line
     for line in content.lines() {
@@ -28,13 +73,27 @@ pub fn parse_circuit(file_path: &str) -> Result<Circuit, std::io::Error> {
             ["name", cn ] => {
                 circuit_name_from_file = cn.to_string();
             }
+            //Byte-string input, one u8 wire per byte
+            ["input", var, "bytes", hex_str] => {
+                let bytes = hex::decode(hex_str.trim_matches('"'))
+                    .map_err(|e| invalid_data(format!("Invalid byte string for '{}': {}", var, e)))?;
+                for (i, byte) in bytes.iter().enumerate() {
+                    inputs.insert(ctx.typed_name(&format!("{}:u8", byte_wire(var, i)))?, *byte as i32);
+                }
+                ctx.byte_inputs.insert(var.to_string(), bytes.len());
+            }
             ["input", var, val] => {
-                let value = val.parse::<i32>().expect("Inavlid input value");
-                inputs.insert(var.to_string(), value);
+                let value = val.parse::<i32>().map_err(|_| invalid_data(format!("Invalid input value: {}", line)))?;
+                inputs.insert(ctx.typed_name(var)?, value);
             }
             ["output", var, val] => {
-                let value = val.parse::<i32>().expect("Invalid output value");
-                outputs.insert(var.to_string(), value);
+                let value = val.parse::<i32>().map_err(|_| invalid_data(format!("Invalid output value: {}", line)))?;
+                outputs.insert(ctx.typed_name(var)?, value);
+            }
+            //Field-valued public input, 0x hex (fr_to_hex layout) or decimal
+            ["public", var, val] => {
+                let value = parse_field_literal(val, policy).map_err(|e| invalid_data(format!("Public input '{}': {}", var, e)))?;
+                public_inputs.push((var.to_string(), value));
             }
             ["sender", s] => {
                 sender = s.to_string();
@@ -42,38 +101,40 @@ pub fn parse_circuit(file_path: &str) -> Result<Circuit, std::io::Error> {
             ["receiver", r] => {
                 receiver = r.to_string();
             }
-            ["amount", amt] => {
-                transfer_amount = amt.parse::<i32>().expect("Invalid transfer amount");
-            }
-            ["add", a, b, c] => {
-                gates.push(Gate::Add(a.to_string(), b.to_string(), c.to_string(), None));
-            }
-            ["mul", a, b, c] => {
-                gates.push(Gate::Mul(a.to_string(), b.to_string(), c.to_string(), None));
-            }
-            ["sub", a, b, c] => {
-                gates.push(Gate::Sub(a.to_string(), b.to_string(), c.to_string(), None));
-            }
-            ["eq", a, b, out] => {
-                gates.push(Gate::Eq(a.to_string(), b.to_string(), out.to_string()));
+            //Prove the sender's balance covers the amount instead of publishing the balances. The
+            //input digest is what commits the proof to the balance, so this binds the inputs too
+            ["balances", "private"] => {
+                private_balances = true;
+                bind_inputs = true;
             }
-            ["const", name, val] => {
-                let value = val.parse::<i32>().expect("Invalid constant value");
-                gates.push(Gate::Const(name.to_string(), value));
+            //Commit to every input with one public digest
+            ["inputs", "bound"] => {
+                bind_inputs = true;
             }
-            ["xor", a, b, c] => {
-                gates.push(Gate::Xor(a.to_string(), b.to_string(), c.to_string()));
+            //Keep transfer spec values out of the public inputs
+            ["transfer_options", options @ ..] if !options.is_empty() => {
+                for option in options {
+                    match *option {
+                        "hide_sender_balance" => transfer_options.expose_sender_balance = false,
+                        "hide_receiver_balance" => transfer_options.expose_receiver_balance = false,
+                        "hide_amount" => transfer_options.expose_amount = false,
+                        other => return Err(invalid_data(format!("Unknown transfer option '{}': expected hide_sender_balance, hide_receiver_balance or hide_amount", other))),
+                    }
+                }
             }
-            ["hash", input, output] => {
-                gates.push(Gate::Hash(input.to_string(), output.to_string()));
-            }
-            _ => {
-                panic!("Unknown or malformed line: {}", line);
+            ["amount", amt] => {
+                transfer_amount = amt.parse::<u64>()
+                    .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, CircuitError::InvalidAmount { amount: amt.to_string() }))?;
             }
+            //Every other line is a gate
+            parts => match parse_gate(line, parts, &mut ctx) {
+                Some(gate) => gates.push(gate?),
+                None => return Err(invalid_data(format!("Unknown or malformed line: {}", line))),
+            },
         }
     }
 
-    Ok(Circuit {
+    let mut circuit = Circuit {
         name: circuit_name_from_file,
         inputs,
         outputs,
@@ -81,5 +142,152 @@ pub fn parse_circuit(file_path: &str) -> Result<Circuit, std::io::Error> {
         sender,
         receiver,
         transfer_amount,
-    })
+        wire_types: ctx.wire_types,
+        public_inputs,
+        private_balances,
+        bind_inputs,
+        transfer_options,
+    };
+    //Resolve well-known names once the sender and receiver lines have been read
+    let mut gates = std::mem::take(&mut circuit.gates);
+    for wire in gates.iter_mut().flat_map(Gate::wires_mut).filter(|wire| wire.starts_with('$')) {
+        *wire = circuit.well_known_wire(wire).ok_or_else(|| {
+            invalid_data(format!("Unknown wire name '{}': expected $one, $transfer_amount, or the $sender_balance or $receiver_balance of a party that is an input", wire))
+        })?;
+    }
+    circuit.gates = gates;
+    circuit.validate_gates().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    circuit.type_check().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    Ok(circuit)
+}
+
+//Parse an inputs file (one `input <name> <value>` line per wire) used to override circuit inputs
+pub fn parse_inputs(file_path: &str) -> Result<HashMap<String, i32>, std::io::Error> {
+    let content = fs::read_to_string(file_path)?;
+    let mut inputs = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["input", var, val] => {
+                let value = val.parse::<i32>().map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid input value: {}", line))
+                })?;
+                inputs.insert(var.to_string(), value);
+            }
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Unknown or malformed line: {}", line),
+                ));
+            }
+        }
+    }
+
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prove, setup, verify, PublicInputSource};
+
+    #[test]
+    fn test_well_known_wires_in_expressions() {
+        //The gates come before the sender line, resolution waits for the whole file
+        let circuit = parse_circuit_str(
+            "name fee
+input x 41
+add x $one x_plus_one
+mul x_plus_one $transfer_amount total
+sub $sender_balance total left
+sender alice
+receiver bob
+input alice 1000
+amount 5
+",
+        )
+        .unwrap();
+        assert!(matches!(&circuit.gates[0], Gate::Add(a, one, _, None) if a == "x" && one == "1"));
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        assert_eq!(witness[&r1cs.var_map["total"]], crate::i32_to_fr(210));
+        assert_eq!(witness[&r1cs.var_map["left"]], crate::i32_to_fr(790));
+        let schema = r1cs.public_input_schema();
+        assert_eq!(schema[1].source.dsl_name(), Some("$transfer_amount"));
+        assert!(schema[1].description.contains("$transfer_amount"));
+
+        let (pk, vk) = setup(&r1cs).expect("Setup failed");
+        let public_inputs = r1cs.public_inputs_from_witness(&witness).unwrap();
+        let proof = prove(&r1cs, &pk, witness).expect("Proving failed");
+        assert!(verify(&vk, &proof, &public_inputs).unwrap());
+        assert_eq!(schema[0].source, PublicInputSource::SenderBalance);
+
+        //Unknown names, balances of a party that is not an input, and redefinitions are rejected
+        for source in [
+            "input x 1\nadd x $two y\n",
+            "input x 1\nsender alice\nadd x $sender_balance y\n",
+            "input transfer_amount_public 5\n",
+            "input x 1\nadd x x $one\n",
+            "public $one 1\n",
+        ] {
+            assert_eq!(parse_circuit_str(source).unwrap_err().kind(), std::io::ErrorKind::InvalidData, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_malformed_lines_are_errors() {
+        for (source, message) in [
+            ("input x ten\n", "Invalid input value: input x ten"),
+            ("output y 1.5\n", "Invalid output value: output y 1.5"),
+            ("const k abc\n", "Invalid constant value: const k abc"),
+            ("input x 1\nfrobnicate x\n", "Unknown or malformed line: frobnicate x"),
+        ] {
+            let err = parse_circuit_str(source).unwrap_err();
+            assert_eq!((err.kind(), err.to_string()), (std::io::ErrorKind::InvalidData, message.to_string()));
+        }
+        assert_eq!(parse_circuit("no/such/circuit.txt").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_negative_money_is_a_typed_error() {
+        let typed = |source: &str| {
+            let err = parse_circuit_str(source).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            err.into_inner().and_then(|e| e.downcast::<CircuitError>().ok()).map(|e| *e)
+        };
+        assert_eq!(
+            typed("input alice -5
+input bob 0
+sender alice
+receiver bob
+amount 1
+"),
+            Some(CircuitError::NegativeBalance { party: "alice".to_string(), balance: -5 })
+        );
+        assert_eq!(
+            typed("input alice 5
+input bob -1
+sender alice
+receiver bob
+amount 1
+"),
+            Some(CircuitError::NegativeBalance { party: "bob".to_string(), balance: -1 })
+        );
+        assert_eq!(typed("amount -5
+"), Some(CircuitError::InvalidAmount { amount: "-5".to_string() }));
+        //Inputs that are not balances may still be negative
+        assert!(parse_circuit_str("input x -5
+input alice 5
+input bob 0
+sender alice
+receiver bob
+amount 1
+").is_ok());
+    }
 }
@@ -0,0 +1,149 @@
+//Size and time report of one setup/prove/verify round over an R1CS system
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ark_bls12_381::Fr;
+use ark_ff::{One, Zero};
+use ark_serialize::CanonicalSerialize;
+use ark_std::rand::{CryptoRng, RngCore};
+
+use crate::pipeline::{PipelineError, R1CSStats};
+use crate::{prove_with_rng, setup_with_rng, verify, NoProgress, Proof, ProvingKey, R1CSSystem, VerifyingKey};
+
+//Serialized size in bytes with and without point compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializedSize {
+    pub compressed: usize,
+    pub uncompressed: usize,
+}
+
+impl SerializedSize {
+    fn of<T: CanonicalSerialize>(value: &T) -> Self {
+        SerializedSize { compressed: value.compressed_size(), uncompressed: value.uncompressed_size() }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PerfReport {
+    pub stats: R1CSStats,
+    pub setup_time: Duration,
+    pub prove_time: Duration,
+    pub verify_time: Duration,
+    pub proving_key_size: SerializedSize,
+    pub verifying_key_size: SerializedSize,
+    pub proof_size: SerializedSize,
+    //Whether the measured proof verified (false means the witness did not satisfy the system)
+    pub verified: bool,
+}
+
+impl ProvingKey {
+    pub fn serialized_size(&self) -> SerializedSize {
+        SerializedSize::of(&self.0)
+    }
+}
+
+impl VerifyingKey {
+    pub fn serialized_size(&self) -> SerializedSize {
+        SerializedSize::of(&self.0)
+    }
+}
+
+impl Proof {
+    pub fn serialized_size(&self) -> SerializedSize {
+        SerializedSize::of(&self.0)
+    }
+}
+
+//Witness assigning zero to every variable except the constant "1". It only satisfies systems
+//whose constraints are all homogeneous, and MSMs skip zero scalars, so prove times measured
+//with it are optimistic
+pub fn zero_witness(r1cs_system: &R1CSSystem) -> HashMap<usize, Fr> {
+    r1cs_system.var_map.iter().map(|(name, idx)| (*idx, if name == "1" { Fr::one() } else { Fr::zero() })).collect()
+}
+
+//Run setup, prove and verify once and report times and serialized sizes. Without a witness the
+//zero witness is used, which must satisfy the system (the prover rejects unsatisfied witnesses)
+pub fn measure<R: RngCore + CryptoRng>(
+    r1cs_system: &R1CSSystem,
+    witness: Option<&HashMap<usize, Fr>>,
+    rng: &mut R,
+) -> Result<PerfReport, PipelineError> {
+    let witness = match witness {
+        Some(witness) => witness.clone(),
+        None => zero_witness(r1cs_system),
+    };
+    if let Some(failing) = r1cs_system.which_is_unsatisfied(&witness).map_err(PipelineError::Witness)? {
+        return Err(PipelineError::Witness(format!("Witness does not satisfy {}", r1cs_system.explain(failing))));
+    }
+    let public_inputs = r1cs_system.public_inputs_from_witness(&witness).map_err(PipelineError::PublicInputs)?;
+
+    let start = Instant::now();
+    let (pk, vk) = setup_with_rng(r1cs_system, rng, &NoProgress).map_err(PipelineError::Setup)?;
+    let setup_time = start.elapsed();
+
+    let start = Instant::now();
+    let proof = prove_with_rng(r1cs_system, &pk, witness, rng, &NoProgress).map_err(PipelineError::Prove)?;
+    let prove_time = start.elapsed();
+
+    let start = Instant::now();
+    let verified = verify(&vk, &proof, &public_inputs).map_err(PipelineError::Verify)?;
+    let verify_time = start.elapsed();
+
+    Ok(PerfReport {
+        stats: R1CSStats {
+            num_constraints: r1cs_system.raw_constraints.len(),
+            num_variables: r1cs_system.num_variables,
+            num_public_inputs: r1cs_system.num_public_inputs,
+        },
+        setup_time,
+        prove_time,
+        verify_time,
+        proving_key_size: pk.serialized_size(),
+        verifying_key_size: vk.serialized_size(),
+        proof_size: proof.serialized_size(),
+        verified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Circuit, Gate};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_measure_reports_sizes_and_times() {
+        //No sender balance and a zero amount, so the all-zero witness satisfies the system
+        let mut circuit = Circuit {
+            name: "perf".to_string(),
+            inputs: HashMap::from([("a".to_string(), 0), ("b".to_string(), 0)]),
+            gates: vec![
+                Gate::Mul("a".to_string(), "b".to_string(), "c".to_string(), None),
+                Gate::Add("c".to_string(), "a".to_string(), "d".to_string(), None),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 0,
+            ..Default::default()
+        };
+        let r1cs = circuit.to_r1cs_system();
+        let mut rng = StdRng::seed_from_u64(3);
+        let report = measure(&r1cs, None, &mut rng).expect("Measuring failed");
+        assert!(report.verified);
+        assert_eq!(report.stats.num_constraints, r1cs.raw_constraints.len());
+        assert!(report.setup_time > Duration::ZERO && report.prove_time > Duration::ZERO && report.verify_time > Duration::ZERO);
+        //Two G1 points and one G2 point
+        assert_eq!(report.proof_size, SerializedSize { compressed: 192, uncompressed: 384 });
+        assert!(report.verifying_key_size.compressed < report.verifying_key_size.uncompressed);
+        assert!(report.proving_key_size.compressed > report.verifying_key_size.compressed);
+
+        //With a transfer amount the zero witness is rejected, a computed one is measured
+        circuit.transfer_amount = 5;
+        circuit.inputs.insert("a".to_string(), 3);
+        let r1cs = circuit.to_r1cs_system();
+        let err = measure(&r1cs, None, &mut rng).expect_err("Zero witness cannot satisfy the transfer spec");
+        assert!(err.to_string().contains("transfer spec"), "{}", err);
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        assert!(measure(&r1cs, Some(&witness), &mut rng).expect("Measuring failed").verified);
+    }
+}
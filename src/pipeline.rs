@@ -0,0 +1,395 @@
+//End-to-end pipeline: r1cs -> setup -> witness -> prove -> public inputs -> verify
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use ark_bls12_381::Fr;
+use ark_relations::r1cs::SynthesisError;
+use ark_std::rand::rngs::OsRng;
+use ark_std::rand::{CryptoRng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::cache::KeyCacheError;
+use crate::{parse_circuit_str, BudgetExceeded, KeyCache, ConstraintBudget, GatePolicy, PolicyViolation, prove_with_rng, setup_with_rng, verify, Circuit, CircuitError, NoProgress, ProgressSink, Proof, ProveError, ProvingKey, VerifyingKey};
+
+//Options controlling key reuse, rng seeding and verification
+#[derive(Clone, Copy)]
+pub struct PipelineOptions<'a> {
+    //Reuse existing keys instead of running setup
+    pub keys: Option<(&'a ProvingKey, &'a VerifyingKey)>,
+    //Without keys, load them from this cache or run setup into it (rng_seed does not apply to
+    //that setup). Ignored when keys are supplied
+    pub cache: Option<&'a KeyCache>,
+    //Seed for setup and proving randomness, None uses OsRng
+    pub rng_seed: Option<u64>,
+    //Verify the generated proof
+    pub verify: bool,
+    //Return the generated keys in the result (ignored when keys are reused)
+    pub return_keys: bool,
+    //Precomputed witness (e.g. loaded from a .wit file) used instead of computing it
+    pub witness: Option<&'a HashMap<usize, Fr>>,
+    //Gate policy the circuit must comply with, checked with the other validation
+    pub policy: Option<&'a GatePolicy>,
+    //Most rows and variables the circuit may compile to, checked while building the R1CS
+    pub budget: Option<ConstraintBudget>,
+    pub progress: &'a dyn ProgressSink,
+}
+
+impl Default for PipelineOptions<'_> {
+    fn default() -> Self {
+        PipelineOptions { keys: None, cache: None, rng_seed: None, verify: true, return_keys: false, witness: None, policy: None, budget: None, progress: &NoProgress }
+    }
+}
+
+//Counts of the R1CS system the proof was generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct R1CSStats {
+    pub num_constraints: usize,
+    pub num_variables: usize,
+    pub num_public_inputs: usize,
+}
+
+//Wall-clock duration of each pipeline stage. parse is zero unless the pipeline parsed the DSL
+//itself (run_pipeline_str), setup_or_cache is zero when keys are supplied or a PipelineOptions::cache
+//hit loads them (cache_load is the load time, zero otherwise) and verify is zero when verification
+//is disabled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub parse: Duration,
+    pub validate: Duration,
+    pub r1cs: Duration,
+    pub setup_or_cache: Duration,
+    pub cache_load: Duration,
+    pub witness: Duration,
+    pub prove: Duration,
+    pub verify: Duration,
+}
+
+impl Timings {
+    //Each stage with its name, in pipeline order
+    pub fn stages(&self) -> [(&'static str, Duration); 8] {
+        [
+            ("parse", self.parse),
+            ("validate", self.validate),
+            ("r1cs", self.r1cs),
+            ("setup_or_cache", self.setup_or_cache),
+            ("cache_load", self.cache_load),
+            ("witness", self.witness),
+            ("prove", self.prove),
+            ("verify", self.verify),
+        ]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages().iter().map(|(_, duration)| *duration).sum()
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stages: Vec<String> = self.stages().iter().map(|(name, duration)| format!("{} {:.2?}", name, duration)).collect();
+        write!(f, "{} (total {:.2?})", stages.join(", "), self.total())
+    }
+}
+
+pub struct PipelineResult {
+    pub stats: R1CSStats,
+    pub keys: Option<(ProvingKey, VerifyingKey)>,
+    pub proof: Proof,
+    //The values of r1cs.public_input_names, in order (what verify() takes)
+    pub public_inputs: Vec<Fr>,
+    //None when verification was disabled
+    pub verified: Option<bool>,
+    //Whether PipelineOptions::cache had the keys, None when no cache was used
+    pub cache_hit: Option<bool>,
+    pub timings: Timings,
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    //The DSL text does not parse (run_pipeline_str)
+    Parse(String),
+    //The circuit is malformed (e.g. a constant is defined twice), checked before setup
+    Circuit(CircuitError),
+    //The circuit breaks PipelineOptions::policy, every violation is listed
+    Policy(Vec<PolicyViolation>),
+    //The circuit compiles to more than PipelineOptions::budget allows
+    Budget(BudgetExceeded),
+    Setup(ProveError),
+    //PipelineOptions::cache could not load or store the keys
+    Cache(KeyCacheError),
+    Witness(String),
+    PublicInputs(String),
+    Prove(ProveError),
+    Verify(SynthesisError),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::Parse(e) => write!(f, "Failed to parse circuit: {}", e),
+            PipelineError::Circuit(e) => write!(f, "Invalid circuit: {}", e),
+            PipelineError::Policy(violations) => {
+                let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+                write!(f, "Circuit rejected by gate policy: {}", violations.join("; "))
+            }
+            PipelineError::Budget(e) => write!(f, "Circuit too large: {}", e),
+            PipelineError::Setup(e) => write!(f, "Setup failed: {}", e),
+            PipelineError::Cache(e) => write!(f, "Key cache failed: {}", e),
+            PipelineError::Witness(e) => write!(f, "Witness computation failed: {}", e),
+            PipelineError::PublicInputs(e) => write!(f, "Public input assembly failed: {}", e),
+            PipelineError::Prove(e) => write!(f, "Proof generation failed: {}", e),
+            PipelineError::Verify(e) => write!(f, "Verification failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+//Run the whole proving pipeline for a circuit
+pub fn run_pipeline(circuit: &Circuit, options: PipelineOptions) -> Result<PipelineResult, PipelineError> {
+    match options.rng_seed {
+        Some(seed) => run_pipeline_with_rng(circuit, options, &mut StdRng::seed_from_u64(seed)),
+        None => run_pipeline_with_rng(circuit, options, &mut OsRng),
+    }
+}
+
+//Parse DSL text and run the pipeline on it, timing the parse as well
+pub fn run_pipeline_str(content: &str, options: PipelineOptions) -> Result<PipelineResult, PipelineError> {
+    let start = Instant::now();
+    let circuit = parse_circuit_str(content).map_err(|e| PipelineError::Parse(e.to_string()))?;
+    let parse = start.elapsed();
+    let mut result = run_pipeline(&circuit, options)?;
+    result.timings.parse = parse;
+    Ok(result)
+}
+
+fn run_pipeline_with_rng<R: RngCore + CryptoRng>(
+    circuit: &Circuit,
+    options: PipelineOptions,
+    rng: &mut R,
+) -> Result<PipelineResult, PipelineError> {
+    let span = tracing::info_span!("pipeline", circuit = %circuit.name);
+    let _guard = span.enter();
+    let mut timings = Timings::default();
+    let start = Instant::now();
+    if let Some(policy) = options.policy {
+        let violations = policy.check(circuit);
+        if !violations.is_empty() {
+            return Err(PipelineError::Policy(violations));
+        }
+    }
+    circuit.validate_gates().map_err(PipelineError::Circuit)?;
+    timings.validate = start.elapsed();
+
+    let start = Instant::now();
+    let r1cs = match options.budget {
+        Some(budget) => circuit.to_r1cs_system_bounded(budget).map_err(PipelineError::Budget)?,
+        None => circuit.to_r1cs_system(),
+    };
+    timings.r1cs = start.elapsed();
+    let stats = R1CSStats {
+        num_constraints: r1cs.raw_constraints.len(),
+        num_variables: r1cs.num_variables,
+        num_public_inputs: r1cs.num_public_inputs,
+    };
+
+    let mut cache_hit = None;
+    let generated_keys = match (options.keys, options.cache) {
+        (Some(_), _) => None,
+        (None, Some(cache)) => {
+            let start = Instant::now();
+            let (pk, vk, hit) = cache.fetch(circuit, options.progress).map_err(PipelineError::Cache)?;
+            //A hit runs no setup
+            if hit {
+                timings.cache_load = start.elapsed();
+            } else {
+                timings.setup_or_cache = start.elapsed();
+            }
+            cache_hit = Some(hit);
+            Some((pk, vk))
+        }
+        (None, None) => {
+            let start = Instant::now();
+            let keys = setup_with_rng(&r1cs, rng, options.progress).map_err(PipelineError::Setup)?;
+            timings.setup_or_cache = start.elapsed();
+            Some(keys)
+        }
+    };
+    let (pk, vk) = match (options.keys, &generated_keys) {
+        (Some(keys), _) => keys,
+        (None, Some((pk, vk))) => (pk, vk),
+        (None, None) => unreachable!("keys are generated when none are supplied"),
+    };
+
+    let start = Instant::now();
+    let witness = match options.witness {
+        Some(witness) => witness.clone(),
+        None => circuit.compute_witness(&r1cs.var_map).map_err(PipelineError::Witness)?,
+    };
+    timings.witness = start.elapsed();
+    let public_inputs = r1cs.public_inputs_from_witness(&witness).map_err(PipelineError::PublicInputs)?;
+
+    let start = Instant::now();
+    let proof = prove_with_rng(&r1cs, pk, witness, rng, options.progress).map_err(PipelineError::Prove)?;
+    timings.prove = start.elapsed();
+
+    let verified = if options.verify {
+        let start = Instant::now();
+        let valid = verify(vk, &proof, &public_inputs).map_err(PipelineError::Verify)?;
+        timings.verify = start.elapsed();
+        Some(valid)
+    } else {
+        None
+    };
+    tracing::info!(
+        validate_us = timings.validate.as_micros() as u64,
+        r1cs_us = timings.r1cs.as_micros() as u64,
+        setup_or_cache_us = timings.setup_or_cache.as_micros() as u64,
+        cache_load_us = timings.cache_load.as_micros() as u64,
+        witness_us = timings.witness.as_micros() as u64,
+        prove_us = timings.prove.as_micros() as u64,
+        verify_us = timings.verify.as_micros() as u64,
+        "pipeline stage timings"
+    );
+
+    Ok(PipelineResult {
+        stats,
+        keys: if options.return_keys { generated_keys } else { None },
+        proof,
+        public_inputs,
+        verified,
+        cache_hit,
+        timings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mul_circuit;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    type CapturedSpans = Arc<Mutex<Vec<(&'static str, HashMap<&'static str, String>)>>>;
+
+    //Records this crate's spans and their fields, span ids are indices into the list plus one
+    struct CaptureSpans(CapturedSpans);
+
+    struct Fields<'a>(&'a mut HashMap<&'static str, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for CaptureSpans {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target().starts_with("zk_framework")
+        }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = HashMap::new();
+            span.record(&mut Fields(&mut fields));
+            spans.push((span.metadata().name(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0.lock().unwrap();
+            values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1].1));
+        }
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn test_seeded_pipeline_with_key_reuse() {
+        let circuit = mul_circuit("pipeline");
+
+        let options = PipelineOptions { rng_seed: Some(7), return_keys: true, ..PipelineOptions::default() };
+        let first = run_pipeline(&circuit, options).expect("Pipeline failed");
+        let second = run_pipeline(&circuit, options).expect("Pipeline failed");
+        assert_eq!(first.verified, Some(true));
+        assert_eq!(first.proof, second.proof, "Seeded runs must be reproducible");
+
+        let (pk, vk) = first.keys.as_ref().expect("Keys were requested");
+        let reused = run_pipeline(&circuit, PipelineOptions { keys: Some((pk, vk)), verify: false, ..PipelineOptions::default() })
+            .expect("Pipeline failed");
+        assert!(reused.keys.is_none());
+        assert_eq!(reused.verified, None);
+        assert!(verify(vk, &reused.proof, &reused.public_inputs).unwrap());
+    }
+
+    #[test]
+    fn test_pipeline_stages_emit_spans() {
+        let circuit = mul_circuit("traced");
+        let captured = CapturedSpans::default();
+        let result = tracing::subscriber::with_default(CaptureSpans(captured.clone()), || {
+            run_pipeline(&circuit, PipelineOptions { rng_seed: Some(1), ..PipelineOptions::default() })
+        })
+        .expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+
+        let spans = captured.lock().unwrap();
+        let names: Vec<&str> = spans.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["pipeline", "to_r1cs_system", "setup", "compute_witness", "prove", "verify"]);
+        let fields = |name: &str| &spans.iter().find(|(n, _)| *n == name).unwrap().1;
+        assert_eq!(fields("pipeline")["circuit"], "traced");
+        let num_constraints = result.stats.num_constraints.to_string();
+        assert_eq!(fields("to_r1cs_system")["num_constraints"], num_constraints);
+        assert_eq!(fields("prove")["num_constraints"], num_constraints);
+        assert_eq!(fields("verify")["valid"], "true");
+        for (name, fields) in spans.iter() {
+            assert!(*name == "pipeline" || fields.contains_key("elapsed_us"), "{} has no duration", name);
+        }
+    }
+
+    #[test]
+    fn test_timings_cover_every_stage() {
+        let dsl = "name timed\ninput a 3\ninput b 4\namount 5\nmul a b c\n";
+        let result = run_pipeline_str(dsl, PipelineOptions { return_keys: true, ..PipelineOptions::default() }).expect("Pipeline failed");
+        for (stage, duration) in result.timings.stages().into_iter().filter(|(stage, _)| *stage != "cache_load") {
+            assert!(duration > Duration::ZERO, "{} was not timed", stage);
+        }
+        assert!(result.timings.to_string().contains("setup_or_cache"));
+
+        //Reused keys skip setup, and a circuit passed in was not parsed by the pipeline
+        let (pk, vk) = result.keys.as_ref().expect("Keys were requested");
+        let circuit = parse_circuit_str(dsl).unwrap();
+        let reused = run_pipeline(&circuit, PipelineOptions { keys: Some((pk, vk)), ..PipelineOptions::default() }).expect("Pipeline failed");
+        assert_eq!(reused.timings.setup_or_cache, Duration::ZERO);
+        assert_eq!(reused.timings.parse, Duration::ZERO);
+        assert!(reused.timings.prove > Duration::ZERO);
+
+        assert!(matches!(run_pipeline_str("clamp a cap b", PipelineOptions::default()), Err(PipelineError::Parse(_))));
+    }
+
+    #[test]
+    fn test_cache_hits_are_reported() {
+        let circuit = mul_circuit("cached");
+        let dir = std::env::temp_dir().join(format!("zk_pipeline_cache_test_{}", std::process::id()));
+        let cache = KeyCache::new(&dir);
+        let options = PipelineOptions { cache: Some(&cache), return_keys: true, ..PipelineOptions::default() };
+
+        let miss = run_pipeline(&circuit, options).expect("Pipeline failed");
+        assert_eq!((miss.cache_hit, miss.verified), (Some(false), Some(true)));
+        assert!(miss.timings.setup_or_cache > Duration::ZERO);
+        assert_eq!(miss.timings.cache_load, Duration::ZERO);
+        let hit = run_pipeline(&circuit, options).expect("Pipeline failed");
+        assert_eq!((hit.cache_hit, hit.verified), (Some(true), Some(true)));
+        //A hit reports no setup, only the load
+        assert_eq!(hit.timings.setup_or_cache, Duration::ZERO);
+        assert!(hit.timings.cache_load > Duration::ZERO);
+        assert_eq!(cache.setup_count(), 1);
+        //Keys loaded from the cache are returned like generated ones
+        assert_eq!(hit.keys.map(|(_, vk)| vk.fingerprint()), miss.keys.map(|(_, vk)| vk.fingerprint()));
+        assert_eq!(run_pipeline(&circuit, PipelineOptions::default()).unwrap().cache_hit, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
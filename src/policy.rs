@@ -0,0 +1,152 @@
+//Gate policy: denied or allowed gate kinds and per-kind caps, stored as JSON
+//(e.g. {"deny": ["hash_bytes"], "max_count": {"hash": 4}})
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Circuit, GATE_KINDS};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GatePolicy {
+    //Kinds no gate may have
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+    //When set, the only kinds a gate may have
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow: Option<Vec<String>>,
+    //Most gates of a kind one circuit may use
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max_count: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    //The gate's kind is on the deny list
+    Denied { gate_index: usize, kind: &'static str },
+    //The policy has an allow list and the gate's kind is not on it
+    NotAllowed { gate_index: usize, kind: &'static str },
+    //The circuit uses more gates of a kind than max_count allows
+    TooMany { kind: String, count: usize, limit: usize },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyViolation::Denied { gate_index, kind } => write!(f, "Gate #{} is a '{}' gate, which the policy denies", gate_index, kind),
+            PolicyViolation::NotAllowed { gate_index, kind } => {
+                write!(f, "Gate #{} is a '{}' gate, which is not on the policy's allow list", gate_index, kind)
+            }
+            PolicyViolation::TooMany { kind, count, limit } => {
+                write!(f, "Circuit has {} '{}' gates, the policy allows at most {}", count, kind, limit)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PolicyError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    //The policy names a kind no gate has (most likely a typo, which would silently allow the gate)
+    UnknownKind(String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::Io(e) => write!(f, "Cannot read gate policy: {}", e),
+            PolicyError::Json(e) => write!(f, "Invalid gate policy: {}", e),
+            PolicyError::UnknownKind(kind) => write!(f, "Gate policy names unknown gate kind '{}' (known: {})", kind, GATE_KINDS.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+impl GatePolicy {
+    pub fn from_json(json: &str) -> Result<Self, PolicyError> {
+        let policy: GatePolicy = serde_json::from_str(json).map_err(PolicyError::Json)?;
+        let named = policy.deny.iter().chain(policy.allow.iter().flatten()).chain(policy.max_count.keys());
+        if let Some(unknown) = named.into_iter().find(|kind| !GATE_KINDS.contains(&kind.as_str())) {
+            return Err(PolicyError::UnknownKind(unknown.clone()));
+        }
+        Ok(policy)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PolicyError> {
+        Self::from_json(&fs::read_to_string(path).map_err(PolicyError::Io)?)
+    }
+
+    //Every way the circuit breaks the policy, empty when it complies
+    pub fn check(&self, circuit: &Circuit) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for (gate_index, gate) in circuit.gates.iter().enumerate() {
+            let kind = gate.kind();
+            *counts.entry(kind).or_insert(0) += 1;
+            if self.deny.iter().any(|denied| denied == kind) {
+                violations.push(PolicyViolation::Denied { gate_index, kind });
+            } else if self.allow.as_ref().is_some_and(|allowed| !allowed.iter().any(|a| a == kind)) {
+                violations.push(PolicyViolation::NotAllowed { gate_index, kind });
+            }
+        }
+        for (kind, limit) in &self.max_count {
+            let count = counts.get(kind.as_str()).copied().unwrap_or(0);
+            if count > *limit {
+                violations.push(PolicyViolation::TooMany { kind: kind.clone(), count, limit: *limit });
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_circuit_str, run_pipeline, PipelineError, PipelineOptions};
+
+    const DSL: &str = "name hashed
+input a 3
+input b 4
+amount 5
+mul a b c
+hash c h1
+hash h1 h2
+";
+
+    #[test]
+    fn test_policy_rejects_and_allows_gates() {
+        let circuit = parse_circuit_str(DSL).unwrap();
+
+        let deny = GatePolicy::from_json(r#"{"deny": ["hash"]}"#).unwrap();
+        let err = run_pipeline(&circuit, PipelineOptions { policy: Some(&deny), ..PipelineOptions::default() }).err().unwrap();
+        let PipelineError::Policy(violations) = &err else { panic!("Expected a policy error, got {}", err) };
+        assert_eq!(
+            violations,
+            &vec![PolicyViolation::Denied { gate_index: 1, kind: "hash" }, PolicyViolation::Denied { gate_index: 2, kind: "hash" }]
+        );
+        assert!(err.to_string().contains("Gate #1 is a 'hash' gate, which the policy denies"), "{}", err);
+
+        let allow = GatePolicy::from_json(r#"{"allow": ["mul"], "max_count": {"hash": 1}}"#).unwrap();
+        assert_eq!(
+            allow.check(&circuit),
+            vec![
+                PolicyViolation::NotAllowed { gate_index: 1, kind: "hash" },
+                PolicyViolation::NotAllowed { gate_index: 2, kind: "hash" },
+                PolicyViolation::TooMany { kind: "hash".to_string(), count: 2, limit: 1 },
+            ]
+        );
+
+        //The same circuit passes once the policy allows its gates
+        let permissive = GatePolicy::from_json(r#"{"allow": ["mul", "hash"], "max_count": {"hash": 2}}"#).unwrap();
+        assert!(permissive.check(&circuit).is_empty());
+        let result = run_pipeline(&circuit, PipelineOptions { policy: Some(&permissive), ..PipelineOptions::default() }).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+
+        assert!(matches!(GatePolicy::from_json(r#"{"deny": ["sha256"]}"#), Err(PolicyError::UnknownKind(kind)) if kind == "sha256"));
+    }
+}
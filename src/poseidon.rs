@@ -0,0 +1,467 @@
+//Poseidon hash over the BLS12-381 scalar field (width 3, x^5, 8 full and 57 partial rounds),
+//one permutation for the native hash, the witness trace and the constraints
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use ark_bls12_381::Fr;
+use ark_crypto_primitives::sponge::poseidon::find_poseidon_ark_and_mds;
+use ark_ff::{Field, One, Zero};
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable};
+
+use crate::{get_index, _R1CSConstraintInternal};
+
+pub const WIDTH: usize = 3;
+pub const RATE: usize = 2;
+pub const FULL_ROUNDS: usize = 8;
+pub const PARTIAL_ROUNDS: usize = 57;
+pub const ALPHA: u64 = 5;
+//Each S-box costs three constraints: x^2, x^4 and x^5
+pub const CONSTRAINTS_PER_SBOX: usize = 3;
+
+pub struct PoseidonParams {
+    //Round constants, one row of WIDTH elements per round
+    pub ark: Vec<Vec<Fr>>,
+    pub mds: Vec<Vec<Fr>>,
+}
+
+pub fn params() -> &'static PoseidonParams {
+    static PARAMS: OnceLock<PoseidonParams> = OnceLock::new();
+    PARAMS.get_or_init(|| {
+        let (ark, mds) = find_poseidon_ark_and_mds::<Fr>(255, RATE, FULL_ROUNDS as u64, PARTIAL_ROUNDS as u64, 0);
+        PoseidonParams { ark, mds }
+    })
+}
+
+//Number of permutations (and so constraints) used to hash n inputs
+pub fn num_permutations(num_inputs: usize) -> usize {
+    num_inputs.div_ceil(RATE).max(1)
+}
+
+pub fn num_constraints(num_inputs: usize) -> usize {
+    let sboxes_per_permutation = FULL_ROUNDS * WIDTH + PARTIAL_ROUNDS;
+    //One extra row binds the output wire to the final state
+    num_permutations(num_inputs) * sboxes_per_permutation * CONSTRAINTS_PER_SBOX + 1
+}
+
+//Operations the permutation needs from a representation of state elements
+trait Backend {
+    type Elem: Clone;
+    type Error;
+    fn constant(&mut self, c: Fr) -> Self::Elem;
+    //sum of coeff * elem
+    fn linear(&mut self, terms: &[(Fr, &Self::Elem)]) -> Self::Elem;
+    fn sbox(&mut self, x: &Self::Elem) -> Result<Self::Elem, Self::Error>;
+}
+
+fn permute<B: Backend>(b: &mut B, mut state: Vec<B::Elem>) -> Result<Vec<B::Elem>, B::Error> {
+    let p = params();
+    let half_full = FULL_ROUNDS / 2;
+    for round in 0..(FULL_ROUNDS + PARTIAL_ROUNDS) {
+        //Add round constants
+        for (i, elem) in state.iter_mut().enumerate() {
+            let c = b.constant(p.ark[round][i]);
+            *elem = b.linear(&[(Fr::one(), elem), (Fr::one(), &c)]);
+        }
+        //S-boxes, only on the first element in partial rounds
+        let full = round < half_full || round >= half_full + PARTIAL_ROUNDS;
+        for (i, elem) in state.iter_mut().enumerate() {
+            if full || i == 0 {
+                *elem = b.sbox(elem)?;
+            }
+        }
+        //MDS mix
+        state = (0..WIDTH)
+            .map(|i| {
+                let terms: Vec<(Fr, &B::Elem)> = (0..WIDTH).map(|j| (p.mds[i][j], &state[j])).collect();
+                b.linear(&terms)
+            })
+            .collect();
+    }
+    Ok(state)
+}
+
+fn sponge<B: Backend>(b: &mut B, inputs: &[B::Elem]) -> Result<B::Elem, B::Error> {
+    let mut state = vec![b.constant(Fr::from(inputs.len() as u64)), b.constant(Fr::zero()), b.constant(Fr::zero())];
+    if inputs.is_empty() {
+        state = permute(b, state)?;
+    }
+    for chunk in inputs.chunks(RATE) {
+        for (i, input) in chunk.iter().enumerate() {
+            state[1 + i] = b.linear(&[(Fr::one(), &state[1 + i]), (Fr::one(), input)]);
+        }
+        state = permute(b, state)?;
+    }
+    Ok(state[1].clone())
+}
+
+//Native evaluation, optionally recording the S-box intermediates under the names the rows backend allocates
+struct Native<'a> {
+    prefix: &'a str,
+    counter: usize,
+    trace: Option<&'a mut Vec<(String, Fr)>>,
+}
+
+impl Backend for Native<'_> {
+    type Elem = Fr;
+    type Error = std::convert::Infallible;
+
+    fn constant(&mut self, c: Fr) -> Fr {
+        c
+    }
+
+    fn linear(&mut self, terms: &[(Fr, &Fr)]) -> Fr {
+        terms.iter().map(|(c, x)| *c * **x).sum()
+    }
+
+    fn sbox(&mut self, x: &Fr) -> Result<Fr, Self::Error> {
+        let x2 = x.square();
+        let x4 = x2.square();
+        let x5 = x4 * x;
+        if let Some(trace) = self.trace.as_mut() {
+            for val in [x2, x4, x5] {
+                trace.push((aux_name(self.prefix, self.counter), val));
+                self.counter += 1;
+            }
+        }
+        Ok(x5)
+    }
+}
+
+fn aux_name(prefix: &str, counter: usize) -> String {
+    format!("{}_poseidon_{}", prefix, counter)
+}
+
+//Hash field elements natively
+pub fn poseidon_hash(inputs: &[Fr]) -> Fr {
+    let mut native = Native { prefix: "", counter: 0, trace: None };
+    match sponge(&mut native, inputs) {
+        Ok(out) => out,
+        Err(e) => match e {},
+    }
+}
+
+//Hash natively and return the values of every auxiliary wire emit_poseidon_rows allocates with the same prefix
+pub(crate) fn poseidon_witness(inputs: &[Fr], prefix: &str) -> (Fr, Vec<(String, Fr)>) {
+    let mut trace = Vec::new();
+    let mut native = Native { prefix, counter: 0, trace: Some(&mut trace) };
+    let out = match sponge(&mut native, inputs) {
+        Ok(out) => out,
+        Err(e) => match e {},
+    };
+    (out, trace)
+}
+
+//Rows backend: elements are linear combinations over R1CS variable indices (index of "1" for constants)
+struct Rows<'a> {
+    prefix: &'a str,
+    counter: usize,
+    one_idx: usize,
+    var_map: &'a mut HashMap<String, usize>,
+    next_index: &'a mut usize,
+    constraints: &'a mut Vec<_R1CSConstraintInternal>,
+}
+
+impl Rows<'_> {
+    fn alloc(&mut self) -> usize {
+        let name = aux_name(self.prefix, self.counter);
+        self.counter += 1;
+        get_index(&name, self.var_map, self.next_index)
+    }
+}
+
+impl Backend for Rows<'_> {
+    type Elem = HashMap<usize, Fr>;
+    type Error = std::convert::Infallible;
+
+    fn constant(&mut self, c: Fr) -> Self::Elem {
+        HashMap::from([(self.one_idx, c)])
+    }
+
+    fn linear(&mut self, terms: &[(Fr, &Self::Elem)]) -> Self::Elem {
+        let mut out: HashMap<usize, Fr> = HashMap::new();
+        for (c, lc) in terms {
+            for (idx, coeff) in lc.iter() {
+                *out.entry(*idx).or_insert_with(Fr::zero) += *c * coeff;
+            }
+        }
+        out.retain(|_, coeff| !coeff.is_zero());
+        out
+    }
+
+    fn sbox(&mut self, x: &Self::Elem) -> Result<Self::Elem, Self::Error> {
+        let x2 = self.alloc();
+        let x4 = self.alloc();
+        let x5 = self.alloc();
+        let one = |idx: usize| HashMap::from([(idx, Fr::one())]);
+        self.constraints.push(_R1CSConstraintInternal { a: x.clone(), b: x.clone(), c: one(x2) });
+        self.constraints.push(_R1CSConstraintInternal { a: one(x2), b: one(x2), c: one(x4) });
+        self.constraints.push(_R1CSConstraintInternal { a: one(x4), b: x.clone(), c: one(x5) });
+        Ok(one(x5))
+    }
+}
+
+//Emit the rows constraining output = poseidon_hash(inputs); auxiliary wires are named after prefix
+pub(crate) fn emit_poseidon_rows(
+    inputs: &[usize],
+    output: usize,
+    prefix: &str,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    let input_lcs: Vec<HashMap<usize, Fr>> = inputs.iter().map(|idx| HashMap::from([(*idx, Fr::one())])).collect();
+    emit_sponge_rows(&input_lcs, output, prefix, var_map, next_index, constraints);
+}
+
+//emit_poseidon_rows over linear combinations of wires
+pub(crate) fn emit_sponge_rows(
+    input_lcs: &[HashMap<usize, Fr>],
+    output: usize,
+    prefix: &str,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    let one_idx = var_map["1"];
+    let mut rows = Rows { prefix, counter: 0, one_idx, var_map, next_index, constraints };
+    let out = match sponge(&mut rows, input_lcs) {
+        Ok(out) => out,
+        Err(e) => match e {},
+    };
+    rows.constraints.push(_R1CSConstraintInternal {
+        a: out,
+        b: HashMap::from([(one_idx, Fr::one())]),
+        c: HashMap::from([(output, Fr::one())]),
+    });
+}
+
+//Bytes packed into one sponge input by hash_bytes; 248 bits stay below the 255-bit modulus
+pub const BYTES_PER_ELEMENT: usize = 31;
+
+//Sponge inputs of hash_bytes: the byte count (so trailing zero bytes count), then the bytes
+//packed little endian, BYTES_PER_ELEMENT to an element
+fn pack_bytes(bytes: &[Fr]) -> Vec<Fr> {
+    let mut packed = vec![Fr::from(bytes.len() as u64)];
+    for chunk in bytes.chunks(BYTES_PER_ELEMENT) {
+        let mut weight = Fr::one();
+        let mut element = Fr::zero();
+        for byte in chunk {
+            element += weight * byte;
+            weight *= Fr::from(256u64);
+        }
+        packed.push(element);
+    }
+    packed
+}
+
+//Hash a byte string natively, the value a hash_bytes gate assigns for these bytes
+pub fn hash_bytes(bytes: &[u8]) -> Fr {
+    let bytes: Vec<Fr> = bytes.iter().map(|byte| Fr::from(*byte)).collect();
+    poseidon_hash(&pack_bytes(&bytes))
+}
+
+//Constraints of a hash_bytes gate over num_bytes bytes; packing is linear and costs none
+pub fn hash_bytes_constraints(num_bytes: usize) -> usize {
+    num_constraints(1 + num_bytes.div_ceil(BYTES_PER_ELEMENT))
+}
+
+//poseidon_witness of hash_bytes over byte wire values, which must already be range checked
+pub(crate) fn hash_bytes_witness(bytes: &[Fr], prefix: &str) -> (Fr, Vec<(String, Fr)>) {
+    poseidon_witness(&pack_bytes(bytes), prefix)
+}
+
+//Emit the rows constraining output = hash_bytes(bytes), packing the byte wires inside the
+//sponge inputs' linear combinations
+pub(crate) fn emit_hash_bytes_rows(
+    bytes: &[usize],
+    output: usize,
+    prefix: &str,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    let mut input_lcs = vec![HashMap::from([(var_map["1"], Fr::from(bytes.len() as u64))])];
+    for chunk in bytes.chunks(BYTES_PER_ELEMENT) {
+        let mut weight = Fr::one();
+        let mut element: HashMap<usize, Fr> = HashMap::new();
+        for idx in chunk {
+            *element.entry(*idx).or_insert(Fr::zero()) += weight;
+            weight *= Fr::from(256u64);
+        }
+        input_lcs.push(element);
+    }
+    emit_sponge_rows(&input_lcs, output, prefix, var_map, next_index, constraints);
+}
+
+//Gadget backend over an arkworks constraint system, values are None in setup mode
+struct Gadget<'a> {
+    cs: &'a ConstraintSystemRef<Fr>,
+}
+
+impl Backend for Gadget<'_> {
+    type Elem = (LinearCombination<Fr>, Option<Fr>);
+    type Error = SynthesisError;
+
+    fn constant(&mut self, c: Fr) -> Self::Elem {
+        (lc!() + (c, Variable::One), Some(c))
+    }
+
+    fn linear(&mut self, terms: &[(Fr, &Self::Elem)]) -> Self::Elem {
+        let mut out = LinearCombination::zero();
+        let mut value = Some(Fr::zero());
+        for (c, (lc, val)) in terms {
+            out = out + (*c, lc);
+            value = value.zip(*val).map(|(acc, v)| acc + *c * v);
+        }
+        (out, value)
+    }
+
+    fn sbox(&mut self, x: &Self::Elem) -> Result<Self::Elem, SynthesisError> {
+        let (x_lc, x_val) = x;
+        let x2_val = x_val.map(|v| v.square());
+        let x4_val = x2_val.map(|v| v.square());
+        let x5_val = x4_val.zip(*x_val).map(|(a, b)| a * b);
+        let x2 = self.cs.new_witness_variable(|| x2_val.ok_or(SynthesisError::AssignmentMissing))?;
+        let x4 = self.cs.new_witness_variable(|| x4_val.ok_or(SynthesisError::AssignmentMissing))?;
+        let x5 = self.cs.new_witness_variable(|| x5_val.ok_or(SynthesisError::AssignmentMissing))?;
+        self.cs.enforce_constraint(x_lc.clone(), x_lc.clone(), lc!() + x2)?;
+        self.cs.enforce_constraint(lc!() + x2, lc!() + x2, lc!() + x4)?;
+        self.cs.enforce_constraint(lc!() + x4, x_lc.clone(), lc!() + x5)?;
+        Ok((lc!() + x5, x5_val))
+    }
+}
+
+//Allocate a witness variable constrained to poseidon_hash(inputs) in an arkworks constraint system
+pub fn poseidon_hash_gadget(cs: &ConstraintSystemRef<Fr>, inputs: &[Variable]) -> Result<Variable, SynthesisError> {
+    let elems: Vec<(LinearCombination<Fr>, Option<Fr>)> =
+        inputs.iter().map(|v| (lc!() + *v, cs.assigned_value(*v))).collect();
+    let (out_lc, out_val) = sponge(&mut Gadget { cs }, &elems)?;
+    let out = cs.new_witness_variable(|| out_val.ok_or(SynthesisError::AssignmentMissing))?;
+    cs.enforce_constraint(out_lc, lc!() + Variable::One, lc!() + out)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use ark_std::UniformRand;
+
+    #[test]
+    fn test_native_matches_rows_and_gadget() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for n in 0..5 {
+            let inputs: Vec<Fr> = (0..n).map(|_| Fr::rand(&mut rng)).collect();
+            let expected = poseidon_hash(&inputs);
+
+            //Rows + witness trace
+            let mut var_map = HashMap::from([("1".to_string(), 0)]);
+            let mut next_index = 1;
+            let mut values = HashMap::from([(0, Fr::one())]);
+            let input_idx: Vec<usize> = (0..n)
+                .map(|i| {
+                    let idx = get_index(&format!("in{}", i), &mut var_map, &mut next_index);
+                    values.insert(idx, inputs[i]);
+                    idx
+                })
+                .collect();
+            let out_idx = get_index("out", &mut var_map, &mut next_index);
+            let mut constraints = Vec::new();
+            emit_poseidon_rows(&input_idx, out_idx, "h", &mut var_map, &mut next_index, &mut constraints);
+            assert_eq!(constraints.len(), num_constraints(n));
+
+            let (out, trace) = poseidon_witness(&inputs, "h");
+            assert_eq!(out, expected);
+            values.insert(out_idx, out);
+            for (name, val) in trace {
+                values.insert(var_map[&name], val);
+            }
+            assert_eq!(values.len(), var_map.len());
+            let eval = |lc: &HashMap<usize, Fr>| -> Fr { lc.iter().map(|(idx, c)| values[idx] * c).sum() };
+            for row in &constraints {
+                assert_eq!(eval(&row.a) * eval(&row.b), eval(&row.c));
+            }
+
+            //Gadget
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let vars: Vec<Variable> = inputs.iter().map(|v| cs.new_witness_variable(|| Ok(*v)).unwrap()).collect();
+            let out_var = poseidon_hash_gadget(&cs, &vars).unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            assert_eq!(cs.assigned_value(out_var), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_hash_separates_lengths_and_order() {
+        let (a, b) = (Fr::from(1u64), Fr::from(2u64));
+        assert_ne!(poseidon_hash(&[a, b]), poseidon_hash(&[b, a]));
+        assert_ne!(poseidon_hash(&[a]), poseidon_hash(&[a, Fr::zero()]));
+        assert_ne!(poseidon_hash(&[]), poseidon_hash(&[Fr::zero()]));
+    }
+
+    #[test]
+    fn test_hash_gate_uses_poseidon() {
+        use crate::{run_pipeline, Circuit, Gate, PipelineOptions};
+        let circuit = Circuit {
+            name: "hash".to_string(),
+            inputs: HashMap::from([("x".to_string(), 9)]),
+            gates: vec![Gate::Hash("x".to_string(), "h".to_string())],
+            sender: String::new(),
+            receiver: String::new(),
+            transfer_amount: 0,
+            ..Default::default()
+        };
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).expect("Witness computation failed");
+        assert_eq!(witness[&r1cs.var_map["h"]], poseidon_hash(&[Fr::from(9u64)]));
+        let result = run_pipeline(&circuit, PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+    }
+
+    #[test]
+    fn test_hashn_gate_matches_native_sponge() {
+        for n in 1..=5 {
+            let names: Vec<String> = (0..n).map(|i| format!("x{}", i)).collect();
+            let inputs: String = names.iter().enumerate().map(|(i, name)| format!("input {} {}\n", name, 10 * i + 3)).collect();
+            let dsl = format!("name commitment\n{}amount 5\nhashn {} -> h\n", inputs, names.join(" "));
+            let circuit = crate::parse_circuit_str(&dsl).unwrap();
+            let r1cs = circuit.to_r1cs_system();
+            let rows = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(0)).count();
+            assert_eq!(rows, num_constraints(n), "hashn of {} inputs", n);
+            let witness = circuit.compute_witness(&r1cs.var_map).expect("Witness computation failed");
+            let values: Vec<Fr> = (0..n).map(|i| Fr::from(10 * i as u64 + 3)).collect();
+            assert_eq!(witness[&r1cs.var_map["h"]], poseidon_hash(&values), "hashn of {} inputs", n);
+            assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None, "hashn of {} inputs", n);
+            if n == 4 {
+                let result = crate::run_pipeline(&circuit, crate::PipelineOptions::default()).expect("Pipeline failed");
+                assert_eq!(result.verified, Some(true));
+            }
+        }
+    }
+
+    #[test]
+    fn test_preimage_example_proves() {
+        let mut circuit = crate::parse_circuit("examples/preimage.txt").expect("Example must parse");
+        let digest = hash_bytes(b"correct horse ba");
+        assert_eq!(circuit.public_inputs, vec![("digest".to_string(), digest)]);
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(r1cs.public_input_names, vec!["transfer_amount_public".to_string(), "digest".to_string()]);
+
+        let options = crate::PipelineOptions { return_keys: true, ..crate::PipelineOptions::default() };
+        let result = crate::run_pipeline(&circuit, options).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+        assert_eq!(result.public_inputs[1], digest);
+        let (_, vk) = result.keys.expect("Keys were requested");
+        let mut other_digest = result.public_inputs.clone();
+        other_digest[1] = hash_bytes(b"correct horse bb");
+        assert!(!crate::verify(&vk, &result.proof, &other_digest).unwrap());
+
+        //A wrong preimage fails the eq, a byte out of range fails the type check
+        circuit.inputs.insert(crate::parser::byte_wire("msg", 15), b'b' as i32);
+        assert!(circuit.compute_witness(&r1cs.var_map).unwrap_err().contains("Assertion failed: wire 'h'"));
+        circuit.inputs.insert(crate::parser::byte_wire("msg", 15), 256 + b'a' as i32);
+        assert!(matches!(circuit.type_check(), Err(crate::TypeError::ValueOutOfRange { .. })));
+    }
+}
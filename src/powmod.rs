@@ -0,0 +1,271 @@
+//Modular exponentiation gate, out = base^e mod n for constants e and n below 2^64 (DSL
+//`powmod m 65537 3233 c`), square-and-multiply with one x * y = q * n + r step per bit
+use std::collections::HashMap;
+
+use ark_bls12_381::Fr;
+use ark_ff::{BigInteger, One, PrimeField};
+
+use crate::uint::{bit_name, bit_values, emit_bits, recomposition};
+use crate::{get_index, _R1CSConstraintInternal};
+
+//Width of the quotient of the base reduction, bounding the base to 2^64 * n
+const BASE_QUOTIENT_BITS: usize = 64;
+
+//Multiplication after the base reduction: the accumulator times itself or times the base
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Square,
+    MultiplyByBase,
+}
+
+//Left-to-right square-and-multiply over the bits of e below its leading one
+fn schedule(e: u64) -> Vec<Step> {
+    if e == 0 {
+        return Vec::new();
+    }
+    let mut steps = Vec::new();
+    for i in (0..63 - e.leading_zeros()).rev() {
+        steps.push(Step::Square);
+        if (e >> i) & 1 == 1 {
+            steps.push(Step::MultiplyByBase);
+        }
+    }
+    steps
+}
+
+//Bits of every remainder, enough for any value below n
+pub fn modulus_bits(n: u64) -> u32 {
+    64 - n.max(2).leading_zeros()
+}
+
+//Rows emitted by one powmod gate
+pub fn constraint_cost(e: u64, n: u64) -> usize {
+    let k = modulus_bits(n) as usize;
+    (BASE_QUOTIENT_BITS + 2 * k + 2) + schedule(e).len() * (3 * k + 2) + 1
+}
+
+//base^e mod n, the value the gate assigns to out (n must be at least 2)
+pub fn powmod(base: u64, e: u64, n: u64) -> u64 {
+    let n = n as u128;
+    let reduced = base as u128 % n;
+    let acc = schedule(e).iter().fold(reduced, |acc, step| match step {
+        Step::Square => acc * acc % n,
+        Step::MultiplyByBase => acc * reduced % n,
+    });
+    (if e == 0 { 1 % n } else { acc }) as u64
+}
+
+fn prefix(out: &str) -> String {
+    format!("{}_powmod", out)
+}
+
+//Native value of the base, which must be below 2^64 * n
+fn base_value(base: &str, n: u64, values: &HashMap<String, Fr>) -> Result<u128, String> {
+    let val = values.get(base).ok_or_else(|| format!("Var {} not found", base))?;
+    let le_bits = val.into_bigint().to_bits_le();
+    let native = le_bits.iter().take(128).rev().fold(0u128, |acc, bit| (acc << 1) | *bit as u128);
+    if le_bits.iter().skip(128).any(|bit| *bit) || (native / n as u128) >> BASE_QUOTIENT_BITS != 0 {
+        return Err(format!("Operand '{}' of powmod is {}, which is not below 2^64 * {}", base, val, n));
+    }
+    Ok(native)
+}
+
+//Wires the gate assigns: quotient, remainder and bound bits of every step, then out
+pub(crate) fn powmod_witness(base: &str, e: u64, n: u64, out: &str, values: &HashMap<String, Fr>) -> Result<Vec<(String, Fr)>, String> {
+    if n < 2 {
+        return Err(format!("Modulus of powmod must be at least 2, got {}", n));
+    }
+    let base_val = base_value(base, n, values)?;
+    let (k, wide_n) = (modulus_bits(n) as usize, n as u128);
+    let prefix = prefix(out);
+    let mut assigned = Vec::new();
+    let mut reduce = |step: usize, t: u128, quotient_bits: usize| {
+        let (q, r) = (t / wide_n, t % wide_n);
+        for (part, value, count) in [("q", q, quotient_bits), ("r", r, k), ("d", wide_n - 1 - r, k)] {
+            let part = format!("s{}{}", step, part);
+            assigned.extend(bit_values(value, count).map(|(i, bit)| (bit_name(&prefix, &part, i), bit)));
+        }
+        r
+    };
+
+    let reduced = reduce(0, base_val, BASE_QUOTIENT_BITS);
+    let mut acc = reduced;
+    for (i, step) in schedule(e).iter().enumerate() {
+        let y = match step {
+            Step::Square => acc,
+            Step::MultiplyByBase => reduced,
+        };
+        acc = reduce(i + 1, acc * y, k);
+    }
+    let out_val = if e == 0 { 1 % wide_n } else { acc };
+    assigned.push((out.to_string(), Fr::from(out_val)));
+    Ok(assigned)
+}
+
+//Emit the constraint_cost(e, n) rows of one gate, in the order of powmod_roles
+pub(crate) fn emit_powmod_rows(
+    base: &str,
+    e: u64,
+    n: u64,
+    out: &str,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    //A modulus below 2 has no witness (powmod_witness), the rows only need to be well formed
+    let n = n.max(2);
+    let one_idx = var_map["1"];
+    let base_idx = get_index(base, var_map, next_index);
+    let k = modulus_bits(n) as usize;
+    let prefix = prefix(out);
+    let n_fr = Fr::from(n);
+
+    //x * y = q * n + r and r + d = n - 1, returning r as a combination of its bits
+    let mut reduce = |step: usize, x: HashMap<usize, Fr>, y: HashMap<usize, Fr>, quotient_bits: usize,
+                      var_map: &mut HashMap<String, usize>, next_index: &mut usize| {
+        let q = emit_bits(&prefix, &format!("s{}q", step), quotient_bits, var_map, next_index, constraints);
+        let r = emit_bits(&prefix, &format!("s{}r", step), k, var_map, next_index, constraints);
+        let d = emit_bits(&prefix, &format!("s{}d", step), k, var_map, next_index, constraints);
+        let remainder = recomposition(&r);
+        let mut product = remainder.clone();
+        for (idx, coeff) in recomposition(&q) {
+            *product.entry(idx).or_default() += coeff * n_fr;
+        }
+        constraints.push(_R1CSConstraintInternal { a: x, b: y, c: product });
+        let mut bound = remainder.clone();
+        bound.extend(recomposition(&d));
+        constraints.push(_R1CSConstraintInternal {
+            a: bound,
+            b: HashMap::from([(one_idx, Fr::one())]),
+            c: HashMap::from([(one_idx, Fr::from(n - 1))]),
+        });
+        remainder
+    };
+
+    let one = HashMap::from([(one_idx, Fr::one())]);
+    let reduced = reduce(0, HashMap::from([(base_idx, Fr::one())]), one.clone(), BASE_QUOTIENT_BITS, var_map, next_index);
+    let mut acc = reduced.clone();
+    for (i, step) in schedule(e).iter().enumerate() {
+        let y = match step {
+            Step::Square => acc.clone(),
+            Step::MultiplyByBase => reduced.clone(),
+        };
+        acc = reduce(i + 1, acc, y, k, var_map, next_index);
+    }
+    let result = if e == 0 { HashMap::from([(one_idx, Fr::from(1 % n))]) } else { acc };
+    let out_idx = get_index(out, var_map, next_index);
+    constraints.push(_R1CSConstraintInternal { a: result, b: one, c: HashMap::from([(out_idx, Fr::one())]) });
+}
+
+//Role of each row emit_powmod_rows emits
+pub(crate) fn powmod_roles(base: &str, e: u64, n: u64) -> Vec<String> {
+    let k = modulus_bits(n) as usize;
+    let mut roles = Vec::with_capacity(constraint_cost(e, n));
+    let labels = std::iter::once(format!("reduction of {}", base)).chain(schedule(e).into_iter().enumerate().map(|(i, step)| match step {
+        Step::Square => format!("step {} (square)", i + 1),
+        Step::MultiplyByBase => format!("step {} (multiply by {})", i + 1, base),
+    }));
+    for (step, label) in labels.enumerate() {
+        let quotient_bits = if step == 0 { BASE_QUOTIENT_BITS } else { k };
+        roles.extend((0..quotient_bits).map(|i| format!("booleanity of {} quotient bit {}", label, i)));
+        roles.extend((0..k).map(|i| format!("booleanity of {} remainder bit {}", label, i)));
+        roles.extend((0..k).map(|i| format!("booleanity of {} bound bit {}", label, i)));
+        roles.push(format!("{}: product equals quotient * {} + remainder", label, n));
+        roles.push(format!("{}: remainder below {}", label, n));
+    }
+    roles.push("output equation".to_string());
+    roles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{i64_to_fr, parse_circuit_str, run_pipeline, Circuit, Gate, PipelineOptions};
+
+    //Textbook RSA: p = 61, q = 53, n = 3233, e = 17, d = 2753
+    const DSL: &str = "name rsa
+input m 65
+public ciphertext 2790
+powmod m 17 3233 c
+eq c ciphertext
+powmod c 2753 3233 decrypted
+eq decrypted m
+";
+
+    #[test]
+    fn test_textbook_rsa_roundtrip() {
+        assert_eq!(powmod(65, 17, 3233), 2790);
+        assert_eq!(powmod(2790, 2753, 3233), 65);
+        let circuit = parse_circuit_str(DSL).unwrap();
+        assert!(matches!(&circuit.gates[0], Gate::PowMod(base, 17, 3233, out) if base == "m" && out == "c"));
+        let r1cs = circuit.to_r1cs_system();
+        //Every row of the expansion is attributed to its gate
+        for (gate_index, e) in [(0, 17), (2, 2753)] {
+            let origins: Vec<_> = r1cs.constraint_origins.iter().filter(|origin| origin.gate_index == Some(gate_index)).collect();
+            assert_eq!(origins.len(), constraint_cost(e, 3233));
+            assert!(origins.iter().all(|origin| origin.gate.starts_with("PowMod")));
+        }
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        assert_eq!(witness[&r1cs.var_map["c"]], Fr::from(2790u64));
+        assert_eq!(witness[&r1cs.var_map["decrypted"]], Fr::from(65u64));
+
+        let options = PipelineOptions { return_keys: true, ..PipelineOptions::default() };
+        let result = run_pipeline(&circuit, options).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+        let (_, vk) = result.keys.expect("Keys were requested");
+        let mut other = result.public_inputs.clone();
+        other[1] = i64_to_fr(2791);
+        assert!(!crate::verify(&vk, &result.proof, &other).unwrap());
+    }
+
+    #[test]
+    fn test_unreduced_remainder_is_rejected() {
+        //30^3 = 27000 = 27 * 1000 + 0, also 26 * 1000 + 1000 with a remainder that fits 10 bits
+        let circuit = parse_circuit_str("name unreduced\ninput x 30\namount 5\npowmod x 3 1000 y\n").unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        let mut witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None);
+        let prefix = prefix("y");
+        for (part, value, count) in [("s2q", 26, 10), ("s2r", 1000, 10)] {
+            for (i, bit) in bit_values(value, count) {
+                witness.insert(r1cs.var_map[&bit_name(&prefix, part, i)], bit);
+            }
+        }
+        witness.insert(r1cs.var_map["y"], Fr::from(1000u64));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("The remainder must be below n");
+        assert!(r1cs.explain(failing).contains("step 2 (multiply by x): remainder below 1000"), "{}", r1cs.explain(failing));
+    }
+
+    #[test]
+    fn test_matches_native_modpow() {
+        let n64 = u64::MAX - 58;
+        for (base, e, n) in [(2, 10, 1000), (7, 0, 13), (0, 5, 97), (12, 1, 5), (u32::MAX as u64, 65537, 3233), (3, 65537, n64), (1 << 40, 3, n64)] {
+            let circuit = Circuit {
+                name: "powmod".to_string(),
+                inputs: HashMap::new(),
+                gates: vec![Gate::PowMod("x".to_string(), e, n, "y".to_string())],
+                sender: "alice".to_string(),
+                receiver: "bob".to_string(),
+                transfer_amount: 5,
+                ..Default::default()
+            };
+            let r1cs = circuit.to_r1cs_system();
+            assert_eq!(r1cs.raw_constraints.len(), 1 + constraint_cost(e, n));
+            let mut values = circuit.input_wire_values();
+            values.insert("x".to_string(), Fr::from(base));
+            values.extend(crate::eval_gate(&circuit.gates[0], &values).unwrap());
+            assert_eq!(values["y"], Fr::from(powmod(base, e, n)), "{}^{} mod {}", base, e, n);
+            let witness: HashMap<usize, Fr> = r1cs.var_map.iter().map(|(name, idx)| (*idx, values[name])).collect();
+            assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None, "{}^{} mod {}", base, e, n);
+        }
+        assert_eq!(powmod(2, 10, 1000), 24);
+        assert_eq!(constraint_cost(65537, 3233), 737);
+
+        //A base beyond 2^64 * n has no witness
+        let mut values = HashMap::from([("x".to_string(), Fr::from(1u128 << 70))]);
+        let err = crate::eval_gate(&Gate::PowMod("x".to_string(), 3, 5, "y".to_string()), &values).unwrap_err();
+        assert!(err.contains("not below 2^64 * 5"), "{}", err);
+        values.insert("x".to_string(), Fr::from(4u64));
+        assert!(crate::eval_gate(&Gate::PowMod("x".to_string(), 3, 1, "y".to_string()), &values).is_err());
+    }
+}
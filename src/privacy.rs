@@ -0,0 +1,112 @@
+//Privacy audit of the public inputs: flags every public input that copies a private input,
+//by name or by value in a sample witness
+use std::fmt;
+
+use crate::{Circuit, R1CSSystem};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeakReason {
+    //The public input is the transfer spec copy of the party's balance input
+    NameCorrelation,
+    //The public input has the private input's value in the sample witness
+    EqualValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakWarning {
+    pub public_input: String,
+    pub private_input: String,
+    pub reason: LeakReason,
+}
+
+impl fmt::Display for LeakWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            LeakReason::NameCorrelation => write!(
+                f,
+                "Public input '{}' reveals the private input '{}' (use `balances private` to keep balances out of the public inputs)",
+                self.public_input, self.private_input
+            ),
+            LeakReason::EqualValue => write!(
+                f,
+                "Public input '{}' has the same value as the private input '{}' in the sample witness",
+                self.public_input, self.private_input
+            ),
+        }
+    }
+}
+
+impl R1CSSystem {
+    //Public inputs that copy private inputs of the circuit this system was built from. Value
+    //matches of 0 and 1 are too common to mean anything and are not reported
+    pub fn privacy_report(&self, circuit: &Circuit) -> Vec<LeakWarning> {
+        let mut private: Vec<(&String, &i32)> = circuit.inputs.iter().collect();
+        private.sort();
+        let sample = circuit.compute_witness(&self.var_map).ok().and_then(|witness| self.public_inputs_from_witness(&witness).ok());
+
+        let mut warnings = Vec::new();
+        for (i, public_input) in self.public_input_names.iter().enumerate() {
+            for (name, value) in &private {
+                let reason = if *public_input == format!("{}_initial_balance", name) {
+                    LeakReason::NameCorrelation
+                } else if sample.as_ref().is_some_and(|values| **value > 1 && values[i] == crate::i32_to_fr(**value)) {
+                    LeakReason::EqualValue
+                } else {
+                    continue;
+                };
+                warnings.push(LeakWarning { public_input: public_input.clone(), private_input: name.to_string(), reason });
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples_support::TRANSFER_DSL;
+    use crate::{parse_circuit_str, run_pipeline, PipelineOptions};
+
+    #[test]
+    fn test_private_balances_silence_the_report() {
+        let circuit = parse_circuit_str(TRANSFER_DSL).unwrap();
+        let r1cs = circuit.to_r1cs_system();
+        let leaks: Vec<(String, String, LeakReason)> =
+            r1cs.privacy_report(&circuit).into_iter().map(|w| (w.public_input, w.private_input, w.reason)).collect();
+        assert_eq!(
+            leaks,
+            vec![
+                ("alice_initial_balance".to_string(), "alice".to_string(), LeakReason::NameCorrelation),
+                ("bob_initial_balance".to_string(), "bob".to_string(), LeakReason::NameCorrelation),
+            ]
+        );
+        //A private input copied into a declared public input is caught by its value
+        let mut copied = circuit.clone();
+        copied.inputs.insert("secret".to_string(), 4242);
+        copied.public_inputs.push(("leak".to_string(), crate::i32_to_fr(4242)));
+        let r1cs_copied = copied.to_r1cs_system();
+        let warnings = r1cs_copied.privacy_report(&copied);
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[2], LeakWarning { public_input: "leak".to_string(), private_input: "secret".to_string(), reason: LeakReason::EqualValue });
+
+        let private = parse_circuit_str(&format!("{}balances private\n", TRANSFER_DSL)).unwrap();
+        let r1cs = private.to_r1cs_system();
+        assert!(r1cs.privacy_report(&private).is_empty());
+        assert_eq!(r1cs.public_input_names, vec!["transfer_amount_public".to_string(), crate::INPUT_DIGEST_WIRE.to_string()]);
+        let result = run_pipeline(&private, PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+
+        //Spending more than the balance has no witness, and a forged one breaks the range check (a
+        //forged initial balance alone breaks its equality with the input wire)
+        let mut overdraft = private.clone();
+        overdraft.transfer_amount = 101;
+        let err = overdraft.compute_witness(&r1cs.var_map).unwrap_err();
+        assert_eq!(err, "Balance of sender 'alice' is below the transfer amount 101");
+        let mut witness = private.compute_witness(&r1cs.var_map).unwrap();
+        witness.insert(r1cs.var_map["alice"], crate::i32_to_fr(29));
+        witness.insert(r1cs.var_map["alice_initial_balance"], crate::i32_to_fr(29));
+        witness.insert(r1cs.var_map["alice_balance_after"], crate::i32_to_fr(-1));
+        let failing = r1cs.which_is_unsatisfied(&witness).unwrap().expect("-1 is not a u32");
+        assert!(r1cs.explain(failing).contains("balance sufficiency"), "{}", r1cs.explain(failing));
+    }
+}
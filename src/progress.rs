@@ -0,0 +1,73 @@
+//Progress reporting for the long running stages (setup and proving)
+
+//Coarse-grained stage events emitted by setup/prove
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    SetupStarted,
+    SetupFinished,
+    ProvingStarted,
+    ProvingFinished,
+    //Constraint synthesis of the R1CS system into the arkworks constraint system
+    SynthesisStarted { num_constraints: usize, num_variables: usize },
+    SynthesisFinished { num_constraints: usize, num_instance_variables: usize, num_witness_variables: usize },
+}
+
+//Receiver of progress events, implemented for closures so callers can pass |event| { ... }
+pub trait ProgressSink {
+    fn on_event(&self, event: ProgressEvent);
+}
+
+impl<F: Fn(ProgressEvent)> ProgressSink for F {
+    fn on_event(&self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
+//Default sink used by setup/prove, ignores every event
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn on_event(&self, _event: ProgressEvent) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mul_circuit;
+    use crate::{prove_with_progress, setup_with_progress};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_recording_sink_event_sequence() {
+        let circuit = mul_circuit("progress");
+        let r1cs = circuit.to_r1cs_system();
+        let events = RefCell::new(Vec::new());
+        let sink = |event: ProgressEvent| events.borrow_mut().push(event);
+
+        let (pk, _vk) = setup_with_progress(&r1cs, &sink).expect("Setup failed");
+        let witness = circuit.compute_witness(&r1cs.var_map).expect("Witness computation failed");
+        prove_with_progress(&r1cs, &pk, witness, &sink).expect("Proof generation failed");
+
+        let synthesis_started = ProgressEvent::SynthesisStarted { num_constraints: 2, num_variables: 5 };
+        let synthesis_finished = ProgressEvent::SynthesisFinished {
+            num_constraints: 2,
+            //arkworks' own constant one, our explicit "1" and transfer_amount_public
+            num_instance_variables: 3,
+            num_witness_variables: 3,
+        };
+        assert_eq!(
+            events.into_inner(),
+            vec![
+                ProgressEvent::SetupStarted,
+                synthesis_started.clone(),
+                synthesis_finished.clone(),
+                ProgressEvent::SetupFinished,
+                ProgressEvent::ProvingStarted,
+                synthesis_started,
+                synthesis_finished,
+                ProgressEvent::ProvingFinished,
+            ]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//A prover bound to one R1CS system and proving key, shared behind an Arc by every proof,
+//for relayers proving many witnesses
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use ark_bls12_381::Fr;
+use ark_std::rand::rngs::OsRng;
+
+use crate::{prove_shared, NoProgress, Proof, ProveError, ProvingKey, R1CSSystem};
+
+//Witness values by R1CS variable index, as returned by Circuit::compute_witness
+pub type Witness = HashMap<usize, Fr>;
+
+#[derive(Clone)]
+pub struct Prover {
+    r1cs_system: Arc<R1CSSystem>,
+    pk: Arc<ProvingKey>,
+    max_in_flight: usize,
+}
+
+impl Prover {
+    pub fn new(r1cs_system: Arc<R1CSSystem>, pk: Arc<ProvingKey>) -> Self {
+        let max_in_flight = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Prover { r1cs_system, pk, max_in_flight }
+    }
+
+    //Number of witnesses prove_stream proves (and holds) at once, the number of cores by default
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    pub fn prove(&self, witness: Witness) -> Result<Proof, ProveError> {
+        prove_shared(self.r1cs_system.clone(), &self.pk, witness, &mut OsRng, &NoProgress)
+    }
+
+    //Prove every witness, in parallel; the iterator is lazy and yields one result per witness
+    pub fn prove_stream<I: IntoIterator<Item = Witness>>(&self, witnesses: I) -> ProofStream<'_, I::IntoIter> {
+        ProofStream { prover: self, witnesses: witnesses.into_iter(), ready: VecDeque::new() }
+    }
+}
+
+pub struct ProofStream<'a, I> {
+    prover: &'a Prover,
+    witnesses: I,
+    //Results of the last window not yet yielded
+    ready: VecDeque<Result<Proof, ProveError>>,
+}
+
+impl<I: Iterator<Item = Witness>> Iterator for ProofStream<'_, I> {
+    type Item = Result<Proof, ProveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() {
+            let window: Vec<Witness> = self.witnesses.by_ref().take(self.prover.max_in_flight).collect();
+            let prover = self.prover;
+            self.ready = std::thread::scope(|scope| {
+                let handles: Vec<_> = window.into_iter().map(|witness| scope.spawn(move || prover.prove(witness))).collect();
+                handles.into_iter().map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect()
+            });
+        }
+        self.ready.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{i32_to_fr, setup, verify, Circuit, Gate};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_prove_stream_of_32_witnesses() {
+        assert_send_sync::<Prover>();
+        let mut circuit = Circuit {
+            name: "test_add".to_string(),
+            inputs: HashMap::from([("a".to_string(), 10), ("b".to_string(), 20)]),
+            gates: vec![
+                Gate::Add("a".to_string(), "b".to_string(), "c".to_string(), None),
+                Gate::Add("c".to_string(), "transfer_amount_public".to_string(), "d".to_string(), None),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        let r1cs = Arc::new(circuit.to_r1cs_system());
+        let (pk, vk) = setup(&r1cs).expect("Setup failed");
+        let witnesses: Vec<Witness> = (0..32)
+            .map(|i| {
+                circuit.inputs.insert("a".to_string(), i);
+                circuit.compute_witness(&r1cs.var_map).unwrap()
+            })
+            .collect();
+        let public_inputs = r1cs.public_inputs_from_witness(&witnesses[0]).unwrap();
+        assert_eq!(public_inputs, vec![i32_to_fr(5)]);
+
+        let prover = Prover::new(r1cs.clone(), Arc::new(pk)).with_max_in_flight(8);
+        let proofs: Vec<Proof> = prover.prove_stream(witnesses.clone()).collect::<Result<_, _>>().expect("Proving failed");
+        assert_eq!(proofs.len(), 32);
+        assert!(proofs.iter().all(|proof| verify(&vk, proof, &public_inputs).unwrap()));
+
+        //An incomplete witness fails on its own, the others in its window still prove
+        let mut bad = witnesses[1].clone();
+        bad.remove(&r1cs.var_map["d"]);
+        let results: Vec<_> = prover.prove_stream(vec![witnesses[0].clone(), bad, witnesses[2].clone()]).collect();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[1], Err(ProveError::InvalidCircuit(_))));
+        assert!(results[0].is_ok() && results[2].is_ok());
+    }
+}
@@ -0,0 +1,226 @@
+//Registry of verifiable circuits keyed by id, each stored as <dir>/<id>/ with vk.bin and metadata.json
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_crypto_primitives::snark::SNARK;
+use ark_groth16::{Groth16, PreparedVerifyingKey};
+use ark_relations::r1cs::SynthesisError;
+use ark_serialize::SerializationError;
+use serde::{Deserialize, Serialize};
+
+use crate::artifact::ArtifactError;
+use crate::{with_constant_one, Proof, R1CSSystem, VerifyingKey};
+
+const VK_FILE: &str = "vk.bin";
+const METADATA_FILE: &str = "metadata.json";
+
+//Describes what a circuit's public inputs mean
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitMetadata {
+    #[serde(default)]
+    pub description: String,
+    //Names of the public inputs, in verification order
+    pub public_inputs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merkle_depth: Option<usize>,
+}
+
+impl CircuitMetadata {
+    pub fn for_system(r1cs_system: &R1CSSystem, description: &str) -> Self {
+        CircuitMetadata {
+            description: description.to_string(),
+            public_inputs: r1cs_system.public_input_names.clone(),
+            merkle_depth: None,
+        }
+    }
+}
+
+pub struct RegisteredCircuit {
+    pub vk: VerifyingKey,
+    pub metadata: CircuitMetadata,
+    prepared_vk: PreparedVerifyingKey<Bls12_381>,
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    UnknownCircuit(String),
+    Io(std::io::Error),
+    Serialization(SerializationError),
+    Artifact { id: String, error: ArtifactError },
+    Metadata(String),
+    PublicInputCount { expected: usize, found: usize },
+    Verification(SynthesisError),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::UnknownCircuit(id) => write!(f, "Unknown circuit id '{}'", id),
+            RegistryError::Io(e) => write!(f, "Registry I/O error: {}", e),
+            RegistryError::Serialization(e) => write!(f, "Invalid verifying key: {}", e),
+            RegistryError::Artifact { id, error } => write!(f, "Invalid verifying key of circuit '{}': {}", id, error),
+            RegistryError::Metadata(e) => write!(f, "Invalid circuit metadata: {}", e),
+            RegistryError::PublicInputCount { expected, found } => {
+                write!(f, "Expected {} public inputs, got {}", expected, found)
+            }
+            RegistryError::Verification(e) => write!(f, "Verification failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<std::io::Error> for RegistryError {
+    fn from(e: std::io::Error) -> Self {
+        RegistryError::Io(e)
+    }
+}
+
+impl From<SerializationError> for RegistryError {
+    fn from(e: SerializationError) -> Self {
+        RegistryError::Serialization(e)
+    }
+}
+
+#[derive(Default)]
+pub struct CircuitRegistry {
+    circuits: BTreeMap<String, RegisteredCircuit>,
+}
+
+impl CircuitRegistry {
+    pub fn new() -> Self {
+        CircuitRegistry::default()
+    }
+
+    //Load every <id>/ subdirectory of dir
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        Self::load_dir_with_legacy(dir, false)
+    }
+
+    //load_dir, optionally accepting headerless vk.bin files written before artifact headers
+    pub fn load_dir_with_legacy(dir: impl AsRef<Path>, allow_legacy: bool) -> Result<Self, RegistryError> {
+        let mut registry = CircuitRegistry::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let id = path.file_name().and_then(|n| n.to_str())
+                .ok_or_else(|| RegistryError::Metadata(format!("Non UTF-8 circuit directory {:?}", path)))?
+                .to_string();
+            let vk = VerifyingKey::from_artifact(&fs::read(path.join(VK_FILE))?, None, allow_legacy)
+                .map_err(|error| RegistryError::Artifact { id: id.clone(), error })?;
+            let metadata = serde_json::from_slice(&fs::read(path.join(METADATA_FILE))?)
+                .map_err(|e| RegistryError::Metadata(format!("{}: {}", id, e)))?;
+            registry.register(&id, vk, metadata)?;
+        }
+        Ok(registry)
+    }
+
+    //Write a circuit in the layout load_dir reads
+    pub fn save_entry(dir: impl AsRef<Path>, id: &str, vk: &VerifyingKey, metadata: &CircuitMetadata) -> Result<(), RegistryError> {
+        let entry_dir = dir.as_ref().join(id);
+        fs::create_dir_all(&entry_dir)?;
+        fs::write(entry_dir.join(VK_FILE), vk.to_artifact(None)?)?;
+        let json = serde_json::to_vec_pretty(metadata).map_err(|e| RegistryError::Metadata(e.to_string()))?;
+        fs::write(entry_dir.join(METADATA_FILE), json)?;
+        Ok(())
+    }
+
+    //Register (or replace) a circuit, preparing its verifying key once
+    pub fn register(&mut self, id: &str, vk: VerifyingKey, metadata: CircuitMetadata) -> Result<(), RegistryError> {
+        let prepared_vk = Groth16::<Bls12_381>::process_vk(vk.inner()).map_err(RegistryError::Verification)?;
+        self.circuits.insert(id.to_string(), RegisteredCircuit { vk, metadata, prepared_vk });
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<&RegisteredCircuit, RegistryError> {
+        self.circuits.get(id).ok_or_else(|| RegistryError::UnknownCircuit(id.to_string()))
+    }
+
+    //Registered ids (sorted) with their metadata
+    pub fn list(&self) -> Vec<(&str, &CircuitMetadata)> {
+        self.circuits.iter().map(|(id, c)| (id.as_str(), &c.metadata)).collect()
+    }
+
+    //Verify a proof against the circuit registered under id; public inputs are ordered like verify()
+    pub fn verify(&self, id: &str, proof: &Proof, public_inputs: &[Fr]) -> Result<bool, RegistryError> {
+        let circuit = self.get(id)?;
+        let expected = circuit.metadata.public_inputs.len();
+        if public_inputs.len() != expected {
+            return Err(RegistryError::PublicInputCount { expected, found: public_inputs.len() });
+        }
+        Groth16::<Bls12_381>::verify_with_processed_vk(&circuit.prepared_vk, &with_constant_one(public_inputs), &proof.0)
+            .map_err(RegistryError::Verification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_pipeline, Circuit, Gate, PipelineOptions};
+    use std::collections::HashMap;
+
+    fn circuit(name: &str, gates: Vec<Gate>, inputs: &[(&str, i32)]) -> Circuit {
+        Circuit {
+            name: name.to_string(),
+            inputs: inputs.iter().map(|(n, v)| (n.to_string(), *v)).collect::<HashMap<_, _>>(),
+            gates,
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_registry_dispatches_by_id() {
+        let transfer = circuit(
+            "transfer",
+            vec![Gate::Sub("alice".to_string(), "transfer_amount_public".to_string(), "alice_after".to_string(), None)],
+            &[("alice", 20), ("bob", 1)],
+        );
+        let deposit = circuit("deposit", vec![Gate::Hash("secret".to_string(), "note".to_string())], &[("secret", 77)]);
+
+        let dir = std::env::temp_dir().join(format!("zk_registry_test_{}", std::process::id()));
+        let mut proofs = Vec::new();
+        for (id, c) in [("transfer", &transfer), ("deposit", &deposit)] {
+            let result = run_pipeline(c, PipelineOptions { return_keys: true, verify: false, ..PipelineOptions::default() })
+                .expect("Pipeline failed");
+            let (_, vk) = result.keys.expect("Keys were requested");
+            let metadata = CircuitMetadata::for_system(&c.to_r1cs_system(), c.name.as_str());
+            CircuitRegistry::save_entry(&dir, id, &vk, &metadata).unwrap();
+            proofs.push((id, result.proof, result.public_inputs));
+        }
+
+        let registry = CircuitRegistry::load_dir(&dir).unwrap();
+        //A headerless key is only accepted with the legacy flag
+        let legacy_vk = registry.get("deposit").unwrap().vk.to_bytes().unwrap();
+        fs::write(dir.join("deposit").join(VK_FILE), legacy_vk).unwrap();
+        assert!(matches!(
+            CircuitRegistry::load_dir(&dir),
+            Err(RegistryError::Artifact { ref id, error: ArtifactError::MissingHeader }) if id == "deposit"
+        ));
+        assert!(CircuitRegistry::load_dir_with_legacy(&dir, true).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+        let ids: Vec<&str> = registry.list().iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!["deposit", "transfer"]);
+        assert_eq!(registry.get("transfer").unwrap().metadata.public_inputs.len(), 3);
+
+        for (id, proof, publics) in &proofs {
+            assert!(registry.verify(id, proof, publics).unwrap(), "{} proof must verify", id);
+        }
+        //The transfer proof does not verify as a deposit
+        let (_, transfer_proof, _) = &proofs[0];
+        let (_, _, deposit_publics) = &proofs[1];
+        assert!(!registry.verify("deposit", transfer_proof, deposit_publics).unwrap());
+        assert!(matches!(registry.verify("withdraw", transfer_proof, deposit_publics), Err(RegistryError::UnknownCircuit(_))));
+        assert!(matches!(
+            registry.verify("transfer", transfer_proof, deposit_publics),
+            Err(RegistryError::PublicInputCount { expected: 3, found: 1 })
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+//Interactive circuit construction for the `repl` subcommand, main.rs feeds it one line at a time.
+//Lines starting with ':' are commands (:witness, :r1cs, :prove, :verify, :reset, :save), others DSL statements
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use ark_bls12_381::Fr;
+use ark_ff::{One, PrimeField, Zero};
+
+use crate::{parse_circuit_str, run_pipeline, verify, Circuit, KeyCache, PipelineOptions, Proof, R1CSSystem, WitnessEngine};
+
+pub struct Repl {
+    //The DSL statements accepted so far, the working circuit is their parse
+    lines: Vec<String>,
+    circuit: Option<Circuit>,
+    cache: KeyCache,
+    //Rows :r1cs already printed, by their origin and content
+    printed: HashSet<String>,
+    //Engine of the current circuit, its variable names by index, and the inputs redefined since
+    //its last computation
+    engine: Option<(WitnessEngine, Vec<String>)>,
+    changed_inputs: HashMap<String, i32>,
+    //Proof and public inputs of the last :prove
+    proof: Option<(Proof, Vec<Fr>)>,
+}
+
+//Variable names by index
+fn names(r1cs: &R1CSSystem) -> Vec<String> {
+    let mut names = vec![String::new(); r1cs.num_variables];
+    for (name, idx) in &r1cs.var_map {
+        names[*idx] = name.clone();
+    }
+    names
+}
+
+//A coefficient as a small signed integer where it is one
+fn signed(coeff: &Fr) -> String {
+    let negated = -*coeff;
+    if negated.into_bigint() < coeff.into_bigint() {
+        format!("-{}", negated)
+    } else {
+        coeff.to_string()
+    }
+}
+
+//A side of a row, e.g. "(a + 2*b - c)", terms by variable index
+fn linear_combination(terms: &HashMap<usize, Fr>, names: &[String]) -> String {
+    let mut sorted: Vec<(&usize, &Fr)> = terms.iter().filter(|(_, coeff)| !coeff.is_zero()).collect();
+    sorted.sort_by_key(|(idx, _)| **idx);
+    let parts: Vec<String> = sorted
+        .iter()
+        .map(|(idx, coeff)| match (coeff.is_one(), (-**coeff).is_one()) {
+            (true, _) => names[**idx].clone(),
+            (_, true) => format!("-{}", names[**idx]),
+            _ => format!("{}*{}", signed(coeff), names[**idx]),
+        })
+        .collect();
+    match parts.len() {
+        0 => "0".to_string(),
+        1 => parts[0].clone(),
+        _ => format!("({})", parts.join(" + ").replace("+ -", "- ")),
+    }
+}
+
+impl Repl {
+    pub fn new(cache: KeyCache) -> Self {
+        Repl {
+            lines: Vec::new(),
+            circuit: None,
+            cache,
+            printed: HashSet::new(),
+            engine: None,
+            changed_inputs: HashMap::new(),
+            proof: None,
+        }
+    }
+
+    //The accepted statements, one per line
+    pub fn source(&self) -> String {
+        self.lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    //Run one line, returning what to print
+    pub fn eval(&mut self, line: &str) -> Result<String, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            return Ok(String::new());
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            [":witness"] => self.witness(),
+            [":r1cs"] => self.r1cs(),
+            [":prove"] => self.prove(),
+            [":verify"] => self.verify(),
+            [":reset"] => {
+                *self = Repl::new(KeyCache::new(self.cache.dir()));
+                Ok("Circuit cleared".to_string())
+            }
+            [":save", path] => {
+                fs::write(path, self.source()).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+                Ok(format!("Wrote {} statements to {}", self.lines.len(), path))
+            }
+            [command, ..] if command.starts_with(':') => {
+                Err(format!("Unknown command {}: expected :witness, :r1cs, :prove, :verify, :reset or :save <file>", command))
+            }
+            _ => self.statement(line, &parts),
+        }
+    }
+
+    fn statement(&mut self, line: &str, parts: &[&str]) -> Result<String, String> {
+        let circuit = parse_circuit_str(&format!("{}{}\n", self.source(), line)).map_err(|e| e.to_string())?;
+        //A new value for an existing input keeps the engine, which only re-evaluates its readers
+        let redefined = match (parts, &self.circuit) {
+            (["input", name, val], Some(current)) if current.inputs.contains_key(*name) => val.parse::<i32>().ok().map(|val| (name.to_string(), val)),
+            _ => None,
+        };
+        match redefined {
+            Some((name, val)) if self.engine.is_some() => {
+                self.changed_inputs.insert(name, val);
+            }
+            _ => {
+                self.engine = None;
+                self.changed_inputs.clear();
+            }
+        }
+        self.lines.push(line.to_string());
+        self.circuit = Some(circuit);
+        Ok(String::new())
+    }
+
+    fn circuit(&self) -> Result<&Circuit, String> {
+        self.circuit.as_ref().ok_or_else(|| "The circuit is empty, enter DSL statements first".to_string())
+    }
+
+    fn witness(&mut self) -> Result<String, String> {
+        if self.engine.is_none() {
+            let circuit = self.circuit()?;
+            let r1cs = circuit.to_r1cs_system();
+            self.engine = Some((WitnessEngine::new(circuit, &r1cs), names(&r1cs)));
+        }
+        let (engine, names) = self.engine.as_mut().expect("The engine was just built");
+        //Falls back to a full computation the first time
+        let witness = engine.recompute(&self.changed_inputs)?;
+        self.changed_inputs.clear();
+        Ok(names.iter().enumerate().map(|(idx, name)| format!("{} = {}", name, witness[&idx])).collect::<Vec<_>>().join("\n"))
+    }
+
+    fn r1cs(&mut self) -> Result<String, String> {
+        let r1cs = self.circuit()?.to_r1cs_system();
+        let names = names(&r1cs);
+        let mut rows = Vec::new();
+        for (row, constraint) in r1cs.raw_constraints.iter().enumerate() {
+            let equation = format!(
+                "{} * {} = {}",
+                linear_combination(&constraint.a, &names),
+                linear_combination(&constraint.b, &names),
+                linear_combination(&constraint.c, &names)
+            );
+            let origin = match r1cs.constraint_origins.get(row) {
+                Some(origin) => match origin.gate_index {
+                    Some(gate_index) => format!("{} of gate #{} {}", origin.role, gate_index, origin.gate),
+                    None => format!("{} of {}", origin.role, origin.gate),
+                },
+                None => String::new(),
+            };
+            if self.printed.insert(format!("{} {}", equation, origin)) {
+                rows.push(format!("{}: {}    // {}", row, equation, origin));
+            }
+        }
+        if rows.is_empty() {
+            return Ok("No new constraints".to_string());
+        }
+        Ok(rows.join("\n"))
+    }
+
+    fn prove(&mut self) -> Result<String, String> {
+        let circuit = self.circuit()?;
+        let options = PipelineOptions { cache: Some(&self.cache), verify: false, ..PipelineOptions::default() };
+        let result = run_pipeline(circuit, options).map_err(|e| e.to_string())?;
+        let summary = format!(
+            "Proof generated: {} constraints, {} public inputs ({})",
+            result.stats.num_constraints,
+            result.public_inputs.len(),
+            if result.cache_hit == Some(true) { "cached keys" } else { "new keys" }
+        );
+        self.proof = Some((result.proof, result.public_inputs));
+        Ok(summary)
+    }
+
+    fn verify(&mut self) -> Result<String, String> {
+        let (proof, public_inputs) = self.proof.as_ref().ok_or(":verify needs a proof, run :prove first")?;
+        let circuit = self.circuit.as_ref().ok_or(":verify needs a circuit")?;
+        let (_, vk) = self.cache.get_or_setup(circuit).map_err(|e| e.to_string())?;
+        let valid = verify(&vk, proof, public_inputs).map_err(|e| format!("Verification failed: {}", e))?;
+        Ok(format!("Verification Result: {}", valid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_session() {
+        let dir = std::env::temp_dir().join(format!("zk_repl_test_{}", std::process::id()));
+        let mut repl = Repl::new(KeyCache::new(dir.join("keys")));
+        let mut run = |line: &str| repl.eval(line);
+
+        assert!(run(":witness").unwrap_err().contains("The circuit is empty"));
+        for line in ["name session", "input a 3", "input b 4", "mul a b c"] {
+            assert_eq!(run(line), Ok(String::new()), "{}", line);
+        }
+        let witness = run(":witness").unwrap();
+        assert!(witness.contains("\nc = 12"), "{}", witness);
+
+        //:r1cs only prints the rows added since the last one
+        let first = run(":r1cs").unwrap();
+        assert!(first.contains("a * b = c    // output equation of gate #0"), "{}", first);
+        run("sub c a d").unwrap();
+        let second = run(":r1cs").unwrap();
+        assert!(second.contains("(-a + c) * 1 = d    // output equation of gate #1"), "{}", second);
+        assert!(!second.contains("a * b = c"), "{}", second);
+        assert_eq!(run(":r1cs"), Ok("No new constraints".to_string()));
+
+        //A redefined input is recomputed, a bad statement leaves the circuit alone
+        run("input a 5").unwrap();
+        let witness = run(":witness").unwrap();
+        assert!(witness.contains("\nc = 20") && witness.contains("\nd = 15"), "{}", witness);
+        assert!(run("frobnicate a").unwrap_err().contains("Unknown or malformed line"));
+        assert!(run("mul a b c").is_err());
+        assert!(run(":frobnicate").unwrap_err().starts_with("Unknown command :frobnicate"));
+
+        assert!(run(":verify").unwrap_err().contains("run :prove first"));
+        assert!(run(":prove").unwrap().ends_with("(new keys)"));
+        assert_eq!(run(":verify"), Ok("Verification Result: true".to_string()));
+        assert!(run(":prove").unwrap().ends_with("(cached keys)"));
+
+        //The saved file is the circuit the session built
+        let saved = dir.join("session.txt");
+        assert_eq!(run(&format!(":save {}", saved.display())), Ok(format!("Wrote 6 statements to {}", saved.display())));
+        let reparsed = crate::parse_circuit(saved.to_str().unwrap()).unwrap();
+        assert_eq!(reparsed.canonical_hash(), parse_circuit_str(&repl.source()).unwrap().canonical_hash());
+        assert_eq!(reparsed.inputs["a"], 5);
+
+        assert_eq!(repl.eval(":reset"), Ok("Circuit cleared".to_string()));
+        assert!(repl.eval(":witness").is_err());
+        assert_eq!(repl.source(), "");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
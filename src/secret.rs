@@ -0,0 +1,115 @@
+//Witness values wiped with zero on drop. compute_witness and proving hold theirs in one,
+//Circuit::inputs and the map compute_witness returns stay with the caller
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+use ark_bls12_381::Fr;
+use zeroize::Zeroize;
+
+pub struct SecretWitness<K = usize, V: Zeroize = Fr>(HashMap<K, V>);
+
+impl<K, V: Zeroize> SecretWitness<K, V> {
+    pub fn new(values: HashMap<K, V>) -> Self {
+        SecretWitness(values)
+    }
+
+    //The values by variable index, keep copies as short-lived as this wrapper
+    pub fn expose(&self) -> &HashMap<K, V> {
+        &self.0
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Zeroize + Copy> SecretWitness<K, V> {
+    //Insert copies of the values and wipe the originals, so the vector is freed clean
+    pub(crate) fn absorb(&mut self, mut values: Vec<(K, V)>) {
+        for (key, value) in values.iter_mut() {
+            self.0.insert(key.clone(), *value);
+            value.zeroize();
+        }
+    }
+
+    //Growing the map frees its old table unwiped, reserve before absorbing
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional);
+    }
+}
+
+impl<K, V: Zeroize> From<HashMap<K, V>> for SecretWitness<K, V> {
+    fn from(values: HashMap<K, V>) -> Self {
+        SecretWitness(values)
+    }
+}
+
+impl<K, V: Zeroize> Zeroize for SecretWitness<K, V> {
+    fn zeroize(&mut self) {
+        self.0.values_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+impl<K, V: Zeroize> Drop for SecretWitness<K, V> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+//Never prints a value
+impl<K, V: Zeroize> fmt::Debug for SecretWitness<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretWitness({} values)", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_circuit_str, run_pipeline, KeyCache, PipelineOptions};
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_witness_is_wiped_and_never_written() {
+        //A canary records the value it holds when the map frees it, after SecretWitness::drop ran
+        static FREED: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+        struct Canary(u64);
+        impl Zeroize for Canary {
+            fn zeroize(&mut self) {
+                self.0.zeroize();
+            }
+        }
+        impl Drop for Canary {
+            fn drop(&mut self) {
+                FREED.lock().unwrap().push(self.0);
+            }
+        }
+        let witness = SecretWitness::new(HashMap::from([(1, Canary(0x5ec2e7)), (2, Canary(0x5ec2e7))]));
+        assert_eq!(format!("{:?}", witness), "SecretWitness(2 values)");
+        FREED.lock().unwrap().clear();
+        drop(witness);
+        assert_eq!(*FREED.lock().unwrap(), vec![0, 0]);
+
+        //A full pipeline run against cached keys leaves only the keys in the cache directory
+        let circuit = parse_circuit_str("name secret\ninput a 3\ninput b 4\namount 0\nmul a b c\nassert_nonzero c\n").unwrap();
+        let dir = std::env::temp_dir().join(format!("zk_secret_test_{}", std::process::id()));
+        let cache = KeyCache::new(&dir);
+        let (pk, vk) = cache.get_or_setup(&circuit).unwrap();
+        let result = run_pipeline(&circuit, PipelineOptions { keys: Some((&pk, &vk)), ..PipelineOptions::default() }).unwrap();
+        assert_eq!(result.verified, Some(true));
+        fn files(dir: &Path, found: &mut Vec<String>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    files(&path, found);
+                } else {
+                    found.push(path.file_name().unwrap().to_string_lossy().into_owned());
+                }
+            }
+        }
+        let mut found = Vec::new();
+        files(&dir, &mut found);
+        found.sort();
+        assert_eq!(found, vec!["manifest.json", "pk.bin", "vk.bin"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
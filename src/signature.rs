@@ -0,0 +1,351 @@
+//Schnorr signatures over Bandersnatch (embedded in BLS12-381), challenge
+//e = poseidon_hash(R.x, R.y, A.x, A.y, m) and cofactored verification
+use ark_bls12_381::Fr;
+use ark_ec::twisted_edwards::TECurveConfig;
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ed_on_bls12_381_bandersnatch::{BandersnatchConfig, EdwardsAffine, Fr as ScalarField};
+use ark_ff::{AdditiveGroup, BigInteger, Field, One, PrimeField, Zero};
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::rand::{CryptoRng, RngCore};
+use ark_std::UniformRand;
+
+use crate::poseidon::{poseidon_hash, poseidon_hash_gadget};
+
+pub struct SigningKey(ScalarField);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey(pub EdwardsAffine);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    pub r: EdwardsAffine,
+    pub s: ScalarField,
+}
+
+impl SigningKey {
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        SigningKey(ScalarField::rand(rng))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey((EdwardsAffine::generator() * self.0).into_affine())
+    }
+
+    pub fn sign<R: RngCore + CryptoRng>(&self, message: Fr, rng: &mut R) -> Signature {
+        let k = ScalarField::rand(rng);
+        let r = (EdwardsAffine::generator() * k).into_affine();
+        let e = challenge(&r, &self.public_key(), message);
+        let e = ScalarField::from_le_bytes_mod_order(&e.into_bigint().to_bytes_le());
+        Signature { r, s: k + e * self.0 }
+    }
+}
+
+impl PublicKey {
+    //Affine coordinates, in the order the gadget takes them
+    pub fn coordinates(&self) -> [Fr; 2] {
+        [self.0.x, self.0.y]
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = Vec::new();
+        self.0.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        Ok(PublicKey(EdwardsAffine::deserialize_compressed(bytes)?))
+    }
+}
+
+impl Signature {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = Vec::new();
+        self.r.serialize_compressed(&mut bytes)?;
+        self.s.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, SerializationError> {
+        let r = EdwardsAffine::deserialize_compressed(&mut bytes)?;
+        let s = ScalarField::deserialize_compressed(&mut bytes)?;
+        Ok(Signature { r, s })
+    }
+}
+
+fn challenge(r: &EdwardsAffine, public_key: &PublicKey, message: Fr) -> Fr {
+    poseidon_hash(&[r.x, r.y, public_key.0.x, public_key.0.y, message])
+}
+
+pub fn verify(public_key: &PublicKey, message: Fr, signature: &Signature) -> bool {
+    //e is used as an integer, as in the circuit; for A in the prime subgroup this equals e mod n
+    let e = challenge(&signature.r, public_key, message);
+    let lhs = EdwardsAffine::generator() * signature.s;
+    let rhs = signature.r.into_group() + public_key.0.mul_bigint(e.into_bigint());
+    (lhs - rhs).into_affine().mul_by_cofactor().is_zero()
+}
+
+//Message a spend authorization signs: binds the nullifier, the new note commitment, amount and fee
+pub fn transfer_message(nullifier: Fr, new_commitment: Fr, amount: Fr, fee: Fr) -> Fr {
+    poseidon_hash(&[nullifier, new_commitment, amount, fee])
+}
+
+pub fn sign_transfer<R: RngCore + CryptoRng>(
+    key: &SigningKey,
+    nullifier: Fr,
+    new_commitment: Fr,
+    amount: Fr,
+    fee: Fr,
+    rng: &mut R,
+) -> Signature {
+    key.sign(transfer_message(nullifier, new_commitment, amount, fee), rng)
+}
+
+//In-circuit value: a linear combination and its assignment (None in setup mode)
+type Num = (LinearCombination<Fr>, Option<Fr>);
+
+struct PointVar {
+    x: Num,
+    y: Num,
+}
+
+fn num(cs: &ConstraintSystemRef<Fr>, v: Variable) -> Num {
+    (lc!() + v, cs.assigned_value(v))
+}
+
+fn constant(c: Fr) -> Num {
+    (lc!() + (c, Variable::One), Some(c))
+}
+
+fn linear(terms: &[(Fr, &Num)]) -> Num {
+    let mut out = LinearCombination::zero();
+    let mut value = Some(Fr::zero());
+    for (c, (lc, val)) in terms {
+        out = out + (*c, lc);
+        value = value.zip(*val).map(|(acc, v)| acc + *c * v);
+    }
+    (out, value)
+}
+
+fn alloc(cs: &ConstraintSystemRef<Fr>, value: Option<Fr>) -> Result<Num, SynthesisError> {
+    let v = cs.new_witness_variable(|| value.ok_or(SynthesisError::AssignmentMissing))?;
+    Ok((lc!() + v, value))
+}
+
+fn mul(cs: &ConstraintSystemRef<Fr>, a: &Num, b: &Num) -> Result<Num, SynthesisError> {
+    let out = alloc(cs, a.1.zip(b.1).map(|(a, b)| a * b))?;
+    cs.enforce_constraint(a.0.clone(), b.0.clone(), out.0.clone())?;
+    Ok(out)
+}
+
+//Allocate numerator / denominator, failing in prove mode if the denominator is zero
+fn div(cs: &ConstraintSystemRef<Fr>, numerator: &Num, denominator: &Num) -> Result<Num, SynthesisError> {
+    let value = match (numerator.1, denominator.1) {
+        (Some(n), Some(d)) => Some(n * d.inverse().ok_or(SynthesisError::DivisionByZero)?),
+        _ => None,
+    };
+    let out = alloc(cs, value)?;
+    cs.enforce_constraint(out.0.clone(), denominator.0.clone(), numerator.0.clone())?;
+    Ok(out)
+}
+
+fn enforce_equal(cs: &ConstraintSystemRef<Fr>, a: &Num, b: &Num) -> Result<(), SynthesisError> {
+    cs.enforce_constraint(a.0.clone() - &b.0, lc!() + Variable::One, lc!())
+}
+
+//Little-endian bits constrained to be boolean
+fn alloc_bits(cs: &ConstraintSystemRef<Fr>, bits: Option<Vec<bool>>, len: usize) -> Result<Vec<Num>, SynthesisError> {
+    (0..len)
+        .map(|i| {
+            let bit = alloc(cs, bits.as_ref().map(|b| Fr::from(b[i])))?;
+            cs.enforce_constraint(bit.0.clone(), lc!() + Variable::One - &bit.0, lc!())?;
+            Ok(bit)
+        })
+        .collect()
+}
+
+fn te_coeffs() -> (Fr, Fr) {
+    (BandersnatchConfig::COEFF_A, BandersnatchConfig::COEFF_D)
+}
+
+fn identity() -> PointVar {
+    PointVar { x: constant(Fr::zero()), y: constant(Fr::one()) }
+}
+
+//a*x^2 + y^2 == 1 + d*x^2*y^2
+fn enforce_on_curve(cs: &ConstraintSystemRef<Fr>, p: &PointVar) -> Result<(), SynthesisError> {
+    let (a, d) = te_coeffs();
+    let x2 = mul(cs, &p.x, &p.x)?;
+    let y2 = mul(cs, &p.y, &p.y)?;
+    let x2y2 = mul(cs, &x2, &y2)?;
+    let one = constant(Fr::one());
+    enforce_equal(cs, &linear(&[(a, &x2), (Fr::one(), &y2)]), &linear(&[(Fr::one(), &one), (d, &x2y2)]))
+}
+
+//Unified twisted Edwards addition (also used for doubling), 7 constraints
+fn add(cs: &ConstraintSystemRef<Fr>, p: &PointVar, q: &PointVar) -> Result<PointVar, SynthesisError> {
+    let (a, d) = te_coeffs();
+    let one = constant(Fr::one());
+    let x1y2 = mul(cs, &p.x, &q.y)?;
+    let y1x2 = mul(cs, &p.y, &q.x)?;
+    let x1x2 = mul(cs, &p.x, &q.x)?;
+    let y1y2 = mul(cs, &p.y, &q.y)?;
+    let t = mul(cs, &x1y2, &y1x2)?;
+    let x = div(cs, &linear(&[(Fr::one(), &x1y2), (Fr::one(), &y1x2)]), &linear(&[(Fr::one(), &one), (d, &t)]))?;
+    let y = div(cs, &linear(&[(Fr::one(), &y1y2), (-a, &x1x2)]), &linear(&[(Fr::one(), &one), (-d, &t)]))?;
+    Ok(PointVar { x, y })
+}
+
+fn negate(p: &PointVar) -> PointVar {
+    PointVar { x: linear(&[(-Fr::one(), &p.x)]), y: p.y.clone() }
+}
+
+//bit ? p : identity
+fn select(cs: &ConstraintSystemRef<Fr>, bit: &Num, p: &PointVar) -> Result<PointVar, SynthesisError> {
+    let one = constant(Fr::one());
+    let x = mul(cs, bit, &p.x)?;
+    let y_minus_one = mul(cs, bit, &linear(&[(Fr::one(), &p.y), (-Fr::one(), &one)]))?;
+    Ok(PointVar { x, y: linear(&[(Fr::one(), &y_minus_one), (Fr::one(), &one)]) })
+}
+
+//Fixed-base multiplication by the generator, selecting precomputed 2^i * B linearly
+fn mul_generator(cs: &ConstraintSystemRef<Fr>, bits: &[Num]) -> Result<PointVar, SynthesisError> {
+    let one = constant(Fr::one());
+    let mut base = EdwardsAffine::generator().into_group();
+    let mut acc = identity();
+    for bit in bits {
+        let b = base.into_affine();
+        let term = PointVar { x: linear(&[(b.x, bit)]), y: linear(&[(b.y - Fr::one(), bit), (Fr::one(), &one)]) };
+        acc = add(cs, &acc, &term)?;
+        base.double_in_place();
+    }
+    Ok(acc)
+}
+
+//Variable-base double-and-add, least significant bit first
+fn mul_point(cs: &ConstraintSystemRef<Fr>, p: &PointVar, bits: &[Num]) -> Result<PointVar, SynthesisError> {
+    let mut acc = identity();
+    let mut base = PointVar { x: p.x.clone(), y: p.y.clone() };
+    for (i, bit) in bits.iter().enumerate() {
+        acc = add(cs, &acc, &select(cs, bit, &base)?)?;
+        if i + 1 < bits.len() {
+            base = add(cs, &base, &base)?;
+        }
+    }
+    Ok(acc)
+}
+
+fn to_bits<B: BigInteger>(value: B, len: usize) -> Vec<bool> {
+    (0..len).map(|i| value.get_bit(i)).collect()
+}
+
+//Constrain `signature` (a witness, None in setup mode) to be a valid signature of `message`
+//under the public key with coordinates `public_key`
+pub fn verify_signature_gadget(
+    cs: &ConstraintSystemRef<Fr>,
+    public_key: [Variable; 2],
+    message: Variable,
+    signature: Option<&Signature>,
+) -> Result<(), SynthesisError> {
+    let pk = PointVar { x: num(cs, public_key[0]), y: num(cs, public_key[1]) };
+    let rx = cs.new_witness_variable(|| signature.map(|s| s.r.x).ok_or(SynthesisError::AssignmentMissing))?;
+    let ry = cs.new_witness_variable(|| signature.map(|s| s.r.y).ok_or(SynthesisError::AssignmentMissing))?;
+    let r = PointVar { x: num(cs, rx), y: num(cs, ry) };
+    enforce_on_curve(cs, &pk)?;
+    enforce_on_curve(cs, &r)?;
+
+    //Decompose the challenge; the 255-bit decomposition may also encode e + p, which only
+    //gives a forger a second fixed challenge per (R, A, m) and does not help without the key
+    let e = poseidon_hash_gadget(cs, &[rx, ry, public_key[0], public_key[1], message])?;
+    let e_len = Fr::MODULUS_BIT_SIZE as usize;
+    let e_bits = alloc_bits(cs, cs.assigned_value(e).map(|v| to_bits(v.into_bigint(), e_len)), e_len)?;
+    let mut packed = LinearCombination::zero();
+    let mut coeff = Fr::one();
+    for bit in &e_bits {
+        packed = packed + (coeff, &bit.0);
+        coeff.double_in_place();
+    }
+    cs.enforce_constraint(packed, lc!() + Variable::One, lc!() + e)?;
+
+    let s_len = ScalarField::MODULUS_BIT_SIZE as usize;
+    let s_bits = alloc_bits(cs, signature.map(|s| to_bits(s.s.into_bigint(), s_len)), s_len)?;
+
+    let lhs = mul_generator(cs, &s_bits)?;
+    let e_pk = mul_point(cs, &pk, &e_bits)?;
+    let rhs = add(cs, &r, &e_pk)?;
+    let diff = add(cs, &lhs, &negate(&rhs))?;
+    let diff2 = add(cs, &diff, &diff)?;
+    let diff4 = add(cs, &diff2, &diff2)?;
+    enforce_equal(cs, &diff4.x, &constant(Fr::zero()))?;
+    enforce_equal(cs, &diff4.y, &constant(Fr::one()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::Bls12_381;
+    use ark_crypto_primitives::snark::SNARK;
+    use ark_groth16::Groth16;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    //Spend authorization: the transfer fields are public, the signature is a witness
+    #[derive(Clone)]
+    struct SpendAuthCircuit {
+        public_key: PublicKey,
+        fields: [Fr; 4],
+        signature: Option<Signature>,
+    }
+
+    impl ConstraintSynthesizer<Fr> for SpendAuthCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let fields = self.fields.iter().map(|f| cs.new_input_variable(|| Ok(*f))).collect::<Result<Vec<_>, _>>()?;
+            let [ax, ay] = self.public_key.coordinates();
+            let pk = [cs.new_witness_variable(|| Ok(ax))?, cs.new_witness_variable(|| Ok(ay))?];
+            let message = poseidon_hash_gadget(&cs, &fields)?;
+            verify_signature_gadget(&cs, pk, message, self.signature.as_ref())
+        }
+    }
+
+    fn is_satisfied(circuit: SpendAuthCircuit) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn test_native_sign_verify() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let key = SigningKey::generate(&mut rng);
+        let pk = key.public_key();
+        let sig = key.sign(Fr::from(42u64), &mut rng);
+        assert!(verify(&pk, Fr::from(42u64), &sig));
+        assert!(!verify(&pk, Fr::from(43u64), &sig));
+        assert!(!verify(&SigningKey::generate(&mut rng).public_key(), Fr::from(42u64), &sig));
+
+        assert_eq!(Signature::from_bytes(&sig.to_bytes().unwrap()).unwrap(), sig);
+        assert_eq!(PublicKey::from_bytes(&pk.to_bytes().unwrap()).unwrap(), pk);
+    }
+
+    #[test]
+    fn test_spend_authorization_circuit() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let key = SigningKey::generate(&mut rng);
+        let fields = [Fr::from(11u64), Fr::from(22u64), Fr::from(500u64), Fr::from(3u64)];
+        let signature = sign_transfer(&key, fields[0], fields[1], fields[2], fields[3], &mut rng);
+        let circuit = SpendAuthCircuit { public_key: key.public_key(), fields, signature: Some(signature) };
+
+        //A signature over a different amount does not satisfy the circuit
+        let mut other_amount = circuit.clone();
+        other_amount.fields[2] = Fr::from(501u64);
+        assert!(!is_satisfied(other_amount));
+        assert!(is_satisfied(circuit.clone()));
+
+        let (pk, vk) =
+            Groth16::<Bls12_381>::circuit_specific_setup(SpendAuthCircuit { signature: None, ..circuit.clone() }, &mut rng)
+                .unwrap();
+        let proof = Groth16::<Bls12_381>::prove(&pk, circuit, &mut rng).unwrap();
+        assert!(Groth16::<Bls12_381>::verify(&vk, &fields, &proof).unwrap());
+    }
+}
@@ -0,0 +1,156 @@
+//Static check for under-constrained variables (a wire no constraint checks), from where each
+//variable occurs in the A, B and C matrices, so no witness is needed
+use std::fmt;
+
+use crate::{R1CSSystem, _R1CSConstraintInternal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnderconstrainedReason {
+    //The variable occurs in no row: any value satisfies the system
+    Unused,
+    //The variable occurs in one row, only on its C side, and no other row reads it
+    DefinedOnly,
+    //A public input that occurs in no row: the proof verifies for any value of it
+    UnboundPublicInput,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnderconstrainedWarning {
+    pub variable: String,
+    pub reason: UnderconstrainedReason,
+    //The defining row for DefinedOnly
+    pub row: Option<usize>,
+}
+
+impl fmt::Display for UnderconstrainedWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.reason, self.row) {
+            (UnderconstrainedReason::Unused, _) => write!(
+                f,
+                "Variable '{}' is in no constraint, a prover can set it to anything",
+                self.variable
+            ),
+            (UnderconstrainedReason::DefinedOnly, Some(row)) => write!(
+                f,
+                "Variable '{}' is only defined by constraint {} and never used by another constraint",
+                self.variable, row
+            ),
+            (UnderconstrainedReason::DefinedOnly, None) => write!(
+                f,
+                "Variable '{}' is only defined by one constraint and never used by another",
+                self.variable
+            ),
+            (UnderconstrainedReason::UnboundPublicInput, _) => write!(
+                f,
+                "Public input '{}' is in no constraint, proofs verify for any value of it",
+                self.variable
+            ),
+        }
+    }
+}
+
+//Where one variable occurs
+#[derive(Default, Clone, Copy)]
+struct Occurrences {
+    rows: usize,
+    //Row of the last occurrence
+    last_row: usize,
+    in_a_or_b: bool,
+}
+
+//k * 1 = v, the row of a Const gate
+fn is_constant_assignment(constraint: &_R1CSConstraintInternal) -> bool {
+    constraint.a.keys().chain(constraint.b.keys()).all(|idx| *idx == 0) && constraint.c.len() == 1
+}
+
+impl R1CSSystem {
+    //Variables a prover may be able to set freely, in variable index order. The constant one is
+    //never reported
+    pub fn underconstrained_report(&self) -> Vec<UnderconstrainedWarning> {
+        let mut occurrences = vec![Occurrences::default(); self.num_variables];
+        for (row, constraint) in self.raw_constraints.iter().enumerate() {
+            let mut seen: Vec<usize> = constraint.a.keys().chain(constraint.b.keys()).chain(constraint.c.keys()).copied().collect();
+            seen.sort_unstable();
+            seen.dedup();
+            for idx in seen {
+                let entry = &mut occurrences[idx];
+                entry.rows += 1;
+                entry.last_row = row;
+                entry.in_a_or_b |= constraint.a.contains_key(&idx) || constraint.b.contains_key(&idx);
+            }
+        }
+
+        let names = self.var_names();
+        let mut warnings = Vec::new();
+        for (idx, entry) in occurrences.iter().enumerate().skip(1) {
+            let public = self.public_input_names.contains(&names[idx]);
+            let (reason, row) = match (entry.rows, entry.in_a_or_b) {
+                (0, _) if public => (UnderconstrainedReason::UnboundPublicInput, None),
+                (0, _) => (UnderconstrainedReason::Unused, None),
+                //A public input in one row is fixed by the verifier, not defined by the prover
+                (1, false) if !public && !is_constant_assignment(&self.raw_constraints[entry.last_row]) => {
+                    (UnderconstrainedReason::DefinedOnly, Some(entry.last_row))
+                }
+                _ => continue,
+            };
+            warnings.push(UnderconstrainedWarning { variable: names[idx].clone(), reason, row });
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples_support::{MERKLE_DSL, TRANSFER_DSL};
+    use crate::parse_circuit_str;
+    use ark_bls12_381::Fr;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_underconstrained_variables_are_reported() {
+        //The example circuits check everything they compute
+        for source in [TRANSFER_DSL, MERKLE_DSL] {
+            let r1cs = parse_circuit_str(source).unwrap().to_r1cs_system();
+            assert_eq!(r1cs.underconstrained_report(), Vec::new(), "{}", source);
+        }
+
+        //cube is computed but never compared with y, and y itself ends up in no row
+        let circuit = parse_circuit_str("name forgot_the_check
+input x 3
+public y 27
+amount 0
+mul x x sq
+mul sq x cube
+").unwrap();
+        let mut r1cs = circuit.to_r1cs_system();
+        let report = r1cs.underconstrained_report();
+        let flagged: Vec<(&str, UnderconstrainedReason)> = report.iter().map(|w| (w.variable.as_str(), w.reason)).collect();
+        assert!(flagged.contains(&("cube", UnderconstrainedReason::DefinedOnly)), "{:?}", report);
+        assert!(flagged.contains(&("y", UnderconstrainedReason::UnboundPublicInput)), "{:?}", report);
+        let cube = report.iter().find(|w| w.variable == "cube").unwrap();
+        assert!(r1cs.explain(cube.row.unwrap()).contains("gate #1"), "{}", r1cs.explain(cube.row.unwrap()));
+        //Constants only read through folded coefficients are pinned by their own row
+        let folded = parse_circuit_str("name folded\ninput x 3\namount 0\nconst w 4\ndot (w x) -> y\nassert_nonzero y\n").unwrap();
+        assert_eq!(folded.to_r1cs_system().underconstrained_report(), Vec::new());
+
+        //A hint wire the witness generator fills in but no row mentions
+        r1cs.var_map.insert("hint".to_string(), r1cs.num_variables);
+        r1cs.num_variables += 1;
+        let report = r1cs.underconstrained_report();
+        assert_eq!(report.last().map(|w| (w.variable.as_str(), w.reason)), Some(("hint", UnderconstrainedReason::Unused)));
+        assert_eq!(report.last().unwrap().to_string(), "Variable 'hint' is in no constraint, a prover can set it to anything");
+
+        //Read by a later row, cube is no longer reported
+        let mut checked = r1cs.clone();
+        let one = checked.var_map["1"];
+        checked.raw_constraints.push(_R1CSConstraintInternal {
+            a: HashMap::from([(checked.var_map["cube"], Fr::from(1u64))]),
+            b: HashMap::from([(one, Fr::from(1u64))]),
+            c: HashMap::from([(checked.var_map["y"], Fr::from(1u64))]),
+        });
+        checked.constraint_origins.clear();
+        let flagged: Vec<String> = checked.underconstrained_report().into_iter().map(|w| w.variable).collect();
+        assert_eq!(flagged, vec!["hint".to_string()]);
+    }
+}
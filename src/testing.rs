@@ -0,0 +1,347 @@
+//Random well-formed circuits and pipeline assertions for property-based tests
+use std::collections::HashMap;
+
+use ark_bls12_381::Fr;
+use ark_ff::{One, Zero};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::{i32_to_fr, is_satisfied, poseidon_hash, run_pipeline, Circuit, Gate, PipelineOptions, PipelineResult};
+
+//Relative weights of the gate types drawn by random_circuit
+#[derive(Debug, Clone, Copy)]
+pub struct GateMix {
+    pub add: u32,
+    pub sub: u32,
+    pub mul: u32,
+    pub xor: u32,
+    pub eq: u32,
+    pub hash: u32,
+}
+
+impl Default for GateMix {
+    fn default() -> Self {
+        GateMix { add: 4, sub: 3, mul: 4, xor: 1, eq: 1, hash: 1 }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitConfig {
+    pub num_inputs: usize,
+    //Number of gate layers, every gate takes an operand from the previous layer
+    pub depth: usize,
+    //Gates per layer
+    pub width: usize,
+    //Maximum number of gates reading a single wire
+    pub max_fan_out: usize,
+    pub mix: GateMix,
+}
+
+impl Default for CircuitConfig {
+    fn default() -> Self {
+        CircuitConfig { num_inputs: 3, depth: 3, width: 3, max_fan_out: 3, mix: GateMix::default() }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Add,
+    Sub,
+    Mul,
+    Xor,
+    Eq,
+    Hash,
+}
+
+struct Wire {
+    name: String,
+    value: Fr,
+    uses: usize,
+}
+
+struct Builder<'a, R: Rng> {
+    rng: &'a mut R,
+    config: &'a CircuitConfig,
+    //Nonzero wires usable as arithmetic operands
+    wires: Vec<Wire>,
+    gates: Vec<Gate>,
+    next_wire: usize,
+}
+
+impl<R: Rng> Builder<'_, R> {
+    fn fresh_name(&mut self) -> String {
+        self.next_wire += 1;
+        format!("w{}", self.next_wire)
+    }
+
+    fn push_wire(&mut self, name: String, value: Fr) -> usize {
+        self.wires.push(Wire { name, value, uses: 0 });
+        self.wires.len() - 1
+    }
+
+    //New constant wire, used when every existing wire has reached its fan-out
+    fn fresh_const(&mut self) -> usize {
+        let val = self.rng.gen_range(1..=100);
+        let name = self.fresh_name();
+        self.gates.push(Gate::Const(name.clone(), i64::from(val)));
+        self.push_wire(name, i32_to_fr(val))
+    }
+
+    //Pick a wire index from `range` with remaining fan-out budget
+    fn pick(&mut self, range: std::ops::Range<usize>) -> usize {
+        let candidates: Vec<usize> = range.filter(|&i| self.wires[i].uses < self.config.max_fan_out).collect();
+        let idx = match candidates.choose(self.rng) {
+            Some(&i) => i,
+            None => self.fresh_const(),
+        };
+        self.wires[idx].uses += 1;
+        idx
+    }
+
+    fn kind(&mut self) -> Kind {
+        let mix = self.config.mix;
+        let weighted = [
+            (Kind::Add, mix.add),
+            (Kind::Sub, mix.sub),
+            (Kind::Mul, mix.mul),
+            (Kind::Xor, mix.xor),
+            (Kind::Eq, mix.eq),
+            (Kind::Hash, mix.hash),
+        ];
+        weighted.choose_weighted(self.rng, |(_, w)| *w).map(|(k, _)| *k).unwrap_or(Kind::Mul)
+    }
+
+    fn binary(&mut self, kind: Kind, a: usize, b: usize) -> Option<usize> {
+        let (a_name, b_name) = (self.wires[a].name.clone(), self.wires[b].name.clone());
+        let (a_val, b_val) = (self.wires[a].value, self.wires[b].value);
+        let out = self.fresh_name();
+        //x - x does not constrain x at all
+        let kind = match kind {
+            Kind::Sub if a == b => Kind::Add,
+            kind => kind,
+        };
+        let (gate, value) = match kind {
+            Kind::Add => (Gate::Add(a_name, b_name, out.clone(), None), a_val + b_val),
+            Kind::Sub => (Gate::Sub(a_name, b_name, out.clone(), None), a_val - b_val),
+            _ => (Gate::Mul(a_name, b_name, out.clone(), None), a_val * b_val),
+        };
+        self.gates.push(gate);
+        //A zero output would let a later Mul ignore its other operand
+        (!value.is_zero()).then(|| self.push_wire(out, value))
+    }
+
+    fn gate(&mut self, prev_layer: std::ops::Range<usize>) {
+        let a = self.pick(prev_layer);
+        match self.kind() {
+            kind @ (Kind::Add | Kind::Sub | Kind::Mul) => {
+                let b = self.pick(0..self.wires.len());
+                self.binary(kind, a, b);
+            }
+            Kind::Hash => {
+                let value = poseidon_hash(&[self.wires[a].value]);
+                let out = self.fresh_name();
+                self.gates.push(Gate::Hash(self.wires[a].name.clone(), out.clone()));
+                self.push_wire(out, value);
+            }
+            //Equality between two evaluation orders of the same product
+            Kind::Eq => {
+                let b = self.pick(0..self.wires.len());
+                let left = self.binary(Kind::Mul, a, b);
+                let right = self.binary(Kind::Mul, b, a);
+                if let (Some(l), Some(r)) = (left, right) {
+                    let out = self.fresh_name();
+                    self.gates.push(Gate::Eq(self.wires[l].name.clone(), self.wires[r].name.clone(), out));
+                }
+            }
+            //Xor needs bit inputs, so it runs on fresh constant bits
+            Kind::Xor => {
+                let bits: Vec<(String, i32)> = (0..2).map(|_| (self.fresh_name(), self.rng.gen_range(0..=1))).collect();
+                for (name, bit) in &bits {
+                    self.gates.push(Gate::Const(name.clone(), i64::from(*bit)));
+                }
+                let out = self.fresh_name();
+                self.gates.push(Gate::Xor(bits[0].0.clone(), bits[1].0.clone(), out.clone()));
+                if bits[0].1 != bits[1].1 {
+                    self.push_wire(out, Fr::one());
+                }
+            }
+        }
+    }
+}
+
+//Generate a well-formed circuit; the transfer is from input "in0" to "in1" when there are two inputs
+pub fn random_circuit<R: Rng>(rng: &mut R, config: &CircuitConfig) -> Circuit {
+    let inputs: HashMap<String, i32> = (0..config.num_inputs).map(|i| (format!("in{}", i), rng.gen_range(1..=1000))).collect();
+    let mut builder = Builder { rng, config, wires: Vec::new(), gates: Vec::new(), next_wire: 0 };
+    let mut names: Vec<(&String, &i32)> = inputs.iter().collect();
+    names.sort();
+    for (name, val) in names {
+        builder.push_wire(name.clone(), i32_to_fr(*val));
+    }
+    if builder.wires.is_empty() {
+        builder.fresh_const();
+    }
+
+    let mut layer_start = 0;
+    for _ in 0..config.depth {
+        let layer_end = builder.wires.len();
+        for _ in 0..config.width {
+            builder.gate(layer_start..layer_end);
+        }
+        //A layer that produced no usable wires (e.g. only Xor gates on equal bits) keeps the previous layer
+        if builder.wires.len() > layer_end {
+            layer_start = layer_end;
+        }
+    }
+
+    let (sender, receiver) = match config.num_inputs {
+        0 | 1 => ("alice".to_string(), "bob".to_string()),
+        _ => ("in0".to_string(), "in1".to_string()),
+    };
+    let transfer_amount = inputs.get(&sender).map_or(5, |balance| builder.rng.gen_range(1..=*balance) as u64);
+    Circuit {
+        name: "random".to_string(),
+        inputs,
+        gates: builder.gates,
+        sender,
+        receiver,
+        transfer_amount,
+        ..Default::default()
+    }
+}
+
+//c = a * b with a = 3 and b = 4 and a transfer of 5 from alice to bob (neither holds a balance),
+//the smallest circuit that proves, shared by module tests
+pub fn mul_circuit(name: &str) -> Circuit {
+    Circuit {
+        name: name.to_string(),
+        inputs: HashMap::from([("a".to_string(), 3), ("b".to_string(), 4)]),
+        gates: vec![Gate::Mul("a".to_string(), "b".to_string(), "c".to_string(), None)],
+        sender: "alice".to_string(),
+        receiver: "bob".to_string(),
+        transfer_amount: 5,
+        ..Default::default()
+    }
+}
+
+//Synthetic circuit: a chain of n Mul gates over a single input, used by the benches
+pub fn mul_chain(n: usize) -> Circuit {
+    let mut gates = vec![Gate::Const("k".to_string(), 3)];
+    let mut prev = "x".to_string();
+    for i in 0..n {
+        let out = format!("m{}", i);
+        gates.push(Gate::Mul(prev, "k".to_string(), out.clone(), None));
+        prev = out;
+    }
+    Circuit {
+        name: format!("mul_chain_{}", n),
+        inputs: HashMap::from([("x".to_string(), 7)]),
+        gates,
+        sender: "alice".to_string(),
+        receiver: "bob".to_string(),
+        transfer_amount: 5,
+        ..Default::default()
+    }
+}
+
+//Synthetic Merkle-path-shaped circuit: each level hashes the running node with a sibling input.
+//The hash gate takes a single input, so the pair is packed as node + 2 * sibling first
+pub fn merkle_path(depth: usize) -> Circuit {
+    let mut inputs = HashMap::from([("leaf".to_string(), 1)]);
+    let mut gates = vec![Gate::Const("two".to_string(), 2)];
+    let mut node = "leaf".to_string();
+    for level in 0..depth {
+        let sibling = format!("sibling{}", level);
+        inputs.insert(sibling.clone(), level as i32 + 2);
+        let scaled = format!("scaled{}", level);
+        let packed = format!("packed{}", level);
+        let next = format!("node{}", level);
+        gates.push(Gate::Mul(sibling, "two".to_string(), scaled.clone(), None));
+        gates.push(Gate::Add(node, scaled, packed.clone(), None));
+        gates.push(Gate::Hash(packed, next.clone()));
+        node = next;
+    }
+    Circuit {
+        name: format!("merkle_path_{}", depth),
+        inputs,
+        gates,
+        sender: "alice".to_string(),
+        receiver: "bob".to_string(),
+        transfer_amount: 5,
+        ..Default::default()
+    }
+}
+
+//Run the full pipeline and assert the proof verifies
+pub fn assert_roundtrip(circuit: &Circuit) -> PipelineResult {
+    let result = run_pipeline(circuit, PipelineOptions { rng_seed: Some(0), ..PipelineOptions::default() })
+        .unwrap_or_else(|e| panic!("Pipeline failed for {:?}: {}", circuit, e));
+    assert_eq!(result.verified, Some(true), "Proof did not verify for {:?}", circuit);
+    result
+}
+
+//Perturb one randomly chosen witness value and assert the witness no longer satisfies the
+//constraints, returning the perturbed variable name
+pub fn assert_mutation_rejected<R: Rng>(circuit: &Circuit, rng: &mut R) -> String {
+    let r1cs = circuit.to_r1cs_system();
+    let mut witness = circuit.compute_witness(&r1cs.var_map).expect("Witness computation failed");
+    //"1" is allocated as the constant one regardless of the witness
+    let mut candidates: Vec<(&String, &usize)> = r1cs.var_map.iter().filter(|(name, _)| name.as_str() != "1").collect();
+    candidates.sort_by_key(|(_, idx)| **idx);
+    let (name, idx) = *candidates.choose(rng).expect("Circuit has no witness variables");
+    let delta = Fr::from(rng.gen_range(1..=u64::MAX));
+    *witness.get_mut(idx).expect("Witness covers var_map") += delta;
+    assert!(
+        !is_satisfied(&r1cs, witness).expect("Synthesis failed"),
+        "Perturbing '{}' still satisfies the constraints of {:?}",
+        name,
+        circuit
+    );
+    name.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn config_strategy() -> impl Strategy<Value = CircuitConfig> {
+        (0usize..4, 1usize..4, 1usize..4, 1usize..4).prop_map(|(num_inputs, depth, width, max_fan_out)| CircuitConfig {
+            num_inputs,
+            depth,
+            width,
+            max_fan_out,
+            mix: GateMix::default(),
+        })
+    }
+
+    #[test]
+    fn test_synthetic_circuits_roundtrip() {
+        assert_eq!(mul_chain(10).to_r1cs_system().raw_constraints.len(), 12);
+        assert_roundtrip(&mul_chain(10));
+        assert_roundtrip(&merkle_path(2));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn prop_random_circuits_prove_and_verify(seed in any::<u64>(), config in config_strategy()) {
+            let circuit = random_circuit(&mut StdRng::seed_from_u64(seed), &config);
+            assert_roundtrip(&circuit);
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_perturbed_witness_is_rejected(seed in any::<u64>(), config in config_strategy()) {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let circuit = random_circuit(&mut rng, &config);
+            assert_mutation_rejected(&circuit, &mut rng);
+        }
+    }
+}
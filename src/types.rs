@@ -0,0 +1,384 @@
+//Optional wire types (`input flag:bool 1`), inferred for gate outputs and enforced with range
+//constraints, untyped wires are field elements
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use ark_bls12_381::Fr;
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::{get_index, Circuit, ConstraintOrigin, Gate, UIntOp, _R1CSConstraintInternal};
+
+//Widest unsigned type; sums and products that could exceed it (and wrap around the field
+//modulus) are inferred as field
+pub const MAX_UINT_BITS: u32 = 252;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WireType {
+    Field,
+    Bool,
+    //Unsigned integer of the given bit width
+    UInt(u32),
+}
+
+impl WireType {
+    //Bit width of bounded types, None for field
+    pub fn bits(&self) -> Option<u32> {
+        match self {
+            WireType::Field => None,
+            WireType::Bool => Some(1),
+            WireType::UInt(bits) => Some(*bits),
+        }
+    }
+
+    fn bounded(bits: u32) -> WireType {
+        if bits > MAX_UINT_BITS {
+            WireType::Field
+        } else {
+            WireType::UInt(bits)
+        }
+    }
+
+    fn fits(&self, value: i64) -> bool {
+        match self.bits() {
+            None => true,
+            Some(bits) => value >= 0 && (bits >= 63 || value < (1i64 << bits)),
+        }
+    }
+}
+
+impl fmt::Display for WireType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireType::Field => write!(f, "field"),
+            WireType::Bool => write!(f, "bool"),
+            WireType::UInt(bits) => write!(f, "u{}", bits),
+        }
+    }
+}
+
+impl FromStr for WireType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "field" => Ok(WireType::Field),
+            "bool" => Ok(WireType::Bool),
+            _ => match s.strip_prefix('u').and_then(|bits| bits.parse::<u32>().ok()) {
+                Some(bits) if (1..=MAX_UINT_BITS).contains(&bits) => Ok(WireType::UInt(bits)),
+                _ => Err(format!("Unknown wire type '{}' (expected field, bool or u1..u{})", s, MAX_UINT_BITS)),
+            },
+        }
+    }
+}
+
+//Split a DSL wire declaration `name:type` into its name and optional type
+pub fn parse_typed_name(decl: &str) -> Result<(&str, Option<WireType>), String> {
+    match decl.split_once(':') {
+        Some((name, ty)) => Ok((name, Some(ty.parse()?))),
+        None => Ok((decl, None)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeError {
+    //A gate that needs bits (Xor) got a wider operand
+    NotBool { gate_index: usize, wire: String, found: WireType },
+    //A machine-integer gate got an operand declared wider than its width
+    OperandTooWide { gate_index: usize, wire: String, found: WireType, bits: u32 },
+    //An input or constant does not fit its declared type
+    ValueOutOfRange { wire: String, value: i64, declared: WireType },
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeError::NotBool { gate_index, wire, found } => {
+                write!(f, "Gate #{} needs a bool operand but '{}' is {}", gate_index, wire, found)
+            }
+            TypeError::OperandTooWide { gate_index, wire, found, bits } => {
+                write!(f, "Gate #{} needs a u{} operand but '{}' is {}", gate_index, bits, wire, found)
+            }
+            TypeError::ValueOutOfRange { wire, value, declared } => {
+                write!(f, "Value {} of '{}' does not fit its declared type {}", value, wire, declared)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+impl Circuit {
+    //Infer the type of every wire, checking gate operands and declared values. Declared types
+    //win over inferred ones, the declared wire is range checked instead (e.g. an Add of two u64
+    //is u65 unless its output is declared u64)
+    pub fn type_check(&self) -> Result<HashMap<String, WireType>, TypeError> {
+        let mut types: HashMap<String, WireType> = HashMap::new();
+        for (name, value) in &self.inputs {
+            let ty = self.wire_types.get(name).copied().unwrap_or(WireType::Field);
+            if !ty.fits(*value as i64) {
+                return Err(TypeError::ValueOutOfRange { wire: name.clone(), value: *value as i64, declared: ty });
+            }
+            types.insert(name.clone(), ty);
+        }
+        let type_of = |types: &HashMap<String, WireType>, wire: &String| types.get(wire).copied().unwrap_or(WireType::Field);
+
+        for (gate_index, gate) in self.gates.iter().enumerate() {
+            let (output, inferred) = match gate {
+                Gate::Add(a, b, c, _) => {
+                    let ty = match (type_of(&types, a).bits(), type_of(&types, b).bits()) {
+                        (Some(x), Some(y)) => WireType::bounded(x.max(y) + 1),
+                        _ => WireType::Field,
+                    };
+                    (c, ty)
+                }
+                Gate::Mul(a, b, c, _) => {
+                    let ty = match (type_of(&types, a), type_of(&types, b)) {
+                        (WireType::Bool, WireType::Bool) => WireType::Bool,
+                        (x, y) => match (x.bits(), y.bits()) {
+                            (Some(x), Some(y)) => WireType::bounded(x + y),
+                            _ => WireType::Field,
+                        },
+                    };
+                    (c, ty)
+                }
+                //Differences of unsigned values can wrap around the modulus
+                Gate::Sub(_, _, c, _) => (c, WireType::Field),
+                //Quotients are field inverses, not integer division
+                Gate::Div(_, _, c, _) => (c, WireType::Field),
+                Gate::Xor(a, b, c) => {
+                    for wire in [a, b] {
+                        let found = type_of(&types, wire);
+                        if matches!(found, WireType::UInt(bits) if bits > 1) {
+                            return Err(TypeError::NotBool { gate_index, wire: wire.clone(), found });
+                        }
+                    }
+                    (c, WireType::Bool)
+                }
+                Gate::Const(name, val) => {
+                    if let Some(declared) = self.wire_types.get(name) {
+                        if !declared.fits(*val) {
+                            return Err(TypeError::ValueOutOfRange { wire: name.clone(), value: *val, declared: *declared });
+                        }
+                    }
+                    (name, WireType::Field)
+                }
+                Gate::Eq(_, _, out) => (out, WireType::Field),
+                Gate::Hash(_, output) | Gate::HashN(_, output) | Gate::Nullifier(_, _, output) => (output, WireType::Field),
+                Gate::DotProduct(_, out) => (out, WireType::Field),
+                //The gate packs its operands as bytes and relies on their declared range checks
+                Gate::HashBytes(bytes, output) => {
+                    for wire in bytes {
+                        let found = type_of(&types, wire);
+                        if !matches!(found.bits(), Some(width) if width <= 8) {
+                            return Err(TypeError::OperandTooWide { gate_index, wire: wire.clone(), found, bits: 8 });
+                        }
+                    }
+                    (output, WireType::Field)
+                }
+                Gate::AssertNonZero(_) | Gate::AssertBool(_) | Gate::AssertEq(..) | Gate::AssertLeConst(..) => continue,
+                //The gate constrains every output to a bit
+                Gate::Decode(_, outs) => {
+                    for out in outs {
+                        types.insert(out.clone(), self.wire_types.get(out).copied().unwrap_or(WireType::Bool));
+                    }
+                    continue;
+                }
+                //min(x, cap) is at most x, so it keeps x's type
+                Gate::Clamp(x, _, out) => (out, type_of(&types, x)),
+                //The outputs are the operands in some order
+                Gate::CondSwap(bit, x, y, left, right) => {
+                    let found = type_of(&types, bit);
+                    if matches!(found, WireType::UInt(bits) if bits > 1) {
+                        return Err(TypeError::NotBool { gate_index, wire: bit.clone(), found });
+                    }
+                    let ty = match (type_of(&types, x).bits(), type_of(&types, y).bits()) {
+                        (Some(a), Some(b)) => WireType::bounded(a.max(b)),
+                        _ => WireType::Field,
+                    };
+                    for out in [left, right] {
+                        types.insert(out.clone(), self.wire_types.get(out).copied().unwrap_or(ty));
+                    }
+                    continue;
+                }
+                //flag * value is value or zero, so it keeps value's type
+                Gate::RevealIf(flag, value, disclosed) => {
+                    let found = type_of(&types, flag);
+                    if matches!(found, WireType::UInt(bits) if bits > 1) {
+                        return Err(TypeError::NotBool { gate_index, wire: flag.clone(), found });
+                    }
+                    (disclosed, type_of(&types, value))
+                }
+                //The result is a remainder below n
+                Gate::PowMod(_, _, n, out) => (out, WireType::bounded(crate::powmod::modulus_bits(*n))),
+                //Untyped operands are range checked by the gate itself
+                Gate::UInt(op, bits, a, b, low, high) => {
+                    for wire in [a, b] {
+                        let found = type_of(&types, wire);
+                        if matches!(found.bits(), Some(width) if width > *bits) {
+                            return Err(TypeError::OperandTooWide { gate_index, wire: wire.clone(), found, bits: *bits });
+                        }
+                    }
+                    let high_type = match op {
+                        UIntOp::Add | UIntOp::Sub => WireType::Bool,
+                        UIntOp::Mul => WireType::UInt(*bits),
+                    };
+                    types.insert(low.clone(), self.wire_types.get(low).copied().unwrap_or(WireType::UInt(*bits)));
+                    types.insert(high.clone(), self.wire_types.get(high).copied().unwrap_or(high_type));
+                    continue;
+                }
+            };
+            let ty = self.wire_types.get(output).copied().unwrap_or(inferred);
+            types.insert(output.clone(), ty);
+        }
+        Ok(types)
+    }
+
+    //Wires carrying a declared bounded type that exist in the circuit, sorted for a stable layout
+    pub(crate) fn range_checked_wires(&self) -> Vec<(&String, u32)> {
+        if self.wire_types.is_empty() {
+            return Vec::new();
+        }
+        let produced: Vec<&String> = self.gates.iter().flat_map(Gate::outputs).collect();
+        let mut wires: Vec<(&String, u32)> = self
+            .wire_types
+            .iter()
+            .filter(|(name, _)| self.inputs.contains_key(*name) || produced.contains(name))
+            .filter_map(|(name, ty)| ty.bits().map(|bits| (name, bits)))
+            .collect();
+        wires.sort();
+        wires
+    }
+
+    //Bit decompositions of the range checked wires, computed from the wire values
+    pub(crate) fn range_check_values(&self, wire_values_by_name: &HashMap<String, Fr>) -> Result<Vec<(String, Fr)>, String> {
+        let mut assigned = Vec::new();
+        for (wire, bits) in self.range_checked_wires() {
+            let value = wire_values_by_name.get(wire).ok_or_else(|| format!("Var {} not found", wire))?;
+            let bit_values = bit_values(wire, value, bits)
+                .ok_or_else(|| format!("Value of '{}' does not fit its declared type {}", wire, self.wire_types[wire]))?;
+            assigned.extend(bit_values);
+        }
+        Ok(assigned)
+    }
+}
+
+fn bit_name(wire: &str, i: usize) -> String {
+    format!("{}_bit{}", wire, i)
+}
+
+//Values of the bit wires emit_bit_decomposition allocates for wire, None when the value needs
+//more than bits bits (a bool is its own single bit and has none)
+pub(crate) fn bit_values(wire: &str, value: &Fr, bits: u32) -> Option<Vec<(String, Fr)>> {
+    let le_bits = value.into_bigint().to_bits_le();
+    if le_bits.iter().skip(bits as usize).any(|bit| *bit) {
+        return None;
+    }
+    if bits == 1 {
+        return Some(Vec::new());
+    }
+    Some(le_bits.iter().take(bits as usize).enumerate().map(|(i, bit)| (bit_name(wire, i), if *bit { Fr::one() } else { Fr::zero() })).collect())
+}
+
+//Booleanity of each bit of wire and the recomposition sum(2^i * bit_i) = wire, with the
+//origin of each row built from its role
+pub(crate) fn emit_bit_decomposition(
+    wire: &str,
+    bits: u32,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+    origins: &mut Vec<ConstraintOrigin>,
+    origin: impl Fn(String) -> ConstraintOrigin,
+) {
+    let one_idx = var_map["1"];
+    let wire_idx = get_index(wire, var_map, next_index);
+    let mut sum = HashMap::new();
+    let mut weight = Fr::one();
+    for i in 0..bits as usize {
+        let bit_idx = if bits == 1 { wire_idx } else { get_index(&bit_name(wire, i), var_map, next_index) };
+        constraints.push(_R1CSConstraintInternal {
+            a: HashMap::from([(bit_idx, Fr::one())]),
+            b: HashMap::from([(bit_idx, Fr::one())]),
+            c: HashMap::from([(bit_idx, Fr::one())]),
+        });
+        origins.push(origin(format!("booleanity of bit {}", i)));
+        sum.insert(bit_idx, weight);
+        weight += weight;
+    }
+    if bits > 1 {
+        constraints.push(_R1CSConstraintInternal {
+            a: sum,
+            b: HashMap::from([(one_idx, Fr::one())]),
+            c: HashMap::from([(wire_idx, Fr::one())]),
+        });
+        origins.push(origin("bit recomposition".to_string()));
+    }
+}
+
+//Emit the range constraints of every declared bounded wire: booleanity of each bit and the
+//recomposition sum(2^i * bit_i) = wire (a bool is its own single bit)
+pub(crate) fn emit_range_checks(
+    circuit: &Circuit,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+    origins: &mut Vec<ConstraintOrigin>,
+) {
+    for (wire, bits) in circuit.range_checked_wires() {
+        let origin = |role: String| ConstraintOrigin {
+            gate_index: None,
+            gate: format!("range check ({}: {})", wire, circuit.wire_types[wire]),
+            role,
+        };
+        emit_bit_decomposition(wire, bits, var_map, next_index, constraints, origins, origin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_circuit_str;
+    use crate::testing::assert_roundtrip;
+
+    #[test]
+    fn test_xor_on_u64_is_a_type_error() {
+        let source = "name typed\ninput bal:u64 5000\ninput flag:bool 1\nxor bal flag out\n";
+        let err = parse_circuit_str(source).unwrap_err();
+        assert!(err.to_string().contains("Gate #0 needs a bool operand but 'bal' is u64"), "{}", err);
+
+        let err = parse_circuit_str("name typed\ninput flag:bool 2\n").unwrap_err();
+        assert!(err.to_string().contains("Value 2 of 'flag' does not fit its declared type bool"), "{}", err);
+        assert!(parse_circuit_str("name typed\ninput x:u0 1\n").is_err());
+    }
+
+    #[test]
+    fn test_add_widens_unless_range_checked() {
+        let source = "name widen\ninput a:u8 200\ninput b:u8 50\ninput f:bool 1\nadd a b s\nmul f f g\nadd s a t\n";
+        let circuit = parse_circuit_str(source).unwrap();
+        let types = circuit.type_check().unwrap();
+        assert_eq!(types["s"], WireType::UInt(9));
+        assert_eq!(types["t"], WireType::UInt(10));
+        assert_eq!(types["g"], WireType::Bool);
+        //Untyped wires stay field and bypass the checks
+        assert_eq!(types.get("transfer_amount_public"), None);
+        let err = parse_circuit_str(&format!("{}xor s f x\n", source)).unwrap_err();
+        assert!(err.to_string().contains("'s' is u9"), "{}", err);
+
+        //Declaring the sum u8 narrows it with a range check: 250 fits, 300 does not
+        let narrowed = parse_circuit_str(&format!("{}output s:u8 0\n", source)).unwrap();
+        assert_eq!(narrowed.type_check().unwrap()["t"], WireType::UInt(9));
+        let plain_rows = circuit.to_r1cs_system().raw_constraints.len();
+        let r1cs = narrowed.to_r1cs_system();
+        //8 bits and a recomposition for s, on top of a, b (8 + 1 each) and f (1)
+        assert_eq!(r1cs.raw_constraints.len(), plain_rows + 9);
+        assert_ne!(narrowed.canonical_hash(), circuit.canonical_hash());
+        assert_roundtrip(&narrowed);
+
+        let mut overflowing = narrowed.clone();
+        overflowing.inputs.insert("b".to_string(), 100);
+        let err = overflowing.compute_witness(&r1cs.var_map).unwrap_err();
+        assert!(err.contains("'s' does not fit its declared type u8"), "{}", err);
+    }
+}
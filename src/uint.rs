@@ -0,0 +1,334 @@
+//Machine-integer gates (u32add, u64sub, u32mul, ...) with carry, borrow or high word, the
+//operands and the result are range checked by bit decomposition
+use std::collections::HashMap;
+use std::fmt;
+
+use ark_bls12_381::Fr;
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::{get_index, sum_terms, _R1CSConstraintInternal};
+
+//Operand widths the gates support
+pub const UINT_WIDTHS: [u32; 2] = [32, 64];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UIntOp {
+    //Outputs: sum and carry
+    Add,
+    //Outputs: difference and borrow
+    Sub,
+    //Outputs: low and high word of the product
+    Mul,
+}
+
+impl UIntOp {
+    //DSL keyword of the operation at a width, e.g. "u32add"
+    pub fn keyword(&self, bits: u32) -> String {
+        let op = match self {
+            UIntOp::Add => "add",
+            UIntOp::Sub => "sub",
+            UIntOp::Mul => "mul",
+        };
+        format!("u{}{}", bits, op)
+    }
+
+    //Operation and width of a DSL keyword, None for anything else
+    pub fn parse_keyword(keyword: &str) -> Option<(UIntOp, u32)> {
+        let rest = keyword.strip_prefix('u')?;
+        let split = rest.find(|c: char| !c.is_ascii_digit())?;
+        let bits: u32 = rest[..split].parse().ok()?;
+        let op = match &rest[split..] {
+            "add" => UIntOp::Add,
+            "sub" => UIntOp::Sub,
+            "mul" => UIntOp::Mul,
+            _ => return None,
+        };
+        UINT_WIDTHS.contains(&bits).then_some((op, bits))
+    }
+
+    //Bits of the decomposed result t
+    fn result_bits(&self, bits: u32) -> usize {
+        match self {
+            UIntOp::Add | UIntOp::Sub => bits as usize + 1,
+            UIntOp::Mul => 2 * bits as usize,
+        }
+    }
+}
+
+impl fmt::Display for UIntOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UIntOp::Add => write!(f, "add"),
+            UIntOp::Sub => write!(f, "sub"),
+            UIntOp::Mul => write!(f, "mul"),
+        }
+    }
+}
+
+//Number of R1CS rows one gate emits
+pub fn constraint_cost(op: UIntOp, bits: u32) -> usize {
+    2 * (bits as usize + 1) + op.result_bits(bits) + 3
+}
+
+//Prefix of the witness variables of the gate writing `low`
+fn prefix(op: UIntOp, bits: u32, low: &str) -> String {
+    format!("{}_{}", low, op.keyword(bits))
+}
+
+pub(crate) fn bit_name(prefix: &str, part: &str, i: usize) -> String {
+    format!("{}_{}_bit{}", prefix, part, i)
+}
+
+pub(crate) fn bit_values(value: u128, count: usize) -> impl Iterator<Item = (usize, Fr)> {
+    (0..count).map(move |i| (i, if (value >> i) & 1 == 1 { Fr::one() } else { Fr::zero() }))
+}
+
+//Native value of an operand, which must fit the gate's width
+fn operand_value(op: UIntOp, bits: u32, name: &str, values: &HashMap<String, Fr>) -> Result<u128, String> {
+    let val = values.get(name).ok_or_else(|| format!("Var {} not found", name))?;
+    let le_bits = val.into_bigint().to_bits_le();
+    if le_bits.iter().skip(bits as usize).any(|bit| *bit) {
+        return Err(format!("Operand '{}' of {} is {}, which is not a u{}", name, op.keyword(bits), val, bits));
+    }
+    Ok(le_bits.iter().take(bits as usize).rev().fold(0u128, |acc, bit| (acc << 1) | *bit as u128))
+}
+
+//Wires the gate assigns: operand bits, result bits, then the low and high outputs
+pub(crate) fn uint_witness(
+    op: UIntOp,
+    bits: u32,
+    (a, b, low, high): (&str, &str, &str, &str),
+    values: &HashMap<String, Fr>,
+) -> Result<Vec<(String, Fr)>, String> {
+    if !UINT_WIDTHS.contains(&bits) {
+        return Err(format!("Unsupported integer width {} (expected 32 or 64)", bits));
+    }
+    let (a_val, b_val) = (operand_value(op, bits, a, values)?, operand_value(op, bits, b, values)?);
+    let modulus = 1u128 << bits;
+    let t = match op {
+        UIntOp::Add => a_val + b_val,
+        UIntOp::Sub => a_val + modulus - b_val,
+        UIntOp::Mul => a_val * b_val,
+    };
+    let high_val = match op {
+        UIntOp::Sub => 1 - (t >> bits),
+        UIntOp::Add | UIntOp::Mul => t >> bits,
+    };
+
+    let prefix = prefix(op, bits, low);
+    let mut assigned = Vec::new();
+    for (part, value, count) in [("a", a_val, bits as usize), ("b", b_val, bits as usize), ("r", t, op.result_bits(bits))] {
+        assigned.extend(bit_values(value, count).map(|(i, bit)| (bit_name(&prefix, part, i), bit)));
+    }
+    assigned.push((low.to_string(), Fr::from(t % modulus)));
+    assigned.push((high.to_string(), Fr::from(high_val)));
+    Ok(assigned)
+}
+
+//Booleanity rows of count fresh bits, returning their indices
+pub(crate) fn emit_bits(
+    prefix: &str,
+    part: &str,
+    count: usize,
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) -> Vec<usize> {
+    (0..count)
+        .map(|i| {
+            let idx = get_index(&bit_name(prefix, part, i), var_map, next_index);
+            constraints.push(_R1CSConstraintInternal {
+                a: HashMap::from([(idx, Fr::one())]),
+                b: HashMap::from([(idx, Fr::one())]),
+                c: HashMap::from([(idx, Fr::one())]),
+            });
+            idx
+        })
+        .collect()
+}
+
+//sum(2^i * bit_i) over the given bits
+pub(crate) fn recomposition(bit_indices: &[usize]) -> HashMap<usize, Fr> {
+    let mut weight = Fr::one();
+    let mut terms = Vec::with_capacity(bit_indices.len());
+    for idx in bit_indices {
+        terms.push((*idx, weight));
+        weight += weight;
+    }
+    sum_terms(&terms)
+}
+
+//Emit the constraint_cost(op, bits) rows of one gate, in the order of uint_roles
+pub(crate) fn emit_uint_rows(
+    op: UIntOp,
+    bits: u32,
+    (a, b, low, high): (&str, &str, &str, &str),
+    var_map: &mut HashMap<String, usize>,
+    next_index: &mut usize,
+    constraints: &mut Vec<_R1CSConstraintInternal>,
+) {
+    let one_idx = var_map["1"];
+    let prefix = prefix(op, bits, low);
+    let mut operand_indices = Vec::new();
+    for (part, operand) in [("a", a), ("b", b)] {
+        let operand_idx = get_index(operand, var_map, next_index);
+        let operand_bits = emit_bits(&prefix, part, bits as usize, var_map, next_index, constraints);
+        constraints.push(_R1CSConstraintInternal {
+            a: recomposition(&operand_bits),
+            b: HashMap::from([(one_idx, Fr::one())]),
+            c: HashMap::from([(operand_idx, Fr::one())]),
+        });
+        operand_indices.push(operand_idx);
+    }
+    let (a_idx, b_idx) = (operand_indices[0], operand_indices[1]);
+    let result_bits = emit_bits(&prefix, "r", op.result_bits(bits), var_map, next_index, constraints);
+    let (low_bits, high_bits) = result_bits.split_at(bits as usize);
+    let t = recomposition(&result_bits);
+
+    //Result equation
+    let modulus: Fr = Fr::from(1u128 << bits);
+    constraints.push(match op {
+        UIntOp::Add => _R1CSConstraintInternal {
+            a: sum_terms(&[(a_idx, Fr::one()), (b_idx, Fr::one())]),
+            b: HashMap::from([(one_idx, Fr::one())]),
+            c: t,
+        },
+        UIntOp::Sub => _R1CSConstraintInternal {
+            a: sum_terms(&[(a_idx, Fr::one()), (b_idx, -Fr::one()), (one_idx, modulus)]),
+            b: HashMap::from([(one_idx, Fr::one())]),
+            c: t,
+        },
+        UIntOp::Mul => _R1CSConstraintInternal {
+            a: HashMap::from([(a_idx, Fr::one())]),
+            b: HashMap::from([(b_idx, Fr::one())]),
+            c: t,
+        },
+    });
+
+    let low_idx = get_index(low, var_map, next_index);
+    constraints.push(_R1CSConstraintInternal {
+        a: recomposition(low_bits),
+        b: HashMap::from([(one_idx, Fr::one())]),
+        c: HashMap::from([(low_idx, Fr::one())]),
+    });
+    let high_idx = get_index(high, var_map, next_index);
+    let high_lc = match op {
+        //The borrow is the complement of the top bit
+        UIntOp::Sub => sum_terms(&[(one_idx, Fr::one()), (high_bits[0], -Fr::one())]),
+        UIntOp::Add | UIntOp::Mul => recomposition(high_bits),
+    };
+    constraints.push(_R1CSConstraintInternal {
+        a: high_lc,
+        b: HashMap::from([(one_idx, Fr::one())]),
+        c: HashMap::from([(high_idx, Fr::one())]),
+    });
+}
+
+//Role of each row emit_uint_rows emits
+pub(crate) fn uint_roles(op: UIntOp, bits: u32, a: &str, b: &str) -> Vec<String> {
+    let mut roles = Vec::with_capacity(constraint_cost(op, bits));
+    for operand in [a, b] {
+        roles.extend((0..bits).map(|i| format!("booleanity of bit {} of {}", i, operand)));
+        roles.push(format!("u{} range of {}", bits, operand));
+    }
+    roles.extend((0..op.result_bits(bits)).map(|i| format!("booleanity of result bit {}", i)));
+    roles.push(format!("{} result equation", op));
+    roles.push("low word".to_string());
+    roles.push(match op {
+        UIntOp::Add => "carry",
+        UIntOp::Sub => "borrow",
+        UIntOp::Mul => "high word",
+    }.to_string());
+    roles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_circuit_str, run_pipeline, Circuit, Gate, PipelineOptions};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    //Evaluate one gate on native operands and check its rows are satisfied, returning (low, high)
+    fn eval(op: UIntOp, bits: u32, a: u64, b: u64) -> Result<(u64, u64), String> {
+        let circuit = Circuit {
+            name: "uint".to_string(),
+            inputs: HashMap::new(),
+            gates: vec![Gate::UInt(op, bits, "a".to_string(), "b".to_string(), "lo".to_string(), "hi".to_string())],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        let r1cs = circuit.to_r1cs_system();
+        assert_eq!(r1cs.raw_constraints.len(), 1 + constraint_cost(op, bits));
+        let mut values = circuit.input_wire_values();
+        values.insert("a".to_string(), Fr::from(a));
+        values.insert("b".to_string(), Fr::from(b));
+        values.extend(crate::eval_gate(&circuit.gates[0], &values)?);
+        let witness: HashMap<usize, Fr> = r1cs.var_map.iter().map(|(name, idx)| (*idx, values[name])).collect();
+        assert_eq!(r1cs.which_is_unsatisfied(&witness).unwrap(), None, "{} {} {}", op.keyword(bits), a, b);
+        let to_u64 = |name: &str| values[name].into_bigint().as_ref()[0];
+        Ok((to_u64("lo"), to_u64("hi")))
+    }
+
+    #[test]
+    fn test_matches_native_arithmetic() {
+        let mut rng = StdRng::seed_from_u64(23);
+        let boundary32 = [0, 1, u32::MAX as u64 - 1, u32::MAX as u64];
+        let boundary64 = [0, 1, u64::MAX - 1, u64::MAX];
+        let mut pairs32: Vec<(u32, u32)> = (0..8).map(|_| (rng.gen(), rng.gen())).collect();
+        let mut pairs64: Vec<(u64, u64)> = (0..8).map(|_| (rng.gen(), rng.gen())).collect();
+        for (x, y) in boundary32.iter().flat_map(|x| boundary32.iter().map(move |y| (*x, *y))) {
+            pairs32.push((x as u32, y as u32));
+        }
+        for (x, y) in boundary64.iter().flat_map(|x| boundary64.iter().map(move |y| (*x, *y))) {
+            pairs64.push((x, y));
+        }
+
+        for (x, y) in pairs32 {
+            let (sum, carry) = eval(UIntOp::Add, 32, x as u64, y as u64).unwrap();
+            assert_eq!((sum, carry == 1), (x.wrapping_add(y) as u64, x.checked_add(y).is_none()));
+            let (diff, borrow) = eval(UIntOp::Sub, 32, x as u64, y as u64).unwrap();
+            assert_eq!((diff, borrow == 1), (x.wrapping_sub(y) as u64, x.checked_sub(y).is_none()));
+            let (lo, hi) = eval(UIntOp::Mul, 32, x as u64, y as u64).unwrap();
+            let wide = x as u64 * y as u64;
+            assert_eq!((lo, hi), (x.wrapping_mul(y) as u64, wide >> 32));
+            assert_eq!(hi == 0, x.checked_mul(y).is_some());
+        }
+        for (x, y) in pairs64 {
+            assert_eq!(eval(UIntOp::Add, 64, x, y).unwrap(), (x.wrapping_add(y), x.checked_add(y).is_none() as u64));
+            assert_eq!(eval(UIntOp::Sub, 64, x, y).unwrap(), (x.wrapping_sub(y), x.checked_sub(y).is_none() as u64));
+            let wide = x as u128 * y as u128;
+            assert_eq!(eval(UIntOp::Mul, 64, x, y).unwrap(), (wide as u64, (wide >> 64) as u64));
+        }
+
+        let err = eval(UIntOp::Add, 32, 1 << 32, 0).unwrap_err();
+        assert!(err.contains("Operand 'a' of u32add is 4294967296, which is not a u32"), "{}", err);
+    }
+
+    #[test]
+    fn test_parser_keywords_prove() {
+        assert_eq!(UIntOp::parse_keyword("u64mul"), Some((UIntOp::Mul, 64)));
+        assert_eq!(UIntOp::parse_keyword("u16add"), None);
+        let source = "name uint\ninput x 2147483647\nconst y 4294967295\nu32add x y sum carry\nu32sub x y diff borrow\n\
+                      u32mul y y lo hi\nsender alice\nreceiver bob\namount 0\n";
+        let circuit = parse_circuit_str(source).unwrap();
+        assert_eq!(circuit.type_check().unwrap()["carry"], crate::WireType::Bool);
+        let result = run_pipeline(&circuit, PipelineOptions::default()).expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        let value = |name: &str| witness[&r1cs.var_map[name]];
+        assert_eq!(value("sum"), Fr::from(2147483646u64));
+        assert_eq!(value("carry"), Fr::one());
+        assert_eq!(value("diff"), Fr::from(2147483648u64));
+        assert_eq!(value("borrow"), Fr::one());
+        assert_eq!(value("lo"), Fr::one());
+        assert_eq!(value("hi"), Fr::from(4294967294u64));
+
+        let err = parse_circuit_str("name uint\ninput big:u64 5\nu32add big big s c\n").unwrap_err();
+        assert!(err.to_string().contains("Gate #0 needs a u32 operand but 'big' is u64"), "{}", err);
+    }
+}
@@ -0,0 +1,33 @@
+//wasm-bindgen exports for verifying proofs in the browser (wasm feature)
+use wasm_bindgen::prelude::*;
+
+//Verify a proof from its compressed bytes, public inputs are hex encoded (see fr_codec).
+//Any decoding error is reported as an invalid proof.
+#[wasm_bindgen]
+pub fn verify_proof(vk_bytes: &[u8], proof_bytes: &[u8], public_inputs_hex: Vec<String>) -> bool {
+    crate::verify_bytes(vk_bytes, proof_bytes, &public_inputs_hex).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::HEADER_LEN;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    //Runs natively and, under wasm-pack test, on wasm32 where a clock read or other std call
+    //missing in the browser would panic
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_verify_proof_on_pinned_fixtures() {
+        //The test_add fixtures without their artifact headers
+        let vk = &include_bytes!("../fixtures/test_add/vk.bin")[HEADER_LEN..];
+        let proof = &include_bytes!("../fixtures/test_add/proof.bin")[HEADER_LEN..];
+        let public_inputs: Vec<String> = serde_json::from_str(include_str!("../fixtures/test_add/public_inputs.json")).unwrap();
+        assert!(verify_proof(vk, proof, public_inputs.clone()));
+
+        let other = vec![format!("0x06{}", "00".repeat(31))];
+        assert!(!verify_proof(vk, proof, other));
+        assert!(!verify_proof(&vk[1..], proof, public_inputs));
+    }
+}
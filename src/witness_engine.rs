@@ -0,0 +1,174 @@
+//Incremental witness computation, only the gates downstream of a changed input are re-evaluated
+use std::collections::{BTreeSet, HashMap};
+
+use ark_bls12_381::Fr;
+
+use crate::{eval_gate, i32_to_fr, Circuit, Gate, R1CSSystem};
+
+pub struct WitnessEngine {
+    circuit: Circuit,
+    var_map: HashMap<String, usize>,
+    //Indices of the gates reading each wire
+    readers: HashMap<String, Vec<usize>>,
+    //Outputs of each gate, so a gate overwriting an input can be detected
+    outputs: Vec<String>,
+    //Dirty gates can only be re-evaluated in isolation when every wire has a single writer
+    //and no wire is range checked
+    incremental: bool,
+    values: HashMap<String, Fr>,
+    witness: HashMap<usize, Fr>,
+    //False until a compute succeeds (and again after a failed one)
+    computed: bool,
+}
+
+impl WitnessEngine {
+    pub fn new(circuit: &Circuit, r1cs_system: &R1CSSystem) -> Self {
+        let mut readers: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, gate) in circuit.gates.iter().enumerate() {
+            for operand in gate.operands() {
+                readers.entry(operand.clone()).or_default().push(i);
+            }
+        }
+        let outputs: Vec<String> = circuit.gates.iter().flat_map(Gate::outputs).cloned().collect();
+        let mut distinct: Vec<&String> = outputs.iter().collect();
+        distinct.sort();
+        distinct.dedup();
+        WitnessEngine {
+            circuit: circuit.clone(),
+            var_map: r1cs_system.var_map.clone(),
+            readers,
+            incremental: distinct.len() == outputs.len() && circuit.wire_types.is_empty(),
+            outputs,
+            values: HashMap::new(),
+            witness: HashMap::new(),
+            computed: false,
+        }
+    }
+
+    //Full computation with the circuit's inputs overridden by `inputs`; same result as compute_witness
+    pub fn compute(&mut self, inputs: &HashMap<String, i32>) -> Result<&HashMap<usize, Fr>, String> {
+        self.computed = false;
+        self.circuit.validate_gates().map_err(|e| e.to_string())?;
+        self.circuit.inputs.extend(inputs.iter().map(|(name, val)| (name.clone(), *val)));
+        self.values = self.circuit.input_wire_values();
+        for gate in &self.circuit.gates {
+            let assigned = eval_gate(gate, &self.values)?;
+            self.values.extend(assigned);
+        }
+        let bits = self.circuit.range_check_values(&self.values)?;
+        self.values.extend(bits);
+
+        self.witness = HashMap::with_capacity(self.var_map.len());
+        for (name, idx) in &self.var_map {
+            match self.values.get(name) {
+                Some(val) => {
+                    self.witness.insert(*idx, *val);
+                }
+                None => {
+                    return Err(format!("Variable '{}' (index {}) is in R1CS var_map but has no computed witness value.", name, idx))
+                }
+            }
+        }
+        self.computed = true;
+        Ok(&self.witness)
+    }
+
+    //Apply changed inputs to the last computation, re-evaluating only the gates that depend on them.
+    //Falls back to a full computation when nothing was computed yet, an input is new, or the
+    //circuit reassigns wires or declares wire types
+    pub fn recompute(&mut self, changed_inputs: &HashMap<String, i32>) -> Result<&HashMap<usize, Fr>, String> {
+        let fallback = !self.computed
+            || !self.incremental
+            || changed_inputs.keys().any(|name| !self.circuit.inputs.contains_key(name) || self.outputs.contains(name));
+        if fallback {
+            return self.compute(changed_inputs);
+        }
+
+        let mut dirty: BTreeSet<usize> = BTreeSet::new();
+        let mut assigned: Vec<(String, Fr)> = Vec::new();
+        for (name, val) in changed_inputs {
+            if self.circuit.inputs.insert(name.clone(), *val) == Some(*val) {
+                continue;
+            }
+            assigned.push((name.clone(), i32_to_fr(*val)));
+            //The transfer spec mirrors the sender and receiver balances in public wires
+            if *name == self.circuit.sender || *name == self.circuit.receiver {
+                assigned.push((format!("{}_initial_balance", name), i32_to_fr(*val)));
+            }
+        }
+
+        self.computed = false;
+        loop {
+            for (name, val) in assigned.drain(..) {
+                if let Some(readers) = self.readers.get(&name) {
+                    dirty.extend(readers);
+                }
+                if let Some(idx) = self.var_map.get(&name) {
+                    self.witness.insert(*idx, val);
+                }
+                self.values.insert(name, val);
+            }
+            //Readers always come after the writer, so popping the lowest index evaluates in gate order
+            match dirty.pop_first() {
+                Some(i) => assigned = eval_gate(&self.circuit.gates[i], &self.values)?,
+                None => break,
+            }
+        }
+        self.computed = true;
+        Ok(&self.witness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{random_circuit, CircuitConfig};
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_reassigned_wires_are_rejected() {
+        let circuit = Circuit {
+            name: "reassign".to_string(),
+            inputs: HashMap::from([("x".to_string(), 2), ("y".to_string(), 3)]),
+            gates: vec![
+                Gate::Mul("x".to_string(), "y".to_string(), "t".to_string(), None),
+                Gate::Add("t".to_string(), "x".to_string(), "t".to_string(), None),
+                Gate::Hash("t".to_string(), "h".to_string()),
+            ],
+            sender: "alice".to_string(),
+            receiver: "bob".to_string(),
+            transfer_amount: 5,
+            ..Default::default()
+        };
+        let r1cs = circuit.to_r1cs_system();
+        let mut engine = WitnessEngine::new(&circuit, &r1cs);
+        let err = engine.compute(&HashMap::new()).unwrap_err();
+        assert_eq!(err, "Gate #1 reads its own output wire 't'");
+        assert!(engine.recompute(&HashMap::from([("x".to_string(), 7)])).is_err());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_recompute_matches_compute_witness(seed in any::<u64>(), rounds in 1usize..4) {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let config = CircuitConfig { num_inputs: 4, ..CircuitConfig::default() };
+            let mut circuit = random_circuit(&mut rng, &config);
+            let r1cs = circuit.to_r1cs_system();
+            let mut engine = WitnessEngine::new(&circuit, &r1cs);
+            prop_assert_eq!(engine.compute(&HashMap::new()).unwrap(), &circuit.compute_witness(&r1cs.var_map).unwrap());
+
+            for _ in 0..rounds {
+                let changed: HashMap<String, i32> = (0..rng.gen_range(1..=2))
+                    .map(|_| (format!("in{}", rng.gen_range(0..4)), rng.gen_range(1..=1000)))
+                    .collect();
+                circuit.inputs.extend(changed.clone());
+                let expected = circuit.compute_witness(&r1cs.var_map).unwrap();
+                prop_assert_eq!(engine.recompute(&changed).unwrap(), &expected);
+            }
+        }
+    }
+}
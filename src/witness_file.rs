@@ -0,0 +1,197 @@
+//Binary witness files (.wit): artifact header | count u64 | count x (index u64 | compressed
+//value), sorted by index
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use ark_bls12_381::Fr;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+use crate::artifact::{read_header, write_header, ArtifactError, ArtifactKind};
+use crate::R1CSSystem;
+
+const FR_BYTES: usize = 32;
+
+#[derive(Debug)]
+pub enum WitnessFileError {
+    Io(std::io::Error),
+    Header(ArtifactError),
+    Format(String),
+    CircuitMismatch { expected: String, found: String },
+    MissingValue { name: String, index: usize },
+}
+
+impl fmt::Display for WitnessFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitnessFileError::Io(e) => write!(f, "Witness file I/O error: {}", e),
+            WitnessFileError::Header(e) => write!(f, "Invalid witness file header: {}", e),
+            WitnessFileError::Format(e) => write!(f, "Malformed witness file: {}", e),
+            WitnessFileError::CircuitMismatch { expected, found } => {
+                write!(f, "Witness was computed for circuit {}, expected circuit {}", found, expected)
+            }
+            WitnessFileError::MissingValue { name, index } => {
+                write!(f, "Witness has no value for variable '{}' (index {})", name, index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WitnessFileError {}
+
+impl From<std::io::Error> for WitnessFileError {
+    fn from(e: std::io::Error) -> Self {
+        WitnessFileError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WitnessFile {
+    pub circuit_hash: [u8; 32],
+    pub values: HashMap<usize, Fr>,
+}
+
+impl WitnessFile {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<(&usize, &Fr)> = self.values.iter().collect();
+        entries.sort_by_key(|(idx, _)| **idx);
+
+        let mut bytes = write_header(ArtifactKind::Witness, Some(&self.circuit_hash));
+        bytes.reserve(8 + entries.len() * (8 + FR_BYTES));
+        bytes.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for (idx, val) in entries {
+            bytes.extend_from_slice(&(*idx as u64).to_le_bytes());
+            val.serialize_compressed(&mut bytes).expect("Serializing into a Vec cannot fail");
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WitnessFileError> {
+        //The circuit is checked by check(), which also reports missing values
+        let (header, mut reader) = read_header(bytes, ArtifactKind::Witness, None).map_err(WitnessFileError::Header)?;
+        let circuit_hash = header.circuit_hash.ok_or_else(|| WitnessFileError::Format("No circuit hash in header".to_string()))?;
+        let count = u64::from_le_bytes(take_bytes(&mut reader, 8, "count")?.try_into().unwrap()) as usize;
+
+        let mut values = HashMap::new();
+        for _ in 0..count {
+            let idx = u64::from_le_bytes(take_bytes(&mut reader, 8, "index")?.try_into().unwrap()) as usize;
+            let val = Fr::deserialize_compressed(take_bytes(&mut reader, FR_BYTES, "value")?)
+                .map_err(|e| WitnessFileError::Format(format!("Invalid value for index {}: {}", idx, e)))?;
+            if values.insert(idx, val).is_some() {
+                return Err(WitnessFileError::Format(format!("Duplicate index {}", idx)));
+            }
+        }
+        if !reader.is_empty() {
+            return Err(WitnessFileError::Format("Trailing bytes".to_string()));
+        }
+        Ok(WitnessFile { circuit_hash, values })
+    }
+
+    //Check the witness belongs to the circuit and covers every variable of its R1CS system
+    pub fn check(&self, circuit_hash: &[u8; 32], r1cs_system: &R1CSSystem) -> Result<(), WitnessFileError> {
+        if &self.circuit_hash != circuit_hash {
+            return Err(WitnessFileError::CircuitMismatch {
+                expected: hex::encode(circuit_hash),
+                found: hex::encode(self.circuit_hash),
+            });
+        }
+        let mut vars: Vec<(&String, &usize)> = r1cs_system.var_map.iter().collect();
+        vars.sort_by_key(|(_, idx)| **idx);
+        for (name, idx) in vars {
+            if !self.values.contains_key(idx) {
+                return Err(WitnessFileError::MissingValue { name: name.clone(), index: *idx });
+            }
+        }
+        Ok(())
+    }
+}
+
+//Split the next n bytes off the reader
+fn take_bytes<'a>(reader: &mut &'a [u8], n: usize, what: &str) -> Result<&'a [u8], WitnessFileError> {
+    if reader.len() < n {
+        return Err(WitnessFileError::Format(format!("Truncated {}", what)));
+    }
+    let (head, rest) = reader.split_at(n);
+    *reader = rest;
+    Ok(head)
+}
+
+pub fn save_witness(
+    path: impl AsRef<Path>,
+    witness: &HashMap<usize, Fr>,
+    circuit_hash: [u8; 32],
+) -> Result<(), WitnessFileError> {
+    let file = WitnessFile { circuit_hash, values: witness.clone() };
+    fs::write(path, file.to_bytes())?;
+    Ok(())
+}
+
+pub fn load_witness(path: impl AsRef<Path>) -> Result<WitnessFile, WitnessFileError> {
+    WitnessFile::from_bytes(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::HEADER_LEN;
+    use crate::testing::mul_circuit;
+    use crate::{run_pipeline, Gate, PipelineOptions};
+
+    #[test]
+    fn test_round_trip_and_prove() {
+        let circuit = mul_circuit("wit");
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        let path = std::env::temp_dir().join(format!("zk_witness_test_{}.wit", std::process::id()));
+
+        save_witness(&path, &witness, circuit.canonical_hash()).unwrap();
+        let loaded = load_witness(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.values, witness);
+        loaded.check(&circuit.canonical_hash(), &r1cs).unwrap();
+
+        let result = run_pipeline(&circuit, PipelineOptions { witness: Some(&loaded.values), ..PipelineOptions::default() })
+            .expect("Pipeline failed");
+        assert_eq!(result.verified, Some(true));
+
+        let bytes = loaded.to_bytes();
+        assert!(WitnessFile::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let err = WitnessFile::from_bytes(&bytes[HEADER_LEN..]).unwrap_err();
+        assert!(matches!(err, WitnessFileError::Header(ArtifactError::MissingHeader)), "{}", err);
+    }
+
+    #[test]
+    fn test_mismatched_circuit_is_rejected() {
+        let circuit = mul_circuit("wit");
+        let r1cs = circuit.to_r1cs_system();
+        let witness = circuit.compute_witness(&r1cs.var_map).unwrap();
+        let file = WitnessFile::from_bytes(&WitnessFile { circuit_hash: circuit.canonical_hash(), values: witness }.to_bytes()).unwrap();
+
+        let mut other = circuit.clone();
+        other.gates[0] = Gate::Add("a".to_string(), "b".to_string(), "c".to_string(), None);
+        let err = file.check(&other.canonical_hash(), &other.to_r1cs_system()).unwrap_err();
+        assert!(matches!(err, WitnessFileError::CircuitMismatch { .. }), "{}", err);
+
+        let mut partial = file.clone();
+        partial.values.remove(&r1cs.var_map["c"]);
+        let err = partial.check(&circuit.canonical_hash(), &r1cs).unwrap_err();
+        assert!(matches!(err, WitnessFileError::MissingValue { ref name, .. } if name == "c"), "{}", err);
+    }
+
+    #[test]
+    fn test_witness_bytes_are_pinned() {
+        let file = WitnessFile { circuit_hash: [1; 32], values: HashMap::from([(2, Fr::from(5u64)), (0, Fr::from(1u64))]) };
+        let expected = [
+            //Header of kind Witness with the circuit hash
+            hex::encode(b"ZKFA") + "0100" + "01" + "04" + "01" + &"01".repeat(32),
+            //Count, then the values sorted by index
+            "0200000000000000".to_string(),
+            "0000000000000000".to_string() + "01" + &"00".repeat(31),
+            "0200000000000000".to_string() + "05" + &"00".repeat(31),
+        ]
+        .concat();
+        assert_eq!(hex::encode(file.to_bytes()), expected);
+        assert_eq!(WitnessFile::from_bytes(&file.to_bytes()).unwrap(), file);
+    }
+}